@@ -0,0 +1,61 @@
+import crypto from 'node:crypto';
+import fs from 'node:fs/promises';
+import path from 'node:path';
+import { fileURLToPath } from 'node:url';
+
+// Fills src-tauri/tool-hashes.json: every key is a constant download URL, every value its SHA-256.
+// Only unpinned entries (null/"") are downloaded unless --all is given, which re-checks everything.
+
+const __filename = fileURLToPath(import.meta.url);
+const __dirname = path.dirname(__filename);
+const manifestPath = path.resolve(__dirname, '..', 'src-tauri', 'tool-hashes.json');
+
+async function sha256Url(url) {
+  const res = await fetch(url, { redirect: 'follow', headers: { 'User-Agent': 'vecho-studio' } });
+  if (!res.ok || !res.body) {
+    throw new Error(`GET ${url} failed: ${res.status}`);
+  }
+  const hash = crypto.createHash('sha256');
+  for await (const chunk of res.body) {
+    hash.update(chunk);
+  }
+  return hash.digest('hex');
+}
+
+async function main() {
+  const all = process.argv.includes('--all');
+  const manifest = JSON.parse(await fs.readFile(manifestPath, 'utf8'));
+  let changed = 0;
+  let failed = 0;
+
+  for (const [url, pinned] of Object.entries(manifest)) {
+    if (pinned && !all) continue;
+    try {
+      const actual = await sha256Url(url);
+      if (pinned && pinned !== actual) {
+        console.error(`[pin] MISMATCH ${url}\n  pinned ${pinned}\n  actual ${actual}`);
+        failed++;
+        continue;
+      }
+      if (!pinned) {
+        manifest[url] = actual;
+        changed++;
+      }
+      console.log(`[pin] ${actual}  ${url}`);
+    } catch (e) {
+      console.error(`[pin] ${e.message}`);
+      failed++;
+    }
+  }
+
+  if (changed > 0) {
+    await fs.writeFile(manifestPath, JSON.stringify(manifest, null, 2) + '\n');
+  }
+  console.log(`[pin] ${changed} pinned, ${failed} failed`);
+  if (failed > 0) process.exit(1);
+}
+
+main().catch((e) => {
+  console.error(e);
+  process.exit(1);
+});
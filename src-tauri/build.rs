@@ -5,10 +5,34 @@ use std::{
 };
 
 fn main() {
+    check_tool_hashes();
     ensure_sidecars();
     tauri_build::build()
 }
 
+// Release builds ship tool-hashes.json as the trust root for downloads, so every entry must be pinned.
+fn check_tool_hashes() {
+    println!("cargo:rerun-if-changed=tool-hashes.json");
+    if env::var("PROFILE").as_deref() != Ok("release") {
+        return;
+    }
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR"));
+    let manifest = fs::read_to_string(manifest_dir.join("tool-hashes.json")).expect("read tool-hashes.json");
+    let unpinned: Vec<&str> = manifest
+        .lines()
+        .filter_map(|line| line.trim().trim_end_matches(',').rsplit_once(':'))
+        .filter(|(_, value)| matches!(value.trim(), "null" | "\"\""))
+        .map(|(key, _)| key.trim().trim_matches('"'))
+        .collect();
+    if !unpinned.is_empty() {
+        panic!(
+            "tool-hashes.json has {} unpinned entries; run `npm run tools:pin-hashes` before a release build:\n  {}",
+            unpinned.len(),
+            unpinned.join("\n  ")
+        );
+    }
+}
+
 fn ensure_sidecars() {
     let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR"));
     let target = env::var("TARGET").expect("TARGET");
@@ -0,0 +1,215 @@
+//! SHA-256 lookups for tool and model downloads.
+//!
+//! Expected hashes come from a pinned manifest (`{ "<url>": "<sha256>" }`) or from a release's
+//! checksum list (`SHA2-256SUMS` / `checksums.sha256`). Both are keyed by the original download URL,
+//! never by a mirror, so a rewritten download is still checked against the upstream value.
+//! Offline bundles are checked file by file against their own `SHA256SUMS` and the pinned manifest.
+
+use std::path::{Path, PathBuf};
+
+/// Checksum lists published next to GitHub release assets, tried in order.
+pub const RELEASE_SUMS_FILES: &[&str] = &["SHA2-256SUMS", "checksums.sha256"];
+
+/// Lowercase 64-digit hex, accepting an optional `sha256:` prefix and surrounding quotes.
+pub fn normalize_sha256(s: &str) -> Option<String> {
+    let s = s.trim().trim_matches('"');
+    let s = s.strip_prefix("sha256:").unwrap_or(s).to_ascii_lowercase();
+    (s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit())).then_some(s)
+}
+
+/// Pinned hash for `url`. Entries without a value (`null` or `""`) count as unpinned.
+pub fn manifest_sha256(manifest: &str, url: &str) -> Option<String> {
+    let v: serde_json::Value = serde_json::from_str(manifest).ok()?;
+    v.get(url).and_then(|x| x.as_str()).and_then(normalize_sha256)
+}
+
+/// Pinned hash of the manifest URL whose last path segment is `file_name`. Only meaningful for
+/// files installed exactly as downloaded (models, single binaries), not for archive contents.
+pub fn manifest_sha256_by_file_name(manifest: &str, file_name: &str) -> Option<String> {
+    let v: serde_json::Value = serde_json::from_str(manifest).ok()?;
+    v.as_object()?
+        .iter()
+        .filter(|(url, _)| url.rsplit('/').next() == Some(file_name))
+        .find_map(|(_, h)| h.as_str().and_then(normalize_sha256))
+}
+
+/// Hash for `name` in a `sha256sum`-style list (`<hash>  <name>` or `<hash> *<name>`).
+pub fn sums_file_sha256(sums: &str, name: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let hash = parts.next()?;
+        let file = parts.next()?.trim_start_matches('*');
+        let file = file.strip_prefix("./").unwrap_or(file);
+        (file == name).then(|| normalize_sha256(hash)).flatten()
+    })
+}
+
+/// Lowercase hex SHA-256 of a file's contents.
+pub fn sha256_file(path: &Path) -> Result<String, String> {
+    use sha2::{Digest, Sha256};
+    use std::io::Read;
+
+    let mut f = std::fs::File::open(path).map_err(|e| format!("open for checksum failed: {e}"))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = f.read(&mut buf).map_err(|e| format!("read for checksum failed: {e}"))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// Checksum list at the root of an offline bundle; paths are relative to that root (`bin/ffmpeg`).
+pub const BUNDLE_SUMS_FILE: &str = "SHA256SUMS";
+
+/// Hashes every bundle file (`(relative path, staged path)`, `/`-separated) and checks it against
+/// the bundle's [`BUNDLE_SUMS_FILE`] and, by file name, against the pinned `manifests`. A file known
+/// to neither is rejected. Returns one message per rejected file; nothing is executed.
+pub fn verify_bundle_files(files: &[(String, PathBuf)], sums: Option<&str>, manifests: &[&str]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (rel, path) in files {
+        let name = rel.rsplit('/').next().unwrap_or(rel);
+        let listed = sums.and_then(|s| sums_file_sha256(s, rel));
+        let pinned = manifests.iter().find_map(|m| manifest_sha256_by_file_name(m, name));
+        if listed.is_none() && pinned.is_none() {
+            problems.push(format!("{rel}: not listed in {BUNDLE_SUMS_FILE}"));
+            continue;
+        }
+        match sha256_file(path) {
+            Ok(actual) if [listed, pinned].iter().flatten().all(|h| *h == actual) => {}
+            Ok(_) => problems.push(format!("{rel}: checksum mismatch")),
+            Err(e) => problems.push(format!("{rel}: {e}")),
+        }
+    }
+    problems
+}
+
+/// For `https://github.com/<owner>/<repo>/releases/{latest/download|download/<tag>}/<name>`, the
+/// release directory URL (with trailing slash) and the asset name.
+pub fn github_release_asset_parts(url: &str) -> Option<(&str, &str)> {
+    let rest = url.strip_prefix("https://github.com/")?;
+    let parts: Vec<&str> = rest.split('/').collect();
+    match parts.get(2..)? {
+        ["releases", "latest", "download", name] | ["releases", "download", _, name] if !name.is_empty() => {
+            Some((&url[..url.len() - name.len()], name))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn normalize_accepts_prefix_and_case() {
+        assert_eq!(normalize_sha256(&format!("sha256:{}", HASH.to_uppercase())).as_deref(), Some(HASH));
+        assert_eq!(normalize_sha256(&format!("\"{HASH}\"")).as_deref(), Some(HASH));
+        assert_eq!(normalize_sha256("abc"), None);
+        assert_eq!(normalize_sha256(&"z".repeat(64)), None);
+    }
+
+    #[test]
+    fn manifest_skips_unpinned_entries() {
+        let manifest = format!(r#"{{ "https://a/x.zip": "{HASH}", "https://a/y.zip": null, "https://a/z.zip": "" }}"#);
+        assert_eq!(manifest_sha256(&manifest, "https://a/x.zip").as_deref(), Some(HASH));
+        assert_eq!(manifest_sha256(&manifest, "https://a/y.zip"), None);
+        assert_eq!(manifest_sha256(&manifest, "https://a/z.zip"), None);
+        assert_eq!(manifest_sha256(&manifest, "https://a/missing.zip"), None);
+        assert_eq!(manifest_sha256("not json", "https://a/x.zip"), None);
+    }
+
+    #[test]
+    fn sums_file_lookup() {
+        let other = "0".repeat(64);
+        let sums = format!("{other}  yt-dlp\n{HASH}  yt-dlp_linux\n{other} *yt-dlp.exe\n");
+        assert_eq!(sums_file_sha256(&sums, "yt-dlp_linux").as_deref(), Some(HASH));
+        assert_eq!(sums_file_sha256(&sums, "yt-dlp.exe").as_deref(), Some(other.as_str()));
+        assert_eq!(sums_file_sha256(&sums, "yt-dlp_macos"), None);
+        assert_eq!(sums_file_sha256(&format!("{HASH}  ./ffmpeg.zip"), "ffmpeg.zip").as_deref(), Some(HASH));
+    }
+
+    #[test]
+    fn mirrored_download_verifies_against_pinned_hash() {
+        let original = "https://github.com/k2-fsa/sherpa-onnx/releases/download/asr-models/silero_vad.onnx";
+        let mirrored = format!("https://ghproxy.net/{original}");
+        let manifest = format!(r#"{{ "{original}": "{HASH}" }}"#);
+
+        // The bytes came from the mirror; the expected hash is keyed by the original URL only.
+        let file = std::env::temp_dir().join(format!("vecho-checksums-{}.onnx", std::process::id()));
+        std::fs::write(&file, b"test").unwrap();
+        let actual = sha256_file(&file);
+        let _ = std::fs::remove_file(&file);
+
+        assert_eq!(manifest_sha256(&manifest, &mirrored), None);
+        assert_eq!(manifest_sha256(&manifest, original), Some(actual.unwrap()));
+    }
+
+    #[test]
+    fn manifest_lookup_by_file_name() {
+        let manifest = format!(
+            r#"{{ "https://h/a/ggml-base.bin": "{HASH}", "https://h/b/ggml-tiny.bin": null, "https://h/c/ggml-tiny.bin": "{HASH}" }}"#
+        );
+        assert_eq!(manifest_sha256_by_file_name(&manifest, "ggml-base.bin").as_deref(), Some(HASH));
+        assert_eq!(manifest_sha256_by_file_name(&manifest, "ggml-tiny.bin").as_deref(), Some(HASH));
+        assert_eq!(manifest_sha256_by_file_name(&manifest, "base.bin"), None);
+        assert_eq!(manifest_sha256_by_file_name("[]", "ggml-base.bin"), None);
+    }
+
+    #[test]
+    fn bundle_files_need_a_matching_known_hash() {
+        let dir = std::env::temp_dir().join(format!("vecho-bundle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, body: &[u8]| {
+            let p = dir.join(name);
+            std::fs::write(&p, body).unwrap();
+            p
+        };
+        // HASH is sha256("test").
+        let listed = file("ffmpeg", b"test");
+        let pinned = file("ggml-base.bin", b"test");
+        let tampered = file("ffprobe", b"changed");
+        let unknown = file("extra.dll", b"test");
+        let sums = format!("{HASH}  bin/ffmpeg
+{HASH}  bin/ffprobe
+{}  models/whisper/ggml-base.bin
+", "0".repeat(64));
+        let manifest = format!(r#"{{ "https://h/ggml-base.bin": "{HASH}" }}"#);
+
+        let check = |files: Vec<(&str, &PathBuf)>, sums: Option<&str>| {
+            let files: Vec<(String, PathBuf)> = files.into_iter().map(|(r, p)| (r.to_string(), p.clone())).collect();
+            verify_bundle_files(&files, sums, &[manifest.as_str()])
+        };
+        let ok = check(vec![("bin/ffmpeg", &listed)], Some(&sums));
+        let pinned_only = check(vec![("models/whisper/ggml-base.bin", &pinned)], None);
+        let conflicting = check(vec![("models/whisper/ggml-base.bin", &pinned)], Some(&sums));
+        let bad = check(vec![("bin/ffprobe", &tampered), ("bin/extra.dll", &unknown)], Some(&sums));
+        let no_sums = check(vec![("bin/ffmpeg", &listed)], None);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(ok.is_empty(), "{ok:?}");
+        assert!(pinned_only.is_empty(), "{pinned_only:?}");
+        assert_eq!(conflicting, vec!["models/whisper/ggml-base.bin: checksum mismatch"]);
+        assert_eq!(bad, vec!["bin/ffprobe: checksum mismatch", "bin/extra.dll: not listed in SHA256SUMS"]);
+        assert_eq!(no_sums, vec!["bin/ffmpeg: not listed in SHA256SUMS"]);
+    }
+
+    #[test]
+    fn github_release_parts() {
+        assert_eq!(
+            github_release_asset_parts("https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp.exe"),
+            Some(("https://github.com/yt-dlp/yt-dlp/releases/latest/download/", "yt-dlp.exe"))
+        );
+        assert_eq!(
+            github_release_asset_parts("https://github.com/o/r/releases/download/v1.2/a.tar.bz2"),
+            Some(("https://github.com/o/r/releases/download/v1.2/", "a.tar.bz2"))
+        );
+        assert_eq!(github_release_asset_parts("https://github.com/o/r/archive/main.zip"), None);
+        assert_eq!(github_release_asset_parts("https://huggingface.co/o/r/resolve/main/a.bin"), None);
+    }
+}
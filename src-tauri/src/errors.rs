@@ -0,0 +1,116 @@
+//! Errors returned by commands and carried in failed job events.
+//!
+//! Commands serialize an [`AppError`] as `{ "code": "...", "message": "..." }`. `code` is one of the
+//! [`ErrorCode`] names and is stable; `message` is the human-readable detail and may change.
+//! There is deliberately no `From<String>`: failure sites pick the variant, and helpers that still
+//! return `String` are wrapped with `AppError::Internal` (or a better variant) at the call site.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorCode {
+    MediaNotFound,
+    InvalidInput,
+    ToolMissing,
+    ToolDownloadFailed,
+    ChecksumMismatch,
+    Offline,
+    MediaDownloadFailed,
+    ProviderAuth,
+    ProviderRateLimited,
+    ProviderError,
+    NothingRecognized,
+    Cancelled,
+    Internal,
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum AppError {
+    #[error("media not found")]
+    MediaNotFound,
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("{0}")]
+    ToolMissing(String),
+    #[error("{0}")]
+    ToolDownloadFailed(String),
+    #[error("{0}")]
+    ChecksumMismatch(String),
+    #[error("{0}")]
+    Offline(String),
+    /// yt-dlp could not fetch the media itself (bad link, unavailable video, cookies, network).
+    #[error("{0}")]
+    MediaDownloadFailed(String),
+    #[error("{0}")]
+    ProviderAuth(String),
+    #[error("{0}")]
+    ProviderRateLimited(String),
+    #[error("{0}")]
+    ProviderError(String),
+    #[error("{0}")]
+    NothingRecognized(String),
+    #[error("job cancelled")]
+    Cancelled,
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl AppError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::MediaNotFound => ErrorCode::MediaNotFound,
+            AppError::InvalidInput(_) => ErrorCode::InvalidInput,
+            AppError::ToolMissing(_) => ErrorCode::ToolMissing,
+            AppError::ToolDownloadFailed(_) => ErrorCode::ToolDownloadFailed,
+            AppError::ChecksumMismatch(_) => ErrorCode::ChecksumMismatch,
+            AppError::Offline(_) => ErrorCode::Offline,
+            AppError::MediaDownloadFailed(_) => ErrorCode::MediaDownloadFailed,
+            AppError::ProviderAuth(_) => ErrorCode::ProviderAuth,
+            AppError::ProviderRateLimited(_) => ErrorCode::ProviderRateLimited,
+            AppError::ProviderError(_) => ErrorCode::ProviderError,
+            AppError::NothingRecognized(_) => ErrorCode::NothingRecognized,
+            AppError::Cancelled => ErrorCode::Cancelled,
+            AppError::Internal(_) => ErrorCode::Internal,
+        }
+    }
+
+    /// Error for a non-success HTTP status from an AI provider.
+    pub fn provider_status(status: u16, message: String) -> Self {
+        match status {
+            401 | 403 => AppError::ProviderAuth(message),
+            429 => AppError::ProviderRateLimited(message),
+            _ => AppError::ProviderError(message),
+        }
+    }
+}
+
+impl Serialize for AppError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("AppError", 2)?;
+        s.serialize_field("code", &self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provider_status_codes() {
+        assert_eq!(AppError::provider_status(401, "x".into()).code(), ErrorCode::ProviderAuth);
+        assert_eq!(AppError::provider_status(403, "x".into()).code(), ErrorCode::ProviderAuth);
+        assert_eq!(AppError::provider_status(429, "x".into()).code(), ErrorCode::ProviderRateLimited);
+        assert_eq!(AppError::provider_status(500, "x".into()).code(), ErrorCode::ProviderError);
+    }
+
+    #[test]
+    fn serializes_code_and_message() {
+        let v = serde_json::to_value(AppError::ToolDownloadFailed("download stalled".into())).unwrap();
+        assert_eq!(v, serde_json::json!({ "code": "ToolDownloadFailed", "message": "download stalled" }));
+        let v = serde_json::to_value(AppError::Cancelled).unwrap();
+        assert_eq!(v, serde_json::json!({ "code": "Cancelled", "message": "job cancelled" }));
+    }
+}
@@ -1,2 +1,7 @@
+pub mod checksums;
+pub mod errors;
 pub mod portable;
+pub mod search;
+pub mod subtitles;
 pub mod types;
+pub mod vad;
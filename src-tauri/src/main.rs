@@ -1,6 +1,6 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
@@ -8,18 +8,42 @@ use base64::Engine;
 use futures_util::StreamExt;
 use tauri::{Emitter, Manager, State};
 
+use vecho_studio::checksums;
+use vecho_studio::errors::{AppError, ErrorCode};
 use vecho_studio::portable;
-use vecho_studio::types::{JobProgressEvent, JobStatus, JobType, EVENT_JOB_PROGRESS};
+use vecho_studio::search;
+use vecho_studio::subtitles;
+use vecho_studio::types::{
+  ChatStreamEvent, ChatStreamKind, JobProgressEvent, JobRecord, JobStatus, JobType, EVENT_CHAT_STREAM, EVENT_JOB_PROGRESS,
+};
+use vecho_studio::vad;
 
 const SIDECAR_ENV_DIR: &str = "VECHO_SIDECAR_DIR";
 const MODELS_ENV_DIR: &str = "VECHO_MODELS_DIR";
 const TOOLS_ENV_DIR: &str = "VECHO_TOOLS_DIR";
+const OFFLINE_ENV: &str = "VECHO_OFFLINE";
+const OFFLINE_BUNDLE_ENV: &str = "VECHO_OFFLINE_BUNDLE";
 
 struct AppState {
   data_root: tokio::sync::OnceCell<std::path::PathBuf>,
   state_io_lock: tokio::sync::Mutex<()>,
   tools_lock: tokio::sync::Mutex<()>,
   uploads: tokio::sync::Mutex<HashMap<String, UploadSession>>,
+  // Sync mutex: updated from `emit_job`, which is not async.
+  jobs: std::sync::Mutex<HashMap<String, JobEntry>>,
+  job_slots: std::sync::Mutex<JobSlots>,
+  job_slots_changed: tokio::sync::Notify,
+  // Cancel senders for streaming chat replies, keyed by message id.
+  chat_streams: std::sync::Mutex<HashMap<String, tokio::sync::watch::Sender<bool>>>,
+  // Library search index, built on the first search and updated from `mark_search_dirty`.
+  search_index: tokio::sync::Mutex<LibrarySearch>,
+}
+
+struct JobEntry {
+  record: JobRecord,
+  cancel: tokio::sync::watch::Sender<bool>,
+  // `{ command, args }` for jobs that can be replayed after a restart.
+  resume: Option<serde_json::Value>,
 }
 
 impl Default for AppState {
@@ -29,6 +53,14 @@ impl Default for AppState {
       state_io_lock: tokio::sync::Mutex::new(()),
       tools_lock: tokio::sync::Mutex::new(()),
       uploads: tokio::sync::Mutex::new(HashMap::new()),
+      jobs: std::sync::Mutex::new(HashMap::new()),
+      job_slots: std::sync::Mutex::new(JobSlots {
+        running: 0,
+        limit: DEFAULT_MAX_CONCURRENT_JOBS,
+      }),
+      job_slots_changed: tokio::sync::Notify::new(),
+      chat_streams: std::sync::Mutex::new(HashMap::new()),
+      search_index: tokio::sync::Mutex::new(LibrarySearch::default()),
     }
   }
 }
@@ -98,10 +130,10 @@ struct SaveStateArgs {
 }
 
 #[tauri::command]
-async fn load_state(app: tauri::AppHandle, state: State<'_, Arc<AppState>>) -> Result<Option<serde_json::Value>, String> {
+async fn load_state(app: tauri::AppHandle, state: State<'_, Arc<AppState>>) -> Result<Option<serde_json::Value>, AppError> {
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
 
   let path = state_file_path(dir);
@@ -111,30 +143,32 @@ async fn load_state(app: tauri::AppHandle, state: State<'_, Arc<AppState>>) -> R
 
   let bytes = tokio::fs::read(&path)
     .await
-    .map_err(|e| format!("read state file failed: {e}"))?;
+    .map_err(|e| AppError::Internal(format!("read state file failed: {e}")))?;
 
   let parsed = serde_json::from_slice::<serde_json::Value>(&bytes)
-    .map_err(|e| format!("parse state file failed: {e}"))?;
+    .map_err(|e| AppError::Internal(format!("parse state file failed: {e}")))?;
   Ok(Some(parsed))
 }
 
 #[tauri::command]
-async fn save_state(app: tauri::AppHandle, state: State<'_, Arc<AppState>>, args: SaveStateArgs) -> Result<(), String> {
+async fn save_state(app: tauri::AppHandle, state: State<'_, Arc<AppState>>, args: SaveStateArgs) -> Result<(), AppError> {
   let _guard = state.state_io_lock.lock().await;
 
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
 
   let path = state_file_path(dir);
   let bytes = serde_json::to_vec_pretty(&args.state)
-    .map_err(|e| format!("serialize state failed: {e}"))?;
+    .map_err(|e| AppError::Internal(format!("serialize state failed: {e}")))?;
 
   tokio::task::spawn_blocking(move || atomic_write_bytes(&path, &bytes))
     .await
-    .map_err(|e| format!("join state write task failed: {e}"))??;
+    .map_err(|e| AppError::Internal(format!("join state write task failed: {e}")))?
+    .map_err(AppError::Internal)?;
 
+  sync_search_state(&state, &args.state).await;
   Ok(())
 }
 
@@ -145,6 +179,28 @@ struct ImportUrlArgs {
   media_id: Option<String>,
   #[serde(default)]
   quality: Option<String>,
+  // Also fetch the site's own subtitles / auto-generated captions.
+  #[serde(default)]
+  source_subtitles: bool,
+  #[serde(default)]
+  auto_captions: bool,
+  // yt-dlp `--sub-langs` expression, e.g. "en.*,zh.*".
+  #[serde(default)]
+  subtitle_langs: Option<String>,
+  // Write transcription.json from the downloaded subtitles (when none exists yet).
+  #[serde(default)]
+  seed_transcription: bool,
+  // Playlist/channel URLs: import every entry as its own media item.
+  #[serde(default)]
+  playlist: bool,
+  // 1-based, inclusive range of playlist entries to consider.
+  #[serde(default)]
+  playlist_start: Option<u32>,
+  #[serde(default)]
+  playlist_end: Option<u32>,
+  // Max number of new media items to create (already-imported entries don't count).
+  #[serde(default)]
+  playlist_limit: Option<u32>,
 }
 
 #[derive(Debug, Clone)]
@@ -183,10 +239,10 @@ struct UploadFinishArgs {
 }
 
 #[tauri::command]
-async fn get_data_root(app: tauri::AppHandle, state: State<'_, Arc<AppState>>) -> Result<String, String> {
+async fn get_data_root(app: tauri::AppHandle, state: State<'_, Arc<AppState>>) -> Result<String, AppError> {
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
   Ok(dir.to_string_lossy().to_string())
 }
@@ -209,10 +265,10 @@ async fn stage_external_file(
   app: tauri::AppHandle,
   args: StageExternalFileArgs,
   state: State<'_, Arc<AppState>>,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, AppError> {
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
 
   let media_id = args.media_id.trim().to_string();
@@ -220,13 +276,13 @@ async fn stage_external_file(
 
   let src = std::path::PathBuf::from(args.abs_path.trim());
   if !src.is_file() {
-    return Err("source file not found".to_string());
+    return Err(AppError::InvalidInput("source file not found".to_string()));
   }
 
   let media_dir = dir.join("media").join(&media_id);
   tokio::fs::create_dir_all(&media_dir)
     .await
-    .map_err(|e| format!("create media dir failed: {e}"))?;
+    .map_err(|e| AppError::Internal(format!("create media dir failed: {e}")))?;
 
   // Remove previous source.* if any
   if let Ok(p) = find_source_file(&media_dir) {
@@ -243,7 +299,7 @@ async fn stage_external_file(
 
   tokio::fs::copy(&src, &dst)
     .await
-    .map_err(|e| format!("copy file failed: {e}"))?;
+    .map_err(|e| AppError::Internal(format!("copy file failed: {e}")))?;
 
   let stored_rel = dst
     .strip_prefix(&dir)
@@ -265,10 +321,10 @@ async fn delete_media_storage(
   app: tauri::AppHandle,
   args: MediaDirArgs,
   state: State<'_, Arc<AppState>>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
 
   let media_id = args.media_id.trim().to_string();
@@ -281,16 +337,20 @@ async fn delete_media_storage(
 
   tokio::fs::remove_dir_all(&media_dir)
     .await
-    .map_err(|e| format!("delete media dir failed: {e}"))?;
+    .map_err(|e| AppError::Internal(format!("delete media dir failed: {e}")))?;
+  mark_search_dirty(&media_dir);
   Ok(())
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 struct TranslateSubtitlesArgs {
   media_id: String,
   ai: AiSettings,
   target_lang: String,
+  // Track to translate from; defaults to `original`.
+  #[serde(default)]
+  source_track: Option<String>,
 }
 
 fn subtitles_file_path(media_dir: &Path) -> PathBuf {
@@ -315,12 +375,19 @@ fn build_subtitles_from_transcription(media_id: &str, transcription: &serde_json
       if text.is_empty() {
         continue;
       }
-      segs.push(serde_json::json!({
+      let mut seg = serde_json::json!({
         "id": if !id.is_empty() { id.to_string() } else { format!("seg-{}", nanoid()) },
         "start": start,
         "end": end.max(start),
         "text": text,
-      }));
+      });
+      if let Some(words) = s.get("words").filter(|w| w.is_array()) {
+        seg["words"] = words.clone();
+      }
+      if let Some(speaker) = s.get("speaker").filter(|v| v.is_string()) {
+        seg["speaker"] = speaker.clone();
+      }
+      segs.push(seg);
     }
   }
 
@@ -562,14 +629,14 @@ async fn translate_subtitle_pairs_with_ai(
   ai: &AiSettings,
   target_lang: &str,
   payload_json: &str,
-) -> Result<Vec<(String, String)>, String> {
+) -> Result<Vec<(String, String)>, AppError> {
   let lang = target_lang.trim().to_lowercase();
   let payload = payload_json.trim();
   if lang.trim().is_empty() {
-    return Err("target_lang is empty".to_string());
+    return Err(AppError::InvalidInput("target_lang is empty".to_string()));
   }
   if payload.is_empty() {
-    return Err("translate payload is empty".to_string());
+    return Err(AppError::InvalidInput("translate payload is empty".to_string()));
   }
 
   let want_zh = lang.starts_with("zh");
@@ -616,19 +683,19 @@ Input:\n{payload}\n",
   let mut last_err: Option<String> = None;
   let max_opts: [u32; 4] = [8192, 4096, 2048, 1024];
 
-  async fn retryable<F, Fut>(mut f: F) -> Result<String, String>
+  async fn retryable<F, Fut>(mut f: F) -> Result<String, AppError>
   where
     F: FnMut() -> Fut,
-    Fut: std::future::Future<Output = Result<String, String>>,
+    Fut: std::future::Future<Output = Result<String, AppError>>,
   {
     let delays_ms: [u64; 3] = [350, 900, 1700];
-    let mut last: Option<String> = None;
+    let mut last: Option<AppError> = None;
     for (i, d) in delays_ms.iter().enumerate() {
       match f().await {
         Ok(v) => return Ok(v),
         Err(e) => {
-          let low = e.to_lowercase();
-          let is_retry = low.contains("http 429")
+          let low = e.to_string().to_lowercase();
+          let is_retry = matches!(e, AppError::ProviderRateLimited(_))
             || low.contains("rate limit")
             || low.contains("http 503")
             || low.contains("http 502")
@@ -644,7 +711,7 @@ Input:\n{payload}\n",
         }
       }
     }
-    Err(last.unwrap_or_else(|| "request failed".to_string()))
+    Err(last.unwrap_or_else(|| AppError::ProviderError("request failed".to_string())))
   }
 
   for p in prompts {
@@ -652,18 +719,18 @@ Input:\n{payload}\n",
       AiProvider::OpenaiCompatible => {
         let base = normalize_base_url(&ai.openai.base_url);
         if base.is_empty() {
-          return Err("openai baseUrl is empty".to_string());
+          return Err(AppError::InvalidInput("openai baseUrl is empty".to_string()));
         }
         let model = ai.openai.chat_model.trim();
         if model.is_empty() {
-          return Err("openai model is empty".to_string());
+          return Err(AppError::InvalidInput("openai model is empty".to_string()));
         }
         let messages = vec![
           serde_json::json!({ "role": "system", "content": format!("You are a translation engine. Translate to {lang_label}. Output ONLY JSON.", lang_label = lang_label) }),
           serde_json::json!({ "role": "user", "content": p }),
         ];
 
-        let mut last_req_err: Option<String> = None;
+        let mut last_req_err: Option<AppError> = None;
         let mut out: Option<String> = None;
 
         let looks_like_unknown_param = |e: &str| {
@@ -689,7 +756,7 @@ Input:\n{payload}\n",
               break;
             }
             Err(e) => {
-              if looks_like_unknown_param(&e) {
+              if looks_like_unknown_param(&e.to_string()) {
                 last_req_err = Some(e);
                 break;
               }
@@ -715,7 +782,7 @@ Input:\n{payload}\n",
               break;
             }
             Err(e) => {
-                if looks_like_unknown_param(&e) {
+                if looks_like_unknown_param(&e.to_string()) {
                   last_req_err = Some(e);
                   break;
                 }
@@ -740,7 +807,7 @@ Input:\n{payload}\n",
           }
         }
 
-        out.ok_or_else(|| last_req_err.unwrap_or_else(|| "openai translate request failed".to_string()))?
+        out.ok_or_else(|| last_req_err.unwrap_or_else(|| AppError::ProviderError("openai translate request failed".to_string())))?
       }
       AiProvider::Gemini => {
         retryable(|| {
@@ -754,6 +821,10 @@ Input:\n{payload}\n",
         })
         .await?
       }
+      AiProvider::Anthropic | AiProvider::Ollama => {
+        let system = format!("You are a translation engine. Translate to {lang_label}. Output ONLY JSON.");
+        retryable(|| ai_complete(ai, &system, &p, true)).await?
+      }
     };
 
     match parse_translation_pairs(&raw) {
@@ -792,7 +863,7 @@ Input:\n{payload}\n",
     }
   }
 
-  Err(last_err.unwrap_or_else(|| "translate failed".to_string()))
+  Err(AppError::ProviderError(last_err.unwrap_or_else(|| "translate failed".to_string())))
 }
 
 fn should_split_translation_error(err: &str) -> bool {
@@ -861,6 +932,7 @@ async fn translate_ids_with_auto_split(
       status: JobStatus::Running,
       progress: (0.10 + 0.80 * prog).clamp(0.0, 0.95),
       message: Some(format!("{label} {done}/{total}")),
+      error_code: None,
     });
 
     let payload = serde_json::to_string(&list).unwrap_or_else(|_| "[]".to_string());
@@ -891,6 +963,7 @@ async fn translate_ids_with_auto_split(
         }
       }
       Err(e) => {
+        let e = e.to_string();
         last_err = Some(e.clone());
 
         // If the model output is truncated / malformed, split and retry.
@@ -920,17 +993,17 @@ async fn load_subtitles(
   app: tauri::AppHandle,
   args: MediaDirArgs,
   state: State<'_, Arc<AppState>>,
-) -> Result<Option<serde_json::Value>, String> {
+) -> Result<Option<serde_json::Value>, AppError> {
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
   let media_id = args.media_id.trim().to_string();
   validate_media_id(&media_id)?;
 
   let media_dir = dir.join("media").join(&media_id);
   if !media_dir.is_dir() {
-    return Err("media not found".to_string());
+    return Err(AppError::MediaNotFound);
   }
   Ok(load_subtitles_json(&media_dir).await)
 }
@@ -940,36 +1013,48 @@ async fn ensure_subtitles(
   app: tauri::AppHandle,
   args: MediaDirArgs,
   state: State<'_, Arc<AppState>>,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, AppError> {
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
   let media_id = args.media_id.trim().to_string();
   validate_media_id(&media_id)?;
 
   let media_dir = dir.join("media").join(&media_id);
   if !media_dir.is_dir() {
-    return Err("media not found".to_string());
+    return Err(AppError::MediaNotFound);
   }
 
-  if let Some(existing) = load_subtitles_json(&media_dir).await {
-    return Ok(existing);
+  let mut existing = load_subtitles_json(&media_dir).await;
+  let transcription_path = media_dir.join("transcription.json");
+  if let Some(subs) = existing.as_mut() {
+    // Imported/source tracks can exist before there is a transcription; fill in `original` once there is one.
+    if get_track_mut(subs, "original").is_some() || !transcription_path.is_file() {
+      return Ok(subs.clone());
+    }
   }
 
-  let transcription_path = media_dir.join("transcription.json");
   if !transcription_path.is_file() {
-    return Err("no transcription found".to_string());
+    return Err(AppError::InvalidInput("no transcription found".to_string()));
   }
   let transcription: serde_json::Value = serde_json::from_slice(
     &tokio::fs::read(&transcription_path)
       .await
-      .map_err(|e| format!("read transcription failed: {e}"))?,
+      .map_err(|e| AppError::Internal(format!("read transcription failed: {e}")))?,
   )
-  .map_err(|e| format!("parse transcription failed: {e}"))?;
+  .map_err(|e| AppError::Internal(format!("parse transcription failed: {e}")))?;
 
-  let subs = build_subtitles_from_transcription(&media_id, &transcription);
-  write_json_atomic(&subtitles_file_path(&media_dir), &subs)?;
+  let mut subs = build_subtitles_from_transcription(&media_id, &transcription);
+  if let Some(existing) = existing {
+    let original = subs.get("tracks").and_then(|v| v.get(0)).cloned();
+    subs = existing;
+    if let Some(original) = original {
+      upsert_track(&mut subs, original);
+    }
+  }
+  write_json_atomic(&subtitles_file_path(&media_dir), &subs).map_err(AppError::Internal)?;
+  mark_search_dirty(&media_dir);
   Ok(subs)
 }
 
@@ -978,31 +1063,38 @@ async fn translate_subtitles(
   app: tauri::AppHandle,
   args: TranslateSubtitlesArgs,
   state: State<'_, Arc<AppState>>,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, AppError> {
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
   let media_id = args.media_id.trim().to_string();
   validate_media_id(&media_id)?;
 
   let target_lang = args.target_lang.trim().to_lowercase();
   if target_lang.is_empty() {
-    return Err("target_lang is empty".to_string());
+    return Err(AppError::InvalidInput("target_lang is empty".to_string()));
   }
 
   let media_dir = dir.join("media").join(&media_id);
   if !media_dir.is_dir() {
-    return Err("media not found".to_string());
+    return Err(AppError::MediaNotFound);
   }
 
-  let mut subs = if let Some(v) = load_subtitles_json(&media_dir).await {
-    v
-  } else {
-    ensure_subtitles(app.clone(), MediaDirArgs { media_id: media_id.clone() }, state).await?
+  let source_track = args
+    .source_track
+    .as_deref()
+    .map(|s| s.trim())
+    .filter(|s| !s.is_empty())
+    .unwrap_or("original")
+    .to_string();
+
+  let mut subs = match load_subtitles_json(&media_dir).await {
+    Some(v) if source_track != "original" => v,
+    _ => ensure_subtitles(app.clone(), MediaDirArgs { media_id: media_id.clone() }, state).await?,
   };
 
-  let job_id = format!("job-{}", nanoid());
+  let job_id = new_job_id();
   let _ = emit_job(&app, JobProgressEvent {
     job_id: job_id.clone(),
     media_id: media_id.clone(),
@@ -1010,9 +1102,11 @@ async fn translate_subtitles(
     status: JobStatus::Running,
     progress: 0.0,
     message: Some("translating subtitles".to_string()),
+    error_code: None,
   });
+  remember_job_resume(&app, &job_id, "translate_subtitles", &args);
 
-  let result: Result<serde_json::Value, String> = async {
+  let result: Result<serde_json::Value, AppError> = run_job(&app, &job_id, async {
 
   // Get original track data (clone so we can mutate `subs` later).
   let orig_track = subs
@@ -1020,10 +1114,10 @@ async fn translate_subtitles(
     .and_then(|v| v.as_array())
     .and_then(|arr| {
       arr.iter()
-        .find(|t| t.get("id").and_then(|v| v.as_str()) == Some("original"))
+        .find(|t| t.get("id").and_then(|v| v.as_str()) == Some(source_track.as_str()))
     })
     .cloned()
-    .ok_or_else(|| "missing original subtitle track".to_string())?;
+    .ok_or_else(|| AppError::InvalidInput(format!("missing subtitle track: {source_track}")))?;
 
   let orig_lang = orig_track
     .get("language")
@@ -1038,7 +1132,7 @@ async fn translate_subtitles(
     .cloned()
     .unwrap_or_default();
   if orig_segs.is_empty() {
-    return Err("original subtitle track is empty".to_string());
+    return Err(AppError::InvalidInput("original subtitle track is empty".to_string()));
   }
 
   // Translate in chunks.
@@ -1056,7 +1150,7 @@ async fn translate_subtitles(
     id_to_meta.insert(id, (start, end.max(start), text));
   }
   if id_order.is_empty() {
-    return Err("original track has no usable segments".to_string());
+    return Err(AppError::InvalidInput("original track has no usable segments".to_string()));
   }
 
   let mut out_map: std::collections::HashMap<String, String> = std::collections::HashMap::new();
@@ -1096,6 +1190,7 @@ async fn translate_subtitles(
       status: JobStatus::Running,
       progress: 0.05,
       message: Some(format!("translating (chunks={})", total_chunks)),
+      error_code: None,
     });
 
     let sem = std::sync::Arc::new(tokio::sync::Semaphore::new(concurrency));
@@ -1166,6 +1261,7 @@ async fn translate_subtitles(
         status: JobStatus::Running,
         progress: 0.92,
         message: Some(format!("repairing missing translations ({}/{})", missing_count, total)),
+        error_code: None,
       });
 
       let err = translate_ids_with_auto_split(
@@ -1198,7 +1294,7 @@ async fn translate_subtitles(
         preview
       })
       .unwrap_or_else(|| "unknown error".to_string());
-    return Err(format!("translation produced no segments\n\nlast error (first 380 chars):\n{hint}"));
+    return Err(AppError::ProviderError(format!("translation produced no segments\n\nlast error (first 380 chars):\n{hint}")));
   }
 
   // Build translated track.
@@ -1264,9 +1360,10 @@ async fn translate_subtitles(
     "generatedAt": now_iso(),
   });
 
-  write_json_atomic(&subtitles_file_path(&media_dir), &subs)?;
+  write_json_atomic(&subtitles_file_path(&media_dir), &subs).map_err(AppError::Internal)?;
+  mark_search_dirty(&media_dir);
   Ok(subs)
-  }
+  })
   .await;
 
   match result {
@@ -1278,6 +1375,7 @@ async fn translate_subtitles(
         status: JobStatus::Succeeded,
         progress: 1.0,
         message: Some("subtitle translation finished".to_string()),
+        error_code: None,
       });
       Ok(v)
     }
@@ -1288,22 +1386,163 @@ async fn translate_subtitles(
         job_type: JobType::Subtitle,
         status: JobStatus::Failed,
         progress: 1.0,
-        message: Some(e.clone()),
+        message: Some(e.to_string()),
+        error_code: Some(e.code()),
       });
       Err(e)
     }
   }
 }
 
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportSubtitleFileArgs {
+  media_id: String,
+  // Either a file on disk or the file contents (e.g. from a browser file input).
+  #[serde(default)]
+  abs_path: Option<String>,
+  #[serde(default)]
+  content: Option<String>,
+  #[serde(default)]
+  file_name: Option<String>,
+  #[serde(default)]
+  track_id: Option<String>,
+  #[serde(default)]
+  label: Option<String>,
+  #[serde(default)]
+  language: Option<String>,
+  // Keep inline tags (`<i>`, `{\b1}`) and ASS style names instead of plain text.
+  #[serde(default)]
+  preserve_styling: bool,
+  // Also write transcription.json from this track so chat/summary use it.
+  #[serde(default)]
+  replace_transcription: bool,
+}
+
+#[tauri::command]
+async fn import_subtitle_file(
+  app: tauri::AppHandle,
+  args: ImportSubtitleFileArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
+  let media_id = args.media_id.trim().to_string();
+  validate_media_id(&media_id)?;
+
+  let media_dir = dir.join("media").join(&media_id);
+  if !media_dir.is_dir() {
+    return Err(AppError::MediaNotFound);
+  }
+
+  const MAX_SUBTITLE_BYTES: u64 = 32 * 1024 * 1024;
+  let (raw, file_name) = match (args.abs_path.as_deref().map(|s| s.trim()).filter(|s| !s.is_empty()), args.content) {
+    (Some(p), _) => {
+      let path = PathBuf::from(p);
+      let meta = tokio::fs::metadata(&path)
+        .await
+        .map_err(|_| AppError::InvalidInput("subtitle file not found".to_string()))?;
+      if meta.len() > MAX_SUBTITLE_BYTES {
+        return Err(AppError::InvalidInput("subtitle file is too large".to_string()));
+      }
+      let bytes = tokio::fs::read(&path)
+        .await
+        .map_err(|e| AppError::Internal(format!("read subtitle file failed: {e}")))?;
+      let name = path.file_name().and_then(|s| s.to_str()).map(|s| s.to_string());
+      (subtitles::decode_text(&bytes), args.file_name.or(name))
+    }
+    (None, Some(content)) => (content, args.file_name),
+    (None, None) => return Err(AppError::InvalidInput("either absPath or content is required".to_string())),
+  };
+
+  let format = subtitles::SubtitleFormat::detect(file_name.as_deref(), &raw);
+  let cues = subtitles::parse_subtitles(format, &raw, args.preserve_styling).map_err(AppError::InvalidInput)?;
+  if cues.is_empty() {
+    return Err(AppError::InvalidInput("no subtitle cues found in file".to_string()));
+  }
+
+  let stem = file_name
+    .as_deref()
+    .map(|n| n.rsplit_once('.').map(|(a, _)| a).unwrap_or(n).to_string())
+    .filter(|s| !s.trim().is_empty());
+  let track_id = args
+    .track_id
+    .map(|s| s.trim().to_string())
+    .filter(|s| !s.is_empty())
+    .unwrap_or_else(|| format!("import-{}", nanoid()));
+  let label = args
+    .label
+    .map(|s| s.trim().to_string())
+    .filter(|s| !s.is_empty())
+    .or(stem)
+    .unwrap_or_else(|| track_id.clone());
+  let language = args.language.map(|s| s.trim().to_string()).unwrap_or_default();
+
+  let segments = cues_to_track_segments(&cues);
+  let track = serde_json::json!({
+    "id": track_id,
+    "label": label,
+    "language": language,
+    "kind": "imported",
+    "format": format!("{format:?}").to_lowercase(),
+    "generatedAt": now_iso(),
+    "segments": segments,
+  });
+
+  let mut transcription: Option<serde_json::Value> = None;
+  if args.replace_transcription {
+    let segments_ms: Vec<(i64, i64, String)> = cues
+      .iter()
+      .map(|c| {
+        let text = subtitles::strip_markup(&c.text).replace('\n', " ");
+        ((c.start * 1000.0).round() as i64, (c.end * 1000.0).round() as i64, text)
+      })
+      .collect();
+    let lang = if language.is_empty() { None } else { Some(language.as_str()) };
+    let t = build_transcription(&media_id, lang, &format!("imported-subtitles:{label}"), segments_ms);
+    write_json_atomic(&media_dir.join("transcription.json"), &t).map_err(AppError::Internal)?;
+    transcription = Some(t);
+  }
+
+  let mut subs = if media_dir.join("transcription.json").is_file() {
+    ensure_subtitles(app.clone(), MediaDirArgs { media_id: media_id.clone() }, state).await?
+  } else {
+    load_subtitles_json(&media_dir).await.unwrap_or_else(|| empty_subtitles(&media_id))
+  };
+  if let Some(t) = transcription.as_ref() {
+    if let Some(original) = build_subtitles_from_transcription(&media_id, t)
+      .get("tracks")
+      .and_then(|v| v.get(0))
+      .cloned()
+    {
+      upsert_track(&mut subs, original);
+    }
+  }
+  upsert_track(&mut subs, track);
+  subs["generatedAt"] = serde_json::Value::String(now_iso());
+  write_json_atomic(&subtitles_file_path(&media_dir), &subs).map_err(AppError::Internal)?;
+  mark_search_dirty(&media_dir);
+
+  Ok(serde_json::json!({
+    "media_id": media_id,
+    "track_id": track_id,
+    "cue_count": cues.len(),
+    "subtitles": subs,
+    "transcription": transcription,
+  }))
+}
+
 #[tauri::command]
 async fn get_media_storage_info(
   app: tauri::AppHandle,
   args: MediaDirArgs,
   state: State<'_, Arc<AppState>>,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, AppError> {
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
 
   let media_id = args.media_id.trim().to_string();
@@ -1311,7 +1550,7 @@ async fn get_media_storage_info(
 
   let media_dir = dir.join("media").join(&media_id);
   if !media_dir.is_dir() {
-    return Err("media not found".to_string());
+    return Err(AppError::MediaNotFound);
   }
 
   let mut files: Vec<String> = Vec::new();
@@ -1338,10 +1577,10 @@ async fn reveal_media_dir(
   app: tauri::AppHandle,
   args: MediaDirArgs,
   state: State<'_, Arc<AppState>>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
 
   let media_id = args.media_id.trim().to_string();
@@ -1349,7 +1588,7 @@ async fn reveal_media_dir(
 
   let media_dir = dir.join("media").join(&media_id);
   if !media_dir.is_dir() {
-    return Err("media not found".to_string());
+    return Err(AppError::MediaNotFound);
   }
 
   #[cfg(target_os = "windows")]
@@ -1375,263 +1614,668 @@ async fn reveal_media_dir(
     std::process::Command::new("xdg-open")
       .arg(media_dir)
       .spawn()
-      .map_err(|e| format!("failed to open folder: {e}"))?;
+      .map_err(|e| AppError::Internal(format!("failed to open folder: {e}")))?;
     return Ok(());
   }
 }
 
 #[tauri::command]
-async fn import_url(app: tauri::AppHandle, args: ImportUrlArgs, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, String> {
-  let ImportUrlArgs { url, media_id, quality } = args;
+async fn import_url(app: tauri::AppHandle, args: ImportUrlArgs, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, AppError> {
+  let ImportUrlArgs {
+    url,
+    media_id,
+    quality,
+    source_subtitles,
+    auto_captions,
+    subtitle_langs,
+    seed_transcription,
+    playlist,
+    playlist_start,
+    playlist_end,
+    playlist_limit,
+  } = args;
 
   let url = if url.starts_with("http://") || url.starts_with("https://") {
     url
   } else if let Some(extracted) = extract_first_http_url(&url) {
     extracted
   } else {
-    return Err("only http(s) URLs are supported".to_string());
+    return Err(AppError::InvalidInput("only http(s) URLs are supported".to_string()));
   };
 
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
 
   let media_id = media_id.unwrap_or_else(|| format!("media-{}", nanoid()));
   validate_media_id(&media_id)?;
-  let job_id = format!("job-{}", nanoid());
 
-  let media_dir = dir.join("media").join(&media_id);
-  tokio::fs::create_dir_all(&media_dir)
-    .await
-    .map_err(|e| format!("create media dir failed: {e}"))?;
+  let opts = ImportOptions {
+    quality,
+    source_subtitles,
+    auto_captions,
+    subtitle_langs,
+    seed_transcription,
+  };
 
-  let output_template = media_dir.join("source.%(ext)s");
+  if playlist {
+    let range = PlaylistRange {
+      start: playlist_start,
+      end: playlist_end,
+      limit: playlist_limit,
+    };
+    return import_playlist_url(app.clone(), state.inner(), dir, media_id, url, &opts, range).await;
+  }
 
-  // Prepare tools (first run may need to download them).
-  let _ = emit_job(&app, JobProgressEvent {
-    job_id: job_id.clone(),
-    media_id: media_id.clone(),
-    job_type: JobType::Download,
-    status: JobStatus::Running,
-    progress: 0.0,
-    message: Some("preparing download tools".to_string()),
-  });
+  import_single_url(app.clone(), state.inner(), dir, new_job_id(), media_id, url, &opts).await
+}
 
-  let ytdlp = ensure_ytdlp(&app, state.inner(), dir).await?;
-  let (ffmpeg_dir, has_ffmpeg) = match ensure_ffmpeg_bundle(&app, state.inner(), dir).await {
-    Ok((ffmpeg, _ffprobe)) => (ffmpeg.parent().map(|p| p.to_path_buf()), true),
-    Err(_) => (None, false),
-  };
+struct ImportOptions {
+  quality: Option<String>,
+  source_subtitles: bool,
+  auto_captions: bool,
+  subtitle_langs: Option<String>,
+  seed_transcription: bool,
+}
 
-  // Optional cookies file for restricted content.
-  // Priority: explicit env var > data_root/db/cookies.txt
-  let cookies_path = std::env::var("VECHO_YTDLP_COOKIES")
-    .ok()
-    .map(PathBuf::from)
-    .or_else(|| {
-      let p = dir.join("db").join("cookies.txt");
-      if p.is_file() { Some(p) } else { None }
-    });
+#[derive(Clone, Copy)]
+struct PlaylistRange {
+  start: Option<u32>,
+  end: Option<u32>,
+  limit: Option<u32>,
+}
+
+struct PlaylistEntry {
+  url: String,
+  title: Option<String>,
+  // `extractor:id`, matching what `find_info_json` gives for an imported item.
+  key: Option<String>,
+}
+
+async fn import_playlist_url(
+  app: tauri::AppHandle,
+  state: &Arc<AppState>,
+  dir: &Path,
+  media_id: String,
+  url: String,
+  opts: &ImportOptions,
+  range: PlaylistRange,
+) -> Result<serde_json::Value, AppError> {
+  let job_id = new_job_id();
+  let entry_jobs = std::sync::Mutex::new(Vec::<String>::new());
 
   let _ = emit_job(&app, JobProgressEvent {
     job_id: job_id.clone(),
     media_id: media_id.clone(),
-    job_type: JobType::Download,
+    job_type: JobType::Import,
     status: JobStatus::Running,
-    progress: 0.01,
-    message: Some("starting download".to_string()),
+    progress: 0.0,
+    message: Some("listing playlist entries".to_string()),
+    error_code: None,
   });
 
-  let is_youtube = looks_like_youtube_url(&url);
-
-  // yt-dlp may need a JS runtime for YouTube extraction (varies by version/site behavior).
-  let js_runtime = if is_youtube {
-    if is_exe_available("deno") {
-      Some("deno".to_string())
-    } else if is_exe_available("node") {
-      Some("node".to_string())
-    } else {
-      None
+  // The batch job only drives the per-entry download jobs, which take the concurrency slots.
+  let result: Result<serde_json::Value, AppError> = run_job_unthrottled(&app, &job_id, async {
+    let ytdlp = ensure_ytdlp(&app, state, dir).await?;
+    let listing = ytdlp_list_playlist(&ytdlp, &url, ytdlp_cookies_path(dir).as_deref(), range).await?;
+    let entries = parse_playlist_entries(&listing);
+    if entries.is_empty() {
+      return Err(AppError::InvalidInput("playlist has no entries".to_string()));
     }
-  } else {
-    None
-  };
-
-  let insecure = std::env::var("VECHO_YTDLP_INSECURE")
-    .ok()
-    .map(|v| {
-      let s = v.trim().to_lowercase();
-      s == "1" || s == "true" || s == "yes"
-    })
-    .unwrap_or(false);
-
-  let mut opts = YtDlpRunOpts {
-    has_ffmpeg,
-    ffmpeg_dir: ffmpeg_dir.clone(),
-    cookies_path: cookies_path.clone(),
-    retries: if is_youtube { 20 } else { 10 },
-    fragment_retries: if is_youtube { 20 } else { 10 },
-    extractor_retries: 3,
-    socket_timeout: if is_youtube { 30 } else { 20 },
-    concurrent_fragments: if is_youtube { 2 } else { 4 },
-    force_ipv4: false,
-    youtube_compat: is_youtube,
-    js_runtime,
-    insecure,
-    format: select_ytdlp_format(has_ffmpeg, quality.as_deref()),
-  };
-
-  match run_ytdlp_download(&app, &job_id, &media_id, &ytdlp, &url, &output_template, &opts).await {
-    Ok(()) => {}
-    Err(mut tail) => {
-      // Retry once with safer options for common flaky TLS/fragment issues.
-      if is_youtube && is_retryable_ytdlp_failure(&tail) {
-        let _ = emit_job(&app, JobProgressEvent {
-          job_id: job_id.clone(),
-          media_id: media_id.clone(),
-          job_type: JobType::Download,
-          status: JobStatus::Running,
-          progress: 0.02,
-          message: Some("download hiccup detected; retrying with safer settings (IPv4, fewer fragments)".to_string()),
-        });
 
-        opts.force_ipv4 = true;
-        opts.concurrent_fragments = 1;
-        opts.retries = 30;
-        opts.fragment_retries = 30;
-        opts.socket_timeout = 45;
+    let mut imported = {
+      let dir = dir.to_path_buf();
+      tokio::task::spawn_blocking(move || imported_extractor_ids(&dir))
+        .await
+        .map_err(|e| AppError::Internal(format!("scan imported media failed: {e}")))?
+    };
 
-        match run_ytdlp_download(&app, &job_id, &media_id, &ytdlp, &url, &output_template, &opts).await {
-          Ok(()) => {
-            tail.clear();
-          }
-          Err(tail2) => {
-            tail = tail2;
-          }
-        }
-      }
+    let total = entries.len();
+    let limit = range.limit.map(|n| n as usize).unwrap_or(usize::MAX);
+    let mut next_media_id = Some(media_id.clone());
+    let mut items: Vec<serde_json::Value> = Vec::new();
+    let mut skipped: Vec<serde_json::Value> = Vec::new();
+    let mut failed: Vec<serde_json::Value> = Vec::new();
 
-      if !tail.trim().is_empty() {
-        let _ = emit_job(&app, JobProgressEvent {
-          job_id: job_id.clone(),
-          media_id: media_id.clone(),
-          job_type: JobType::Download,
-          status: JobStatus::Failed,
-          progress: 0.0,
-          message: Some("download failed".to_string()),
-        });
-        return Err(format!("yt-dlp failed\n{tail}"));
+    for (idx, entry) in entries.into_iter().enumerate() {
+      if items.len() >= limit {
+        break;
       }
-    }
-  }
 
-  let _ = emit_job(&app, JobProgressEvent {
-    job_id: job_id.clone(),
-    media_id: media_id.clone(),
-    job_type: JobType::Download,
-    status: JobStatus::Running,
-    progress: 0.92,
-    message: Some("analyzing media".to_string()),
-  });
-
-  let stored_path = find_source_file(&media_dir)?;
-  let file_size = std::fs::metadata(&stored_path)
-    .map(|m| m.len())
-    .unwrap_or(0);
-  let stored_rel = stored_path
-    .strip_prefix(dir)
-    .ok()
-    .map(|p| p.to_string_lossy().replace('\\', "/"));
+      let label = entry.title.clone().unwrap_or_else(|| entry.url.clone());
+      let _ = emit_job(&app, JobProgressEvent {
+        job_id: job_id.clone(),
+        media_id: media_id.clone(),
+        job_type: JobType::Import,
+        status: JobStatus::Running,
+        progress: idx as f32 / total as f32,
+        message: Some(format!("importing {}/{}: {}", idx + 1, total, label)),
+        error_code: None,
+      });
 
-  let mut title: Option<String> = None;
-  let mut uploader: Option<String> = None;
-  let mut upload_date: Option<String> = None;
+      if let Some(existing) = entry.key.as_ref().and_then(|k| imported.get(k)) {
+        skipped.push(serde_json::json!({
+          "url": entry.url,
+          "title": entry.title,
+          "media_id": existing,
+          "reason": "already imported",
+        }));
+        continue;
+      }
 
-  let mut duration: Option<f64> = None;
-  let mut meta: Option<serde_json::Value> = None;
-  let mut thumbnail: Option<String> = None;
-  let mut warning: Option<String> = None;
+      // The caller's media id goes to the first entry we actually import.
+      let entry_media_id = next_media_id.take().unwrap_or_else(|| format!("media-{}", nanoid()));
+      let entry_job_id = format!("job-{}", nanoid());
+      entry_jobs
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(entry_job_id.clone());
 
-  if let Some(info_path) = find_info_json(&media_dir) {
-    match std::fs::read(&info_path)
-      .map_err(|e| format!("read info json failed: {e}"))
-      .and_then(|b| serde_json::from_slice::<serde_json::Value>(&b).map_err(|e| format!("parse info json failed: {e}")))
-    {
-      Ok(info) => {
-        title = info.get("title").and_then(|v| v.as_str()).map(|s| s.to_string());
-        uploader = info
-          .get("uploader")
-          .or_else(|| info.get("channel"))
-          .or_else(|| info.get("uploader_id"))
-          .and_then(|v| v.as_str())
-          .map(|s| s.to_string());
-        upload_date = info
-          .get("upload_date")
-          .and_then(|v| v.as_str())
-          .and_then(ytdlp_upload_date_to_iso);
-      }
-      Err(e) => {
-        if warning.is_none() {
-          warning = Some(e);
+      match import_single_url(app.clone(), state, dir, entry_job_id, entry_media_id.clone(), entry.url.clone(), opts).await {
+        Ok(v) => {
+          if let Some(key) = entry.key {
+            imported.insert(key, entry_media_id);
+          }
+          items.push(v);
         }
+        // Cancelling the entry that is currently downloading stops the whole batch.
+        Err(e @ AppError::Cancelled) => return Err(e),
+        Err(e) => failed.push(serde_json::json!({
+          "url": entry.url,
+          "title": entry.title,
+          "media_id": entry_media_id,
+          "error": e,
+        })),
       }
     }
-  }
 
-  match ffprobe_analyze(&app, &stored_path).await {
-    Ok((d, m, is_video)) => {
-      duration = d;
-      meta = m;
-      if is_video {
-        let seek = d.map(|sec| (sec * 0.1).max(1.0).min(10.0));
-        match ffmpeg_thumbnail_data_url(&app, &stored_path, seek).await {
-          Ok(t) => thumbnail = Some(t),
-          Err(e) => {
-            if warning.is_none() {
-              warning = Some(e);
-            }
-          }
-        }
+    Ok(serde_json::json!({
+      "job_id": job_id,
+      "playlist": {
+        "id": listing.get("id"),
+        "title": listing.get("title"),
+        "uploader": listing.get("uploader").or_else(|| listing.get("channel")),
+        "entry_count": total,
+      },
+      "items": items,
+      "skipped": skipped,
+      "failed": failed,
+    }))
+  })
+  .await;
+
+  match result {
+    Ok(v) => {
+      let _ = emit_job(&app, JobProgressEvent {
+        job_id: job_id.clone(),
+        media_id: media_id.clone(),
+        job_type: JobType::Import,
+        status: JobStatus::Succeeded,
+        progress: 1.0,
+        message: None,
+        error_code: None,
+      });
+      Ok(v)
+    }
+    Err(e @ AppError::Cancelled) => {
+      let started = entry_jobs.lock().unwrap_or_else(|e| e.into_inner()).clone();
+      for id in started.iter() {
+        mark_job_cancelled(&app, id);
       }
+      mark_job_cancelled(&app, &job_id);
+      Err(e)
     }
     Err(e) => {
-      if warning.is_none() {
-        warning = Some(e);
-      }
+      let _ = emit_job(&app, JobProgressEvent {
+        job_id: job_id.clone(),
+        media_id: media_id.clone(),
+        job_type: JobType::Import,
+        status: JobStatus::Failed,
+        progress: 1.0,
+        message: Some(e.to_string()),
+        error_code: Some(e.code()),
+      });
+      Err(e)
     }
   }
+}
 
-  let _ = emit_job(&app, JobProgressEvent {
-    job_id: job_id.clone(),
-    media_id: media_id.clone(),
-    job_type: JobType::Download,
-    status: JobStatus::Succeeded,
-    progress: 1.0,
-    message: None,
-  });
+async fn ytdlp_list_playlist(
+  ytdlp: &Path,
+  url: &str,
+  cookies_path: Option<&Path>,
+  range: PlaylistRange,
+) -> Result<serde_json::Value, AppError> {
+  let mut cmd = tokio::process::Command::new(ytdlp);
+  apply_ytdlp_proxy(&mut cmd);
+  cmd
+    .kill_on_drop(true)
+    .arg("--flat-playlist")
+    .arg("--dump-single-json")
+    .arg("--no-warnings");
+  if range.start.is_some() || range.end.is_some() {
+    let start = range.start.unwrap_or(1).max(1);
+    let end = range.end.map(|n| n.to_string()).unwrap_or_default();
+    cmd.arg("--playlist-items").arg(format!("{start}:{end}"));
+  }
+  if let Some(cookies) = cookies_path {
+    cmd.arg("--cookies").arg(cookies);
+  }
+  cmd
+    .arg(url)
+    .stdout(std::process::Stdio::piped())
+    .stderr(std::process::Stdio::piped());
 
-  Ok(serde_json::json!({
-    "media_id": media_id,
-    "job_id": job_id,
-    "stored_path": stored_path.to_string_lossy(),
-    "stored_rel": stored_rel,
-    "file_size": file_size,
-    "duration": duration,
-    "meta": meta,
-    "thumbnail": thumbnail,
-    "title": title,
-    "uploader": uploader,
-    "upload_date": upload_date,
-    "warning": warning
-  }))
+  let out = cmd
+    .output()
+    .await
+    .map_err(|e| AppError::ToolMissing(format!("spawn yt-dlp failed: {e}")))?;
+  if !out.status.success() {
+    let tail = output_tail(&out.stderr, 30);
+    return Err(AppError::MediaDownloadFailed(format!("yt-dlp playlist listing failed\n{tail}")));
+  }
+  serde_json::from_slice(&out.stdout)
+    .map_err(|e| AppError::MediaDownloadFailed(format!("parse playlist listing failed: {e}")))
 }
 
-#[tauri::command]
-async fn upload_begin(app: tauri::AppHandle, args: UploadBeginArgs, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, String> {
+fn parse_playlist_entries(listing: &serde_json::Value) -> Vec<PlaylistEntry> {
+  let str_field = |v: &serde_json::Value, k: &str| {
+    v.get(k)
+      .and_then(|x| x.as_str())
+      .map(|s| s.trim().to_string())
+      .filter(|s| !s.is_empty())
+  };
+
+  let Some(entries) = listing.get("entries").and_then(|v| v.as_array()) else {
+    // Not a playlist: treat the single video as a one-entry list.
+    return str_field(listing, "webpage_url")
+      .or_else(|| str_field(listing, "original_url"))
+      .map(|url| {
+        vec![PlaylistEntry {
+          url,
+          title: str_field(listing, "title"),
+          key: str_field(listing, "extractor_key")
+            .zip(str_field(listing, "id"))
+            .map(|(ie, id)| ytdlp_extractor_key(&ie, &id)),
+        }]
+      })
+      .unwrap_or_default();
+  };
+
+  entries
+    .iter()
+    .filter_map(|e| {
+      let url = str_field(e, "url")
+        .or_else(|| str_field(e, "webpage_url"))
+        .filter(|u| u.starts_with("http://") || u.starts_with("https://"))?;
+      let key = str_field(e, "ie_key")
+        .or_else(|| str_field(e, "extractor_key"))
+        .zip(str_field(e, "id"))
+        .map(|(ie, id)| ytdlp_extractor_key(&ie, &id));
+      Some(PlaylistEntry {
+        url,
+        title: str_field(e, "title"),
+        key,
+      })
+    })
+    .collect()
+}
+
+fn ytdlp_extractor_key(extractor: &str, id: &str) -> String {
+  format!("{}:{}", extractor.trim().to_lowercase(), id.trim())
+}
+
+#[derive(serde::Deserialize)]
+struct YtDlpInfoKey {
+  id: Option<String>,
+  extractor_key: Option<String>,
+}
+
+// Maps `extractor:id` -> media id for everything already downloaded with yt-dlp.
+fn imported_extractor_ids(data_root: &Path) -> HashMap<String, String> {
+  let mut out = HashMap::new();
+  let Ok(rd) = std::fs::read_dir(data_root.join("media")) else {
+    return out;
+  };
+  for entry in rd.flatten() {
+    let path = entry.path();
+    let Some(media_id) = path.file_name().and_then(|s| s.to_str()).map(|s| s.to_string()) else {
+      continue;
+    };
+    if !path.is_dir() || validate_media_id(&media_id).is_err() {
+      continue;
+    }
+    let Some(info_path) = find_info_json(&path) else {
+      continue;
+    };
+    let Ok(bytes) = std::fs::read(&info_path) else {
+      continue;
+    };
+    if let Ok(YtDlpInfoKey { id: Some(id), extractor_key: Some(ie) }) = serde_json::from_slice::<YtDlpInfoKey>(&bytes) {
+      out.insert(ytdlp_extractor_key(&ie, &id), media_id);
+    }
+  }
+  out
+}
+
+// Optional cookies file for restricted content.
+// Priority: explicit env var > data_root/db/cookies.txt
+fn ytdlp_cookies_path(data_root: &Path) -> Option<PathBuf> {
+  std::env::var("VECHO_YTDLP_COOKIES")
+    .ok()
+    .map(PathBuf::from)
+    .or_else(|| {
+      let p = data_root.join("db").join("cookies.txt");
+      if p.is_file() { Some(p) } else { None }
+    })
+}
+
+async fn import_single_url(
+  app: tauri::AppHandle,
+  state: &Arc<AppState>,
+  dir: &Path,
+  job_id: String,
+  media_id: String,
+  url: String,
+  import_opts: &ImportOptions,
+) -> Result<serde_json::Value, AppError> {
+  let media_dir = dir.join("media").join(&media_id);
+  tokio::fs::create_dir_all(&media_dir)
+    .await
+    .map_err(|e| AppError::Internal(format!("create media dir failed: {e}")))?;
+
+  let output_template = media_dir.join("source.%(ext)s");
+
+  // Prepare tools (first run may need to download them).
+  let _ = emit_job(&app, JobProgressEvent {
+    job_id: job_id.clone(),
+    media_id: media_id.clone(),
+    job_type: JobType::Download,
+    status: JobStatus::Running,
+    progress: 0.0,
+    message: Some("preparing download tools".to_string()),
+    error_code: None,
+  });
+
+  let result: Result<serde_json::Value, AppError> = run_job(&app, &job_id, async {
+    let ytdlp = ensure_ytdlp(&app, state, dir).await?;
+    let (ffmpeg_dir, has_ffmpeg) = match ensure_ffmpeg_bundle(&app, state, dir).await {
+      Ok((ffmpeg, _ffprobe)) => (ffmpeg.parent().map(|p| p.to_path_buf()), true),
+      Err(_) => (None, false),
+    };
+
+    let cookies_path = ytdlp_cookies_path(dir);
+
+    let _ = emit_job(&app, JobProgressEvent {
+      job_id: job_id.clone(),
+      media_id: media_id.clone(),
+      job_type: JobType::Download,
+      status: JobStatus::Running,
+      progress: 0.01,
+      message: Some("starting download".to_string()),
+      error_code: None,
+    });
+
+    let is_youtube = looks_like_youtube_url(&url);
+
+    // yt-dlp may need a JS runtime for YouTube extraction (varies by version/site behavior).
+    let js_runtime = if is_youtube {
+      if is_exe_available("deno") {
+        Some("deno".to_string())
+      } else if is_exe_available("node") {
+        Some("node".to_string())
+      } else {
+        None
+      }
+    } else {
+      None
+    };
+
+    let insecure = std::env::var("VECHO_YTDLP_INSECURE")
+      .ok()
+      .map(|v| {
+        let s = v.trim().to_lowercase();
+        s == "1" || s == "true" || s == "yes"
+      })
+      .unwrap_or(false);
+
+    let mut opts = YtDlpRunOpts {
+      has_ffmpeg,
+      ffmpeg_dir: ffmpeg_dir.clone(),
+      cookies_path: cookies_path.clone(),
+      retries: if is_youtube { 20 } else { 10 },
+      fragment_retries: if is_youtube { 20 } else { 10 },
+      extractor_retries: 3,
+      socket_timeout: if is_youtube { 30 } else { 20 },
+      concurrent_fragments: if is_youtube { 2 } else { 4 },
+      force_ipv4: false,
+      youtube_compat: is_youtube,
+      js_runtime,
+      insecure,
+      format: select_ytdlp_format(has_ffmpeg, import_opts.quality.as_deref()),
+    };
+
+    match run_ytdlp_download(&app, &job_id, &media_id, &ytdlp, &url, &output_template, &opts).await {
+      Ok(()) => {}
+      Err(mut tail) => {
+        // Retry once with safer options for common flaky TLS/fragment issues.
+        if is_youtube && is_retryable_ytdlp_failure(&tail) {
+          let _ = emit_job(&app, JobProgressEvent {
+            job_id: job_id.clone(),
+            media_id: media_id.clone(),
+            job_type: JobType::Download,
+            status: JobStatus::Running,
+            progress: 0.02,
+            message: Some("download hiccup detected; retrying with safer settings (IPv4, fewer fragments)".to_string()),
+            error_code: None,
+          });
+
+          opts.force_ipv4 = true;
+          opts.concurrent_fragments = 1;
+          opts.retries = 30;
+          opts.fragment_retries = 30;
+          opts.socket_timeout = 45;
+
+          match run_ytdlp_download(&app, &job_id, &media_id, &ytdlp, &url, &output_template, &opts).await {
+            Ok(()) => {
+              tail.clear();
+            }
+            Err(tail2) => {
+              tail = tail2;
+            }
+          }
+        }
+
+        // Site changes usually need a newer yt-dlp: update once and retry.
+        let update_config = load_ytdlp_update_config(dir);
+        if !tail.trim().is_empty()
+          && is_ytdlp_extractor_break(&tail)
+          && !offline_mode()
+          && update_config.pinned_version.is_none()
+        {
+          let _ = emit_job(&app, JobProgressEvent {
+            job_id: job_id.clone(),
+            media_id: media_id.clone(),
+            job_type: JobType::Download,
+            status: JobStatus::Running,
+            progress: 0.02,
+            message: Some("extractor error; updating yt-dlp and retrying".to_string()),
+            error_code: None,
+          });
+          let updated = update_ytdlp_binary(state, dir, &update_config.channel, None, false).await.ok();
+          if let Some(v) = updated.filter(|v| v.get("updated").and_then(|u| u.as_bool()) == Some(true)) {
+            let new_ytdlp = PathBuf::from(v.get("path").and_then(|p| p.as_str()).unwrap_or_default());
+            match run_ytdlp_download(&app, &job_id, &media_id, &new_ytdlp, &url, &output_template, &opts).await {
+              Ok(()) => tail.clear(),
+              Err(tail2) => tail = tail2,
+            }
+          }
+        }
+
+        if !tail.trim().is_empty() {
+          return Err(AppError::MediaDownloadFailed(format!("yt-dlp failed\n{tail}")));
+        }
+      }
+    }
+
+    let _ = emit_job(&app, JobProgressEvent {
+      job_id: job_id.clone(),
+      media_id: media_id.clone(),
+      job_type: JobType::Download,
+      status: JobStatus::Running,
+      progress: 0.92,
+      message: Some("analyzing media".to_string()),
+      error_code: None,
+    });
+
+    let stored_path = find_source_file(&media_dir)?;
+    let file_size = std::fs::metadata(&stored_path)
+      .map(|m| m.len())
+      .unwrap_or(0);
+    let stored_rel = stored_path
+      .strip_prefix(dir)
+      .ok()
+      .map(|p| p.to_string_lossy().replace('\\', "/"));
+
+    let mut title: Option<String> = None;
+    let mut uploader: Option<String> = None;
+    let mut upload_date: Option<String> = None;
+
+    let mut duration: Option<f64> = None;
+    let mut meta: Option<serde_json::Value> = None;
+    let mut thumbnail: Option<String> = None;
+    let mut warning: Option<String> = None;
+    let mut info_json: Option<serde_json::Value> = None;
+
+    if let Some(info_path) = find_info_json(&media_dir) {
+      match std::fs::read(&info_path)
+        .map_err(|e| format!("read info json failed: {e}"))
+        .and_then(|b| serde_json::from_slice::<serde_json::Value>(&b).map_err(|e| format!("parse info json failed: {e}")))
+      {
+        Ok(info) => {
+          title = info.get("title").and_then(|v| v.as_str()).map(|s| s.to_string());
+          uploader = info
+            .get("uploader")
+            .or_else(|| info.get("channel"))
+            .or_else(|| info.get("uploader_id"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+          upload_date = info
+            .get("upload_date")
+            .and_then(|v| v.as_str())
+            .and_then(ytdlp_upload_date_to_iso);
+          info_json = Some(info);
+        }
+        Err(e) => {
+          if warning.is_none() {
+            warning = Some(e);
+          }
+        }
+      }
+    }
+
+    match ffprobe_analyze(&app, &stored_path).await {
+      Ok((d, m, is_video)) => {
+        duration = d;
+        meta = m;
+        if is_video {
+          let seek = d.map(|sec| (sec * 0.1).max(1.0).min(10.0));
+          match ffmpeg_thumbnail_data_url(&app, &stored_path, seek).await {
+            Ok(t) => thumbnail = Some(t),
+            Err(e) => {
+              if warning.is_none() {
+                warning = Some(e);
+              }
+            }
+          }
+        }
+      }
+      Err(e) => {
+        if warning.is_none() {
+          warning = Some(e);
+        }
+      }
+    }
+
+    // Source subtitles are best-effort: a failure here only becomes a warning.
+    let mut subtitle_tracks: Vec<String> = Vec::new();
+    let mut transcription: Option<serde_json::Value> = None;
+    if import_opts.source_subtitles || import_opts.auto_captions {
+      let _ = emit_job(&app, JobProgressEvent {
+        job_id: job_id.clone(),
+        media_id: media_id.clone(),
+        job_type: JobType::Download,
+        status: JobStatus::Running,
+        progress: 0.96,
+        message: Some("downloading subtitles".to_string()),
+        error_code: None,
+      });
+      match import_source_subtitles(&ytdlp, &url, &media_dir, &media_id, &opts, import_opts, info_json.as_ref()).await {
+        Ok((ids, seeded)) => {
+          subtitle_tracks = ids;
+          transcription = seeded;
+        }
+        Err(e) => {
+          if warning.is_none() {
+            warning = Some(e);
+          }
+        }
+      }
+    }
+
+    Ok(serde_json::json!({
+      "media_id": media_id,
+      "job_id": job_id,
+      "stored_path": stored_path.to_string_lossy(),
+      "stored_rel": stored_rel,
+      "file_size": file_size,
+      "duration": duration,
+      "meta": meta,
+      "thumbnail": thumbnail,
+      "title": title,
+      "uploader": uploader,
+      "upload_date": upload_date,
+      "subtitle_tracks": subtitle_tracks,
+      "transcription": transcription,
+      "warning": warning
+    }))
+  })
+  .await;
+
+  match result {
+    Ok(v) => {
+      let _ = emit_job(&app, JobProgressEvent {
+        job_id: job_id.clone(),
+        media_id: media_id.clone(),
+        job_type: JobType::Download,
+        status: JobStatus::Succeeded,
+        progress: 1.0,
+        message: None,
+        error_code: None,
+      });
+      Ok(v)
+    }
+    Err(e) => {
+      let _ = emit_job(&app, JobProgressEvent {
+        job_id: job_id.clone(),
+        media_id: media_id.clone(),
+        job_type: JobType::Download,
+        status: JobStatus::Failed,
+        progress: 1.0,
+        message: Some(e.to_string()),
+        error_code: Some(e.code()),
+      });
+      Err(e)
+    }
+  }
+}
+
+#[tauri::command]
+async fn upload_begin(app: tauri::AppHandle, args: UploadBeginArgs, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, AppError> {
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
 
   let upload_id = format!("upl-{}", nanoid());
@@ -1639,7 +2283,7 @@ async fn upload_begin(app: tauri::AppHandle, args: UploadBeginArgs, state: State
     .media_id
     .unwrap_or_else(|| format!("media-{}", nanoid()));
   validate_media_id(&media_id)?;
-  let job_id = format!("job-{}", nanoid());
+  let job_id = new_job_id();
 
   let file_name = std::path::Path::new(&args.name)
     .file_name()
@@ -1671,7 +2315,8 @@ async fn upload_begin(app: tauri::AppHandle, args: UploadBeginArgs, state: State
     }
   })
   .await
-  .map_err(|e| format!("join upload_begin task failed: {e}"))??;
+  .map_err(|e| AppError::Internal(format!("join upload_begin task failed: {e}")))?
+  .map_err(AppError::Internal)?;
 
   {
     let mut uploads = state.uploads.lock().await;
@@ -1697,6 +2342,7 @@ async fn upload_begin(app: tauri::AppHandle, args: UploadBeginArgs, state: State
     status: JobStatus::Running,
     progress: 0.0,
     message: Some(format!("uploading {file_name}")),
+    error_code: None,
   });
 
   Ok(serde_json::json!({
@@ -1707,7 +2353,7 @@ async fn upload_begin(app: tauri::AppHandle, args: UploadBeginArgs, state: State
 }
 
 #[tauri::command]
-async fn upload_chunk(app: tauri::AppHandle, args: UploadChunkArgs, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, String> {
+async fn upload_chunk(app: tauri::AppHandle, args: UploadChunkArgs, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, AppError> {
   let UploadChunkArgs {
     upload_id,
     offset,
@@ -1718,7 +2364,7 @@ async fn upload_chunk(app: tauri::AppHandle, args: UploadChunkArgs, state: State
     let uploads = state.uploads.lock().await;
     let session = uploads
       .get(&upload_id)
-      .ok_or_else(|| "upload not found".to_string())?;
+      .ok_or_else(|| AppError::InvalidInput("upload not found".to_string()))?;
     (
       session.tmp_path.clone(),
       session.media_id.clone(),
@@ -1730,7 +2376,7 @@ async fn upload_chunk(app: tauri::AppHandle, args: UploadChunkArgs, state: State
   };
 
   if offset != expected_offset {
-    return Err(format!("unexpected upload offset: expected {expected_offset}, got {offset}"));
+    return Err(AppError::InvalidInput(format!("unexpected upload offset: expected {expected_offset}, got {offset}")));
   }
 
   let len = bytes.len() as u64;
@@ -1750,15 +2396,16 @@ async fn upload_chunk(app: tauri::AppHandle, args: UploadChunkArgs, state: State
     }
   })
   .await
-  .map_err(|e| format!("join upload_chunk task failed: {e}"))??;
+  .map_err(|e| AppError::Internal(format!("join upload_chunk task failed: {e}")))?
+  .map_err(AppError::Internal)?;
 
   let received = {
     let mut uploads = state.uploads.lock().await;
     let session = uploads
       .get_mut(&upload_id)
-      .ok_or_else(|| "upload not found".to_string())?;
+      .ok_or_else(|| AppError::InvalidInput("upload not found".to_string()))?;
     if session.received != expected_offset {
-      return Err("concurrent upload detected".to_string());
+      return Err(AppError::InvalidInput("concurrent upload detected".to_string()));
     }
     session.received += len;
     session.received
@@ -1778,6 +2425,7 @@ async fn upload_chunk(app: tauri::AppHandle, args: UploadChunkArgs, state: State
     status: JobStatus::Running,
     progress,
     message: Some(format!("uploading {file_name}")),
+    error_code: None,
   });
 
   Ok(serde_json::json!({
@@ -1786,10 +2434,10 @@ async fn upload_chunk(app: tauri::AppHandle, args: UploadChunkArgs, state: State
 }
 
 #[tauri::command]
-async fn upload_finish(app: tauri::AppHandle, args: UploadFinishArgs, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, String> {
+async fn upload_finish(app: tauri::AppHandle, args: UploadFinishArgs, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, AppError> {
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
 
   let UploadFinishArgs { upload_id } = args;
@@ -1797,7 +2445,7 @@ async fn upload_finish(app: tauri::AppHandle, args: UploadFinishArgs, state: Sta
     let mut uploads = state.uploads.lock().await;
     uploads
       .remove(&upload_id)
-      .ok_or_else(|| "upload not found".to_string())?
+      .ok_or_else(|| AppError::InvalidInput("upload not found".to_string()))?
   };
 
   tokio::task::spawn_blocking({
@@ -1813,7 +2461,8 @@ async fn upload_finish(app: tauri::AppHandle, args: UploadFinishArgs, state: Sta
     }
   })
   .await
-  .map_err(|e| format!("join upload_finish task failed: {e}"))??;
+  .map_err(|e| AppError::Internal(format!("join upload_finish task failed: {e}")))?
+  .map_err(AppError::Internal)?;
 
   let _ = emit_job(&app, JobProgressEvent {
     job_id: session.job_id.clone(),
@@ -1822,6 +2471,7 @@ async fn upload_finish(app: tauri::AppHandle, args: UploadFinishArgs, state: Sta
     status: JobStatus::Running,
     progress: 0.905,
     message: Some("preparing media tools".to_string()),
+    error_code: None,
   });
 
   let mut duration: Option<f64> = None;
@@ -1849,6 +2499,7 @@ async fn upload_finish(app: tauri::AppHandle, args: UploadFinishArgs, state: Sta
     status: JobStatus::Running,
     progress: 0.92,
     message: Some(if tools_ready { "analyzing media" } else { "media tools unavailable" }.to_string()),
+    error_code: None,
   });
 
   if tools_ready {
@@ -1875,6 +2526,7 @@ async fn upload_finish(app: tauri::AppHandle, args: UploadFinishArgs, state: Sta
     status: JobStatus::Succeeded,
     progress: 1.0,
     message: None,
+    error_code: None,
   });
 
   Ok(serde_json::json!({
@@ -1892,7 +2544,7 @@ async fn upload_finish(app: tauri::AppHandle, args: UploadFinishArgs, state: Sta
   }))
 }
 
-#[derive(serde::Deserialize, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 #[serde(rename_all = "snake_case")]
 enum TranscriptionEngine {
   LocalSherpaOnnx,
@@ -1900,7 +2552,7 @@ enum TranscriptionEngine {
   OpenaiCompatible,
 }
 
-#[derive(serde::Deserialize, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct OpenAiTranscriptionConfig {
   base_url: String,
@@ -1908,7 +2560,7 @@ struct OpenAiTranscriptionConfig {
   model: String,
 }
 
-#[derive(serde::Deserialize, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct TranscriptionConfig {
   engine: TranscriptionEngine,
@@ -1919,24 +2571,44 @@ struct TranscriptionConfig {
   num_threads: Option<u32>,
   #[serde(default)]
   use_itn: Option<bool>,
+  /// Explicit whisper-cli binary; skips the bundled/PATH lookup.
+  #[serde(default)]
+  whisper_cpp_path: Option<String>,
+  /// whisper.cpp model id (see WHISPER_CPP_MODELS), default large-v3-turbo-q5_0.
+  #[serde(default)]
+  whisper_model: Option<String>,
+  /// User-supplied GGML model file; overrides `whisper_model`.
+  #[serde(default)]
+  whisper_model_path: Option<String>,
+  /// Split on silero-vad speech spans before local SenseVoice recognition (default on).
+  #[serde(default)]
+  vad: Option<bool>,
+  /// Run speaker diarization after recognition (sherpa-onnx, local).
+  #[serde(default)]
+  diarize: Option<bool>,
+  /// Known speaker count; otherwise the clustering threshold decides.
+  #[serde(default)]
+  num_speakers: Option<u32>,
   openai: OpenAiTranscriptionConfig,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 struct TranscribeMediaArgs {
   media_id: String,
   config: TranscriptionConfig,
 }
 
-#[derive(serde::Deserialize, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 #[serde(rename_all = "snake_case")]
 enum AiProvider {
   OpenaiCompatible,
   Gemini,
+  Anthropic,
+  Ollama,
 }
 
-#[derive(serde::Deserialize, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct OpenAiAiConfig {
   base_url: String,
@@ -1945,23 +2617,68 @@ struct OpenAiAiConfig {
   summary_model: String,
 }
 
-#[derive(serde::Deserialize, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct GeminiAiConfig {
   base_url: String,
   api_key: String,
   model: String,
+  /// Chat sampling temperature; defaults to 0.2.
+  #[serde(default)]
+  temperature: Option<f32>,
+  /// Chat reply cap (`generationConfig.maxOutputTokens`).
+  #[serde(default)]
+  max_output_tokens: Option<u32>,
+  /// Threshold applied to all harm categories, e.g. "BLOCK_ONLY_HIGH" or "BLOCK_NONE".
+  #[serde(default)]
+  safety_threshold: Option<String>,
 }
 
-#[derive(serde::Deserialize, Clone)]
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct AiSettings {
   provider: AiProvider,
   openai: OpenAiAiConfig,
   gemini: GeminiAiConfig,
+  #[serde(default)]
+  anthropic: AnthropicAiConfig,
+  #[serde(default)]
+  ollama: OllamaAiConfig,
+  /// Optional embedding model for chat retrieval.
+  #[serde(default)]
+  embedding: EmbeddingConfig,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, serde::Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct AnthropicAiConfig {
+  /// Empty means https://api.anthropic.com.
+  base_url: String,
+  api_key: String,
+  model: String,
+  /// Required by the Messages API; defaults to 8192.
+  max_tokens: Option<u32>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct OllamaAiConfig {
+  /// Empty means http://localhost:11434.
+  base_url: String,
+  model: String,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct EmbeddingConfig {
+  /// OpenAI-compatible base URL (e.g. a local Ollama/LM Studio server); empty reuses `openai.baseUrl`.
+  base_url: String,
+  api_key: String,
+  /// Empty disables vector retrieval.
+  model: String,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 struct SummarizeMediaArgs {
   media_id: String,
@@ -1974,7 +2691,7 @@ struct SummarizeMediaArgs {
   user_lang: Option<String>,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 struct OptimizeTranscriptionArgs {
   media_id: String,
@@ -1991,7 +2708,7 @@ enum ChatRole {
   System,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct ChatMessageIn {
   role: ChatRole,
@@ -2010,6 +2727,15 @@ struct ChatMediaArgs {
   include_summary: bool,
   #[serde(default)]
   user_lang: Option<String>,
+  /// Emit `chat_stream` events while the reply is generated.
+  #[serde(default)]
+  stream: bool,
+  /// Id for the reply (and its stream events); generated when omitted.
+  #[serde(default)]
+  message_id: Option<String>,
+  /// Saved conversation to continue; `messages` then holds only the new turn(s).
+  #[serde(default)]
+  conversation_id: Option<String>,
 }
 
 fn default_true() -> bool {
@@ -2021,19 +2747,19 @@ async fn transcribe_media(
   app: tauri::AppHandle,
   args: TranscribeMediaArgs,
   state: State<'_, Arc<AppState>>,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, AppError> {
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
 
   let media_id = args.media_id.trim().to_string();
   validate_media_id(&media_id)?;
 
-  let job_id = format!("job-{}", nanoid());
+  let job_id = new_job_id();
   let media_dir = dir.join("media").join(&media_id);
   if !media_dir.is_dir() {
-    return Err("media not found".to_string());
+    return Err(AppError::MediaNotFound);
   }
 
   let _ = emit_job(&app, JobProgressEvent {
@@ -2043,9 +2769,11 @@ async fn transcribe_media(
     status: JobStatus::Running,
     progress: 0.0,
     message: Some("preparing transcription".to_string()),
+    error_code: None,
   });
+  remember_job_resume(&app, &job_id, "transcribe_media", &args);
 
-  let result: Result<serde_json::Value, String> = async {
+  let result: Result<(serde_json::Value, Option<String>), AppError> = run_job(&app, &job_id, async {
     // Locate source file.
     let source_path = find_source_file(&media_dir)?;
 
@@ -2056,8 +2784,7 @@ async fn transcribe_media(
       dir,
       Some((&job_id, &media_id, JobType::Transcribe, 0.0, 0.04)),
     )
-    .await
-    .map_err(|e| format!("ffmpeg unavailable: {e}"))?;
+    .await?;
     let ffmpeg = resolve_sidecar(&app, "ffmpeg")?;
     let wav_path = media_dir.join("audio.16k.wav");
 
@@ -2068,11 +2795,12 @@ async fn transcribe_media(
       status: JobStatus::Running,
       progress: 0.05,
       message: Some("extracting audio".to_string()),
+      error_code: None,
     });
 
     ffmpeg_extract_audio_wav(&ffmpeg, &source_path, &wav_path)
       .await
-      .map_err(|e| format!("audio extract failed: {e}"))?;
+      .map_err(|e| AppError::Internal(format!("audio extract failed: {e}")))?;
 
     let _ = emit_job(&app, JobProgressEvent {
       job_id: job_id.clone(),
@@ -2081,6 +2809,7 @@ async fn transcribe_media(
       status: JobStatus::Running,
       progress: 0.12,
       message: Some("preparing transcription engine".to_string()),
+      error_code: None,
     });
 
     let transcription = match args.config.engine {
@@ -2124,24 +2853,48 @@ async fn transcribe_media(
         .await?;
 
         // SenseVoice offline inference can OOM on long audio if we feed the whole file at once.
-        // Split into overlapped chunks to avoid boundary word drops.
+        // Prefer silero-vad speech spans; fall back to fixed overlapped chunks if VAD is unavailable.
         const CHUNK_SECONDS: u32 = 45;
         // Large overlap is intentional: it ensures every moment of audio is decoded
         // at least once away from chunk edges, which reduces boundary deletions.
         const OVERLAP_MS: i64 = 8000;
         let chunks_dir = media_dir.join("_sensevoice_chunks");
-        let _ = emit_job(&app, JobProgressEvent {
-          job_id: job_id.clone(),
-          media_id: media_id.clone(),
-          job_type: JobType::Transcribe,
-          status: JobStatus::Running,
-          progress: 0.27,
-          message: Some(format!("切分音频（每段 {CHUNK_SECONDS}s，重叠 {}ms）", OVERLAP_MS)),
-        });
+        let mut vad_chunks: Option<Vec<AudioChunk>> = None;
+        let mut vad_note = String::new();
+        if args.config.vad.unwrap_or(true) {
+          let _ = emit_job(&app, JobProgressEvent {
+            job_id: job_id.clone(),
+            media_id: media_id.clone(),
+            job_type: JobType::Transcribe,
+            status: JobStatus::Running,
+            progress: 0.27,
+            message: Some("检测语音片段（VAD）".to_string()),
+            error_code: None,
+          });
+          match vad_split_wav(&app, state.inner(), dir, &sherpa, &ffmpeg, &wav_path, &chunks_dir).await {
+            Ok(c) if !c.is_empty() => vad_chunks = Some(c),
+            Ok(_) => {}
+            Err(e) => vad_note = format!("VAD 不可用（{e}），"),
+          }
+        }
 
-        let chunks = ffmpeg_split_wav_segments_with_overlap(&app, &ffmpeg, &wav_path, &chunks_dir, CHUNK_SECONDS, OVERLAP_MS)
-          .await
-          .map_err(|e| format!("audio chunking failed: {e}"))?;
+        let chunks = match vad_chunks {
+          Some(c) => c,
+          None => {
+            let _ = emit_job(&app, JobProgressEvent {
+              job_id: job_id.clone(),
+              media_id: media_id.clone(),
+              job_type: JobType::Transcribe,
+              status: JobStatus::Running,
+              progress: 0.27,
+              message: Some(format!("{vad_note}切分音频（每段 {CHUNK_SECONDS}s，重叠 {}ms）", OVERLAP_MS)),
+              error_code: None,
+            });
+            ffmpeg_split_wav_segments_with_overlap(&app, &ffmpeg, &wav_path, &chunks_dir, CHUNK_SECONDS, OVERLAP_MS)
+              .await
+              .map_err(|e| AppError::Internal(format!("audio chunking failed: {e}")))?
+          }
+        };
 
         let mut chunk_paths: Vec<PathBuf> = Vec::with_capacity(chunks.len());
         for ch in &chunks {
@@ -2155,6 +2908,7 @@ async fn transcribe_media(
           status: JobStatus::Running,
           progress: 0.28,
           message: Some(format!("recognizing (SenseVoice, chunks={})", chunk_paths.len())),
+          error_code: None,
         });
 
         let requested_auto = lang == "auto";
@@ -2173,7 +2927,7 @@ async fn transcribe_media(
         )
         .await;
 
-        let mut res_list = res_list_res?;
+        let mut res_list = res_list_res.map_err(AppError::Internal)?;
 
         // Smart auto: if one language is clearly dominant, rerun with an explicit language.
         // This reduces deletions (especially English) and makes output more stable.
@@ -2191,6 +2945,7 @@ async fn transcribe_media(
             status: JobStatus::Running,
             progress: 0.31,
             message: Some(format!("auto 检测到主要语言={lock}，将以该语言重新识别以减少漏词")),
+            error_code: None,
           });
 
           res_list = run_sherpa_onnx_sense_voice(
@@ -2205,7 +2960,8 @@ async fn transcribe_media(
             &job_id,
             &media_id,
           )
-          .await?;
+          .await
+          .map_err(AppError::Internal)?;
         }
 
         let lang_hint = locked_lang
@@ -2217,7 +2973,7 @@ async fn transcribe_media(
         // Cleanup chunk wav files.
         let _ = tokio::fs::remove_dir_all(&chunks_dir).await;
         if segs.is_empty() {
-          return Err("SenseVoice 没有识别到任何文本".to_string());
+          return Err(AppError::NothingRecognized("SenseVoice 没有识别到任何文本".to_string()));
         }
 
         let overall_lang = if let Some(lock) = locked_lang.as_deref() {
@@ -2234,8 +2990,9 @@ async fn transcribe_media(
         };
 
         let model_label = "sherpa-onnx:sensevoice-small-float";
-        let t = build_transcription(&media_id, Some(&overall_lang), model_label, segs);
-        write_json_atomic(&media_dir.join("transcription.json"), &t)?;
+        let t = build_transcription_with_words(&media_id, Some(&overall_lang), model_label, segs);
+        write_json_atomic(&media_dir.join("transcription.json"), &t).map_err(AppError::Internal)?;
+        mark_search_dirty(&media_dir);
         t
       }
       TranscriptionEngine::LocalWhisperCpp => {
@@ -2260,18 +3017,56 @@ async fn transcribe_media(
 
         let num_threads = args.config.num_threads.unwrap_or(0);
 
-        let whisper = ensure_whisper_cpp(
-          &app,
-          state.inner(),
-          dir,
-          &job_id,
-          &media_id,
-          allow_cuda,
-          require_cuda,
-        )
-        .await?;
+        let custom_whisper = args
+          .config
+          .whisper_cpp_path
+          .as_deref()
+          .map(str::trim)
+          .filter(|p| !p.is_empty());
+        let whisper = match custom_whisper {
+          Some(p) => whisper_cpp_runtime_from_path(Path::new(p), allow_cuda).await.map_err(AppError::ToolMissing)?,
+          None => {
+            ensure_whisper_cpp(
+              &app,
+              state.inner(),
+              dir,
+              &job_id,
+              &media_id,
+              allow_cuda,
+              require_cuda,
+            )
+            .await?
+          }
+        };
 
-        let model_path = ensure_whisper_cpp_model(&app, state.inner(), dir, &job_id, &media_id).await?;
+        let custom_model = args
+          .config
+          .whisper_model_path
+          .as_deref()
+          .map(str::trim)
+          .filter(|p| !p.is_empty());
+        let (model_path, model_label) = match custom_model {
+          Some(p) => {
+            let p = PathBuf::from(p);
+            if !p.is_file() {
+              return Err(AppError::ToolMissing(format!("whisper model not found: {}", p.display())));
+            }
+            verify_ggml_file(&p).map_err(AppError::InvalidInput)?;
+            let name = p.file_name().and_then(|n| n.to_str()).unwrap_or("custom").to_string();
+            (p, format!("whisper.cpp:custom:{name}"))
+          }
+          None => {
+            let model = args
+              .config
+              .whisper_model
+              .as_deref()
+              .map(str::trim)
+              .filter(|m| !m.is_empty())
+              .unwrap_or(DEFAULT_WHISPER_CPP_MODEL);
+            let p = ensure_whisper_cpp_model(&app, state.inner(), dir, &job_id, &media_id, model).await?;
+            (p, format!("whisper.cpp:{model}"))
+          }
+        };
 
         let _ = emit_job(&app, JobProgressEvent {
           job_id: job_id.clone(),
@@ -2284,6 +3079,7 @@ async fn transcribe_media(
             whisper.provider,
             if num_threads > 0 { num_threads } else { 0 }
           )),
+          error_code: None,
         });
 
         let (detected_lang, segs) = run_whisper_cpp(
@@ -2296,16 +3092,17 @@ async fn transcribe_media(
           &job_id,
           &media_id,
         )
-        .await?;
+        .await
+        .map_err(AppError::Internal)?;
 
         if segs.is_empty() {
-          return Err("whisper.cpp returned no text".to_string());
+          return Err(AppError::NothingRecognized("whisper.cpp returned no text".to_string()));
         }
 
         let overall_lang = detected_lang.unwrap_or_else(|| lang.clone());
-        let model_label = "whisper.cpp:large-v3-turbo-q5_0";
-        let t = build_transcription(&media_id, Some(&overall_lang), model_label, segs);
-        write_json_atomic(&media_dir.join("transcription.json"), &t)?;
+        let t = build_transcription_with_words(&media_id, Some(&overall_lang), &model_label, segs);
+        write_json_atomic(&media_dir.join("transcription.json"), &t).map_err(AppError::Internal)?;
+        mark_search_dirty(&media_dir);
         t
       }
       TranscriptionEngine::OpenaiCompatible => {
@@ -2316,11 +3113,13 @@ async fn transcribe_media(
           status: JobStatus::Running,
           progress: 0.12,
           message: Some("sending audio to provider".to_string()),
+          error_code: None,
         });
 
         let cfg = args.config.openai.clone();
         let t = openai_transcribe(&media_id, &wav_path, &args.config.language, &cfg).await?;
-        write_json_atomic(&media_dir.join("transcription.json"), &t)?;
+        write_json_atomic(&media_dir.join("transcription.json"), &t).map_err(AppError::Internal)?;
+        mark_search_dirty(&media_dir);
         t
       }
     };
@@ -2332,14 +3131,35 @@ async fn transcribe_media(
       status: JobStatus::Running,
       progress: 0.96,
       message: Some("finalizing".to_string()),
+      error_code: None,
     });
 
-    Ok(transcription)
-  }
+    // Diarization is best-effort: the transcript is already saved, so a failure is reported, not fatal.
+    let mut transcription = transcription;
+    let mut diarization_error: Option<String> = None;
+    if args.config.diarize.unwrap_or(false) {
+      match diarize_transcription(
+        &app,
+        state.inner(),
+        dir,
+        &job_id,
+        &media_id,
+        &mut transcription,
+        args.config.num_speakers,
+      )
+      .await
+      {
+        Ok(_) => write_json_atomic(&media_dir.join("transcription.json"), &transcription).map_err(AppError::Internal)?,
+        Err(e) => diarization_error = Some(e.to_string()),
+      }
+    }
+
+    Ok((transcription, diarization_error))
+  })
   .await;
 
   match result {
-    Ok(transcription) => {
+    Ok((transcription, diarization_error)) => {
       let _ = emit_job(&app, JobProgressEvent {
         job_id: job_id.clone(),
         media_id: media_id.clone(),
@@ -2347,12 +3167,14 @@ async fn transcribe_media(
         status: JobStatus::Succeeded,
         progress: 1.0,
         message: None,
+        error_code: None,
       });
 
       Ok(serde_json::json!({
         "media_id": media_id,
         "job_id": job_id,
         "transcription": transcription,
+        "diarization_error": diarization_error,
       }))
     }
     Err(e) => {
@@ -2362,22 +3184,222 @@ async fn transcribe_media(
         job_type: JobType::Transcribe,
         status: JobStatus::Failed,
         progress: 1.0,
-        message: Some(e.clone()),
+        message: Some(e.to_string()),
+        error_code: Some(e.code()),
+      });
+      Err(e)
+    }
+  }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DiarizeMediaArgs {
+  media_id: String,
+  #[serde(default)]
+  num_speakers: Option<u32>,
+}
+
+#[tauri::command]
+async fn diarize_media(
+  app: tauri::AppHandle,
+  args: DiarizeMediaArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
+
+  let media_id = args.media_id.trim().to_string();
+  validate_media_id(&media_id)?;
+  let media_dir = dir.join("media").join(&media_id);
+  if !media_dir.is_dir() {
+    return Err(AppError::MediaNotFound);
+  }
+
+  let job_id = new_job_id();
+  let _ = emit_job(&app, JobProgressEvent {
+    job_id: job_id.clone(),
+    media_id: media_id.clone(),
+    job_type: JobType::Transcribe,
+    status: JobStatus::Running,
+    progress: 0.0,
+    message: Some("preparing speaker diarization".to_string()),
+    error_code: None,
+  });
+  remember_job_resume(&app, &job_id, "diarize_media", &args);
+
+  let result: Result<serde_json::Value, AppError> = run_job(&app, &job_id, async {
+    let transcription_path = media_dir.join("transcription.json");
+    let mut transcription = try_load_json(&transcription_path)
+      .await
+      .ok_or_else(|| AppError::InvalidInput("no transcription for this media".to_string()))?;
+
+    let wav_path = media_dir.join("audio.16k.wav");
+    if !wav_path.is_file() {
+      let source_path = find_source_file(&media_dir)?;
+      let (ffmpeg, _) = ensure_ffmpeg_bundle_with_job(
+        &app,
+        state.inner(),
+        dir,
+        Some((&job_id, &media_id, JobType::Transcribe, 0.0, 0.05)),
+      )
+      .await?;
+      ffmpeg_extract_audio_wav(&ffmpeg, &source_path, &wav_path)
+        .await
+        .map_err(|e| AppError::Internal(format!("audio extract failed: {e}")))?;
+    }
+
+    let count = diarize_transcription(&app, state.inner(), dir, &job_id, &media_id, &mut transcription, args.num_speakers).await?;
+    write_json_atomic(&transcription_path, &transcription).map_err(AppError::Internal)?;
+    Ok(serde_json::json!({
+      "media_id": media_id,
+      "job_id": job_id,
+      "speaker_count": count,
+      "transcription": transcription,
+    }))
+  })
+  .await;
+
+  match result {
+    Ok(payload) => {
+      let _ = emit_job(&app, JobProgressEvent {
+        job_id: job_id.clone(),
+        media_id: media_id.clone(),
+        job_type: JobType::Transcribe,
+        status: JobStatus::Succeeded,
+        progress: 1.0,
+        message: None,
+        error_code: None,
+      });
+      Ok(payload)
+    }
+    Err(e) => {
+      let _ = emit_job(&app, JobProgressEvent {
+        job_id: job_id.clone(),
+        media_id: media_id.clone(),
+        job_type: JobType::Transcribe,
+        status: JobStatus::Failed,
+        progress: 1.0,
+        message: Some(e.to_string()),
+        error_code: Some(e.code()),
       });
       Err(e)
     }
   }
 }
 
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RenameSpeakerArgs {
+  media_id: String,
+  speaker_id: String,
+  name: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MergeSpeakersArgs {
+  media_id: String,
+  /// Speakers folded into `into`.
+  from: Vec<String>,
+  into: String,
+}
+
+async fn load_media_transcription(
+  app: &tauri::AppHandle,
+  state: &AppState,
+  media_id: &str,
+) -> Result<(PathBuf, serde_json::Value), AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(app).map_err(AppError::Internal) })
+    .await?;
+  validate_media_id(media_id)?;
+  let media_dir = dir.join("media").join(media_id);
+  if !media_dir.is_dir() {
+    return Err(AppError::MediaNotFound);
+  }
+  let path = media_dir.join("transcription.json");
+  let t = try_load_json(&path)
+    .await
+    .ok_or_else(|| AppError::InvalidInput("no transcription for this media".to_string()))?;
+  Ok((path, t))
+}
+
+#[tauri::command]
+async fn rename_speaker(
+  app: tauri::AppHandle,
+  args: RenameSpeakerArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let name = args.name.trim();
+  if name.is_empty() {
+    return Err(AppError::InvalidInput("speaker name is empty".to_string()));
+  }
+  let (path, mut t) = load_media_transcription(&app, &state, args.media_id.trim()).await?;
+  let speaker = t
+    .get_mut("speakers")
+    .and_then(|v| v.as_array_mut())
+    .and_then(|arr| arr.iter_mut().find(|s| s.get("id").and_then(|v| v.as_str()) == Some(args.speaker_id.trim())))
+    .ok_or_else(|| AppError::InvalidInput(format!("speaker not found: {}", args.speaker_id.trim())))?;
+  speaker["name"] = serde_json::Value::String(name.to_string());
+  write_json_atomic(&path, &t).map_err(AppError::Internal)?;
+  Ok(serde_json::json!({ "media_id": args.media_id.trim(), "transcription": t }))
+}
+
+#[tauri::command]
+async fn merge_speakers(
+  app: tauri::AppHandle,
+  args: MergeSpeakersArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let into = args.into.trim().to_string();
+  let from: Vec<String> = args
+    .from
+    .iter()
+    .map(|s| s.trim().to_string())
+    .filter(|s| !s.is_empty() && *s != into)
+    .collect();
+  if from.is_empty() {
+    return Err(AppError::InvalidInput("no speakers to merge".to_string()));
+  }
+  let (path, mut t) = load_media_transcription(&app, &state, args.media_id.trim()).await?;
+  let names = transcription_speaker_names(&t);
+  if !names.contains_key(&into) {
+    return Err(AppError::InvalidInput(format!("speaker not found: {into}")));
+  }
+
+  let mut moved = 0usize;
+  if let Some(segs) = t.get_mut("segments").and_then(|v| v.as_array_mut()) {
+    for seg in segs.iter_mut() {
+      if seg.get("speaker").and_then(|v| v.as_str()).is_some_and(|s| from.iter().any(|f| f == s)) {
+        seg["speaker"] = serde_json::Value::String(into.clone());
+        moved += 1;
+      }
+    }
+  }
+  if let Some(arr) = t.get_mut("speakers").and_then(|v| v.as_array_mut()) {
+    arr.retain(|s| !s.get("id").and_then(|v| v.as_str()).is_some_and(|id| from.iter().any(|f| f == id)));
+  }
+  write_json_atomic(&path, &t).map_err(AppError::Internal)?;
+  Ok(serde_json::json!({
+    "media_id": args.media_id.trim(),
+    "segments_updated": moved,
+    "transcription": t,
+  }))
+}
+
 #[tauri::command]
 async fn summarize_media(
   app: tauri::AppHandle,
   args: SummarizeMediaArgs,
   state: State<'_, Arc<AppState>>,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, AppError> {
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
 
   let media_id = args.media_id.trim().to_string();
@@ -2385,21 +3407,21 @@ async fn summarize_media(
 
   let media_dir = dir.join("media").join(&media_id);
   if !media_dir.is_dir() {
-    return Err("media not found".to_string());
+    return Err(AppError::MediaNotFound);
   }
 
   let transcription_path = media_dir.join("transcription.json");
   if !transcription_path.is_file() {
-    return Err("no transcription found; generate transcription first".to_string());
+    return Err(AppError::InvalidInput("no transcription found; generate transcription first".to_string()));
   }
   let transcription: serde_json::Value = serde_json::from_slice(
     &tokio::fs::read(&transcription_path)
       .await
-      .map_err(|e| format!("read transcription failed: {e}"))?,
+      .map_err(|e| AppError::Internal(format!("read transcription failed: {e}")))?,
   )
-  .map_err(|e| format!("parse transcription failed: {e}"))?;
+  .map_err(|e| AppError::Internal(format!("parse transcription failed: {e}")))?;
 
-  let job_id = format!("job-{}", nanoid());
+  let job_id = new_job_id();
   let _ = emit_job(&app, JobProgressEvent {
     job_id: job_id.clone(),
     media_id: media_id.clone(),
@@ -2407,9 +3429,11 @@ async fn summarize_media(
     status: JobStatus::Running,
     progress: 0.0,
     message: Some("summarizing".to_string()),
+    error_code: None,
   });
+  remember_job_resume(&app, &job_id, "summarize_media", &args);
 
-  let result: Result<serde_json::Value, String> = async {
+  let result: Result<serde_json::Value, AppError> = run_job(&app, &job_id, async {
     let summary = summarize_from_transcription(
       &media_id,
       &args.ai,
@@ -2421,12 +3445,13 @@ async fn summarize_media(
       args.prompt_template.as_deref(),
     )
     .await?;
-    write_json_atomic(&media_dir.join("summary.json"), &summary)?;
+    write_json_atomic(&media_dir.join("summary.json"), &summary).map_err(AppError::Internal)?;
+    mark_search_dirty(&media_dir);
     if let Some(content) = summary.get("content").and_then(|v| v.as_str()) {
       let _ = tokio::fs::write(media_dir.join("summary.md"), content).await;
     }
     Ok(summary)
-  }
+  })
   .await;
 
   match result {
@@ -2438,6 +3463,7 @@ async fn summarize_media(
         status: JobStatus::Succeeded,
         progress: 1.0,
         message: None,
+        error_code: None,
       });
 
       Ok(serde_json::json!({
@@ -2453,7 +3479,8 @@ async fn summarize_media(
         job_type: JobType::Summary,
         status: JobStatus::Failed,
         progress: 1.0,
-        message: Some(e.clone()),
+        message: Some(e.to_string()),
+        error_code: Some(e.code()),
       });
       Err(e)
     }
@@ -2465,10 +3492,10 @@ async fn optimize_transcription(
   app: tauri::AppHandle,
   args: OptimizeTranscriptionArgs,
   state: State<'_, Arc<AppState>>,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, AppError> {
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
 
   let media_id = args.media_id.trim().to_string();
@@ -2476,22 +3503,22 @@ async fn optimize_transcription(
 
   let media_dir = dir.join("media").join(&media_id);
   if !media_dir.is_dir() {
-    return Err("media not found".to_string());
+    return Err(AppError::MediaNotFound);
   }
 
   let transcription_path = media_dir.join("transcription.json");
   if !transcription_path.is_file() {
-    return Err("no transcription found; generate transcription first".to_string());
+    return Err(AppError::InvalidInput("no transcription found; generate transcription first".to_string()));
   }
 
   let transcription: serde_json::Value = serde_json::from_slice(
     &tokio::fs::read(&transcription_path)
       .await
-      .map_err(|e| format!("read transcription failed: {e}"))?,
+      .map_err(|e| AppError::Internal(format!("read transcription failed: {e}")))?,
   )
-  .map_err(|e| format!("parse transcription failed: {e}"))?;
+  .map_err(|e| AppError::Internal(format!("parse transcription failed: {e}")))?;
 
-  let job_id = format!("job-{}", nanoid());
+  let job_id = new_job_id();
   let _ = emit_job(&app, JobProgressEvent {
     job_id: job_id.clone(),
     media_id: media_id.clone(),
@@ -2499,9 +3526,11 @@ async fn optimize_transcription(
     status: JobStatus::Running,
     progress: 0.0,
     message: Some("optimizing transcription".to_string()),
+    error_code: None,
   });
+  remember_job_resume(&app, &job_id, "optimize_transcription", &args);
 
-  let result: Result<serde_json::Value, String> = async {
+  let result: Result<serde_json::Value, AppError> = run_job(&app, &job_id, async {
     let optimized = optimize_transcription_with_ai(
       &media_id,
       &args.ai,
@@ -2518,10 +3547,11 @@ async fn optimize_transcription(
       let _ = tokio::fs::copy(&transcription_path, &backup_path).await;
     }
 
-    write_json_atomic(&media_dir.join("transcription.optimized.json"), &optimized)?;
-    write_json_atomic(&media_dir.join("transcription.json"), &optimized)?;
+    write_json_atomic(&media_dir.join("transcription.optimized.json"), &optimized).map_err(AppError::Internal)?;
+    write_json_atomic(&media_dir.join("transcription.json"), &optimized).map_err(AppError::Internal)?;
+    mark_search_dirty(&media_dir);
     Ok(optimized)
-  }
+  })
   .await;
 
   match result {
@@ -2533,6 +3563,7 @@ async fn optimize_transcription(
         status: JobStatus::Succeeded,
         progress: 1.0,
         message: None,
+        error_code: None,
       });
 
       Ok(serde_json::json!({
@@ -2548,19 +3579,30 @@ async fn optimize_transcription(
         job_type: JobType::Optimize,
         status: JobStatus::Failed,
         progress: 1.0,
-        message: Some(e.clone()),
+        message: Some(e.to_string()),
+        error_code: Some(e.code()),
       });
       Err(e)
     }
   }
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 struct ExportMediaArgs {
   media_id: String,
   #[serde(default)]
   export_dir: Option<String>,
+  /// Subtitle formats to write (srt, vtt, ass, ttml, dfxp). Defaults to srt + vtt.
+  #[serde(default)]
+  subtitle_formats: Option<Vec<String>>,
+  #[serde(default)]
+  ass_style: Option<subtitles::AssStyle>,
+  /// Track stacked under `bilingual_primary` (default "original") in an extra bilingual file.
+  #[serde(default)]
+  bilingual_track: Option<String>,
+  #[serde(default)]
+  bilingual_primary: Option<String>,
 }
 
 fn format_mmss(seconds: f64) -> String {
@@ -2571,30 +3613,6 @@ fn format_mmss(seconds: f64) -> String {
   format!("{}:{:02}", m, sec)
 }
 
-fn format_srt_time(seconds: f64) -> String {
-  let s = if seconds.is_finite() { seconds.max(0.0) } else { 0.0 };
-  let ms_total = (s * 1000.0).round() as u64;
-  let ms = ms_total % 1000;
-  let total_sec = ms_total / 1000;
-  let sec = total_sec % 60;
-  let total_min = total_sec / 60;
-  let min = total_min % 60;
-  let hour = total_min / 60;
-  format!("{:02}:{:02}:{:02},{:03}", hour, min, sec, ms)
-}
-
-fn format_vtt_time(seconds: f64) -> String {
-  let s = if seconds.is_finite() { seconds.max(0.0) } else { 0.0 };
-  let ms_total = (s * 1000.0).round() as u64;
-  let ms = ms_total % 1000;
-  let total_sec = ms_total / 1000;
-  let sec = total_sec % 60;
-  let total_min = total_sec / 60;
-  let min = total_min % 60;
-  let hour = total_min / 60;
-  format!("{:02}:{:02}:{:02}.{:03}", hour, min, sec, ms)
-}
-
 fn extract_transcript_segments(t: &serde_json::Value) -> Vec<(f64, f64, String)> {
   let mut out = Vec::new();
   let arr = t.get("segments").and_then(|v| v.as_array());
@@ -2613,6 +3631,36 @@ fn extract_transcript_segments(t: &serde_json::Value) -> Vec<(f64, f64, String)>
   out
 }
 
+// Like `extract_transcript_segments`, with "Speaker: " in front of the text when the transcript was diarized.
+fn extract_transcript_segments_with_speakers(t: &serde_json::Value) -> Vec<(f64, f64, String)> {
+  let names = transcription_speaker_names(t);
+  if names.is_empty() {
+    return extract_transcript_segments(t);
+  }
+  let mut out = Vec::new();
+  for seg in t.get("segments").and_then(|v| v.as_array()).into_iter().flatten() {
+    let start = seg.get("start").and_then(|n| n.as_f64()).unwrap_or(0.0);
+    let end = seg.get("end").and_then(|n| n.as_f64()).unwrap_or(start);
+    let text = seg.get("text").and_then(|s| s.as_str()).unwrap_or("").trim();
+    if text.is_empty() {
+      continue;
+    }
+    let text = match segment_speaker_name(&names, seg) {
+      Some(name) => format!("{name}: {text}"),
+      None => text.to_string(),
+    };
+    out.push((start, end.max(start), text));
+  }
+  out
+}
+
+fn segments_to_cues(t: &serde_json::Value) -> Vec<subtitles::Cue> {
+  extract_transcript_segments(t)
+    .into_iter()
+    .map(|(start, end, text)| subtitles::Cue { start, end, text, style: None })
+    .collect()
+}
+
 async fn try_load_json(path: &Path) -> Option<serde_json::Value> {
   if !path.is_file() {
     return None;
@@ -2626,16 +3674,16 @@ async fn export_media(
   app: tauri::AppHandle,
   args: ExportMediaArgs,
   state: State<'_, Arc<AppState>>,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, AppError> {
   let dir = state
     .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
     .await?;
 
   let media_id = args.media_id.trim().to_string();
   validate_media_id(&media_id)?;
 
-  let job_id = format!("job-{}", nanoid());
+  let job_id = new_job_id();
   let _ = emit_job(&app, JobProgressEvent {
     job_id: job_id.clone(),
     media_id: media_id.clone(),
@@ -2643,9 +3691,11 @@ async fn export_media(
     status: JobStatus::Running,
     progress: 0.0,
     message: Some("exporting".to_string()),
+    error_code: None,
   });
+  remember_job_resume(&app, &job_id, "export_media", &args);
 
-  let result: Result<serde_json::Value, String> = async {
+  let result: Result<serde_json::Value, AppError> = run_job(&app, &job_id, async {
     let state_path = state_file_path(dir);
     let media_dir = dir.join("media").join(&media_id);
 
@@ -2686,6 +3736,7 @@ async fn export_media(
     };
 
     let subtitles = try_load_json(&media_dir.join("subtitles.json")).await;
+    let conversations = load_all_conversations(&media_dir).await;
 
     let notes = media_item
       .as_ref()
@@ -2710,7 +3761,82 @@ async fn export_media(
 
     tokio::fs::create_dir_all(&export_dir)
       .await
-      .map_err(|e| format!("create export dir failed: {e}"))?;
+      .map_err(|e| AppError::Internal(format!("create export dir failed: {e}")))?;
+
+    let formats = args
+      .subtitle_formats
+      .clone()
+      .filter(|f| !f.is_empty())
+      .unwrap_or_else(|| subtitles::DEFAULT_EXPORT_FORMATS.iter().map(|f| f.to_string()).collect());
+    let ass_style = args.ass_style.clone().unwrap_or_default();
+    let writers_for = |language: &str| -> Result<Vec<Box<dyn subtitles::SubtitleWriter>>, String> {
+      let mut out: Vec<Box<dyn subtitles::SubtitleWriter>> = Vec::new();
+      for f in &formats {
+        let w = subtitles::writer_for(f, language, &ass_style).ok_or_else(|| format!("unsupported subtitle format: {f}"))?;
+        if !out.iter().any(|x| x.extension() == w.extension()) {
+          out.push(w);
+        }
+      }
+      Ok(out)
+    };
+
+    // Render subtitle files up front so the step count is known.
+    let mut subtitle_files: Vec<(String, String)> = Vec::new();
+    if let Some(t) = transcription.as_ref() {
+      let cues: Vec<subtitles::Cue> = extract_transcript_segments_with_speakers(t)
+        .into_iter()
+        .map(|(start, end, text)| subtitles::Cue { start, end, text, style: None })
+        .collect();
+      let language = t.get("language").and_then(|v| v.as_str()).unwrap_or("");
+      for w in writers_for(language).map_err(AppError::InvalidInput)? {
+        subtitle_files.push((format!("transcript.{}", w.extension()), w.write(&cues)));
+      }
+    }
+    if let Some(tracks) = subtitles.as_ref().and_then(|s| s.get("tracks")).and_then(|v| v.as_array()) {
+      for tr in tracks {
+        let id = tr.get("id").and_then(|v| v.as_str()).unwrap_or("").trim();
+        if id.is_empty() {
+          continue;
+        }
+        let cues = segments_to_cues(tr);
+        if cues.is_empty() {
+          continue;
+        }
+        let safe_id = sanitize_filename_component(id);
+        let language = tr.get("language").and_then(|v| v.as_str()).unwrap_or("");
+        for w in writers_for(language).map_err(AppError::InvalidInput)? {
+          subtitle_files.push((format!("subtitles.{safe_id}.{}", w.extension()), w.write(&cues)));
+        }
+      }
+
+      if let Some(secondary_id) = args.bilingual_track.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        let primary_id = args
+          .bilingual_primary
+          .as_deref()
+          .map(str::trim)
+          .filter(|s| !s.is_empty())
+          .unwrap_or("original");
+        let find = |id: &str| tracks.iter().find(|t| t.get("id").and_then(|v| v.as_str()) == Some(id));
+        let primary = find(primary_id)
+          .ok_or_else(|| AppError::InvalidInput(format!("subtitle track not found: {primary_id}")))?;
+        let secondary = find(secondary_id)
+          .ok_or_else(|| AppError::InvalidInput(format!("subtitle track not found: {secondary_id}")))?;
+        let cues = subtitles::stack_bilingual(&segments_to_cues(primary), &segments_to_cues(secondary));
+        if !cues.is_empty() {
+          let name = format!(
+            "subtitles.{}+{}",
+            sanitize_filename_component(primary_id),
+            sanitize_filename_component(secondary_id)
+          );
+          let language = primary.get("language").and_then(|v| v.as_str()).unwrap_or("");
+          for w in writers_for(language).map_err(AppError::InvalidInput)? {
+            subtitle_files.push((format!("{name}.{}", w.extension()), w.write(&cues)));
+          }
+        }
+      }
+    } else if args.bilingual_track.is_some() {
+      return Err(AppError::InvalidInput("no subtitle tracks to build a bilingual export from".to_string()));
+    }
 
     // Determine steps so progress feels real.
     let mut planned: Vec<(String, bool)> = Vec::new();
@@ -2718,8 +3844,6 @@ async fn export_media(
     planned.push(("bookmarks.json".to_string(), true));
     planned.push(("transcription.json".to_string(), transcription.is_some()));
     planned.push(("transcript.txt".to_string(), transcription.is_some()));
-    planned.push(("transcript.srt".to_string(), transcription.is_some()));
-    planned.push(("transcript.vtt".to_string(), transcription.is_some()));
     planned.push(("summary.json".to_string(), summary.is_some()));
     planned.push((
       "summary.md".to_string(),
@@ -2729,24 +3853,11 @@ async fn export_media(
         .and_then(|v| v.as_str())
         .is_some(),
     ));
-
-    if let Some(subs) = subtitles.as_ref() {
-      if let Some(tracks) = subs.get("tracks").and_then(|v| v.as_array()) {
-        for tr in tracks {
-          let id = tr.get("id").and_then(|v| v.as_str()).unwrap_or("").trim();
-          if id.is_empty() {
-            continue;
-          }
-          let segs_ok = tr.get("segments").and_then(|v| v.as_array()).map(|a| !a.is_empty()).unwrap_or(false);
-          if !segs_ok {
-            continue;
-          }
-          let safe_id = sanitize_filename_component(id);
-          planned.push((format!("subtitles.{safe_id}.srt"), true));
-          planned.push((format!("subtitles.{safe_id}.vtt"), true));
-        }
-      }
+    for (name, _) in &subtitle_files {
+      planned.push((name.clone(), true));
     }
+    planned.push(("conversations.json".to_string(), !conversations.is_empty()));
+    planned.push(("conversations.md".to_string(), !conversations.is_empty()));
 
     let total_steps = planned.iter().filter(|(_, ok)| *ok).count().max(1) as f32;
     let mut done_steps: f32 = 0.0;
@@ -2762,6 +3873,7 @@ async fn export_media(
         status: JobStatus::Running,
         progress: p,
         message: Some(msg.to_string()),
+        error_code: None,
       });
       let _ = name;
     };
@@ -2769,111 +3881,74 @@ async fn export_media(
     // Notes & bookmarks always exported (even if empty).
     tokio::fs::write(export_dir.join("notes.json"), serde_json::to_vec_pretty(&notes).unwrap_or_default())
       .await
-      .map_err(|e| format!("write notes failed: {e}"))?;
+      .map_err(|e| AppError::Internal(format!("write notes failed: {e}")))?;
     files.push(export_dir.join("notes.json").to_string_lossy().to_string());
     step("notes.json", "wrote notes.json", &job_id, &media_id, &app, &mut done_steps);
 
     tokio::fs::write(export_dir.join("bookmarks.json"), serde_json::to_vec_pretty(&bookmarks).unwrap_or_default())
       .await
-      .map_err(|e| format!("write bookmarks failed: {e}"))?;
+      .map_err(|e| AppError::Internal(format!("write bookmarks failed: {e}")))?;
     files.push(export_dir.join("bookmarks.json").to_string_lossy().to_string());
     step("bookmarks.json", "wrote bookmarks.json", &job_id, &media_id, &app, &mut done_steps);
 
     if let Some(t) = transcription {
       tokio::fs::write(export_dir.join("transcription.json"), serde_json::to_vec_pretty(&t).unwrap_or_default())
         .await
-        .map_err(|e| format!("write transcription.json failed: {e}"))?;
+        .map_err(|e| AppError::Internal(format!("write transcription.json failed: {e}")))?;
       files.push(export_dir.join("transcription.json").to_string_lossy().to_string());
       step("transcription.json", "wrote transcription.json", &job_id, &media_id, &app, &mut done_steps);
 
-      let segments = extract_transcript_segments(&t);
+      let segments = extract_transcript_segments_with_speakers(&t);
       let mut txt = String::new();
       for (start, _end, text) in &segments {
         txt.push_str(&format!("[{}] {}\n", format_mmss(*start), text));
       }
       tokio::fs::write(export_dir.join("transcript.txt"), txt)
         .await
-        .map_err(|e| format!("write transcript.txt failed: {e}"))?;
+        .map_err(|e| AppError::Internal(format!("write transcript.txt failed: {e}")))?;
       files.push(export_dir.join("transcript.txt").to_string_lossy().to_string());
       step("transcript.txt", "wrote transcript.txt", &job_id, &media_id, &app, &mut done_steps);
-
-      let mut srt = String::new();
-      for (i, (start, end, text)) in segments.iter().enumerate() {
-        srt.push_str(&format!("{}\n{} --> {}\n{}\n\n", i + 1, format_srt_time(*start), format_srt_time(*end), text));
-      }
-      tokio::fs::write(export_dir.join("transcript.srt"), srt)
-        .await
-        .map_err(|e| format!("write transcript.srt failed: {e}"))?;
-      files.push(export_dir.join("transcript.srt").to_string_lossy().to_string());
-      step("transcript.srt", "wrote transcript.srt", &job_id, &media_id, &app, &mut done_steps);
-
-      let mut vtt = String::new();
-      vtt.push_str("WEBVTT\n\n");
-      for (start, end, text) in segments {
-        vtt.push_str(&format!("{} --> {}\n{}\n\n", format_vtt_time(start), format_vtt_time(end), text));
-      }
-      tokio::fs::write(export_dir.join("transcript.vtt"), vtt)
-        .await
-        .map_err(|e| format!("write transcript.vtt failed: {e}"))?;
-      files.push(export_dir.join("transcript.vtt").to_string_lossy().to_string());
-      step("transcript.vtt", "wrote transcript.vtt", &job_id, &media_id, &app, &mut done_steps);
     }
 
     if let Some(s) = summary {
       tokio::fs::write(export_dir.join("summary.json"), serde_json::to_vec_pretty(&s).unwrap_or_default())
         .await
-        .map_err(|e| format!("write summary.json failed: {e}"))?;
+        .map_err(|e| AppError::Internal(format!("write summary.json failed: {e}")))?;
       files.push(export_dir.join("summary.json").to_string_lossy().to_string());
       step("summary.json", "wrote summary.json", &job_id, &media_id, &app, &mut done_steps);
 
       if let Some(content) = s.get("content").and_then(|v| v.as_str()) {
         tokio::fs::write(export_dir.join("summary.md"), content)
           .await
-          .map_err(|e| format!("write summary.md failed: {e}"))?;
+          .map_err(|e| AppError::Internal(format!("write summary.md failed: {e}")))?;
         files.push(export_dir.join("summary.md").to_string_lossy().to_string());
         step("summary.md", "wrote summary.md", &job_id, &media_id, &app, &mut done_steps);
       }
     }
 
-    // Export subtitle tracks if present.
-    if let Some(subs) = subtitles {
-      if let Some(tracks) = subs.get("tracks").and_then(|v| v.as_array()) {
-        for tr in tracks {
-          let id = tr.get("id").and_then(|v| v.as_str()).unwrap_or("").trim();
-          if id.is_empty() {
-            continue;
-          }
-          let segments = extract_transcript_segments(tr);
-          if segments.is_empty() {
-            continue;
-          }
+    for (name, content) in subtitle_files {
+      tokio::fs::write(export_dir.join(&name), content)
+        .await
+        .map_err(|e| AppError::Internal(format!("write {name} failed: {e}")))?;
+      files.push(export_dir.join(&name).to_string_lossy().to_string());
+      step(&name, &format!("wrote {name}"), &job_id, &media_id, &app, &mut done_steps);
+    }
 
-          let safe_id = sanitize_filename_component(id);
+    if !conversations.is_empty() {
+      tokio::fs::write(
+        export_dir.join("conversations.json"),
+        serde_json::to_vec_pretty(&conversations).unwrap_or_default(),
+      )
+      .await
+      .map_err(|e| AppError::Internal(format!("write conversations.json failed: {e}")))?;
+      files.push(export_dir.join("conversations.json").to_string_lossy().to_string());
+      step("conversations.json", "wrote conversations.json", &job_id, &media_id, &app, &mut done_steps);
 
-          let mut srt = String::new();
-          for (i, (start, end, text)) in segments.iter().enumerate() {
-            srt.push_str(&format!("{}\n{} --> {}\n{}\n\n", i + 1, format_srt_time(*start), format_srt_time(*end), text));
-          }
-          let srt_name = format!("subtitles.{safe_id}.srt");
-          tokio::fs::write(export_dir.join(&srt_name), srt)
-            .await
-            .map_err(|e| format!("write {srt_name} failed: {e}"))?;
-          files.push(export_dir.join(&srt_name).to_string_lossy().to_string());
-          step(&srt_name, &format!("wrote {srt_name}"), &job_id, &media_id, &app, &mut done_steps);
-
-          let mut vtt = String::new();
-          vtt.push_str("WEBVTT\n\n");
-          for (start, end, text) in segments {
-            vtt.push_str(&format!("{} --> {}\n{}\n\n", format_vtt_time(start), format_vtt_time(end), text));
-          }
-          let vtt_name = format!("subtitles.{safe_id}.vtt");
-          tokio::fs::write(export_dir.join(&vtt_name), vtt)
-            .await
-            .map_err(|e| format!("write {vtt_name} failed: {e}"))?;
-          files.push(export_dir.join(&vtt_name).to_string_lossy().to_string());
-          step(&vtt_name, &format!("wrote {vtt_name}"), &job_id, &media_id, &app, &mut done_steps);
-        }
-      }
+      tokio::fs::write(export_dir.join("conversations.md"), conversations_markdown(&conversations))
+        .await
+        .map_err(|e| AppError::Internal(format!("write conversations.md failed: {e}")))?;
+      files.push(export_dir.join("conversations.md").to_string_lossy().to_string());
+      step("conversations.md", "wrote conversations.md", &job_id, &media_id, &app, &mut done_steps);
     }
 
     Ok(serde_json::json!({
@@ -2882,7 +3957,7 @@ async fn export_media(
       "export_dir": export_dir.to_string_lossy().to_string(),
       "files": files,
     }))
-  }
+  })
   .await;
 
   match result {
@@ -2894,6 +3969,7 @@ async fn export_media(
         status: JobStatus::Succeeded,
         progress: 1.0,
         message: None,
+        error_code: None,
       });
       Ok(payload)
     }
@@ -2904,52 +3980,405 @@ async fn export_media(
         job_type: JobType::Export,
         status: JobStatus::Failed,
         progress: 1.0,
-        message: Some(e.clone()),
+        message: Some(e.to_string()),
+        error_code: Some(e.code()),
       });
       Err(e)
     }
   }
 }
 
-#[tauri::command]
-async fn chat_media(
-  app: tauri::AppHandle,
-  args: ChatMediaArgs,
-  state: State<'_, Arc<AppState>>,
-) -> Result<serde_json::Value, String> {
-  let dir = state
-    .data_root
-    .get_or_try_init(|| async { portable::resolve_data_root(&app) })
-    .await?;
-
-  let media_id = args.media_id.trim().to_string();
-  validate_media_id(&media_id)?;
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RenderVideoArgs {
+  media_id: String,
+  /// "mux" (soft subtitle streams) or "burn" (hard subtitles).
+  mode: String,
+  /// Tracks to mux, in stream order. For "burn" only the first one is used.
+  #[serde(default)]
+  track_ids: Vec<String>,
+  /// Output container: "mp4" or "mkv". Defaults to mkv for mux, mp4 for burn.
+  #[serde(default)]
+  container: Option<String>,
+  /// Soft subtitle codec in mkv: "srt" (default) or "ass". mp4 always uses mov_text.
+  #[serde(default)]
+  subtitle_format: Option<String>,
+  #[serde(default)]
+  ass_style: Option<subtitles::AssStyle>,
+  /// x264-style CRF for burn-in (default 20).
+  #[serde(default)]
+  crf: Option<u32>,
+  #[serde(default)]
+  output_path: Option<String>,
+}
 
-  let media_dir = dir.join("media").join(&media_id);
-  if !media_dir.is_dir() {
-    return Err("media not found".to_string());
+// LGPL ffmpeg builds ship without libx264, so pick the best available H.264 encoder.
+async fn ffmpeg_pick_video_encoder(ffmpeg: &Path) -> String {
+  let out = tokio::process::Command::new(ffmpeg)
+    .kill_on_drop(true)
+    .arg("-hide_banner")
+    .arg("-encoders")
+    .output()
+    .await;
+  let list = out.map(|o| String::from_utf8_lossy(&o.stdout).to_string()).unwrap_or_default();
+  let has = |name: &str| list.lines().any(|l| l.split_whitespace().nth(1) == Some(name));
+  for enc in ["libx264", "h264_videotoolbox", "h264_mf", "libopenh264"] {
+    if has(enc) {
+      return enc.to_string();
+    }
   }
+  "mpeg4".to_string()
+}
 
-  // Fallback: some older states may have summary/transcription persisted in state.json
-  // but not written to per-media files.
-  let media_item: Option<serde_json::Value> = try_load_json(&state_file_path(dir)).await.and_then(|s| {
-    s.get("data")
-      .and_then(|d| d.get("mediaItems"))
-      .and_then(|v| v.as_array())
-      .and_then(|arr| {
-        arr.iter()
-          .find(|it| it.get("id").and_then(|v| v.as_str()) == Some(&media_id))
-          .cloned()
-      })
-  });
+// Runs ffmpeg with `-progress pipe:1` and maps out_time onto [base, base + span] of the export job.
+async fn run_ffmpeg_with_progress(
+  app: &tauri::AppHandle,
+  job_id: &str,
+  media_id: &str,
+  mut cmd: tokio::process::Command,
+  duration: Option<f64>,
+  base: f32,
+  span: f32,
+) -> Result<(), AppError> {
+  use tokio::io::{AsyncBufReadExt, AsyncReadExt};
 
-  let transcription_path = media_dir.join("transcription.json");
+  cmd
+    .kill_on_drop(true)
+    .arg("-progress")
+    .arg("pipe:1")
+    .arg("-nostats")
+    .stdout(std::process::Stdio::piped())
+    .stderr(std::process::Stdio::piped());
+  let mut child = cmd.spawn().map_err(|e| AppError::Internal(format!("spawn ffmpeg failed: {e}")))?;
+  let stdout = child.stdout.take().ok_or_else(|| AppError::Internal("ffmpeg stdout unavailable".to_string()))?;
+  let mut stderr = child.stderr.take().ok_or_else(|| AppError::Internal("ffmpeg stderr unavailable".to_string()))?;
+  let stderr_task = tokio::spawn(async move {
+    let mut buf = Vec::new();
+    let _ = stderr.read_to_end(&mut buf).await;
+    buf
+  });
+
+  let mut last_emit = std::time::Instant::now() - std::time::Duration::from_secs(10);
+  let mut lines = tokio::io::BufReader::new(stdout).lines();
+  while let Ok(Some(line)) = lines.next_line().await {
+    // out_time_us is microseconds; older builds only have out_time_ms (also microseconds).
+    let Some(us) = line
+      .strip_prefix("out_time_us=")
+      .or_else(|| line.strip_prefix("out_time_ms="))
+      .and_then(|v| v.trim().parse::<f64>().ok())
+    else {
+      continue;
+    };
+    let Some(total) = duration.filter(|d| *d > 0.0) else {
+      continue;
+    };
+    if last_emit.elapsed() < std::time::Duration::from_millis(500) {
+      continue;
+    }
+    last_emit = std::time::Instant::now();
+    let frac = ((us / 1_000_000.0) / total).clamp(0.0, 1.0) as f32;
+    let _ = emit_job(app, JobProgressEvent {
+      job_id: job_id.to_string(),
+      media_id: media_id.to_string(),
+      job_type: JobType::Export,
+      status: JobStatus::Running,
+      progress: base + span * frac,
+      message: Some(format!("rendering {}%", (frac * 100.0).round() as i32)),
+      error_code: None,
+    });
+  }
+
+  let status = child.wait().await.map_err(|e| AppError::Internal(format!("wait ffmpeg failed: {e}")))?;
+  let stderr = stderr_task.await.unwrap_or_default();
+  if !status.success() {
+    return Err(AppError::Internal(format!("ffmpeg failed: {}", output_tail(&stderr, 12))));
+  }
+  Ok(())
+}
+
+#[tauri::command]
+async fn render_video(
+  app: tauri::AppHandle,
+  args: RenderVideoArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
+
+  let media_id = args.media_id.trim().to_string();
+  validate_media_id(&media_id)?;
+  let media_dir = dir.join("media").join(&media_id);
+  if !media_dir.is_dir() {
+    return Err(AppError::MediaNotFound);
+  }
+
+  let burn = match args.mode.trim() {
+    "mux" => false,
+    "burn" => true,
+    other => return Err(AppError::InvalidInput(format!("unsupported render mode: {other}"))),
+  };
+  let track_ids: Vec<String> = args
+    .track_ids
+    .iter()
+    .map(|s| s.trim().to_string())
+    .filter(|s| !s.is_empty())
+    .collect();
+  if track_ids.is_empty() {
+    return Err(AppError::InvalidInput("no subtitle track selected".to_string()));
+  }
+  let container = args
+    .container
+    .as_deref()
+    .map(|s| s.trim().to_lowercase())
+    .filter(|s| !s.is_empty())
+    .unwrap_or_else(|| if burn { "mp4" } else { "mkv" }.to_string());
+  if container != "mp4" && container != "mkv" {
+    return Err(AppError::InvalidInput(format!("unsupported container: {container}")));
+  }
+  // ffmpeg runs inside the per-job work dir, which is deleted afterwards.
+  let requested_output = args.output_path.as_deref().map(str::trim).filter(|s| !s.is_empty()).map(PathBuf::from);
+  if requested_output.as_ref().is_some_and(|p| !p.is_absolute()) {
+    return Err(AppError::InvalidInput("output path must be absolute".to_string()));
+  }
+
+  let job_id = new_job_id();
+  let _ = emit_job(&app, JobProgressEvent {
+    job_id: job_id.clone(),
+    media_id: media_id.clone(),
+    job_type: JobType::Export,
+    status: JobStatus::Running,
+    progress: 0.0,
+    message: Some("preparing render".to_string()),
+    error_code: None,
+  });
+  remember_job_resume(&app, &job_id, "render_video", &args);
+
+  let result: Result<serde_json::Value, AppError> = run_job(&app, &job_id, async {
+    let source_path = find_source_file(&media_dir)?;
+
+    let (ffmpeg, _) = ensure_ffmpeg_bundle_with_job(
+      &app,
+      state.inner(),
+      dir,
+      Some((&job_id, &media_id, JobType::Export, 0.0, 0.05)),
+    )
+    .await?;
+
+    let subs = match try_load_json(&media_dir.join("subtitles.json")).await {
+      Some(s) => s,
+      None => match try_load_json(&media_dir.join("transcription.json")).await {
+        Some(t) => build_subtitles_from_transcription(&media_id, &t),
+        None => return Err(AppError::InvalidInput("no subtitles for this media".to_string())),
+      },
+    };
+    let tracks = subs.get("tracks").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+
+    // Subtitle inputs are written next to the media so the burn filter can use a relative path
+    // (filtergraph escaping of Windows drive letters is fragile).
+    let work_dir = media_dir.join("render").join(&job_id);
+    tokio::fs::create_dir_all(&work_dir)
+      .await
+      .map_err(|e| AppError::Internal(format!("create render dir failed: {e}")))?;
+    let _work_dir_guard = RemoveDirOnDrop(work_dir.clone());
+
+    let ass_style = args.ass_style.clone().unwrap_or_default();
+    let soft_ass = !burn && container == "mkv" && args.subtitle_format.as_deref().map(str::trim) == Some("ass");
+    let mut inputs: Vec<(PathBuf, String, String)> = Vec::new();
+    let selected = if burn { &track_ids[..1] } else { &track_ids[..] };
+    for (i, id) in selected.iter().enumerate() {
+      let track = tracks
+        .iter()
+        .find(|t| t.get("id").and_then(|v| v.as_str()) == Some(id.as_str()))
+        .ok_or_else(|| AppError::InvalidInput(format!("subtitle track not found: {id}")))?;
+      let cues = segments_to_cues(track);
+      if cues.is_empty() {
+        return Err(AppError::InvalidInput(format!("subtitle track is empty: {id}")));
+      }
+      let language = track.get("language").and_then(|v| v.as_str()).unwrap_or("").to_string();
+      let label = track.get("label").and_then(|v| v.as_str()).unwrap_or(id).to_string();
+      let writer: Box<dyn subtitles::SubtitleWriter> = if burn || soft_ass {
+        Box::new(subtitles::AssWriter { style: ass_style.clone() })
+      } else {
+        Box::new(subtitles::SrtWriter)
+      };
+      let path = work_dir.join(format!("track{i}.{}", writer.extension()));
+      atomic_write_bytes(&path, writer.write(&cues).as_bytes()).map_err(AppError::Internal)?;
+      inputs.push((path, language, label));
+    }
+
+    let output_path = match requested_output {
+      Some(p) => p,
+      None => {
+        let out_dir = dir.join("exports");
+        tokio::fs::create_dir_all(&out_dir)
+          .await
+          .map_err(|e| AppError::Internal(format!("create export dir failed: {e}")))?;
+        out_dir.join(format!(
+          "{}_{}_{}.{container}",
+          sanitize_filename_component(&media_id),
+          if burn { "burned" } else { "subbed" },
+          now_compact()
+        ))
+      }
+    };
+
+    let duration = ffprobe_analyze(&app, &source_path).await.ok().and_then(|(d, _, _)| d);
+
+    let mut cmd = tokio::process::Command::new(&ffmpeg);
+    cmd
+      .current_dir(&work_dir)
+      .arg("-y")
+      .arg("-hide_banner")
+      .arg("-loglevel")
+      .arg("error")
+      .arg("-i")
+      .arg(&source_path);
+
+    if burn {
+      let encoder = ffmpeg_pick_video_encoder(&ffmpeg).await;
+      let file_name = inputs[0].0.file_name().and_then(|s| s.to_str()).unwrap_or("track0.ass").to_string();
+      cmd
+        .arg("-map")
+        .arg("0:v:0")
+        .arg("-map")
+        .arg("0:a?")
+        .arg("-vf")
+        .arg(format!("subtitles=filename={file_name}"))
+        .arg("-c:v")
+        .arg(&encoder);
+      if encoder == "libx264" {
+        cmd
+          .arg("-preset")
+          .arg("veryfast")
+          .arg("-crf")
+          .arg(args.crf.unwrap_or(20).min(51).to_string());
+      } else {
+        cmd.arg("-b:v").arg("6M");
+      }
+      cmd.arg("-pix_fmt").arg("yuv420p").arg("-c:a").arg("copy");
+    } else {
+      for (path, _, _) in &inputs {
+        cmd.arg("-i").arg(path);
+      }
+      cmd.arg("-map").arg("0:v?").arg("-map").arg("0:a?");
+      for i in 0..inputs.len() {
+        cmd.arg("-map").arg(format!("{}:0", i + 1));
+      }
+      let sub_codec = if container == "mp4" {
+        "mov_text"
+      } else if soft_ass {
+        "ass"
+      } else {
+        "srt"
+      };
+      cmd.arg("-c:v").arg("copy").arg("-c:a").arg("copy").arg("-c:s").arg(sub_codec);
+      for (i, (_, language, label)) in inputs.iter().enumerate() {
+        if !language.trim().is_empty() {
+          cmd.arg(format!("-metadata:s:s:{i}")).arg(format!("language={}", language.trim()));
+        }
+        cmd.arg(format!("-metadata:s:s:{i}")).arg(format!("title={label}"));
+      }
+    }
+    if container == "mp4" {
+      cmd.arg("-movflags").arg("+faststart");
+    }
+    cmd.arg(&output_path);
+
+    let _ = emit_job(&app, JobProgressEvent {
+      job_id: job_id.clone(),
+      media_id: media_id.clone(),
+      job_type: JobType::Export,
+      status: JobStatus::Running,
+      progress: 0.05,
+      message: Some(if burn { "burning subtitles" } else { "muxing subtitles" }.to_string()),
+      error_code: None,
+    });
+
+    let rendered = run_ffmpeg_with_progress(&app, &job_id, &media_id, cmd, duration, 0.05, 0.94).await;
+    if let Err(e) = rendered {
+      let _ = tokio::fs::remove_file(&output_path).await;
+      return Err(e);
+    }
+
+    Ok(serde_json::json!({
+      "media_id": media_id,
+      "job_id": job_id,
+      "mode": if burn { "burn" } else { "mux" },
+      "output_path": output_path.to_string_lossy().to_string(),
+      "tracks": selected,
+    }))
+  })
+  .await;
+
+  match result {
+    Ok(payload) => {
+      let _ = emit_job(&app, JobProgressEvent {
+        job_id: job_id.clone(),
+        media_id: media_id.clone(),
+        job_type: JobType::Export,
+        status: JobStatus::Succeeded,
+        progress: 1.0,
+        message: None,
+        error_code: None,
+      });
+      Ok(payload)
+    }
+    Err(e) => {
+      let _ = emit_job(&app, JobProgressEvent {
+        job_id: job_id.clone(),
+        media_id: media_id.clone(),
+        job_type: JobType::Export,
+        status: JobStatus::Failed,
+        progress: 1.0,
+        message: Some(e.to_string()),
+        error_code: Some(e.code()),
+      });
+      Err(e)
+    }
+  }
+}
+
+#[tauri::command]
+async fn chat_media(
+  app: tauri::AppHandle,
+  args: ChatMediaArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
+
+  let media_id = args.media_id.trim().to_string();
+  validate_media_id(&media_id)?;
+
+  let media_dir = dir.join("media").join(&media_id);
+  if !media_dir.is_dir() {
+    return Err(AppError::MediaNotFound);
+  }
+
+  // Fallback: some older states may have summary/transcription persisted in state.json
+  // but not written to per-media files.
+  let media_item: Option<serde_json::Value> = try_load_json(&state_file_path(dir)).await.and_then(|s| {
+    s.get("data")
+      .and_then(|d| d.get("mediaItems"))
+      .and_then(|v| v.as_array())
+      .and_then(|arr| {
+        arr.iter()
+          .find(|it| it.get("id").and_then(|v| v.as_str()) == Some(&media_id))
+          .cloned()
+      })
+  });
+
+  let transcription_path = media_dir.join("transcription.json");
   let transcription = if args.include_transcription {
     if transcription_path.is_file() {
       serde_json::from_slice::<serde_json::Value>(
         &tokio::fs::read(&transcription_path)
           .await
-          .map_err(|e| format!("read transcription failed: {e}"))?,
+          .map_err(|e| AppError::Internal(format!("read transcription failed: {e}")))?,
       )
       .ok()
     } else {
@@ -2965,7 +4394,7 @@ async fn chat_media(
       if let Ok(v) = serde_json::from_slice::<serde_json::Value>(
         &tokio::fs::read(&p)
           .await
-          .map_err(|e| format!("read summary failed: {e}"))?,
+          .map_err(|e| AppError::Internal(format!("read summary failed: {e}")))?,
       ) {
         v.get("content").and_then(|c| c.as_str()).map(|s| s.to_string())
       } else {
@@ -2986,1040 +4415,4091 @@ async fn chat_media(
   // Remove mermaid blocks from summary to reduce noise for chat.
   let summary_md = summary_md.map(|s| strip_mermaid_code_blocks(&s));
 
-  let reply = chat_with_media_context(
-    &media_id,
-    transcription.as_ref(),
-    summary_md.as_deref(),
-    args.user_lang.as_deref(),
-    &args.ai,
-    &args.messages,
-  )
-  .await?;
+  let conversation_id = args
+    .conversation_id
+    .as_deref()
+    .map(str::trim)
+    .filter(|s| !s.is_empty())
+    .map(|s| s.to_string());
+  let conversation = match conversation_id.as_deref() {
+    Some(id) => Some(load_conversation(&media_dir, id).await?),
+    None => None,
+  };
+  let mut history = conversation.as_ref().map(conversation_messages).unwrap_or_default();
+  history.extend(args.messages.iter().cloned());
+  let prior_summary = conversation
+    .as_ref()
+    .and_then(|c| c.get("historySummary"))
+    .and_then(|v| serde_json::from_value::<ChatHistorySummary>(v.clone()).ok());
+  // Without a saved conversation the summary could not be kept, so older turns are just dropped.
+  let history_summary = match &conversation {
+    Some(_) => compact_chat_history(&args.ai, &history, prior_summary).await,
+    None => None,
+  };
+  let messages = chat_messages_with_summary(&history, history_summary.as_ref());
 
-  Ok(serde_json::json!({
-    "message": {
-      "id": format!("msg-{}", nanoid()),
+  // Appends this turn to the saved conversation, if any.
+  let save_turn = |reply: serde_json::Value| {
+    let media_dir = media_dir.clone();
+    let new_messages = args.messages.clone();
+    let summary = history_summary.clone();
+    let state = state.inner().clone();
+    let id = conversation_id.clone();
+    async move {
+      let Some(id) = id else {
+        return Ok::<(), AppError>(());
+      };
+      let _guard = state.state_io_lock.lock().await;
+      let mut conv = load_conversation(&media_dir, &id).await?;
+      append_conversation_turn(&mut conv, &new_messages, &reply, summary.as_ref());
+      save_conversation(&media_dir, &conv)
+    }
+  };
+
+  let message_id = args
+    .message_id
+    .as_deref()
+    .map(str::trim)
+    .filter(|s| !s.is_empty())
+    .map(|s| s.to_string())
+    .unwrap_or_else(|| format!("msg-{}", nanoid()));
+
+  if !args.stream {
+    let reply = chat_with_media_context(
+      &media_dir,
+      transcription.as_ref(),
+      summary_md.as_deref(),
+      args.user_lang.as_deref(),
+      &args.ai,
+      &messages,
+      None,
+    )
+    .await?;
+
+    let message = serde_json::json!({
+      "id": message_id,
       "role": "assistant",
       "content": reply,
       "timestamp": now_iso(),
+    });
+    save_turn(message.clone()).await?;
+    return Ok(serde_json::json!({ "message": message, "conversation_id": conversation_id }));
+  }
+
+  let emit = |kind: ChatStreamKind, text: Option<String>, error_code| {
+    let _ = app.emit(EVENT_CHAT_STREAM, ChatStreamEvent {
+      message_id: message_id.clone(),
+      media_id: media_id.clone(),
+      kind,
+      text,
+      error_code,
+    });
+  };
+
+  let (cancel_tx, mut cancel_rx) = tokio::sync::watch::channel(false);
+  {
+    let mut streams = state.chat_streams.lock().unwrap_or_else(|e| e.into_inner());
+    if streams.contains_key(&message_id) {
+      return Err(AppError::InvalidInput(format!("message {message_id} is already streaming")));
+    }
+    streams.insert(message_id.clone(), cancel_tx);
+  }
+
+  let mut partial = String::new();
+  let mut on_delta = |content: Option<&str>, reasoning: Option<&str>| {
+    if let Some(r) = reasoning.filter(|r| !r.is_empty()) {
+      emit(ChatStreamKind::Reasoning, Some(r.to_string()), None);
+    }
+    if let Some(c) = content.filter(|c| !c.is_empty()) {
+      partial.push_str(c);
+      emit(ChatStreamKind::Content, Some(c.to_string()), None);
+    }
+  };
+  let outcome = tokio::select! {
+    r = chat_with_media_context(
+      &media_dir,
+      transcription.as_ref(),
+      summary_md.as_deref(),
+      args.user_lang.as_deref(),
+      &args.ai,
+      &messages,
+      Some(&mut on_delta),
+    ) => Some(r),
+    Ok(_) = cancel_rx.wait_for(|c| *c) => None,
+  };
+  state.chat_streams.lock().unwrap_or_else(|e| e.into_inner()).remove(&message_id);
+
+  let (content, cancelled) = match outcome {
+    Some(Ok(reply)) => {
+      // Non-SSE providers answer in one piece; surface it as a single delta.
+      if partial.is_empty() {
+        emit(ChatStreamKind::Content, Some(reply.clone()), None);
+      }
+      emit(ChatStreamKind::Done, None, None);
+      (reply, false)
+    }
+    Some(Err(e)) => {
+      let err = e;
+      emit(ChatStreamKind::Error, Some(err.to_string()), Some(err.code()));
+      return Err(err);
     }
+    None => {
+      emit(ChatStreamKind::Cancelled, None, None);
+      (partial, true)
+    }
+  };
+
+  let message = serde_json::json!({
+    "id": message_id,
+    "role": "assistant",
+    "content": content,
+    "timestamp": now_iso(),
+  });
+  if !content.is_empty() {
+    save_turn(message.clone()).await?;
+  }
+  Ok(serde_json::json!({
+    "message": message,
+    "cancelled": cancelled,
+    "conversation_id": conversation_id,
   }))
 }
 
-fn emit_job(app: &tauri::AppHandle, payload: JobProgressEvent) -> Result<(), String> {
-  app.emit(EVENT_JOB_PROGRESS, payload).map_err(|e| e.to_string())
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CancelChatArgs {
+  message_id: String,
 }
 
-fn nanoid() -> String {
-  // No extra dependency: just good-enough for MVP.
-  // This is NOT cryptographically secure.
-  let t = std::time::SystemTime::now()
-    .duration_since(std::time::UNIX_EPOCH)
-    .unwrap_or_default()
-    .as_millis();
-  format!("{}-{}", t, (t % 10_000) as u32)
+#[tauri::command]
+async fn cancel_chat(args: CancelChatArgs, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, AppError> {
+  let streams = state.chat_streams.lock().unwrap_or_else(|e| e.into_inner());
+  let cancelled = match streams.get(args.message_id.trim()) {
+    Some(tx) => {
+      tx.send_replace(true);
+      true
+    }
+    None => false,
+  };
+  Ok(serde_json::json!({ "message_id": args.message_id, "cancelled": cancelled }))
 }
 
-fn validate_media_id(id: &str) -> Result<(), String> {
-  let s = id.trim();
-  if s.is_empty() {
-    return Err("media_id is empty".to_string());
+// ==================== Conversations ====================
+
+// Older turns beyond this are folded into a running summary instead of being sent verbatim.
+const CHAT_HISTORY_MAX_TURNS: usize = 10;
+const CHAT_HISTORY_KEEP_RECENT: usize = 6;
+const CONVERSATION_TITLE_CHARS: usize = 40;
+
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ChatHistorySummary {
+  content: String,
+  // Number of leading messages the summary replaces.
+  covered: usize,
+  updated_at: String,
+}
+
+fn chat_role_label(role: &ChatRole) -> &'static str {
+  match role {
+    ChatRole::User => "user",
+    ChatRole::Assistant => "assistant",
+    ChatRole::System => "system",
   }
-  if !s.starts_with("media-") {
-    return Err("invalid media_id".to_string());
+}
+
+// Summarizes the turns that no longer fit, on top of the previous summary. Returns the previous
+// summary unchanged when nothing new needs folding in or the model call fails.
+async fn compact_chat_history(
+  ai: &AiSettings,
+  messages: &[ChatMessageIn],
+  prior: Option<ChatHistorySummary>,
+) -> Option<ChatHistorySummary> {
+  let prior = prior.filter(|p| p.covered <= messages.len() && !p.content.trim().is_empty());
+  let covered = prior.as_ref().map_or(0, |p| p.covered);
+  if messages.len() - covered <= CHAT_HISTORY_MAX_TURNS {
+    return prior;
   }
-  if s.len() > 128 {
-    return Err("media_id too long".to_string());
+  let cut = messages.len() - CHAT_HISTORY_KEEP_RECENT;
+
+  let mut turns = String::new();
+  for m in &messages[covered..cut] {
+    let content: String = m.content.chars().take(4000).collect();
+    turns.push_str(&format!("{}: {}\n\n", chat_role_label(&m.role), content.trim()));
   }
-  if !s
-    .chars()
-    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
-  {
-    return Err("invalid media_id characters".to_string());
+  let previous = prior.as_ref().map(|p| p.content.as_str()).unwrap_or("(none)");
+  let prompt = format!(
+    "Update the running summary of a conversation about a media file.\n\
+- Keep facts, answers given, decisions, open questions and any [MM:SS] timestamps.\n\
+- At most 200 words. Write in the language the conversation uses.\n\
+- Output only the summary.\n\n\
+Previous summary:\n{previous}\n\nNew messages:\n\n{turns}"
+  );
+
+  match ai_complete(ai, "", &prompt, false).await {
+    Ok(s) if !s.trim().is_empty() => Some(ChatHistorySummary {
+      content: s.trim().to_string(),
+      covered: cut,
+      updated_at: now_iso(),
+    }),
+    _ => prior,
   }
-  Ok(())
 }
 
-fn extract_first_http_url(raw: &str) -> Option<String> {
-  let s = raw.trim();
-  if s.is_empty() {
-    return None;
+// The messages actually sent: the summary (as a system message) plus the turns it doesn't cover.
+fn chat_messages_with_summary(messages: &[ChatMessageIn], summary: Option<&ChatHistorySummary>) -> Vec<ChatMessageIn> {
+  let mut out = Vec::new();
+  let mut rest = messages;
+  if let Some(s) = summary {
+    out.push(ChatMessageIn {
+      role: ChatRole::System,
+      content: format!("Summary of the earlier conversation:\n{}", s.content),
+    });
+    rest = &messages[s.covered.min(messages.len())..];
   }
+  if rest.len() > CHAT_HISTORY_MAX_TURNS {
+    rest = &rest[rest.len() - CHAT_HISTORY_MAX_TURNS..];
+  }
+  out.extend(rest.iter().cloned());
+  out
+}
 
-  let idx = s
-    .find("https://")
-    .or_else(|| s.find("http://"))?;
-
-  let tail = &s[idx..];
-  let end = tail
-    .find(char::is_whitespace)
-    .unwrap_or_else(|| tail.len());
-
-  let mut url = tail[..end].trim().to_string();
-  while let Some(last) = url.chars().last() {
-    if matches!(last, ']' | '】' | ')' | '）' | '>' | ',' | '，' | '。' | ';' | '；' | '!' | '！' | '?' | '？' | '"' | '\'' | '“' | '”' | '‘' | '’') {
-      url.pop();
-      continue;
-    }
-    break;
-  }
-
-  if url.starts_with("http://") || url.starts_with("https://") {
-    Some(url)
+fn validate_conversation_id(id: &str) -> Result<(), AppError> {
+  let ok = id.starts_with("conv-")
+    && id.len() <= 128
+    && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+  if ok {
+    Ok(())
   } else {
-    None
+    Err(AppError::InvalidInput("invalid conversation_id".to_string()))
   }
 }
 
-fn looks_like_youtube_url(url: &str) -> bool {
-  let u = url.trim().to_lowercase();
-  u.contains("youtube.com") || u.contains("youtu.be")
+fn conversations_dir(media_dir: &Path) -> PathBuf {
+  media_dir.join("conversations")
 }
 
-fn is_exe_available(name: &str) -> bool {
-  let n = name.trim();
-  if n.is_empty() {
-    return false;
-  }
+async fn load_conversation(media_dir: &Path, id: &str) -> Result<serde_json::Value, AppError> {
+  validate_conversation_id(id)?;
+  try_load_json(&conversations_dir(media_dir).join(format!("{id}.json")))
+    .await
+    .ok_or_else(|| AppError::InvalidInput(format!("unknown conversation: {id}")))
+}
 
-  // If user passed an explicit path.
-  let p = PathBuf::from(n);
-  if p.is_file() {
-    return true;
-  }
+fn save_conversation(media_dir: &Path, conv: &serde_json::Value) -> Result<(), AppError> {
+  let id = conv.get("id").and_then(|v| v.as_str()).unwrap_or("");
+  validate_conversation_id(id)?;
+  write_json_atomic(&conversations_dir(media_dir).join(format!("{id}.json")), conv).map_err(AppError::Internal)
+}
 
-  // PATH lookup.
-  let Some(path_os) = std::env::var_os("PATH") else {
-    return false;
-  };
-  for dir in std::env::split_paths(&path_os) {
-    let cand = dir.join(n);
-    if cand.is_file() {
-      return true;
-    }
-    if cfg!(windows) {
-      let cand_exe = dir.join(format!("{n}.exe"));
-      if cand_exe.is_file() {
-        return true;
+// All conversations of a media item, oldest first.
+async fn load_all_conversations(media_dir: &Path) -> Vec<serde_json::Value> {
+  let mut out = Vec::new();
+  if let Ok(mut rd) = tokio::fs::read_dir(conversations_dir(media_dir)).await {
+    while let Ok(Some(entry)) = rd.next_entry().await {
+      let path = entry.path();
+      if path.extension().and_then(|e| e.to_str()) != Some("json") {
+        continue;
+      }
+      if let Some(v) = try_load_json(&path).await {
+        out.push(v);
       }
     }
   }
-  false
+  out.sort_by_key(|c| c.get("createdAt").and_then(|v| v.as_str()).unwrap_or("").to_string());
+  out
 }
 
-fn parse_ytdlp_percent(line: &str) -> Option<f64> {
-  // Typical line: "[download]  12.3% of ..."
-  let pct_idx = line.find('%')?;
-  let bytes = line.as_bytes();
+fn conversation_messages(conv: &serde_json::Value) -> Vec<ChatMessageIn> {
+  conv
+    .get("messages")
+    .and_then(|v| v.as_array())
+    .into_iter()
+    .flatten()
+    .filter_map(|m| serde_json::from_value::<ChatMessageIn>(m.clone()).ok())
+    .collect()
+}
 
-  let mut start = pct_idx;
-  while start > 0 {
-    let b = bytes[start - 1];
-    if (b as char).is_ascii_digit() || b == b'.' {
-      start -= 1;
-      continue;
+fn conversation_info(conv: &serde_json::Value) -> serde_json::Value {
+  serde_json::json!({
+    "id": conv.get("id"),
+    "title": conv.get("title"),
+    "created_at": conv.get("createdAt"),
+    "updated_at": conv.get("updatedAt"),
+    "message_count": conv.get("messages").and_then(|v| v.as_array()).map_or(0, |a| a.len()),
+  })
+}
+
+// Appends the new user turn(s) and the reply, untitled conversations take the first question as title.
+fn append_conversation_turn(
+  conv: &mut serde_json::Value,
+  new_messages: &[ChatMessageIn],
+  reply: &serde_json::Value,
+  summary: Option<&ChatHistorySummary>,
+) {
+  let now = now_iso();
+  if !conv.get("messages").is_some_and(|v| v.is_array()) {
+    conv["messages"] = serde_json::json!([]);
+  }
+  if let Some(msgs) = conv.get_mut("messages").and_then(|v| v.as_array_mut()) {
+    for m in new_messages {
+      msgs.push(serde_json::json!({
+        "id": format!("msg-{}", nanoid()),
+        "role": chat_role_label(&m.role),
+        "content": m.content,
+        "timestamp": now,
+      }));
+    }
+    msgs.push(reply.clone());
+  }
+  if let Some(s) = summary.and_then(|s| serde_json::to_value(s).ok()) {
+    conv["historySummary"] = s;
+  }
+  let untitled = conv.get("title").and_then(|v| v.as_str()).is_none_or(|t| t.trim().is_empty());
+  if untitled {
+    if let Some(q) = new_messages.iter().find(|m| matches!(m.role, ChatRole::User)) {
+      let title: String = q.content.trim().chars().take(CONVERSATION_TITLE_CHARS).collect();
+      conv["title"] = serde_json::json!(title);
     }
-    break;
   }
+  conv["updatedAt"] = serde_json::json!(now);
+}
 
-  if start == pct_idx {
-    return None;
+fn conversations_markdown(conversations: &[serde_json::Value]) -> String {
+  let mut out = String::new();
+  for conv in conversations {
+    let title = conv.get("title").and_then(|v| v.as_str()).unwrap_or("").trim();
+    out.push_str(&format!("# {}\n\n", if title.is_empty() { "Conversation" } else { title }));
+    for m in conv.get("messages").and_then(|v| v.as_array()).into_iter().flatten() {
+      let role = match m.get("role").and_then(|v| v.as_str()).unwrap_or("") {
+        "user" => "User",
+        "assistant" => "Assistant",
+        _ => continue,
+      };
+      let ts = m.get("timestamp").and_then(|v| v.as_str()).unwrap_or("");
+      let content = m.get("content").and_then(|v| v.as_str()).unwrap_or("").trim();
+      out.push_str(&format!("**{role}** ({ts}):\n\n{content}\n\n"));
+    }
   }
+  out
+}
 
-  let num = &line[start..pct_idx];
-  num.trim().parse::<f64>().ok()
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConversationArgs {
+  media_id: String,
+  #[serde(default)]
+  conversation_id: Option<String>,
+  #[serde(default)]
+  title: Option<String>,
 }
 
-fn maybe_emit_ytdlp_progress(
+async fn conversation_media_dir(
   app: &tauri::AppHandle,
-  job_id: &str,
+  state: &AppState,
   media_id: &str,
-  line: &str,
-  state: &Arc<std::sync::Mutex<(i32, std::time::Instant)>>,
-) {
-  let now = std::time::Instant::now();
-
-  let mut guard = state.lock().unwrap_or_else(|e| e.into_inner());
-  let (last_pct, last_emit) = &mut *guard;
+) -> Result<PathBuf, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(app).map_err(AppError::Internal) })
+    .await?;
+  let media_id = media_id.trim();
+  validate_media_id(media_id)?;
+  let media_dir = dir.join("media").join(media_id);
+  if !media_dir.is_dir() {
+    return Err(AppError::MediaNotFound);
+  }
+  Ok(media_dir)
+}
 
-  if let Some(pct) = parse_ytdlp_percent(line) {
-    let pct_i = pct.round() as i32;
+fn required_conversation_id(args: &ConversationArgs) -> Result<String, AppError> {
+  let id = args.conversation_id.as_deref().unwrap_or("").trim().to_string();
+  if id.is_empty() {
+    return Err(AppError::InvalidInput("conversation_id is required".to_string()));
+  }
+  Ok(id)
+}
 
-    if pct_i == *last_pct {
-      return;
-    }
-    if pct_i != 100 && now.duration_since(*last_emit) <= std::time::Duration::from_millis(350) {
-      return;
-    }
+#[tauri::command]
+async fn create_conversation(
+  app: tauri::AppHandle,
+  args: ConversationArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let media_dir = conversation_media_dir(&app, &state, &args.media_id).await?;
+  let now = now_iso();
+  let conv = serde_json::json!({
+    "version": 1,
+    "id": format!("conv-{}", nanoid()),
+    "mediaId": args.media_id.trim(),
+    "title": args.title.as_deref().unwrap_or("").trim(),
+    "createdAt": now,
+    "updatedAt": now,
+    "messages": [],
+  });
+  save_conversation(&media_dir, &conv)?;
+  Ok(conv)
+}
 
-    *last_pct = pct_i;
-    *last_emit = now;
+#[tauri::command]
+async fn list_conversations(
+  app: tauri::AppHandle,
+  args: ConversationArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let media_dir = conversation_media_dir(&app, &state, &args.media_id).await?;
+  let mut list: Vec<serde_json::Value> = load_all_conversations(&media_dir).await.iter().map(conversation_info).collect();
+  // Most recently used first.
+  list.sort_by_key(|c| std::cmp::Reverse(c.get("updated_at").and_then(|v| v.as_str()).unwrap_or("").to_string()));
+  Ok(serde_json::json!({ "media_id": args.media_id, "conversations": list }))
+}
 
-    // Reserve the last 10% for analyze + thumbnail.
-    let p = ((pct / 100.0) as f32).clamp(0.0, 1.0) * 0.9;
-    let _ = emit_job(
-      app,
-      JobProgressEvent {
-        job_id: job_id.to_string(),
-        media_id: media_id.to_string(),
-        job_type: JobType::Download,
-        status: JobStatus::Running,
-        progress: p,
-        message: Some(format!("downloading {pct_i}%")),
-      },
-    );
-    return;
-  }
+#[tauri::command]
+async fn get_conversation(
+  app: tauri::AppHandle,
+  args: ConversationArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let media_dir = conversation_media_dir(&app, &state, &args.media_id).await?;
+  let id = required_conversation_id(&args)?;
+  load_conversation(&media_dir, &id).await
+}
 
-  // Also surface non-percent status/warnings so the UI doesn't look frozen.
-  let t = line.trim();
-  if t.is_empty() {
-    return;
-  }
-  let lower = t.to_lowercase();
-  let looks_useful = lower.starts_with("[download]")
-    || lower.starts_with("[youtube]")
-    || lower.contains("warning")
-    || lower.contains("error")
-    || lower.contains("retry")
-    || lower.contains("extract")
-    || lower.contains("merg")
-    || lower.contains("frag")
-    || lower.contains("destin");
-  if !looks_useful {
-    return;
-  }
-  if now.duration_since(*last_emit) <= std::time::Duration::from_millis(700) {
-    return;
+#[tauri::command]
+async fn rename_conversation(
+  app: tauri::AppHandle,
+  args: ConversationArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let media_dir = conversation_media_dir(&app, &state, &args.media_id).await?;
+  let id = required_conversation_id(&args)?;
+  let title = args.title.as_deref().unwrap_or("").trim().to_string();
+  if title.is_empty() {
+    return Err(AppError::InvalidInput("title is required".to_string()));
   }
-  *last_emit = now;
+  let _guard = state.state_io_lock.lock().await;
+  let mut conv = load_conversation(&media_dir, &id).await?;
+  conv["title"] = serde_json::json!(title);
+  conv["updatedAt"] = serde_json::json!(now_iso());
+  save_conversation(&media_dir, &conv)?;
+  Ok(conversation_info(&conv))
+}
 
-  let mut msg = t.to_string();
-  const MAX: usize = 180;
-  if msg.chars().count() > MAX {
-    msg = msg.chars().take(MAX).collect::<String>();
+#[tauri::command]
+async fn delete_conversation(
+  app: tauri::AppHandle,
+  args: ConversationArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let media_dir = conversation_media_dir(&app, &state, &args.media_id).await?;
+  let id = required_conversation_id(&args)?;
+  validate_conversation_id(&id)?;
+  let path = conversations_dir(&media_dir).join(format!("{id}.json"));
+  let _guard = state.state_io_lock.lock().await;
+  let deleted = path.is_file();
+  if deleted {
+    tokio::fs::remove_file(&path)
+      .await
+      .map_err(|e| AppError::Internal(format!("delete conversation failed: {e}")))?;
   }
-
-  let p = if *last_pct >= 0 {
-    ((*last_pct as f32) / 100.0).clamp(0.0, 1.0) * 0.9
-  } else {
-    0.02
-  };
-
-  let _ = emit_job(
-    app,
-    JobProgressEvent {
-      job_id: job_id.to_string(),
-      media_id: media_id.to_string(),
-      job_type: JobType::Download,
-      status: JobStatus::Running,
-      progress: p,
-      message: Some(msg),
-    },
-  );
+  Ok(serde_json::json!({ "conversation_id": id, "deleted": deleted }))
 }
 
-#[derive(Clone, Debug)]
-struct YtDlpRunOpts {
-  has_ffmpeg: bool,
-  ffmpeg_dir: Option<PathBuf>,
-  cookies_path: Option<PathBuf>,
+// ==================== Collection chat ====================
 
-  format: String,
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChatCollectionArgs {
+  collection_id: String,
+  ai: AiSettings,
+  messages: Vec<ChatMessageIn>,
+  // Subset of the collection's media; defaults to all of it.
+  #[serde(default)]
+  media_ids: Option<Vec<String>>,
+  #[serde(default)]
+  include_summary: bool,
+  #[serde(default)]
+  user_lang: Option<String>,
+  // Total characters of excerpts (and summaries) sent across all media.
+  #[serde(default)]
+  context_chars: Option<usize>,
+}
 
-  retries: u32,
-  fragment_retries: u32,
-  extractor_retries: u32,
-  socket_timeout: u32,
-  concurrent_fragments: u32,
+const COLLECTION_CONTEXT_CHARS: usize = 24_000;
+const COLLECTION_SUMMARY_CHARS: usize = 800;
 
-  force_ipv4: bool,
-  youtube_compat: bool,
-  js_runtime: Option<String>,
-  insecure: bool,
+struct CollectionSource {
+  media_id: String,
+  name: String,
+  dir: PathBuf,
+  windows: Vec<ChatWindow>,
+  summary: Option<String>,
 }
 
-fn select_ytdlp_format(has_ffmpeg: bool, quality: Option<&str>) -> String {
-  // Default: keep compatibility (mp4 merge when possible), allow a simple height cap.
-  let q = quality.unwrap_or("best").trim().to_lowercase();
-  let height: Option<u32> = match q.as_str() {
-    "1080p" | "1080" => Some(1080),
-    "720p" | "720" => Some(720),
-    "480p" | "480" => Some(480),
-    "360p" | "360" => Some(360),
-    "best" | "auto" | "" => None,
-    _ => None,
-  };
+// `[name @ MM:SS]`; brackets in names would break the citation syntax.
+fn collection_citation(name: &str, start: f64) -> String {
+  let name = name.replace('[', "(").replace(']', ")");
+  format!("[{name} @ {}]", seconds_to_timestamp(start))
+}
 
-  if has_ffmpeg {
-    if let Some(h) = height {
-      // Prefer mp4+h264-ish sources when available, fallback to any container.
-      return format!(
-        "bestvideo[height<={h}][ext=mp4]+bestaudio[ext=m4a]/bestvideo[height<={h}]+bestaudio/best[height<={h}][ext=mp4]/best[height<={h}]/best",
-        h = h
-      );
+// Ranks transcript windows of all sources together (shared BM25 statistics, optional embeddings),
+// then fills `budget` chars with the best ones, capping any single media item so several get cited.
+async fn retrieve_collection_context(
+  sources: &[CollectionSource],
+  query: &str,
+  ai: &AiSettings,
+  budget: usize,
+) -> (String, Vec<serde_json::Value>) {
+  let mut out = String::new();
+  let mut used = 0usize;
+  for s in sources {
+    if let Some(sum) = s.summary.as_deref() {
+      let sum: String = sum.trim().chars().take(COLLECTION_SUMMARY_CHARS).collect();
+      let len = sum.chars().count();
+      if !sum.is_empty() && used + len <= budget / 3 {
+        used += len;
+        out.push_str(&format!("Summary of {}:\n{sum}\n\n", s.name));
+      }
     }
-    return "bestvideo[ext=mp4]+bestaudio[ext=m4a]/bestvideo+bestaudio/best".to_string();
   }
 
-  if let Some(h) = height {
-    return format!("best[height<={h}][ext=mp4]/best[height<={h}]/best", h = h);
-  }
-  "best".to_string()
-}
-
-fn is_retryable_ytdlp_failure(stderr_tail: &str) -> bool {
-  let s = stderr_tail.to_lowercase();
-  s.contains("eof occurred in violation of protocol")
-    || s.contains("_ssl.c")
-    || s.contains("tls")
-    || s.contains("ssl")
-    || s.contains("connection reset")
-    || s.contains("timed out")
-    || s.contains("temporary failure")
-}
-
-async fn run_ytdlp_download(
-  app: &tauri::AppHandle,
-  job_id: &str,
-  media_id: &str,
-  ytdlp: &Path,
-  url: &str,
-  output_template: &Path,
-  opts: &YtDlpRunOpts,
-) -> Result<(), String> {
-  let mut cmd = tokio::process::Command::new(ytdlp);
-  cmd
-    .arg("--no-playlist")
-    .arg("--newline")
-    .arg("--write-info-json")
-    .arg("--retries")
-    .arg(opts.retries.to_string())
-    .arg("--fragment-retries")
-    .arg(opts.fragment_retries.to_string())
-    .arg("--extractor-retries")
-    .arg(opts.extractor_retries.to_string())
-    .arg("--socket-timeout")
-    .arg(opts.socket_timeout.to_string())
-    .arg("--concurrent-fragments")
-    .arg(opts.concurrent_fragments.to_string())
-    .arg("-f")
-    .arg(opts.format.as_str());
-
-  if opts.has_ffmpeg {
-    cmd.arg("--merge-output-format").arg("mp4");
-  }
-  if opts.force_ipv4 {
-    cmd.arg("--force-ipv4");
-  }
-  if opts.insecure {
-    cmd.arg("--no-check-certificate");
+  let flat: Vec<(usize, usize)> = sources
+    .iter()
+    .enumerate()
+    .flat_map(|(si, s)| (0..s.windows.len()).map(move |wi| (si, wi)))
+    .collect();
+  let texts: Vec<&str> = flat.iter().map(|&(si, wi)| sources[si].windows[wi].text.as_str()).collect();
+
+  let mut lists = vec![search::Bm25::new(&texts).scores(query)];
+  if let Some(endpoint) = usable_embedding_endpoint(ai) {
+    let embedded = async {
+      let q = openai_embeddings(&endpoint.0, &endpoint.1, &endpoint.2, &[query.to_string()]).await?;
+      let mut sims = vec![0.0f32; flat.len()];
+      let mut offset = 0;
+      for s in sources {
+        let index = load_or_build_embedding_index(&s.dir, &s.windows, &endpoint).await?;
+        for (i, v) in index.iter().enumerate() {
+          sims[offset + i] = search::cosine(v, &q[0]).max(0.0);
+        }
+        offset += s.windows.len();
+      }
+      Ok(sims)
+    }
+    .await;
+    record_embedding_result(&endpoint, &embedded);
+    if let Ok(sims) = embedded {
+      lists.push(sims);
+    }
   }
+  let fused = search::reciprocal_rank_fusion(&lists, 60.0);
 
-  if opts.youtube_compat {
-    cmd.arg("--extractor-args").arg("youtube:player_client=android");
-  }
-  if let Some(rt) = opts.js_runtime.as_ref().map(|s| s.trim()).filter(|s| !s.is_empty()) {
-    cmd.arg("--js-runtimes").arg(rt);
+  let mut order: Vec<usize> = (0..flat.len()).filter(|&i| fused.get(i).copied().unwrap_or(0.0) > 0.0).collect();
+  if order.is_empty() {
+    // No hits: sample the beginning of every item, round-robin.
+    order = (0..flat.len()).collect();
+    order.sort_by_key(|&i| (flat[i].1, flat[i].0));
+  } else {
+    order.sort_by(|&a, &b| fused[b].partial_cmp(&fused[a]).unwrap_or(std::cmp::Ordering::Equal));
   }
 
-  if let Some(dir) = opts.ffmpeg_dir.as_ref() {
-    cmd.arg("--ffmpeg-location").arg(dir);
-  }
-  if let Some(cookies) = opts.cookies_path.as_ref() {
-    cmd.arg("--cookies").arg(cookies);
+  let per_media_cap = (budget / 3).max(budget / sources.len().max(1));
+  let mut per_media = vec![0usize; sources.len()];
+  let mut picked: Vec<(usize, usize)> = Vec::new();
+  for i in order {
+    let (si, wi) = flat[i];
+    let len = sources[si].windows[wi].text.chars().count() + sources[si].name.chars().count() + 16;
+    if used + len > budget {
+      break;
+    }
+    if per_media[si] + len > per_media_cap {
+      continue;
+    }
+    used += len;
+    per_media[si] += len;
+    picked.push((si, wi));
+  }
+  picked.sort_unstable();
+
+  let mut cited: Vec<serde_json::Value> = Vec::new();
+  let mut last_source: Option<usize> = None;
+  for (si, wi) in picked {
+    let s = &sources[si];
+    let w = &s.windows[wi];
+    if last_source != Some(si) {
+      out.push_str(&format!("\n## {}\n", s.name));
+      last_source = Some(si);
+    }
+    let citation = collection_citation(&s.name, w.start);
+    out.push_str(&format!("{citation} {}\n", w.text));
+    cited.push(serde_json::json!({
+      "media_id": s.media_id,
+      "media_name": s.name,
+      "timestamp": w.start,
+      "citation": citation,
+    }));
   }
+  (out, cited)
+}
 
-  cmd
-    .arg("-o")
-    .arg(output_template)
-    .arg(url)
-    .stdout(std::process::Stdio::piped())
-    .stderr(std::process::Stdio::piped());
-
-  let mut child = cmd.spawn().map_err(|e| format!("spawn yt-dlp failed: {e}"))?;
-  let stdout = child.stdout.take().ok_or_else(|| "yt-dlp stdout unavailable".to_string())?;
-  let stderr = child.stderr.take().ok_or_else(|| "yt-dlp stderr unavailable".to_string())?;
-
-  let stderr_tail = Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
-  let progress_state = Arc::new(std::sync::Mutex::new((
-    -1i32,
-    std::time::Instant::now() - std::time::Duration::from_secs(10),
-  )));
+async fn chat_with_collection_context(
+  collection_name: &str,
+  ctx: &str,
+  user_lang: Option<&str>,
+  ai: &AiSettings,
+  messages: &[ChatMessageIn],
+) -> Result<String, AppError> {
+  let lang_hint = user_lang.unwrap_or("").trim().to_lowercase();
+  let mut sys = format!(
+    "You are a research assistant answering questions across the media collection \"{collection_name}\". \
+     Answer using the user's language. "
+  );
+  if lang_hint.starts_with("zh") {
+    sys.push_str("Prefer Chinese. ");
+  } else if lang_hint.starts_with("en") {
+    sys.push_str("Prefer English. ");
+  }
+  sys.push_str(
+    "Use the provided excerpts as the primary source of truth. Cite every fact with the tag in front of the \
+     excerpt it comes from, exactly as written, e.g. [Lecture 3 @ 12:34]. If the excerpts don't cover the question, say so.",
+  );
 
-  let stderr_tail_task = {
-    let stderr_tail = stderr_tail.clone();
-    let progress_state = progress_state.clone();
-    let job_id = job_id.to_string();
-    let media_id = media_id.to_string();
-    let app_handle = app.clone();
-    tokio::spawn(async move {
-      use tokio::io::AsyncBufReadExt;
-      let mut lines = tokio::io::BufReader::new(stderr).lines();
-      while let Ok(Some(line)) = lines.next_line().await {
-        maybe_emit_ytdlp_progress(&app_handle, &job_id, &media_id, line.trim(), &progress_state);
-        let mut buf = stderr_tail.lock().unwrap_or_else(|e| e.into_inner());
-        buf.push(line);
-        if buf.len() > 30 {
-          buf.remove(0);
-        }
+  match ai.provider {
+    AiProvider::OpenaiCompatible => {
+      let mut out_msgs = vec![serde_json::json!({ "role": "system", "content": sys })];
+      if !ctx.trim().is_empty() {
+        out_msgs.push(serde_json::json!({ "role": "user", "content": format!("Excerpts:\n{ctx}") }));
       }
-    })
-  };
-
-  use tokio::io::AsyncBufReadExt;
-  let mut out_lines = tokio::io::BufReader::new(stdout).lines();
-  while let Ok(Some(line)) = out_lines.next_line().await {
-    maybe_emit_ytdlp_progress(app, job_id, media_id, line.trim(), &progress_state);
+      for m in messages {
+        out_msgs.push(serde_json::json!({ "role": chat_role_label(&m.role), "content": m.content }));
+      }
+      let o = &ai.openai;
+      openai_chat_completion(&o.base_url, &o.api_key, &o.chat_model, out_msgs).await
+    }
+    AiProvider::Gemini => {
+      if !ctx.trim().is_empty() {
+        sys.push_str(&format!("\n\nExcerpts:\n{ctx}"));
+      }
+      let g = &ai.gemini;
+      let body = gemini_chat_body(&sys, messages, g);
+      gemini_generate_content_with_body(&g.base_url, &g.api_key, &g.model, &body).await
+    }
+    AiProvider::Anthropic => {
+      if !ctx.trim().is_empty() {
+        sys.push_str(&format!("\n\nExcerpts:\n{ctx}"));
+      }
+      let msgs = anthropic_messages_from_chat(&mut sys, messages);
+      anthropic_messages(&ai.anthropic, &sys, msgs, None, None).await
+    }
+    AiProvider::Ollama => {
+      let mut out_msgs = vec![serde_json::json!({ "role": "system", "content": sys })];
+      if !ctx.trim().is_empty() {
+        out_msgs.push(serde_json::json!({ "role": "user", "content": format!("Excerpts:\n{ctx}") }));
+      }
+      for m in messages {
+        out_msgs.push(serde_json::json!({ "role": chat_role_label(&m.role), "content": m.content }));
+      }
+      ollama_chat(&ai.ollama, out_msgs, false, None).await
+    }
   }
+}
 
-  let status = child
-    .wait()
-    .await
-    .map_err(|e| format!("wait yt-dlp failed: {e}"))?;
-  let _ = stderr_tail_task.await;
+#[tauri::command]
+async fn chat_collection(
+  app: tauri::AppHandle,
+  args: ChatCollectionArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
 
-  if !status.success() {
-    let tail = stderr_tail
-      .lock()
-      .unwrap_or_else(|e| e.into_inner())
-      .join("\n");
-    return Err(tail);
+  let query = args
+    .messages
+    .iter()
+    .rev()
+    .find(|m| matches!(m.role, ChatRole::User))
+    .map(|m| m.content.trim().to_string())
+    .unwrap_or_default();
+  if query.is_empty() {
+    return Err(AppError::InvalidInput("question is empty".to_string()));
   }
-  Ok(())
-}
 
-fn find_source_file(media_dir: &Path) -> Result<PathBuf, String> {
-  let mut best: Option<(PathBuf, u64, std::time::SystemTime)> = None;
+  let saved = try_load_json(&state_file_path(dir)).await.unwrap_or_default();
+  let data = saved.get("data");
+  let collection = data
+    .and_then(|d| d.get("collections"))
+    .and_then(|v| v.as_array())
+    .and_then(|arr| {
+      arr.iter()
+        .find(|c| c.get("id").and_then(|v| v.as_str()) == Some(args.collection_id.trim()))
+    })
+    .ok_or_else(|| AppError::InvalidInput(format!("unknown collection: {}", args.collection_id)))?;
+  let collection_name = collection.get("name").and_then(|v| v.as_str()).unwrap_or("").trim().to_string();
 
-  let rd = std::fs::read_dir(media_dir)
-    .map_err(|e| format!("read media dir failed: {e}"))?;
-  for entry in rd {
-    let entry = entry.map_err(|e| format!("read media dir entry failed: {e}"))?;
-    let path = entry.path();
-    if !path.is_file() {
+  let members: Vec<String> = collection
+    .get("mediaIds")
+    .and_then(|v| v.as_array())
+    .into_iter()
+    .flatten()
+    .filter_map(|v| v.as_str().map(|s| s.to_string()))
+    .filter(|id| args.media_ids.as_ref().is_none_or(|only| only.contains(id)))
+    .collect();
+  let items: HashMap<&str, &serde_json::Value> = data
+    .and_then(|d| d.get("mediaItems"))
+    .and_then(|v| v.as_array())
+    .into_iter()
+    .flatten()
+    .filter_map(|it| it.get("id").and_then(|v| v.as_str()).map(|id| (id, it)))
+    .collect();
+
+  let mut sources: Vec<CollectionSource> = Vec::new();
+  for media_id in members {
+    if validate_media_id(&media_id).is_err() {
       continue;
     }
-
-    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
-    if !name.starts_with("source.") {
+    let media_dir = dir.join("media").join(&media_id);
+    if !media_dir.is_dir() {
       continue;
     }
-    if name.ends_with(".part") {
+    let item = items.get(media_id.as_str());
+    let name = item
+      .and_then(|it| it.get("name"))
+      .and_then(|v| v.as_str())
+      .map(|s| s.trim().to_string())
+      .filter(|s| !s.is_empty())
+      .unwrap_or_else(|| media_id.clone());
+    let transcription = match try_load_json(&media_dir.join("transcription.json")).await {
+      Some(t) => Some(t),
+      None => item.and_then(|it| it.get("transcription").cloned()),
+    };
+    let summary = if args.include_summary {
+      try_load_json(&media_dir.join("summary.json"))
+        .await
+        .and_then(|s| s.get("content").and_then(|c| c.as_str()).map(strip_mermaid_code_blocks))
+    } else {
+      None
+    };
+    let windows = transcription.as_ref().map(transcript_windows).unwrap_or_default();
+    if windows.is_empty() && summary.is_none() {
       continue;
     }
+    sources.push(CollectionSource { media_id, name, dir: media_dir, windows, summary });
+  }
+  if sources.is_empty() {
+    return Err(AppError::InvalidInput("no transcribed media in this collection".to_string()));
+  }
 
-    let meta = entry
-      .metadata()
-      .map_err(|e| format!("read media file metadata failed: {e}"))?;
-    let size = meta.len();
-    let modified = meta.modified().unwrap_or(std::time::UNIX_EPOCH);
+  let budget = args.context_chars.unwrap_or(COLLECTION_CONTEXT_CHARS).clamp(2_000, 400_000);
+  let (ctx, cited) = retrieve_collection_context(&sources, &query, &args.ai, budget).await;
+  // Collection chats aren't saved, so there is nowhere to keep a history summary.
+  let messages = chat_messages_with_summary(&args.messages, None);
+  let reply = chat_with_collection_context(
+    &collection_name,
+    &ctx,
+    args.user_lang.as_deref(),
+    &args.ai,
+    &messages,
+  )
+  .await?;
 
-    match &best {
-      None => best = Some((path, size, modified)),
-      Some((_, best_size, best_mod)) => {
-        if modified > *best_mod || (modified == *best_mod && size > *best_size) {
-          best = Some((path, size, modified));
-        }
-      }
-    }
-  }
+  // Only report excerpts the answer actually cites.
+  let sources_cited: Vec<serde_json::Value> = cited
+    .into_iter()
+    .filter(|c| c.get("citation").and_then(|v| v.as_str()).is_some_and(|tag| reply.contains(tag)))
+    .collect();
 
-  best
-    .map(|(p, _, _)| p)
-    .ok_or_else(|| "downloaded media file not found".to_string())
+  Ok(serde_json::json!({
+    "collection_id": args.collection_id,
+    "message": {
+      "id": format!("msg-{}", nanoid()),
+      "role": "assistant",
+      "content": reply,
+      "timestamp": now_iso(),
+    },
+    "sources": sources_cited,
+    "media_count": sources.len(),
+  }))
 }
 
-fn find_info_json(media_dir: &Path) -> Option<PathBuf> {
-  let mut best: Option<(PathBuf, std::time::SystemTime)> = None;
-  let rd = std::fs::read_dir(media_dir).ok()?;
-  for entry in rd.flatten() {
-    let path = entry.path();
-    if !path.is_file() {
-      continue;
-    }
-    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
-    if !name.ends_with(".info.json") {
+// ==================== Library search ====================
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchLibraryArgs {
+  query: String,
+  #[serde(default)]
+  limit: Option<usize>,
+  // Restrict to these media items (e.g. one collection).
+  #[serde(default)]
+  media_ids: Option<Vec<String>>,
+  // Any of "transcript", "subtitle", "summary", "note".
+  #[serde(default)]
+  kinds: Option<Vec<String>>,
+}
+
+const SEARCH_SNIPPET_CHARS: usize = 160;
+
+#[derive(Default)]
+struct LibrarySearch {
+  index: search::InvertedIndex,
+  built: bool,
+  // Media names and notes from state.json, as of the last save_state.
+  names: HashMap<String, String>,
+  notes: HashMap<String, serde_json::Value>,
+}
+
+// Media re-indexed on the next search. Kept outside AppState so file writers that only have a
+// media dir can mark it.
+fn search_dirty() -> &'static std::sync::Mutex<HashSet<String>> {
+  static DIRTY: std::sync::OnceLock<std::sync::Mutex<HashSet<String>>> = std::sync::OnceLock::new();
+  DIRTY.get_or_init(|| std::sync::Mutex::new(HashSet::new()))
+}
+
+// Call after writing a media item's transcription, summary or subtitles, or deleting it.
+fn mark_search_dirty(media_dir: &Path) {
+  if let Some(id) = media_dir.file_name().and_then(|n| n.to_str()) {
+    search_dirty().lock().unwrap_or_else(|e| e.into_inner()).insert(id.to_string());
+  }
+}
+
+fn state_names_and_notes(saved: &serde_json::Value) -> (HashMap<String, String>, HashMap<String, serde_json::Value>) {
+  let mut names: HashMap<String, String> = HashMap::new();
+  let mut notes: HashMap<String, serde_json::Value> = HashMap::new();
+  for it in saved
+    .get("data")
+    .and_then(|d| d.get("mediaItems"))
+    .and_then(|v| v.as_array())
+    .into_iter()
+    .flatten()
+  {
+    let Some(id) = it.get("id").and_then(|v| v.as_str()) else {
       continue;
+    };
+    if let Some(name) = it.get("name").and_then(|v| v.as_str()) {
+      names.insert(id.to_string(), name.trim().to_string());
     }
-    let modified = entry.metadata().and_then(|m| m.modified()).unwrap_or(std::time::UNIX_EPOCH);
-    match &best {
-      None => best = Some((path, modified)),
-      Some((_, best_mod)) => {
-        if modified > *best_mod {
-          best = Some((path, modified));
-        }
-      }
+    if let Some(n) = it.get("notes") {
+      notes.insert(id.to_string(), n.clone());
     }
   }
-  best.map(|(p, _)| p)
+  (names, notes)
 }
 
-fn ytdlp_upload_date_to_iso(d: &str) -> Option<String> {
-  let s = d.trim();
-  if s.len() != 8 {
-    return None;
+// save_state hook: picks up renamed items and marks the ones whose notes changed.
+async fn sync_search_state(state: &AppState, saved: &serde_json::Value) {
+  let mut lib = state.search_index.lock().await;
+  if !lib.built {
+    return;
   }
-  let (y, rest) = s.split_at(4);
-  let (m, day) = rest.split_at(2);
-  if y.chars().all(|c| c.is_ascii_digit())
-    && m.chars().all(|c| c.is_ascii_digit())
-    && day.chars().all(|c| c.is_ascii_digit())
+  let (names, notes) = state_names_and_notes(saved);
   {
-    return Some(format!("{y}-{m}-{day}"));
+    let mut dirty = search_dirty().lock().unwrap_or_else(|e| e.into_inner());
+    for id in lib.notes.keys().chain(notes.keys()) {
+      if lib.notes.get(id) != notes.get(id) {
+        dirty.insert(id.clone());
+      }
+    }
   }
-  None
+  lib.names = names;
+  lib.notes = notes;
 }
 
-fn sidecar_basename(name: &str) -> String {
-  if cfg!(windows) {
-    format!("{name}.exe")
-  } else {
-    name.to_string()
+fn segment_docs(
+  media_id: &str,
+  kind: &'static str,
+  track: Option<&str>,
+  segments: &[serde_json::Value],
+  out: &mut Vec<search::IndexDoc>,
+) {
+  for seg in segments {
+    let text = seg.get("text").and_then(|v| v.as_str()).unwrap_or("").trim();
+    if text.is_empty() {
+      continue;
+    }
+    out.push(search::IndexDoc {
+      media_id: media_id.to_string(),
+      kind,
+      segment_id: seg.get("id").and_then(|v| v.as_str()).map(|s| s.to_string()),
+      track: track.map(|s| s.to_string()),
+      start: seg.get("start").and_then(|v| v.as_f64()),
+      text: text.to_string(),
+    });
   }
 }
 
-fn is_probably_stub_binary(path: &Path) -> bool {
-  let meta = match std::fs::metadata(path) {
-    Ok(m) => m,
-    Err(_) => return true,
+fn media_search_docs(
+  media_id: &str,
+  transcription: Option<&serde_json::Value>,
+  summary: Option<&serde_json::Value>,
+  subtitles: Option<&serde_json::Value>,
+  notes: Option<&serde_json::Value>,
+) -> Vec<search::IndexDoc> {
+  let mut out = Vec::new();
+  let doc = |kind: &'static str, segment_id: Option<String>, start: Option<f64>, text: &str| search::IndexDoc {
+    media_id: media_id.to_string(),
+    kind,
+    segment_id,
+    track: None,
+    start,
+    text: text.trim().to_string(),
   };
 
-  let len = meta.len();
-  if len == 0 {
-    return true;
+  if let Some(segs) = transcription.and_then(|t| t.get("segments")).and_then(|s| s.as_array()) {
+    segment_docs(media_id, "transcript", None, segs, &mut out);
   }
 
-  // Real ffmpeg/ffprobe/yt-dlp are always multi-MB. Avoid touching large files.
-  if len > 2 * 1024 * 1024 {
-    return false;
+  if let Some(tracks) = subtitles.and_then(|s| s.get("tracks")).and_then(|t| t.as_array()) {
+    for track in tracks {
+      let id = track.get("id").and_then(|v| v.as_str()).unwrap_or("");
+      // The original track mirrors transcription.json.
+      if id == "original" && transcription.is_some() {
+        continue;
+      }
+      if let Some(segs) = track.get("segments").and_then(|s| s.as_array()) {
+        segment_docs(media_id, "subtitle", Some(id), segs, &mut out);
+      }
+    }
   }
 
-  // Very tiny executables are almost certainly invalid/stubs.
-  if len < 16 * 1024 {
-    return true;
+  if let Some(s) = summary {
+    let content = s.get("content").and_then(|v| v.as_str()).unwrap_or("");
+    for para in content.split("\n\n").filter(|p| !p.trim().is_empty()) {
+      out.push(doc("summary", None, None, para));
+    }
+    for kp in s.get("keyPoints").and_then(|v| v.as_array()).into_iter().flatten() {
+      if let Some(text) = kp.as_str().filter(|t| !t.trim().is_empty()) {
+        out.push(doc("summary", None, None, text));
+      }
+    }
+    for ch in s.get("chapters").and_then(|v| v.as_array()).into_iter().flatten() {
+      if let Some(title) = ch.get("title").and_then(|v| v.as_str()).filter(|t| !t.trim().is_empty()) {
+        out.push(doc("summary", None, ch.get("timestamp").and_then(|v| v.as_f64()), title));
+      }
+    }
   }
 
-  // Our stubs print a distinctive marker.
-  if let Ok(bytes) = std::fs::read(path) {
-    let s = String::from_utf8_lossy(&bytes);
-    if s.contains("[vecho] sidecar") && s.contains("is a stub") {
-      return true;
+  for note in notes.and_then(|n| n.as_array()).into_iter().flatten() {
+    let title = note.get("title").and_then(|v| v.as_str()).unwrap_or("").trim();
+    let content = note.get("content").and_then(|v| v.as_str()).unwrap_or("").trim();
+    let text = if title.is_empty() { content.to_string() } else { format!("{title}\n{content}") };
+    if text.trim().is_empty() {
+      continue;
     }
+    out.push(doc(
+      "note",
+      note.get("id").and_then(|v| v.as_str()).map(|s| s.to_string()),
+      note.get("timestamp").and_then(|v| v.as_f64()),
+      &text,
+    ));
   }
-
-  false
+  out
 }
 
-fn resolve_sidecar(app: &tauri::AppHandle, name: &str) -> Result<PathBuf, String> {
-  let file_name = sidecar_basename(name);
+async fn index_media(lib: &mut LibrarySearch, data_root: &Path, media_id: &str) {
+  let media_dir = data_root.join("media").join(media_id);
+  if validate_media_id(media_id).is_err() || !media_dir.is_dir() {
+    lib.index.remove(media_id);
+    return;
+  }
+  let transcription = try_load_json(&media_dir.join("transcription.json")).await;
+  let summary = try_load_json(&media_dir.join("summary.json")).await;
+  let subtitles = try_load_json(&subtitles_file_path(&media_dir)).await;
+  let docs = media_search_docs(
+    media_id,
+    transcription.as_ref(),
+    summary.as_ref(),
+    subtitles.as_ref(),
+    lib.notes.get(media_id),
+  );
+  lib.index.replace(media_id, docs);
+}
 
-  if let Ok(dir) = std::env::var(SIDECAR_ENV_DIR) {
-    let p = PathBuf::from(dir).join(&file_name);
-    if p.is_file() && !is_probably_stub_binary(&p) {
-      return Ok(p);
+// Builds the index from disk on first use; afterwards re-indexes only media marked dirty.
+async fn refresh_search_index(lib: &mut LibrarySearch, data_root: &Path) {
+  let dirty: Vec<String> = std::mem::take(&mut *search_dirty().lock().unwrap_or_else(|e| e.into_inner()))
+    .into_iter()
+    .collect();
+  if lib.built {
+    for media_id in dirty {
+      index_media(lib, data_root, &media_id).await;
     }
+    return;
   }
 
-  // Prefer per-user/per-workspace installed sidecars under the app data root.
-  if let Some(state) = app.try_state::<Arc<AppState>>() {
-    if let Some(root) = state.data_root.get() {
-      let p = root.join("bin").join(&file_name);
-      if p.is_file() && !is_probably_stub_binary(&p) {
-        return Ok(p);
-      }
+  if let Some(saved) = try_load_json(&state_file_path(data_root)).await {
+    (lib.names, lib.notes) = state_names_and_notes(&saved);
+  }
+  if let Ok(mut rd) = tokio::fs::read_dir(data_root.join("media")).await {
+    while let Ok(Some(entry)) = rd.next_entry().await {
+      let media_id = entry.file_name().to_string_lossy().to_string();
+      index_media(lib, data_root, &media_id).await;
     }
   }
+  lib.built = true;
+}
 
-  // First try Tauri resource dir.
-  if let Ok(res_dir) = app.path().resource_dir() {
-    let p = res_dir.join(&file_name);
-    if p.is_file() && !is_probably_stub_binary(&p) {
-      return Ok(p);
-    }
+#[tauri::command]
+async fn search_library(
+  app: tauri::AppHandle,
+  args: SearchLibraryArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let query = args.query.trim().to_string();
+  if search::tokenize(&query).is_empty() {
+    return Err(AppError::InvalidInput("query is empty".to_string()));
+  }
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
 
-    // macOS bundles: sidecars are often next to the executable under Contents/MacOS.
-    if res_dir.file_name().and_then(|s| s.to_str()) == Some("Resources") {
-      if let Some(contents_dir) = res_dir.parent() {
-        let p = contents_dir.join("MacOS").join(&file_name);
-        if p.is_file() && !is_probably_stub_binary(&p) {
-          return Ok(p);
-        }
-      }
+  let mut lib = state.search_index.lock().await;
+  refresh_search_index(&mut lib, dir).await;
+  let (index, names) = (&lib.index, &lib.names);
+
+  let media_ids: Option<HashSet<&str>> = args.media_ids.as_ref().map(|v| v.iter().map(|s| s.as_str()).collect());
+  let kinds: Option<HashSet<&str>> = args.kinds.as_ref().map(|v| v.iter().map(|s| s.as_str()).collect());
+  let hits = index.search(&query, |d| {
+    media_ids.as_ref().is_none_or(|m| m.contains(d.media_id.as_str()))
+      && kinds.as_ref().is_none_or(|k| k.contains(d.kind))
+  });
+  let total = hits.len();
+  let limit = args.limit.unwrap_or(50).clamp(1, 500);
+
+  let results: Vec<serde_json::Value> = hits
+    .into_iter()
+    .take(limit)
+    .map(|(d, score)| {
+      let (snippet, highlights) = search::highlight_snippet(&d.text, &query, SEARCH_SNIPPET_CHARS);
+      serde_json::json!({
+        "media_id": d.media_id,
+        "media_name": names.get(&d.media_id),
+        "kind": d.kind,
+        "segment_id": d.segment_id,
+        "track": d.track,
+        "timestamp": d.start,
+        "snippet": snippet,
+        "highlights": highlights,
+        "score": score,
+      })
+    })
+    .collect();
+
+  Ok(serde_json::json!({ "query": query, "total": total, "results": results }))
+}
+
+const MAX_FINISHED_JOBS: usize = 200;
+const DEFAULT_MAX_CONCURRENT_JOBS: usize = 2;
+const MAX_CONCURRENT_JOBS_CAP: usize = 16;
+
+tokio::task_local! {
+  // Set while replaying a persisted job so the command keeps its original job id.
+  static RESUMED_JOB_ID: String;
+}
+
+struct JobSlots {
+  running: usize,
+  limit: usize,
+}
+
+struct RemoveDirOnDrop(PathBuf);
+
+impl Drop for RemoveDirOnDrop {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_dir_all(&self.0);
+    if let Some(parent) = self.0.parent() {
+      // Only succeeds once no other render is using the shared parent.
+      let _ = std::fs::remove_dir(parent);
     }
   }
+}
 
-  // Dev mode (and Windows): sidecars are copied next to the executable.
-  if let Ok(exe) = std::env::current_exe() {
-    if let Some(dir) = exe.parent() {
-      let p = dir.join(&file_name);
-      if p.is_file() && !is_probably_stub_binary(&p) {
-        return Ok(p);
-      }
+struct JobSlotGuard {
+  state: Arc<AppState>,
+}
+
+impl Drop for JobSlotGuard {
+  fn drop(&mut self) {
+    {
+      let mut slots = self.state.job_slots.lock().unwrap_or_else(|e| e.into_inner());
+      slots.running = slots.running.saturating_sub(1);
     }
+    self.state.job_slots_changed.notify_waiters();
   }
+}
 
-  Err(format!(
-    "sidecar '{name}' not found. Provide binaries under src-tauri/bin or set {SIDECAR_ENV_DIR}."
-  ))
+fn try_acquire_job_slot(state: &Arc<AppState>) -> Option<JobSlotGuard> {
+  let mut slots = state.job_slots.lock().unwrap_or_else(|e| e.into_inner());
+  if slots.running < slots.limit {
+    slots.running += 1;
+    Some(JobSlotGuard { state: state.clone() })
+  } else {
+    None
+  }
 }
 
-fn tools_bin_dir(data_root: &Path) -> PathBuf {
-  data_root.join("bin")
+async fn acquire_job_slot(state: &Arc<AppState>) -> JobSlotGuard {
+  loop {
+    let notified = state.job_slots_changed.notified();
+    tokio::pin!(notified);
+    notified.as_mut().enable();
+    if let Some(slot) = try_acquire_job_slot(state) {
+      return slot;
+    }
+    notified.await;
+  }
 }
 
-fn env_path(var: &str) -> Option<PathBuf> {
-  std::env::var_os(var)
-    .map(PathBuf::from)
-    .filter(|p| !p.as_os_str().is_empty())
+fn jobs_file_path(data_root: &Path) -> PathBuf {
+  data_root.join("db").join("jobs.json")
 }
 
-fn push_unique(vec: &mut Vec<PathBuf>, p: PathBuf) {
-  if !vec.iter().any(|x| x == &p) {
-    vec.push(p);
-  }
+fn new_job_id() -> String {
+  RESUMED_JOB_ID
+    .try_with(|id| id.clone())
+    .unwrap_or_else(|_| format!("job-{}", nanoid()))
 }
 
-fn is_data_dir(p: &Path) -> bool {
-  p.file_name()
-    .and_then(|s| s.to_str())
-    .map(|s| s.eq_ignore_ascii_case("data"))
-    .unwrap_or(false)
+fn is_job_finished(status: &JobStatus) -> bool {
+  matches!(status, JobStatus::Succeeded | JobStatus::Failed | JobStatus::Cancelled)
 }
 
-fn candidate_model_roots(app: &tauri::AppHandle, data_root: &Path) -> Vec<PathBuf> {
-  let mut out: Vec<PathBuf> = Vec::new();
+// Commands whose results live in the media dir, so they can be replayed after a restart.
+// Imports are not: the media item itself is created by the frontend from the command result.
+fn is_resumable_job_command(command: &str) -> bool {
+  matches!(
+    command,
+    "transcribe_media"
+      | "summarize_media"
+      | "optimize_transcription"
+      | "translate_subtitles"
+      | "export_media"
+      | "render_video"
+      | "diarize_media"
+  )
+}
 
-  if let Some(p) = env_path(MODELS_ENV_DIR) {
-    push_unique(&mut out, p);
+// Writes queued/running jobs to db/jobs.json. Called with the registry lock held.
+// Best effort: callers emitting job events ignore the error; it only reaches set_job_concurrency.
+fn persist_jobs(state: &AppState, jobs: &HashMap<String, JobEntry>) -> Result<(), String> {
+  let Some(root) = state.data_root.get() else {
+    return Ok(());
+  };
+
+  let pending: Vec<serde_json::Value> = jobs
+    .values()
+    .filter(|j| !is_job_finished(&j.record.event.status))
+    .map(|j| {
+      let mut v = serde_json::to_value(&j.record).unwrap_or(serde_json::Value::Null);
+      if let (Some(obj), Some(resume)) = (v.as_object_mut(), j.resume.as_ref()) {
+        obj.insert("resume".to_string(), resume.clone());
+      }
+      v
+    })
+    .collect();
+  let limit = state.job_slots.lock().unwrap_or_else(|e| e.into_inner()).limit;
+
+  let payload = serde_json::json!({
+    "version": 1,
+    "max_concurrent_jobs": limit,
+    "jobs": pending,
+  });
+  let bytes = serde_json::to_vec_pretty(&payload).map_err(|e| format!("serialize jobs failed: {e}"))?;
+  atomic_write_bytes(&jobs_file_path(root), &bytes)
+}
+
+fn emit_job(app: &tauri::AppHandle, mut payload: JobProgressEvent) -> Result<(), String> {
+  if matches!(payload.status, JobStatus::Failed) && payload.error_code.is_none() {
+    payload.error_code = Some(ErrorCode::Internal);
+  }
+  if !record_job(app, &payload) {
+    return Ok(());
   }
+  app.emit(EVENT_JOB_PROGRESS, payload).map_err(|e| e.to_string())
+}
 
-  // Current data root.
-  push_unique(&mut out, data_root.join("models"));
+// Returns false when the event must be dropped (the job was already cancelled).
+fn record_job(app: &tauri::AppHandle, payload: &JobProgressEvent) -> bool {
+  let state = app.state::<Arc<AppState>>();
+  let mut jobs = state.jobs.lock().unwrap_or_else(|e| e.into_inner());
+  let now = now_iso();
 
-  // Portable layout: <base>/data, allow <base>/models.
-  if is_data_dir(data_root) {
-    if let Some(base) = data_root.parent() {
-      push_unique(&mut out, base.join("models"));
+  if let Some(entry) = jobs.get_mut(&payload.job_id) {
+    if matches!(entry.record.event.status, JobStatus::Cancelled) {
+      return false;
     }
+    let status_changed = std::mem::discriminant(&entry.record.event.status) != std::mem::discriminant(&payload.status);
+    entry.record.event = payload.clone();
+    entry.record.updated_at = now;
+    if status_changed {
+      let _ = persist_jobs(&state, &jobs);
+    }
+    return true;
   }
 
-  // Default (non-portable) app data dir (for reuse between installed + portable builds).
-  if let Ok(app_data) = app.path().app_data_dir() {
-    push_unique(&mut out, app_data.join("models"));
+  let mut finished: Vec<(String, String)> = jobs
+    .iter()
+    .filter(|(_, j)| is_job_finished(&j.record.event.status))
+    .map(|(id, j)| (j.record.updated_at.clone(), id.clone()))
+    .collect();
+  if finished.len() >= MAX_FINISHED_JOBS {
+    finished.sort();
+    for (_, id) in finished.iter().take(finished.len() + 1 - MAX_FINISHED_JOBS) {
+      jobs.remove(id);
+    }
+  }
+
+  let (cancel, _) = tokio::sync::watch::channel(false);
+  jobs.insert(
+    payload.job_id.clone(),
+    JobEntry {
+      record: JobRecord {
+        event: payload.clone(),
+        created_at: now.clone(),
+        updated_at: now,
+      },
+      cancel,
+      resume: None,
+    },
+  );
+  let _ = persist_jobs(&state, &jobs);
+  true
+}
+
+fn current_job_event(app: &tauri::AppHandle, job_id: &str) -> Option<JobProgressEvent> {
+  let state = app.state::<Arc<AppState>>();
+  let jobs = state.jobs.lock().unwrap_or_else(|e| e.into_inner());
+  jobs.get(job_id).map(|j| j.record.event.clone())
+}
+
+// Stores the command + args needed to replay a job after an app restart.
+fn remember_job_resume<A: serde::Serialize>(app: &tauri::AppHandle, job_id: &str, command: &str, args: &A) {
+  let Ok(args) = serde_json::to_value(args) else {
+    return;
+  };
+  let state = app.state::<Arc<AppState>>();
+  let mut jobs = state.jobs.lock().unwrap_or_else(|e| e.into_inner());
+  if let Some(entry) = jobs.get_mut(job_id) {
+    entry.resume = Some(serde_json::json!({ "command": command, "args": args }));
+    let _ = persist_jobs(&state, &jobs);
   }
+}
 
-  out
+// Runs a job body once a concurrency slot is free, until it finishes or `cancel_job` is called.
+async fn run_job<T>(
+  app: &tauri::AppHandle,
+  job_id: &str,
+  fut: impl std::future::Future<Output = Result<T, AppError>>,
+) -> Result<T, AppError> {
+  let state = app.state::<Arc<AppState>>().inner().clone();
+  run_job_unthrottled(app, job_id, async {
+    let _slot = match try_acquire_job_slot(&state) {
+      Some(slot) => slot,
+      None => {
+        let event = current_job_event(app, job_id);
+        if let Some(event) = event.clone() {
+          let _ = emit_job(app, JobProgressEvent {
+            status: JobStatus::Queued,
+            message: Some("waiting for other jobs to finish".to_string()),
+            ..event
+          });
+        }
+        let slot = acquire_job_slot(&state).await;
+        if let Some(event) = event {
+          let _ = emit_job(app, event);
+        }
+        slot
+      }
+    };
+    fut.await
+  })
+  .await
 }
 
-fn candidate_tools_bin_dirs(app: &tauri::AppHandle, data_root: &Path) -> Vec<PathBuf> {
-  let mut out: Vec<PathBuf> = Vec::new();
+// Like `run_job`, but without taking a concurrency slot (for jobs that only drive other jobs).
+// On cancel the future is dropped: child processes are spawned with `kill_on_drop`,
+// and in-flight reqwest calls are aborted with it.
+async fn run_job_unthrottled<T>(
+  app: &tauri::AppHandle,
+  job_id: &str,
+  fut: impl std::future::Future<Output = Result<T, AppError>>,
+) -> Result<T, AppError> {
+  let rx = {
+    let state = app.state::<Arc<AppState>>();
+    let jobs = state.jobs.lock().unwrap_or_else(|e| e.into_inner());
+    jobs.get(job_id).map(|j| j.cancel.subscribe())
+  };
+  let Some(mut rx) = rx else {
+    return fut.await;
+  };
 
-  if let Some(p) = env_path(TOOLS_ENV_DIR) {
-    push_unique(&mut out, p);
+  tokio::select! {
+    r = fut => r,
+    Ok(_) = rx.wait_for(|c| *c) => {
+      mark_job_cancelled(app, job_id);
+      Err(AppError::Cancelled)
+    }
   }
+}
 
-  // Current data root.
-  push_unique(&mut out, tools_bin_dir(data_root));
+fn mark_job_cancelled(app: &tauri::AppHandle, job_id: &str) {
+  if let Some(event) = current_job_event(app, job_id) {
+    if !is_job_finished(&event.status) {
+      let _ = emit_job(app, JobProgressEvent {
+        status: JobStatus::Cancelled,
+        message: Some(AppError::Cancelled.to_string()),
+        ..event
+      });
+    }
+  }
+}
 
-  // Portable layout: <base>/data, allow <base>/bin.
-  if is_data_dir(data_root) {
-    if let Some(base) = data_root.parent() {
-      push_unique(&mut out, base.join("bin"));
+fn fail_job(app: &tauri::AppHandle, job_id: &str, err: AppError) {
+  if let Some(event) = current_job_event(app, job_id) {
+    if !is_job_finished(&event.status) {
+      let _ = emit_job(app, JobProgressEvent {
+        status: JobStatus::Failed,
+        message: Some(err.to_string()),
+        error_code: Some(err.code()),
+        ..event
+      });
     }
   }
+}
 
-  // Default (non-portable) app data dir.
-  if let Ok(app_data) = app.path().app_data_dir() {
-    push_unique(&mut out, app_data.join("bin"));
+// Loads db/jobs.json on startup: resumable jobs are queued again, everything else is marked failed.
+fn restore_jobs(app: &tauri::AppHandle, data_root: &Path) {
+  let Ok(bytes) = std::fs::read(jobs_file_path(data_root)) else {
+    return;
+  };
+  let Ok(saved) = serde_json::from_slice::<serde_json::Value>(&bytes) else {
+    return;
+  };
+
+  let state = app.state::<Arc<AppState>>();
+  if let Some(limit) = saved.get("max_concurrent_jobs").and_then(|v| v.as_u64()) {
+    let mut slots = state.job_slots.lock().unwrap_or_else(|e| e.into_inner());
+    slots.limit = (limit as usize).clamp(1, MAX_CONCURRENT_JOBS_CAP);
   }
 
-  out
+  let mut resumed: Vec<(String, serde_json::Value)> = Vec::new();
+  {
+    let mut jobs = state.jobs.lock().unwrap_or_else(|e| e.into_inner());
+    let saved_jobs = saved.get("jobs").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+    for item in saved_jobs {
+      let Ok(mut record) = serde_json::from_value::<JobRecord>(item.clone()) else {
+        continue;
+      };
+      let resume = item
+        .get("resume")
+        .filter(|r| r.get("command").and_then(|v| v.as_str()).is_some_and(is_resumable_job_command))
+        .cloned();
+
+      record.updated_at = now_iso();
+      if let Some(resume) = resume.as_ref() {
+        record.event.status = JobStatus::Queued;
+        record.event.progress = 0.0;
+        record.event.message = Some("resuming after restart".to_string());
+        resumed.push((record.event.job_id.clone(), resume.clone()));
+      } else {
+        record.event.status = JobStatus::Failed;
+        record.event.message = Some("interrupted by app restart".to_string());
+        record.event.error_code = Some(ErrorCode::Internal);
+      }
+
+      let (cancel, _) = tokio::sync::watch::channel(false);
+      jobs.insert(record.event.job_id.clone(), JobEntry { record, cancel, resume });
+    }
+    let _ = persist_jobs(&state, &jobs);
+  }
+
+  for (job_id, resume) in resumed {
+    tauri::async_runtime::spawn(resume_job(app.clone(), job_id, resume));
+  }
+}
+
+async fn resume_job(app: tauri::AppHandle, job_id: String, resume: serde_json::Value) {
+  let command = resume.get("command").and_then(|v| v.as_str()).unwrap_or("").to_string();
+  let args = resume.get("args").cloned().unwrap_or(serde_json::Value::Null);
+  let parse_err = |e: serde_json::Error| AppError::InvalidInput(format!("invalid saved job args: {e}"));
+
+  let result: Result<serde_json::Value, AppError> = RESUMED_JOB_ID
+    .scope(job_id.clone(), async {
+      match command.as_str() {
+        "transcribe_media" => match serde_json::from_value(args) {
+          Ok(args) => transcribe_media(app.clone(), args, app.state()).await,
+          Err(e) => Err(parse_err(e)),
+        },
+        "summarize_media" => match serde_json::from_value(args) {
+          Ok(args) => summarize_media(app.clone(), args, app.state()).await,
+          Err(e) => Err(parse_err(e)),
+        },
+        "optimize_transcription" => match serde_json::from_value(args) {
+          Ok(args) => optimize_transcription(app.clone(), args, app.state()).await,
+          Err(e) => Err(parse_err(e)),
+        },
+        "translate_subtitles" => match serde_json::from_value(args) {
+          Ok(args) => translate_subtitles(app.clone(), args, app.state()).await,
+          Err(e) => Err(parse_err(e)),
+        },
+        "export_media" => match serde_json::from_value(args) {
+          Ok(args) => export_media(app.clone(), args, app.state()).await,
+          Err(e) => Err(parse_err(e)),
+        },
+        "render_video" => match serde_json::from_value(args) {
+          Ok(args) => render_video(app.clone(), args, app.state()).await,
+          Err(e) => Err(parse_err(e)),
+        },
+        "diarize_media" => match serde_json::from_value(args) {
+          Ok(args) => diarize_media(app.clone(), args, app.state()).await,
+          Err(e) => Err(parse_err(e)),
+        },
+        other => Err(AppError::InvalidInput(format!("unsupported job command: {other}"))),
+      }
+    })
+    .await;
+
+  // Commands report their own failures once the job is running; this covers early exits.
+  if let Err(e) = result {
+    fail_job(&app, &job_id, e);
+  }
 }
 
-#[cfg(unix)]
-fn set_executable(path: &Path) -> Result<(), String> {
-  use std::os::unix::fs::PermissionsExt;
-  let perm = std::fs::Permissions::from_mode(0o755);
-  std::fs::set_permissions(path, perm).map_err(|e| format!("set executable failed: {e}"))
+#[tauri::command]
+async fn list_jobs(state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, AppError> {
+  let mut records: Vec<JobRecord> = {
+    let jobs = state.jobs.lock().unwrap_or_else(|e| e.into_inner());
+    jobs.values().map(|j| j.record.clone()).collect()
+  };
+  records.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+  let limit = state.job_slots.lock().unwrap_or_else(|e| e.into_inner()).limit;
+  Ok(serde_json::json!({ "jobs": records, "max_concurrent_jobs": limit }))
 }
 
-#[cfg(not(unix))]
-fn set_executable(_: &Path) -> Result<(), String> {
-  Ok(())
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JobIdArgs {
+  job_id: String,
 }
 
-async fn http_download_to_file(url: &str, dest: &Path) -> Result<(), String> {
-  http_download_to_file_with_progress(url, dest, |_done, _total| {}).await
+#[tauri::command]
+async fn get_job(args: JobIdArgs, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, AppError> {
+  let jobs = state.jobs.lock().unwrap_or_else(|e| e.into_inner());
+  let job = jobs
+    .get(args.job_id.trim())
+    .ok_or_else(|| AppError::InvalidInput("job not found".to_string()))?;
+  serde_json::to_value(&job.record).map_err(|e| AppError::Internal(e.to_string()))
 }
 
-async fn http_download_to_file_with_progress<F>(url: &str, dest: &Path, mut on_progress: F) -> Result<(), String>
-where
-  F: FnMut(u64, Option<u64>) + Send,
-{
-  use tokio::io::AsyncWriteExt;
-  use tokio::time::{sleep, timeout, Duration, Instant};
-  use reqwest::StatusCode;
+#[tauri::command]
+async fn cancel_job(args: JobIdArgs, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, AppError> {
+  let jobs = state.jobs.lock().unwrap_or_else(|e| e.into_inner());
+  let job = jobs
+    .get(args.job_id.trim())
+    .ok_or_else(|| AppError::InvalidInput("job not found".to_string()))?;
+  if is_job_finished(&job.record.event.status) {
+    return Ok(serde_json::json!({ "job_id": args.job_id, "cancelled": false }));
+  }
+  job.cancel.send_replace(true);
+  Ok(serde_json::json!({ "job_id": args.job_id, "cancelled": true }))
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JobConcurrencyArgs {
+  limit: usize,
+}
+
+#[tauri::command]
+async fn set_job_concurrency(args: JobConcurrencyArgs, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, AppError> {
+  let limit = args.limit.clamp(1, MAX_CONCURRENT_JOBS_CAP);
+  {
+    let mut slots = state.job_slots.lock().unwrap_or_else(|e| e.into_inner());
+    slots.limit = limit;
+  }
+  state.job_slots_changed.notify_waiters();
+  {
+    let jobs = state.jobs.lock().unwrap_or_else(|e| e.into_inner());
+    persist_jobs(&state, &jobs).map_err(AppError::Internal)?;
+  }
+  Ok(serde_json::json!({ "max_concurrent_jobs": limit }))
+}
+
+fn nanoid() -> String {
+  // No extra dependency: just good-enough for MVP.
+  // This is NOT cryptographically secure.
+  let t = std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_millis();
+  format!("{}-{}", t, (t % 10_000) as u32)
+}
+
+fn validate_media_id(id: &str) -> Result<(), AppError> {
+  let s = id.trim();
+  if s.is_empty() {
+    return Err(AppError::InvalidInput("media_id is empty".to_string()));
+  }
+  if !s.starts_with("media-") {
+    return Err(AppError::InvalidInput("invalid media_id".to_string()));
+  }
+  if s.len() > 128 {
+    return Err(AppError::InvalidInput("media_id too long".to_string()));
+  }
+  if !s
+    .chars()
+    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+  {
+    return Err(AppError::InvalidInput("invalid media_id characters".to_string()));
+  }
+  Ok(())
+}
+
+fn extract_first_http_url(raw: &str) -> Option<String> {
+  let s = raw.trim();
+  if s.is_empty() {
+    return None;
+  }
+
+  let idx = s
+    .find("https://")
+    .or_else(|| s.find("http://"))?;
+
+  let tail = &s[idx..];
+  let end = tail
+    .find(char::is_whitespace)
+    .unwrap_or_else(|| tail.len());
+
+  let mut url = tail[..end].trim().to_string();
+  while let Some(last) = url.chars().last() {
+    if matches!(last, ']' | '】' | ')' | '）' | '>' | ',' | '，' | '。' | ';' | '；' | '!' | '！' | '?' | '？' | '"' | '\'' | '“' | '”' | '‘' | '’') {
+      url.pop();
+      continue;
+    }
+    break;
+  }
+
+  if url.starts_with("http://") || url.starts_with("https://") {
+    Some(url)
+  } else {
+    None
+  }
+}
+
+fn looks_like_youtube_url(url: &str) -> bool {
+  let u = url.trim().to_lowercase();
+  u.contains("youtube.com") || u.contains("youtu.be")
+}
+
+fn is_exe_available(name: &str) -> bool {
+  let n = name.trim();
+  if n.is_empty() {
+    return false;
+  }
+
+  // If user passed an explicit path.
+  let p = PathBuf::from(n);
+  if p.is_file() {
+    return true;
+  }
+
+  // PATH lookup.
+  let Some(path_os) = std::env::var_os("PATH") else {
+    return false;
+  };
+  for dir in std::env::split_paths(&path_os) {
+    let cand = dir.join(n);
+    if cand.is_file() {
+      return true;
+    }
+    if cfg!(windows) {
+      let cand_exe = dir.join(format!("{n}.exe"));
+      if cand_exe.is_file() {
+        return true;
+      }
+    }
+  }
+  false
+}
+
+fn parse_ytdlp_percent(line: &str) -> Option<f64> {
+  // Typical line: "[download]  12.3% of ..."
+  let pct_idx = line.find('%')?;
+  let bytes = line.as_bytes();
+
+  let mut start = pct_idx;
+  while start > 0 {
+    let b = bytes[start - 1];
+    if (b as char).is_ascii_digit() || b == b'.' {
+      start -= 1;
+      continue;
+    }
+    break;
+  }
+
+  if start == pct_idx {
+    return None;
+  }
+
+  let num = &line[start..pct_idx];
+  num.trim().parse::<f64>().ok()
+}
+
+fn maybe_emit_ytdlp_progress(
+  app: &tauri::AppHandle,
+  job_id: &str,
+  media_id: &str,
+  line: &str,
+  state: &Arc<std::sync::Mutex<(i32, std::time::Instant)>>,
+) {
+  let now = std::time::Instant::now();
+
+  let mut guard = state.lock().unwrap_or_else(|e| e.into_inner());
+  let (last_pct, last_emit) = &mut *guard;
+
+  if let Some(pct) = parse_ytdlp_percent(line) {
+    let pct_i = pct.round() as i32;
+
+    if pct_i == *last_pct {
+      return;
+    }
+    if pct_i != 100 && now.duration_since(*last_emit) <= std::time::Duration::from_millis(350) {
+      return;
+    }
+
+    *last_pct = pct_i;
+    *last_emit = now;
+
+    // Reserve the last 10% for analyze + thumbnail.
+    let p = ((pct / 100.0) as f32).clamp(0.0, 1.0) * 0.9;
+    let _ = emit_job(
+      app,
+      JobProgressEvent {
+        job_id: job_id.to_string(),
+        media_id: media_id.to_string(),
+        job_type: JobType::Download,
+        status: JobStatus::Running,
+        progress: p,
+        message: Some(format!("downloading {pct_i}%")),
+        error_code: None,
+      },
+    );
+    return;
+  }
+
+  // Also surface non-percent status/warnings so the UI doesn't look frozen.
+  let t = line.trim();
+  if t.is_empty() {
+    return;
+  }
+  let lower = t.to_lowercase();
+  let looks_useful = lower.starts_with("[download]")
+    || lower.starts_with("[youtube]")
+    || lower.contains("warning")
+    || lower.contains("error")
+    || lower.contains("retry")
+    || lower.contains("extract")
+    || lower.contains("merg")
+    || lower.contains("frag")
+    || lower.contains("destin");
+  if !looks_useful {
+    return;
+  }
+  if now.duration_since(*last_emit) <= std::time::Duration::from_millis(700) {
+    return;
+  }
+  *last_emit = now;
+
+  let mut msg = t.to_string();
+  const MAX: usize = 180;
+  if msg.chars().count() > MAX {
+    msg = msg.chars().take(MAX).collect::<String>();
+  }
+
+  let p = if *last_pct >= 0 {
+    ((*last_pct as f32) / 100.0).clamp(0.0, 1.0) * 0.9
+  } else {
+    0.02
+  };
+
+  let _ = emit_job(
+    app,
+    JobProgressEvent {
+      job_id: job_id.to_string(),
+      media_id: media_id.to_string(),
+      job_type: JobType::Download,
+      status: JobStatus::Running,
+      progress: p,
+      message: Some(msg),
+      error_code: None,
+    },
+  );
+}
+
+#[derive(Clone, Debug)]
+struct YtDlpRunOpts {
+  has_ffmpeg: bool,
+  ffmpeg_dir: Option<PathBuf>,
+  cookies_path: Option<PathBuf>,
+
+  format: String,
+
+  retries: u32,
+  fragment_retries: u32,
+  extractor_retries: u32,
+  socket_timeout: u32,
+  concurrent_fragments: u32,
+
+  force_ipv4: bool,
+  youtube_compat: bool,
+  js_runtime: Option<String>,
+  insecure: bool,
+}
+
+fn select_ytdlp_format(has_ffmpeg: bool, quality: Option<&str>) -> String {
+  // Default: keep compatibility (mp4 merge when possible), allow a simple height cap.
+  let q = quality.unwrap_or("best").trim().to_lowercase();
+  let height: Option<u32> = match q.as_str() {
+    "1080p" | "1080" => Some(1080),
+    "720p" | "720" => Some(720),
+    "480p" | "480" => Some(480),
+    "360p" | "360" => Some(360),
+    "best" | "auto" | "" => None,
+    _ => None,
+  };
+
+  if has_ffmpeg {
+    if let Some(h) = height {
+      // Prefer mp4+h264-ish sources when available, fallback to any container.
+      return format!(
+        "bestvideo[height<={h}][ext=mp4]+bestaudio[ext=m4a]/bestvideo[height<={h}]+bestaudio/best[height<={h}][ext=mp4]/best[height<={h}]/best",
+        h = h
+      );
+    }
+    return "bestvideo[ext=mp4]+bestaudio[ext=m4a]/bestvideo+bestaudio/best".to_string();
+  }
+
+  if let Some(h) = height {
+    return format!("best[height<={h}][ext=mp4]/best[height<={h}]/best", h = h);
+  }
+  "best".to_string()
+}
+
+fn is_retryable_ytdlp_failure(stderr_tail: &str) -> bool {
+  let s = stderr_tail.to_lowercase();
+  s.contains("eof occurred in violation of protocol")
+    || s.contains("_ssl.c")
+    || s.contains("tls")
+    || s.contains("ssl")
+    || s.contains("connection reset")
+    || s.contains("timed out")
+    || s.contains("temporary failure")
+}
+
+async fn run_ytdlp_download(
+  app: &tauri::AppHandle,
+  job_id: &str,
+  media_id: &str,
+  ytdlp: &Path,
+  url: &str,
+  output_template: &Path,
+  opts: &YtDlpRunOpts,
+) -> Result<(), String> {
+  let mut cmd = tokio::process::Command::new(ytdlp);
+  apply_ytdlp_proxy(&mut cmd);
+  cmd
+    .kill_on_drop(true)
+    .arg("--no-playlist")
+    .arg("--newline")
+    .arg("--write-info-json")
+    .arg("--retries")
+    .arg(opts.retries.to_string())
+    .arg("--fragment-retries")
+    .arg(opts.fragment_retries.to_string())
+    .arg("--extractor-retries")
+    .arg(opts.extractor_retries.to_string())
+    .arg("--socket-timeout")
+    .arg(opts.socket_timeout.to_string())
+    .arg("--concurrent-fragments")
+    .arg(opts.concurrent_fragments.to_string())
+    .arg("-f")
+    .arg(opts.format.as_str());
+
+  if opts.has_ffmpeg {
+    cmd.arg("--merge-output-format").arg("mp4");
+  }
+  if opts.force_ipv4 {
+    cmd.arg("--force-ipv4");
+  }
+  if opts.insecure {
+    cmd.arg("--no-check-certificate");
+  }
+
+  if opts.youtube_compat {
+    cmd.arg("--extractor-args").arg("youtube:player_client=android");
+  }
+  if let Some(rt) = opts.js_runtime.as_ref().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+    cmd.arg("--js-runtimes").arg(rt);
+  }
+
+  if let Some(dir) = opts.ffmpeg_dir.as_ref() {
+    cmd.arg("--ffmpeg-location").arg(dir);
+  }
+  if let Some(cookies) = opts.cookies_path.as_ref() {
+    cmd.arg("--cookies").arg(cookies);
+  }
+
+  cmd
+    .arg("-o")
+    .arg(output_template)
+    .arg(url)
+    .stdout(std::process::Stdio::piped())
+    .stderr(std::process::Stdio::piped());
+
+  let mut child = cmd.spawn().map_err(|e| format!("spawn yt-dlp failed: {e}"))?;
+  let stdout = child.stdout.take().ok_or_else(|| "yt-dlp stdout unavailable".to_string())?;
+  let stderr = child.stderr.take().ok_or_else(|| "yt-dlp stderr unavailable".to_string())?;
+
+  let stderr_tail = Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+  let progress_state = Arc::new(std::sync::Mutex::new((
+    -1i32,
+    std::time::Instant::now() - std::time::Duration::from_secs(10),
+  )));
+
+  let stderr_tail_task = {
+    let stderr_tail = stderr_tail.clone();
+    let progress_state = progress_state.clone();
+    let job_id = job_id.to_string();
+    let media_id = media_id.to_string();
+    let app_handle = app.clone();
+    tokio::spawn(async move {
+      use tokio::io::AsyncBufReadExt;
+      let mut lines = tokio::io::BufReader::new(stderr).lines();
+      while let Ok(Some(line)) = lines.next_line().await {
+        maybe_emit_ytdlp_progress(&app_handle, &job_id, &media_id, line.trim(), &progress_state);
+        let mut buf = stderr_tail.lock().unwrap_or_else(|e| e.into_inner());
+        buf.push(line);
+        if buf.len() > 30 {
+          buf.remove(0);
+        }
+      }
+    })
+  };
+
+  use tokio::io::AsyncBufReadExt;
+  let mut out_lines = tokio::io::BufReader::new(stdout).lines();
+  while let Ok(Some(line)) = out_lines.next_line().await {
+    maybe_emit_ytdlp_progress(app, job_id, media_id, line.trim(), &progress_state);
+  }
+
+  let status = child
+    .wait()
+    .await
+    .map_err(|e| format!("wait yt-dlp failed: {e}"))?;
+  let _ = stderr_tail_task.await;
+
+  if !status.success() {
+    let tail = stderr_tail
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .join("\n");
+    return Err(tail);
+  }
+  Ok(())
+}
+
+// Auto captions come in every auto-translated language; keep the common ones plus YouTube's `*-orig` track.
+const DEFAULT_AUTO_CAPTION_LANGS: &str = ".*-orig,en.*,zh.*,ja.*,ko.*,-live_chat";
+
+async fn run_ytdlp_subtitles(
+  ytdlp: &Path,
+  url: &str,
+  out_dir: &Path,
+  langs: &str,
+  import_opts: &ImportOptions,
+  opts: &YtDlpRunOpts,
+) -> Result<(), String> {
+  let mut cmd = tokio::process::Command::new(ytdlp);
+  apply_ytdlp_proxy(&mut cmd);
+  cmd
+    .kill_on_drop(true)
+    .arg("--skip-download")
+    .arg("--no-playlist")
+    .arg("--sub-langs")
+    .arg(langs)
+    .arg("--sub-format")
+    .arg("json3/vtt/srt/best")
+    .arg("--extractor-retries")
+    .arg(opts.extractor_retries.to_string())
+    .arg("--socket-timeout")
+    .arg(opts.socket_timeout.to_string());
+
+  if import_opts.source_subtitles {
+    cmd.arg("--write-subs");
+  }
+  if import_opts.auto_captions {
+    cmd.arg("--write-auto-subs");
+  }
+  if opts.force_ipv4 {
+    cmd.arg("--force-ipv4");
+  }
+  if opts.insecure {
+    cmd.arg("--no-check-certificate");
+  }
+  if opts.youtube_compat {
+    cmd.arg("--extractor-args").arg("youtube:player_client=android");
+  }
+  if let Some(rt) = opts.js_runtime.as_ref().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+    cmd.arg("--js-runtimes").arg(rt);
+  }
+  if let Some(cookies) = opts.cookies_path.as_ref() {
+    cmd.arg("--cookies").arg(cookies);
+  }
+
+  cmd
+    .arg("-o")
+    .arg(out_dir.join("sub.%(ext)s"))
+    .arg(url)
+    .stdout(std::process::Stdio::piped())
+    .stderr(std::process::Stdio::piped());
+
+  let out = cmd.output().await.map_err(|e| format!("spawn yt-dlp failed: {e}"))?;
+  if !out.status.success() {
+    let tail = output_tail(&out.stderr, 30);
+    return Err(format!("subtitle download failed\n{tail}"));
+  }
+  Ok(())
+}
+
+fn output_tail(bytes: &[u8], max_lines: usize) -> String {
+  let text = String::from_utf8_lossy(bytes);
+  let lines: Vec<&str> = text.lines().collect();
+  lines[lines.len().saturating_sub(max_lines)..].join("\n")
+}
+
+fn empty_subtitles(media_id: &str) -> serde_json::Value {
+  serde_json::json!({
+    "version": 1,
+    "mediaId": media_id,
+    "generatedAt": now_iso(),
+    "tracks": [],
+  })
+}
+
+fn cues_to_track_segments(cues: &[subtitles::Cue]) -> Vec<serde_json::Value> {
+  cues
+    .iter()
+    .enumerate()
+    .map(|(idx, c)| {
+      let mut seg = serde_json::json!({
+        "id": format!("seg-{}", idx + 1),
+        "start": c.start,
+        "end": c.end.max(c.start),
+        "text": c.text,
+      });
+      if let Some(style) = c.style.as_ref() {
+        seg["style"] = serde_json::Value::String(style.clone());
+      }
+      seg
+    })
+    .collect()
+}
+
+// Downloads the site's subtitle tracks into media_dir/source_subs/ and adds them to subtitles.json
+// as `source-<lang>` tracks. Returns the track ids and, when requested, the seeded transcription.
+async fn import_source_subtitles(
+  ytdlp: &Path,
+  url: &str,
+  media_dir: &Path,
+  media_id: &str,
+  opts: &YtDlpRunOpts,
+  import_opts: &ImportOptions,
+  info: Option<&serde_json::Value>,
+) -> Result<(Vec<String>, Option<serde_json::Value>), String> {
+  let subs_dir = media_dir.join("source_subs");
+  let _ = tokio::fs::remove_dir_all(&subs_dir).await;
+  tokio::fs::create_dir_all(&subs_dir)
+    .await
+    .map_err(|e| format!("create subtitles dir failed: {e}"))?;
+
+  let langs = import_opts
+    .subtitle_langs
+    .as_deref()
+    .map(|s| s.trim())
+    .filter(|s| !s.is_empty())
+    .unwrap_or(if import_opts.auto_captions { DEFAULT_AUTO_CAPTION_LANGS } else { "all,-live_chat" });
+  run_ytdlp_subtitles(ytdlp, url, &subs_dir, langs, import_opts, opts).await?;
+
+  // yt-dlp names files `sub.<lang>.<ext>` whether they are manual or automatic;
+  // the info json tells us which languages have real (manual) subtitles.
+  let manual_langs: std::collections::HashSet<String> = info
+    .and_then(|i| i.get("subtitles"))
+    .and_then(|v| v.as_object())
+    .map(|o| o.keys().cloned().collect())
+    .unwrap_or_default();
+
+  let mut files: Vec<(String, PathBuf, subtitles::SubtitleFormat)> = Vec::new();
+  let rd = std::fs::read_dir(&subs_dir).map_err(|e| format!("read subtitles dir failed: {e}"))?;
+  for entry in rd.flatten() {
+    let path = entry.path();
+    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    let Some((lang, ext)) = name.strip_prefix("sub.").and_then(|rest| rest.rsplit_once('.')) else {
+      continue;
+    };
+    let Some(fmt) = subtitles::SubtitleFormat::from_extension(ext) else {
+      continue;
+    };
+    if lang.is_empty() || lang == "live_chat" {
+      continue;
+    }
+    files.push((lang.to_string(), path.clone(), fmt));
+  }
+  files.sort_by(|a, b| a.0.cmp(&b.0));
+
+  let mut tracks: Vec<serde_json::Value> = Vec::new();
+  for (lang, path, fmt) in files {
+    let Ok(raw) = tokio::fs::read_to_string(&path).await else {
+      continue;
+    };
+    let Ok(mut cues) = subtitles::parse_subtitles(fmt, &raw, false) else {
+      continue;
+    };
+    let is_auto = if info.is_some() {
+      !manual_langs.contains(&lang)
+    } else {
+      !import_opts.source_subtitles
+    };
+    if is_auto {
+      cues = subtitles::dedupe_rolling_cues(cues);
+    }
+    if cues.is_empty() {
+      continue;
+    }
+    tracks.push(serde_json::json!({
+      "id": format!("source-{lang}"),
+      "label": if is_auto { format!("{lang} (auto)") } else { lang.clone() },
+      "language": lang.trim_end_matches("-orig"),
+      "kind": if is_auto { "source_auto" } else { "source" },
+      "generatedAt": now_iso(),
+      "segments": cues_to_track_segments(&cues),
+    }));
+  }
+  if tracks.is_empty() {
+    return Err("no subtitles available for this media".to_string());
+  }
+
+  let transcription_path = media_dir.join("transcription.json");
+  let mut seeded: Option<serde_json::Value> = None;
+  if import_opts.seed_transcription && !transcription_path.is_file() {
+    // Prefer manual subtitles in the video's own language, then any manual track, then the original-language captions.
+    let video_lang = info
+      .and_then(|i| i.get("language"))
+      .and_then(|v| v.as_str())
+      .unwrap_or("")
+      .to_lowercase();
+    let lang_of = |t: &serde_json::Value| t.get("language").and_then(|v| v.as_str()).unwrap_or("").to_lowercase();
+    let is_manual = |t: &serde_json::Value| t.get("kind").and_then(|v| v.as_str()) == Some("source");
+    let is_orig = |t: &serde_json::Value| t.get("id").and_then(|v| v.as_str()).is_some_and(|id| id.ends_with("-orig"));
+    let matches_video = |t: &serde_json::Value| !video_lang.is_empty() && lang_of(t).starts_with(&video_lang);
+    let pick = tracks
+      .iter()
+      .find(|t| is_manual(t) && matches_video(t))
+      .or_else(|| tracks.iter().find(|t| is_manual(t)))
+      .or_else(|| tracks.iter().find(|t| is_orig(t) || matches_video(t)))
+      .or_else(|| tracks.first());
+
+    if let Some(track) = pick {
+      let lang = lang_of(track);
+      let kind = track.get("kind").and_then(|v| v.as_str()).unwrap_or("source");
+      let segments_ms: Vec<(i64, i64, String)> = track
+        .get("segments")
+        .and_then(|v| v.as_array())
+        .map(|arr| {
+          arr
+            .iter()
+            .map(|s| {
+              let start = s.get("start").and_then(|v| v.as_f64()).unwrap_or(0.0);
+              let end = s.get("end").and_then(|v| v.as_f64()).unwrap_or(start);
+              let text = s.get("text").and_then(|v| v.as_str()).unwrap_or("").replace('\n', " ");
+              ((start * 1000.0).round() as i64, (end * 1000.0).round() as i64, text)
+            })
+            .collect()
+        })
+        .unwrap_or_default();
+      let t = build_transcription(media_id, Some(&lang), &format!("{kind}-subtitles:{lang}"), segments_ms);
+      write_json_atomic(&transcription_path, &t)?;
+      seeded = Some(t);
+    }
+  }
+
+  let mut subs = load_subtitles_json(media_dir)
+    .await
+    .unwrap_or_else(|| empty_subtitles(media_id));
+  if let Some(t) = seeded.as_ref() {
+    if let Some(original) = build_subtitles_from_transcription(media_id, t)
+      .get("tracks")
+      .and_then(|v| v.get(0))
+      .cloned()
+    {
+      upsert_track(&mut subs, original);
+    }
+  }
+  let mut ids = Vec::new();
+  for track in tracks {
+    ids.push(track.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string());
+    upsert_track(&mut subs, track);
+  }
+  subs["generatedAt"] = serde_json::Value::String(now_iso());
+  write_json_atomic(&subtitles_file_path(media_dir), &subs)?;
+  mark_search_dirty(media_dir);
+
+  Ok((ids, seeded))
+}
+
+fn find_source_file(media_dir: &Path) -> Result<PathBuf, AppError> {
+  let mut best: Option<(PathBuf, u64, std::time::SystemTime)> = None;
+
+  let rd = std::fs::read_dir(media_dir)
+    .map_err(|e| AppError::Internal(format!("read media dir failed: {e}")))?;
+  for entry in rd {
+    let entry = entry.map_err(|e| AppError::Internal(format!("read media dir entry failed: {e}")))?;
+    let path = entry.path();
+    if !path.is_file() {
+      continue;
+    }
+
+    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    if !name.starts_with("source.") {
+      continue;
+    }
+    if name.ends_with(".part") {
+      continue;
+    }
+
+    let meta = entry
+      .metadata()
+      .map_err(|e| AppError::Internal(format!("read media file metadata failed: {e}")))?;
+    let size = meta.len();
+    let modified = meta.modified().unwrap_or(std::time::UNIX_EPOCH);
+
+    match &best {
+      None => best = Some((path, size, modified)),
+      Some((_, best_size, best_mod)) => {
+        if modified > *best_mod || (modified == *best_mod && size > *best_size) {
+          best = Some((path, size, modified));
+        }
+      }
+    }
+  }
+
+  best.map(|(p, _, _)| p).ok_or(AppError::MediaNotFound)
+}
+
+fn find_info_json(media_dir: &Path) -> Option<PathBuf> {
+  let mut best: Option<(PathBuf, std::time::SystemTime)> = None;
+  let rd = std::fs::read_dir(media_dir).ok()?;
+  for entry in rd.flatten() {
+    let path = entry.path();
+    if !path.is_file() {
+      continue;
+    }
+    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    if !name.ends_with(".info.json") {
+      continue;
+    }
+    let modified = entry.metadata().and_then(|m| m.modified()).unwrap_or(std::time::UNIX_EPOCH);
+    match &best {
+      None => best = Some((path, modified)),
+      Some((_, best_mod)) => {
+        if modified > *best_mod {
+          best = Some((path, modified));
+        }
+      }
+    }
+  }
+  best.map(|(p, _)| p)
+}
+
+fn ytdlp_upload_date_to_iso(d: &str) -> Option<String> {
+  let s = d.trim();
+  if s.len() != 8 {
+    return None;
+  }
+  let (y, rest) = s.split_at(4);
+  let (m, day) = rest.split_at(2);
+  if y.chars().all(|c| c.is_ascii_digit())
+    && m.chars().all(|c| c.is_ascii_digit())
+    && day.chars().all(|c| c.is_ascii_digit())
+  {
+    return Some(format!("{y}-{m}-{day}"));
+  }
+  None
+}
+
+fn sidecar_basename(name: &str) -> String {
+  if cfg!(windows) {
+    format!("{name}.exe")
+  } else {
+    name.to_string()
+  }
+}
+
+fn is_probably_stub_binary(path: &Path) -> bool {
+  let meta = match std::fs::metadata(path) {
+    Ok(m) => m,
+    Err(_) => return true,
+  };
+
+  let len = meta.len();
+  if len == 0 {
+    return true;
+  }
+
+  // Real ffmpeg/ffprobe/yt-dlp are always multi-MB. Avoid touching large files.
+  if len > 2 * 1024 * 1024 {
+    return false;
+  }
+
+  // Very tiny executables are almost certainly invalid/stubs.
+  if len < 16 * 1024 {
+    return true;
+  }
+
+  // Our stubs print a distinctive marker.
+  if let Ok(bytes) = std::fs::read(path) {
+    let s = String::from_utf8_lossy(&bytes);
+    if s.contains("[vecho] sidecar") && s.contains("is a stub") {
+      return true;
+    }
+  }
+
+  false
+}
+
+fn resolve_sidecar(app: &tauri::AppHandle, name: &str) -> Result<PathBuf, AppError> {
+  let file_name = sidecar_basename(name);
+
+  if let Ok(dir) = std::env::var(SIDECAR_ENV_DIR) {
+    let p = PathBuf::from(dir).join(&file_name);
+    if p.is_file() && !is_probably_stub_binary(&p) {
+      return Ok(p);
+    }
+  }
+
+  // Prefer per-user/per-workspace installed sidecars under the app data root.
+  if let Some(state) = app.try_state::<Arc<AppState>>() {
+    if let Some(root) = state.data_root.get() {
+      let p = root.join("bin").join(&file_name);
+      if p.is_file() && !is_probably_stub_binary(&p) {
+        return Ok(p);
+      }
+    }
+  }
+
+  // First try Tauri resource dir.
+  if let Ok(res_dir) = app.path().resource_dir() {
+    let p = res_dir.join(&file_name);
+    if p.is_file() && !is_probably_stub_binary(&p) {
+      return Ok(p);
+    }
+
+    // macOS bundles: sidecars are often next to the executable under Contents/MacOS.
+    if res_dir.file_name().and_then(|s| s.to_str()) == Some("Resources") {
+      if let Some(contents_dir) = res_dir.parent() {
+        let p = contents_dir.join("MacOS").join(&file_name);
+        if p.is_file() && !is_probably_stub_binary(&p) {
+          return Ok(p);
+        }
+      }
+    }
+  }
+
+  // Dev mode (and Windows): sidecars are copied next to the executable.
+  if let Ok(exe) = std::env::current_exe() {
+    if let Some(dir) = exe.parent() {
+      let p = dir.join(&file_name);
+      if p.is_file() && !is_probably_stub_binary(&p) {
+        return Ok(p);
+      }
+    }
+  }
+
+  Err(AppError::ToolMissing(format!(
+    "sidecar '{name}' not found. Provide binaries under src-tauri/bin or set {SIDECAR_ENV_DIR}."
+  )))
+}
+
+fn tools_bin_dir(data_root: &Path) -> PathBuf {
+  data_root.join("bin")
+}
+
+fn env_path(var: &str) -> Option<PathBuf> {
+  std::env::var_os(var)
+    .map(PathBuf::from)
+    .filter(|p| !p.as_os_str().is_empty())
+}
+
+fn push_unique(vec: &mut Vec<PathBuf>, p: PathBuf) {
+  if !vec.iter().any(|x| x == &p) {
+    vec.push(p);
+  }
+}
+
+fn is_data_dir(p: &Path) -> bool {
+  p.file_name()
+    .and_then(|s| s.to_str())
+    .map(|s| s.eq_ignore_ascii_case("data"))
+    .unwrap_or(false)
+}
+
+fn candidate_model_roots(app: &tauri::AppHandle, data_root: &Path) -> Vec<PathBuf> {
+  let mut out: Vec<PathBuf> = Vec::new();
+
+  if let Some(p) = env_path(MODELS_ENV_DIR) {
+    push_unique(&mut out, p);
+  }
+
+  // Current data root.
+  push_unique(&mut out, data_root.join("models"));
+
+  // Portable layout: <base>/data, allow <base>/models.
+  if is_data_dir(data_root) {
+    if let Some(base) = data_root.parent() {
+      push_unique(&mut out, base.join("models"));
+    }
+  }
+
+  // Default (non-portable) app data dir (for reuse between installed + portable builds).
+  if let Ok(app_data) = app.path().app_data_dir() {
+    push_unique(&mut out, app_data.join("models"));
+  }
+
+  out
+}
+
+fn candidate_tools_bin_dirs(app: &tauri::AppHandle, data_root: &Path) -> Vec<PathBuf> {
+  let mut out: Vec<PathBuf> = Vec::new();
+
+  if let Some(p) = env_path(TOOLS_ENV_DIR) {
+    push_unique(&mut out, p);
+  }
+
+  // Current data root.
+  push_unique(&mut out, tools_bin_dir(data_root));
+
+  // Portable layout: <base>/data, allow <base>/bin.
+  if is_data_dir(data_root) {
+    if let Some(base) = data_root.parent() {
+      push_unique(&mut out, base.join("bin"));
+    }
+  }
+
+  // Default (non-portable) app data dir.
+  if let Ok(app_data) = app.path().app_data_dir() {
+    push_unique(&mut out, app_data.join("bin"));
+  }
+
+  out
+}
+
+// ==================== Offline mode ====================
+
+// When set, tool/model helpers only use what is already on disk.
+static OFFLINE_MODE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+fn offline_mode() -> bool {
+  OFFLINE_MODE.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+fn set_offline_mode_flag(enabled: bool) {
+  OFFLINE_MODE.store(enabled, std::sync::atomic::Ordering::Relaxed);
+}
+
+fn offline_flag_path(data_root: &Path) -> PathBuf {
+  data_root.join("offline.flag")
+}
+
+// Offline when VECHO_OFFLINE is truthy, a bundle is configured, or <data_root>/offline.flag exists.
+fn offline_mode_from_env(data_root: &Path) -> bool {
+  let env_on = std::env::var(OFFLINE_ENV)
+    .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+    .unwrap_or(false);
+  env_on || env_path(OFFLINE_BUNDLE_ENV).is_some() || offline_flag_path(data_root).is_file()
+}
+
+fn ensure_online(what: &str) -> Result<(), AppError> {
+  if offline_mode() {
+    return Err(AppError::Offline(format!(
+      "offline mode: {what} is not installed and will not be downloaded; install it with install_tools_from_archive or {OFFLINE_BUNDLE_ENV}"
+    )));
+  }
+  Ok(())
+}
+
+// ==================== Download verification ====================
+
+// Pinned SHA-256 per download URL. Release builds ship the file (build.rs rejects null entries);
+// `<data>/tool-hashes.json` (same shape) adds or overrides entries.
+const PINNED_SHA256_MANIFEST: &str = include_str!("../tool-hashes.json");
+const TOOL_HASHES_FILE: &str = "tool-hashes.json";
+// Assets that move with upstream releases (yt-dlp's chosen channel/tag, the latest FFmpeg build)
+// and so can't be pinned here. Only these fall back to the hashes their release publishes.
+const UNPINNED_LATEST_PREFIXES: &[&str] = &[
+  "https://github.com/yt-dlp/yt-dlp/releases/",
+  "https://github.com/yt-dlp/yt-dlp-nightly-builds/releases/",
+  "https://github.com/yt-dlp/FFmpeg-Builds/releases/",
+];
+const ALLOW_UNVERIFIED_ENV: &str = "VECHO_ALLOW_UNVERIFIED_DOWNLOADS";
+
+static DATA_ROOT_FOR_DOWNLOADS: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+
+// Digests reported by the GitHub releases API, keyed by browser_download_url.
+fn github_digest_cache() -> &'static std::sync::Mutex<HashMap<String, String>> {
+  static CACHE: std::sync::OnceLock<std::sync::Mutex<HashMap<String, String>>> = std::sync::OnceLock::new();
+  CACHE.get_or_init(|| std::sync::Mutex::new(HashMap::new()))
+}
+
+fn pinned_sha256(url: &str) -> Option<String> {
+  let local = DATA_ROOT_FOR_DOWNLOADS
+    .get()
+    .and_then(|root| std::fs::read_to_string(root.join(TOOL_HASHES_FILE)).ok())
+    .and_then(|m| checksums::manifest_sha256(&m, url));
+  local.or_else(|| checksums::manifest_sha256(PINNED_SHA256_MANIFEST, url))
+}
+
+// "latest" assets can't be pinned; their release publishes a checksum list next to them.
+async fn release_sums_sha256(url: &str) -> Option<String> {
+  let (release_dir, name) = checksums::github_release_asset_parts(url)?;
+  for sums_file in checksums::RELEASE_SUMS_FILES {
+    let Ok(resp) = http_client()
+      .get(format!("{release_dir}{sums_file}"))
+      .header("User-Agent", "vecho-studio")
+      .send()
+      .await
+    else {
+      continue;
+    };
+    if !resp.status().is_success() {
+      continue;
+    }
+    if let Some(h) = resp.text().await.ok().and_then(|t| checksums::sums_file_sha256(&t, name)) {
+      return Some(h);
+    }
+  }
+  None
+}
+
+// https://github.com/<owner>/<repo>/releases/{latest/download|download/<tag>}/<name>
+async fn github_release_asset_sha256(url: &str) -> Option<String> {
+  let rest = url.strip_prefix("https://github.com/")?;
+  let parts: Vec<&str> = rest.split('/').collect();
+  let (owner, repo) = (parts.first()?, parts.get(1)?);
+  let release = match parts.get(2..)? {
+    ["releases", "latest", "download", _] => "latest".to_string(),
+    ["releases", "download", tag, _] => format!("tags/{tag}"),
+    _ => return None,
+  };
+  let name = parts.last()?;
+
+  let api = format!("https://api.github.com/repos/{owner}/{repo}/releases/{release}");
+  let v: serde_json::Value = http_client()
+    .get(&api)
+    .header("User-Agent", "vecho-studio")
+    .send()
+    .await
+    .ok()?
+    .json()
+    .await
+    .ok()?;
+  v.get("assets")?
+    .as_array()?
+    .iter()
+    .find(|a| a.get("name").and_then(|n| n.as_str()) == Some(*name))
+    .and_then(|a| a.get("digest"))
+    .and_then(|d| d.as_str())
+    .and_then(checksums::normalize_sha256)
+}
+
+// `url` is always the original download URL. Hashes are never looked up through a mirror: a
+// mirror that can swap the file could swap its checksum too.
+async fn expected_sha256(url: &str) -> Result<Option<String>, AppError> {
+  if let Some(h) = pinned_sha256(url) {
+    return Ok(Some(h));
+  }
+  if UNPINNED_LATEST_PREFIXES.iter().any(|p| url.starts_with(p)) {
+    if let Some(h) = release_sums_sha256(url).await {
+      return Ok(Some(h));
+    }
+    let cached = github_digest_cache()
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .get(url)
+      .cloned();
+    if let Some(h) = cached {
+      return Ok(Some(h));
+    }
+    if let Some(h) = github_release_asset_sha256(url).await {
+      return Ok(Some(h));
+    }
+  }
+  let allow = std::env::var(ALLOW_UNVERIFIED_ENV).map(|v| v.trim() == "1").unwrap_or(false);
+  if allow {
+    return Ok(None);
+  }
+  Err(AppError::ChecksumMismatch(format!(
+    "no SHA-256 known for {url}; add it to {TOOL_HASHES_FILE} in the data directory or set {ALLOW_UNVERIFIED_ENV}=1"
+  )))
+}
+
+// Moves a rejected download out of the way (<data>/quarantine) so it is never executed or resumed.
+async fn quarantine_file(path: &Path) -> Option<PathBuf> {
+  let dir = match DATA_ROOT_FOR_DOWNLOADS.get() {
+    Some(root) => root.join("quarantine"),
+    None => path.parent()?.join(".quarantine"),
+  };
+  tokio::fs::create_dir_all(&dir).await.ok()?;
+  let name = path.file_name()?.to_string_lossy().to_string();
+  let dest = dir.join(format!("{}-{name}", now_compact()));
+  tokio::fs::rename(path, &dest).await.ok()?;
+  Some(dest)
+}
+
+async fn verify_download_sha256(url: &str, file: &Path, expected: Option<&str>) -> Result<(), AppError> {
+  let Some(expected) = expected else {
+    return Ok(());
+  };
+  let p = file.to_path_buf();
+  let actual = tokio::task::spawn_blocking(move || checksums::sha256_file(&p))
+    .await
+    .map_err(|e| AppError::Internal(format!("join checksum task failed: {e}")))?
+    .map_err(AppError::Internal)?;
+  if actual == expected {
+    return Ok(());
+  }
+  let moved = quarantine_file(file).await;
+  if moved.is_none() {
+    let _ = tokio::fs::remove_file(file).await;
+  }
+  Err(AppError::ChecksumMismatch(format!(
+    "checksum mismatch for {url}: expected sha256 {expected}, got {actual}; {}",
+    match moved {
+      Some(p) => format!("file quarantined at {}", p.display()),
+      None => "file deleted".to_string(),
+    }
+  )))
+}
+
+// ==================== Network: proxy + mirrors ====================
+
+const NETWORK_CONFIG_FILE: &str = "network.json";
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct MirrorRule {
+  /// A host (`github.com`) or URL prefix (`https://huggingface.co/`).
+  from: String,
+  /// Replacement prefix, e.g. `https://hf-mirror.com/` or `https://ghproxy.net/https://github.com/`.
+  to: String,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct NetworkConfig {
+  /// `http://`, `https://`, `socks5://` or `socks5h://` proxy for downloads, AI requests and yt-dlp.
+  proxy: Option<String>,
+  mirrors: Vec<MirrorRule>,
+}
+
+impl MirrorRule {
+  fn prefix(&self) -> String {
+    let from = self.from.trim();
+    if from.contains("://") {
+      from.to_string()
+    } else {
+      format!("https://{}/", from.trim_end_matches('/'))
+    }
+  }
+}
+
+struct NetworkState {
+  config: NetworkConfig,
+  client: reqwest::Client,
+}
+
+fn network_state() -> &'static std::sync::RwLock<NetworkState> {
+  static NET: std::sync::OnceLock<std::sync::RwLock<NetworkState>> = std::sync::OnceLock::new();
+  NET.get_or_init(|| {
+    let config = NetworkConfig::default();
+    let client = build_http_client(&config).unwrap_or_default();
+    std::sync::RwLock::new(NetworkState { config, client })
+  })
+}
+
+// Only connecting is bounded: downloads and streamed AI replies can legitimately run for a long time,
+// but an unreachable host (e.g. github.com behind a firewall, with only a mirror usable) should fail fast.
+const HTTP_CONNECT_TIMEOUT_SECS: u64 = 15;
+
+fn build_http_client(config: &NetworkConfig) -> Result<reqwest::Client, String> {
+  let mut builder = reqwest::Client::builder().connect_timeout(std::time::Duration::from_secs(HTTP_CONNECT_TIMEOUT_SECS));
+  if let Some(proxy) = config.proxy.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+    // Local model servers (Ollama, LM Studio) stay direct.
+    let proxy = reqwest::Proxy::all(proxy)
+      .map_err(|e| format!("invalid proxy: {e}"))?
+      .no_proxy(reqwest::NoProxy::from_string("localhost,127.0.0.1,::1"));
+    builder = builder.proxy(proxy);
+  }
+  builder.build().map_err(|e| format!("build http client failed: {e}"))
+}
+
+fn apply_network_config(config: NetworkConfig) -> Result<(), String> {
+  let client = build_http_client(&config)?;
+  let mut net = network_state().write().unwrap_or_else(|e| e.into_inner());
+  *net = NetworkState { config, client };
+  Ok(())
+}
+
+// Shared client: every outbound request goes through the configured proxy.
+fn http_client() -> reqwest::Client {
+  network_state().read().unwrap_or_else(|e| e.into_inner()).client.clone()
+}
+
+fn network_config() -> NetworkConfig {
+  network_state().read().unwrap_or_else(|e| e.into_inner()).config.clone()
+}
+
+// First matching mirror rule wins. Checksums are still looked up by the original URL.
+fn mirror_url(url: &str) -> String {
+  let net = network_state().read().unwrap_or_else(|e| e.into_inner());
+  for rule in &net.config.mirrors {
+    let prefix = rule.prefix();
+    if rule.to.trim().is_empty() || prefix.is_empty() {
+      continue;
+    }
+    if let Some(rest) = url.strip_prefix(prefix.as_str()) {
+      let to = rule.to.trim();
+      return if to.ends_with('/') || rest.is_empty() {
+        format!("{to}{rest}")
+      } else {
+        format!("{to}/{rest}")
+      };
+    }
+  }
+  url.to_string()
+}
+
+fn apply_ytdlp_proxy(cmd: &mut tokio::process::Command) {
+  if let Some(proxy) = network_config().proxy.filter(|p| !p.trim().is_empty()) {
+    cmd.arg("--proxy").arg(proxy.trim());
+  }
+}
+
+fn load_network_config(data_root: &Path) {
+  let Ok(bytes) = std::fs::read(data_root.join(NETWORK_CONFIG_FILE)) else {
+    return;
+  };
+  if let Ok(config) = serde_json::from_slice::<NetworkConfig>(&bytes) {
+    let _ = apply_network_config(config);
+  }
+}
+
+#[tauri::command]
+async fn get_network_config() -> Result<serde_json::Value, AppError> {
+  serde_json::to_value(network_config()).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+#[tauri::command]
+async fn set_network_config(
+  app: tauri::AppHandle,
+  args: NetworkConfig,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
+
+  let mut config = args;
+  config.proxy = config.proxy.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+  config.mirrors.retain(|m| !m.from.trim().is_empty() && !m.to.trim().is_empty());
+
+  apply_network_config(config.clone()).map_err(AppError::InvalidInput)?;
+  let value = serde_json::to_value(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+  write_json_atomic(&dir.join(NETWORK_CONFIG_FILE), &value).map_err(AppError::Internal)?;
+  Ok(value)
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> Result<(), String> {
+  use std::os::unix::fs::PermissionsExt;
+  let perm = std::fs::Permissions::from_mode(0o755);
+  std::fs::set_permissions(path, perm).map_err(|e| format!("set executable failed: {e}"))
+}
+
+#[cfg(not(unix))]
+fn set_executable(_: &Path) -> Result<(), String> {
+  Ok(())
+}
+
+async fn http_download_to_file(url: &str, dest: &Path) -> Result<(), AppError> {
+  http_download_to_file_with_progress(url, dest, |_done, _total| {}).await
+}
+
+async fn http_download_to_file_with_progress<F>(url: &str, dest: &Path, mut on_progress: F) -> Result<(), AppError>
+where
+  F: FnMut(u64, Option<u64>) + Send,
+{
+  use tokio::io::AsyncWriteExt;
+  use tokio::time::{sleep, timeout, Duration, Instant};
+  use reqwest::StatusCode;
+
+  ensure_online(url)?;
+  let expected = expected_sha256(url).await?;
+
+  // GitHub release downloads (esp. large archives) can pause for a while depending on network.
+  // Keep a stall guard, but allow several automatic resume attempts.
+  const STALL_TIMEOUT_SECS: u64 = 300;
+  const MAX_CONSECUTIVE_RETRIES_WITHOUT_PROGRESS: usize = 6;
+  const RETRY_BACKOFF_SECS: u64 = 2;
+
+  let dir = dest
+    .parent()
+    .ok_or_else(|| AppError::Internal("invalid destination path".to_string()))?;
+  tokio::fs::create_dir_all(dir)
+    .await
+    .map_err(|e| AppError::Internal(format!("create dir failed: {e}")))?;
+
+  let tmp = dest.with_extension("part");
+
+  let client = http_client();
+  let fetch_url = mirror_url(url);
+  let mut consecutive_retries_without_progress = 0usize;
+  let mut last_progress_downloaded: u64 = tokio::fs::metadata(&tmp)
+    .await
+    .map(|m| m.len())
+    .unwrap_or(0);
+
+  loop {
+    let (resp, mut resume_from): (reqwest::Response, u64) = {
+      let mut restarted = false;
+      loop {
+        let resume_from: u64 = tokio::fs::metadata(&tmp)
+          .await
+          .map(|m| m.len())
+          .unwrap_or(0);
+
+        let mut req = client.get(&fetch_url).header("User-Agent", "vecho-studio");
+        if resume_from > 0 {
+          req = req.header("Range", format!("bytes={resume_from}-"));
+        }
+
+        let resp = match req.send().await {
+          Ok(r) => r,
+          Err(e) => {
+            consecutive_retries_without_progress = consecutive_retries_without_progress.saturating_add(1);
+            if consecutive_retries_without_progress > MAX_CONSECUTIVE_RETRIES_WITHOUT_PROGRESS {
+              return Err(AppError::ToolDownloadFailed(format!("download failed ({url}): {e}")));
+            }
+            sleep(Duration::from_secs(RETRY_BACKOFF_SECS)).await;
+            continue;
+          }
+        };
+
+        let status = resp.status();
+        if status == StatusCode::RANGE_NOT_SATISFIABLE && resume_from > 0 {
+          let _ = tokio::fs::remove_file(&tmp).await;
+          if restarted {
+            return Err(AppError::ToolDownloadFailed("download failed: range not satisfiable".to_string()));
+          }
+          restarted = true;
+          continue;
+        }
+        break (resp, resume_from);
+      }
+    };
+
+    let status = resp.status();
+    if !(status.is_success() || status == StatusCode::PARTIAL_CONTENT) {
+      return Err(AppError::ToolDownloadFailed(format!("download failed ({url}): http {}", status)));
+    }
+
+    // If server ignored our Range request, restart from scratch.
+    let resuming = resume_from > 0 && status == StatusCode::PARTIAL_CONTENT;
+    if resume_from > 0 && !resuming {
+      let _ = tokio::fs::remove_file(&tmp).await;
+      resume_from = 0;
+    }
+
+    // Determine total size.
+    let mut total: Option<u64> = None;
+    if status == StatusCode::PARTIAL_CONTENT {
+      if let Some(v) = resp.headers().get("Content-Range").and_then(|h| h.to_str().ok()) {
+        // Example: "bytes 100-999/1000"
+        if let Some(total_str) = v.split('/').nth(1) {
+          if let Ok(t) = total_str.trim().parse::<u64>() {
+            total = Some(t);
+          }
+        }
+      }
+    }
+    if total.is_none() {
+      // For 200 OK or if Content-Range is missing.
+      total = if status == StatusCode::PARTIAL_CONTENT {
+        resp.content_length().map(|t| t.saturating_add(resume_from))
+      } else {
+        resp.content_length()
+      };
+    }
+
+    // Open destination temp file.
+    let mut downloaded: u64 = resume_from;
+    let mut file = if resuming {
+      tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&tmp)
+        .await
+        .map_err(|e| AppError::Internal(format!("open temp file failed: {e}")))?
+    } else {
+      downloaded = 0;
+      tokio::fs::File::create(&tmp)
+        .await
+        .map_err(|e| AppError::Internal(format!("create temp file failed: {e}")))?
+    };
+
+    // Emit initial progress (useful for resume).
+    on_progress(downloaded, total);
+    let mut last_emit_at = Instant::now();
+    let mut last_emit_downloaded: u64 = downloaded;
+
+    let mut stream = resp.bytes_stream();
+    let mut restart = false;
+
+    let mut stall_secs: u64 = 0;
+    loop {
+      // Use a short timeout so we can emit periodic progress updates.
+      // This avoids the UI looking frozen during long network pauses.
+      let next = timeout(Duration::from_secs(1), stream.next()).await;
+      let next = match next {
+        Ok(v) => {
+          stall_secs = 0;
+          v
+        }
+        Err(_) => {
+          stall_secs = stall_secs.saturating_add(1);
+          if last_emit_at.elapsed() >= Duration::from_secs(1) {
+            on_progress(downloaded, total);
+            last_emit_at = Instant::now();
+            last_emit_downloaded = downloaded;
+          }
+          if stall_secs >= STALL_TIMEOUT_SECS {
+            // Stalled; try to resume by restarting the request.
+            consecutive_retries_without_progress = consecutive_retries_without_progress.saturating_add(1);
+            if consecutive_retries_without_progress > MAX_CONSECUTIVE_RETRIES_WITHOUT_PROGRESS {
+              return Err(AppError::ToolDownloadFailed(format!("download stalled (no data for {STALL_TIMEOUT_SECS}s): {url}")));
+            }
+            restart = true;
+            break;
+          }
+          continue;
+        }
+      };
+
+      let Some(chunk) = next else { break; };
+
+      let chunk = match chunk {
+        Ok(c) => c,
+        Err(e) => {
+          consecutive_retries_without_progress = consecutive_retries_without_progress.saturating_add(1);
+          if consecutive_retries_without_progress > MAX_CONSECUTIVE_RETRIES_WITHOUT_PROGRESS {
+            return Err(AppError::ToolDownloadFailed(format!("download stream error ({url}): {e}")));
+          }
+          restart = true;
+          break;
+        }
+      };
+
+      downloaded = downloaded.saturating_add(chunk.len() as u64);
+
+      // Throttle progress emissions (emitting on every chunk can be too chatty).
+      if downloaded > last_emit_downloaded
+        && (downloaded.saturating_sub(last_emit_downloaded) >= 256 * 1024
+          || last_emit_at.elapsed() >= Duration::from_millis(250))
+      {
+        on_progress(downloaded, total);
+        last_emit_at = Instant::now();
+        last_emit_downloaded = downloaded;
+      }
+      file
+        .write_all(&chunk)
+        .await
+        .map_err(|e| AppError::Internal(format!("write temp file failed: {e}")))?;
+
+      if downloaded > last_progress_downloaded {
+        last_progress_downloaded = downloaded;
+        consecutive_retries_without_progress = 0;
+      }
+    }
+
+    file.flush().await.ok();
+
+    if restart {
+      sleep(Duration::from_secs(RETRY_BACKOFF_SECS)).await;
+      continue;
+    }
+
+    // Finished download; check it before it can be used or executed.
+    verify_download_sha256(url, &tmp, expected.as_deref()).await?;
+
+    // Finalize atomically.
+    if dest.is_file() {
+      let _ = tokio::fs::remove_file(dest).await;
+    }
+    tokio::fs::rename(&tmp, dest)
+      .await
+      .map_err(|e| AppError::Internal(format!("finalize download failed: {e}")))?;
+    return Ok(());
+  }
+}
+
+fn human_bytes(bytes: u64) -> String {
+  const KB: f64 = 1024.0;
+  const MB: f64 = KB * 1024.0;
+  const GB: f64 = MB * 1024.0;
+
+  let b = bytes as f64;
+  if b < KB {
+    return format!("{bytes} B");
+  }
+  if b < MB {
+    return format!("{:.1} KB", b / KB);
+  }
+  if b < GB {
+    return format!("{:.1} MB", b / MB);
+  }
+  format!("{:.2} GB", b / GB)
+}
+
+// The yt-dlp that would be used right now (downloaded copy, else bundled sidecar), without downloading.
+fn find_ytdlp(app: &tauri::AppHandle, data_root: &Path) -> Option<PathBuf> {
+  let dest = tools_bin_dir(data_root).join(sidecar_basename("yt-dlp"));
+  if dest.is_file() && !is_probably_stub_binary(&dest) {
+    return Some(dest);
+  }
+  // Prefer a bundled sidecar (no first-run download).
+  resolve_sidecar(app, "yt-dlp").ok()
+}
+
+async fn ensure_ytdlp(app: &tauri::AppHandle, state: &Arc<AppState>, data_root: &Path) -> Result<PathBuf, AppError> {
+  let _guard = state.tools_lock.lock().await;
+
+  let bin_dir = tools_bin_dir(data_root);
+  tokio::fs::create_dir_all(&bin_dir)
+    .await
+    .map_err(|e| AppError::Internal(format!("create bin dir failed: {e}")))?;
+
+  if let Some(p) = find_ytdlp(app, data_root) {
+    return Ok(p);
+  }
+
+  // First install follows the saved channel and pin, like update_ytdlp does.
+  let config = load_ytdlp_update_config(data_root);
+  let repo = ytdlp_release_repo(&config.channel).unwrap_or("yt-dlp");
+  let url = match config.pinned_version.as_deref() {
+    Some(tag) => format!("https://github.com/yt-dlp/{repo}/releases/download/{tag}/{}", ytdlp_asset_name()),
+    None => format!("https://github.com/yt-dlp/{repo}/releases/latest/download/{}", ytdlp_asset_name()),
+  };
+  let dest = bin_dir.join(sidecar_basename("yt-dlp"));
+
+  http_download_to_file(&url, &dest).await?;
+  set_executable(&dest).map_err(AppError::Internal)?;
+
+  // Prefer this directory for future sidecar resolution.
+  std::env::set_var(SIDECAR_ENV_DIR, &bin_dir);
+
+  // Basic sanity: if it's still tiny, treat as failure.
+  if is_probably_stub_binary(&dest) {
+    return Err(AppError::ToolDownloadFailed("downloaded yt-dlp looks invalid".to_string()));
+  }
+
+  Ok(dest)
+}
+
+// ==================== yt-dlp updates ====================
+
+const YTDLP_UPDATE_FILE: &str = "ytdlp-update.json";
+const YTDLP_AUTO_CHECK_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+fn ytdlp_asset_name() -> &'static str {
+  if cfg!(windows) {
+    "yt-dlp.exe"
+  } else if cfg!(target_os = "macos") {
+    "yt-dlp_macos"
+  } else {
+    match std::env::consts::ARCH {
+      "aarch64" => "yt-dlp_linux_aarch64",
+      _ => "yt-dlp_linux",
+    }
+  }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct YtdlpUpdateConfig {
+  /// "stable" or "nightly".
+  channel: String,
+  /// Check GitHub on startup (at most daily) and update when a newer build exists.
+  auto_check: bool,
+  /// Release tag to stay on (e.g. "2025.01.15"); disables auto updates.
+  pinned_version: Option<String>,
+  /// Unix seconds of the last automatic check.
+  last_check: Option<u64>,
+}
+
+impl Default for YtdlpUpdateConfig {
+  fn default() -> Self {
+    Self { channel: "stable".to_string(), auto_check: false, pinned_version: None, last_check: None }
+  }
+}
+
+fn ytdlp_update_config_path(data_root: &Path) -> PathBuf {
+  data_root.join("db").join(YTDLP_UPDATE_FILE)
+}
+
+fn load_ytdlp_update_config(data_root: &Path) -> YtdlpUpdateConfig {
+  std::fs::read(ytdlp_update_config_path(data_root))
+    .ok()
+    .and_then(|b| serde_json::from_slice(&b).ok())
+    .unwrap_or_default()
+}
+
+fn save_ytdlp_update_config(data_root: &Path, config: &YtdlpUpdateConfig) -> Result<(), String> {
+  let value = serde_json::to_value(config).map_err(|e| e.to_string())?;
+  write_json_atomic(&ytdlp_update_config_path(data_root), &value)
+}
+
+fn ytdlp_release_repo(channel: &str) -> Result<&'static str, AppError> {
+  match channel.trim() {
+    "" | "stable" => Ok("yt-dlp"),
+    "nightly" => Ok("yt-dlp-nightly-builds"),
+    other => Err(AppError::InvalidInput(format!("unknown yt-dlp channel: {other}"))),
+  }
+}
+
+async fn ytdlp_version(exe: &Path) -> Option<String> {
+  if !exe.is_file() || is_probably_stub_binary(exe) {
+    return None;
+  }
+  tool_version_line(exe, "--version").await
+}
+
+// Resolves the release to install: the pinned tag if set, else the channel's latest.
+async fn ytdlp_target_release(channel: &str, version: Option<&str>) -> Result<(String, GithubAsset), AppError> {
+  let repo = ytdlp_release_repo(channel)?;
+  let (tag, assets) = github_release_assets("yt-dlp", repo, version).await?;
+  let asset = assets
+    .into_iter()
+    .find(|a| a.name == ytdlp_asset_name())
+    .ok_or_else(|| AppError::ToolDownloadFailed(format!("yt-dlp release {tag} has no {}", ytdlp_asset_name())))?;
+  Ok((tag, asset))
+}
+
+// Installs the target build into <data>/bin, keeping the old binary as `.prev` and
+// restoring it if the new one does not run.
+async fn update_ytdlp_binary(
+  state: &Arc<AppState>,
+  data_root: &Path,
+  channel: &str,
+  version: Option<&str>,
+  force: bool,
+) -> Result<serde_json::Value, AppError> {
+  let (tag, asset) = ytdlp_target_release(channel, version).await?;
+
+  let _guard = state.tools_lock.lock().await;
+  let bin_dir = tools_bin_dir(data_root);
+  tokio::fs::create_dir_all(&bin_dir)
+    .await
+    .map_err(|e| AppError::Internal(format!("create bin dir failed: {e}")))?;
+  let dest = bin_dir.join(sidecar_basename("yt-dlp"));
+  let previous = ytdlp_version(&dest).await;
+
+  if !force && previous.as_deref() == Some(tag.as_str()) {
+    return Ok(serde_json::json!({
+      "path": dest.to_string_lossy().to_string(),
+      "channel": channel,
+      "previous_version": previous,
+      "version": tag,
+      "updated": false,
+    }));
+  }
+
+  let staged = bin_dir.join(format!("{}.new", sidecar_basename("yt-dlp")));
+  http_download_to_file(&asset.url, &staged).await?;
+  set_executable(&staged).map_err(AppError::Internal)?;
+  if ytdlp_version(&staged).await.is_none() {
+    let _ = tokio::fs::remove_file(&staged).await;
+    return Err(AppError::ToolDownloadFailed(format!("yt-dlp {tag} failed verification (--version); kept the current binary")));
+  }
+
+  let backup = bin_dir.join(format!("{}.prev", sidecar_basename("yt-dlp")));
+  let had_previous = dest.is_file();
+  if had_previous {
+    let _ = tokio::fs::remove_file(&backup).await;
+    tokio::fs::rename(&dest, &backup)
+      .await
+      .map_err(|e| AppError::Internal(format!("back up yt-dlp failed: {e}")))?;
+  }
+  tokio::fs::rename(&staged, &dest)
+    .await
+    .map_err(|e| AppError::Internal(format!("install yt-dlp failed: {e}")))?;
+
+  let Some(installed) = ytdlp_version(&dest).await else {
+    let _ = tokio::fs::remove_file(&dest).await;
+    if had_previous {
+      tokio::fs::rename(&backup, &dest)
+        .await
+        .map_err(|e| AppError::Internal(format!("yt-dlp {tag} failed verification and rollback failed: {e}")))?;
+    }
+    return Err(AppError::ToolDownloadFailed(format!("yt-dlp {tag} failed verification after install; rolled back")));
+  };
+
+  std::env::set_var(SIDECAR_ENV_DIR, &bin_dir);
+  Ok(serde_json::json!({
+    "path": dest.to_string_lossy().to_string(),
+    "channel": channel,
+    "previous_version": previous,
+    "version": installed,
+    "updated": true,
+  }))
+}
+
+async fn update_ytdlp_with_job(
+  app: &tauri::AppHandle,
+  state: &Arc<AppState>,
+  data_root: &Path,
+  channel: &str,
+  version: Option<&str>,
+  force: bool,
+) -> Result<serde_json::Value, AppError> {
+  let job_id = new_job_id();
+  let media_id = String::new();
+  let _ = emit_job(app, JobProgressEvent {
+    job_id: job_id.clone(),
+    media_id: media_id.clone(),
+    job_type: JobType::Download,
+    status: JobStatus::Running,
+    progress: 0.0,
+    message: Some(format!("updating yt-dlp ({channel})")),
+    error_code: None,
+  });
+
+  let result = run_job(app, &job_id, update_ytdlp_binary(state, data_root, channel, version, force)).await;
+
+  let (status, message) = match &result {
+    Ok(v) => (JobStatus::Succeeded, v.get("version").and_then(|x| x.as_str()).map(|x| format!("yt-dlp {x}"))),
+    Err(e) => (JobStatus::Failed, Some(e.to_string())),
+  };
+  let _ = emit_job(app, JobProgressEvent {
+    job_id: job_id.clone(),
+    media_id,
+    job_type: JobType::Download,
+    status,
+    progress: 1.0,
+    message,
+    error_code: result.as_ref().err().map(|e| e.code()),
+  });
+  result.map(|mut v| {
+    v["job_id"] = serde_json::Value::String(job_id);
+    v
+  })
+}
+
+// Messages yt-dlp prints when a site changed under it; a newer build usually fixes these.
+fn is_ytdlp_extractor_break(stderr_tail: &str) -> bool {
+  let s = stderr_tail.to_lowercase();
+  s.contains("unable to extract")
+    || s.contains("please report this issue")
+    || s.contains("confirm you are on the latest version")
+    || s.contains("nsig extraction failed")
+    || s.contains("signature extraction failed")
+    || s.contains("unable to download api page")
+}
+
+fn ytdlp_auto_check_on_startup(app: &tauri::AppHandle, data_root: &Path) {
+  let mut config = load_ytdlp_update_config(data_root);
+  if !config.auto_check || config.pinned_version.is_some() || offline_mode() {
+    return;
+  }
+  let now = std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0);
+  if config.last_check.is_some_and(|t| now.saturating_sub(t) < YTDLP_AUTO_CHECK_INTERVAL_SECS) {
+    return;
+  }
+
+  let app = app.clone();
+  let data_root = data_root.to_path_buf();
+  tauri::async_runtime::spawn(async move {
+    let state = app.state::<Arc<AppState>>().inner().clone();
+    let current = ytdlp_version(&tools_bin_dir(&data_root).join(sidecar_basename("yt-dlp"))).await;
+    if let Ok((tag, _)) = ytdlp_target_release(&config.channel, None).await {
+      config.last_check = Some(now);
+      let _ = save_ytdlp_update_config(&data_root, &config);
+      // Only replace a yt-dlp we downloaded ourselves; bundled sidecars stay as shipped.
+      if current.is_some_and(|v| v != tag) {
+        let _ = update_ytdlp_with_job(&app, &state, &data_root, &config.channel, None, false).await;
+      }
+    }
+  });
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateYtdlpArgs {
+  /// Defaults to the saved channel.
+  #[serde(default)]
+  channel: Option<String>,
+  /// Install this release tag instead of the latest (also used for rollback to a known build).
+  #[serde(default)]
+  version: Option<String>,
+  #[serde(default)]
+  force: bool,
+}
+
+#[tauri::command]
+async fn update_ytdlp(
+  app: tauri::AppHandle,
+  args: UpdateYtdlpArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
+
+  let config = load_ytdlp_update_config(dir);
+  let channel = args.channel.map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).unwrap_or(config.channel);
+  let version = args
+    .version
+    .map(|v| v.trim().to_string())
+    .filter(|v| !v.is_empty())
+    .or(config.pinned_version);
+  ytdlp_release_repo(&channel)?;
+  update_ytdlp_with_job(&app, state.inner(), dir, &channel, version.as_deref(), args.force).await
+}
+
+#[tauri::command]
+async fn check_ytdlp_update(app: tauri::AppHandle, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
+
+  let config = load_ytdlp_update_config(dir);
+  let exe = find_ytdlp(&app, dir);
+  let current = match exe.as_deref() {
+    Some(p) => ytdlp_version(p).await,
+    None => None,
+  };
+  let (latest, error) = match ytdlp_target_release(&config.channel, config.pinned_version.as_deref()).await {
+    Ok((tag, _)) => (Some(tag), None),
+    Err(e) => (None, Some(e)),
+  };
+  Ok(serde_json::json!({
+    "path": exe.map(|p| p.to_string_lossy().to_string()),
+    "current_version": current,
+    "latest_version": latest,
+    "update_available": matches!((&current, &latest), (Some(c), Some(l)) if c != l),
+    "error": error,
+    "config": config,
+  }))
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetYtdlpUpdateConfigArgs {
+  #[serde(default)]
+  channel: Option<String>,
+  #[serde(default)]
+  auto_check: Option<bool>,
+  /// Empty string clears the pin.
+  #[serde(default)]
+  pinned_version: Option<String>,
+}
+
+#[tauri::command]
+async fn set_ytdlp_update_config(
+  app: tauri::AppHandle,
+  args: SetYtdlpUpdateConfigArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
+
+  let mut config = load_ytdlp_update_config(dir);
+  if let Some(channel) = args.channel {
+    ytdlp_release_repo(&channel)?;
+    config.channel = channel.trim().to_string();
+  }
+  if let Some(auto) = args.auto_check {
+    config.auto_check = auto;
+  }
+  if let Some(pin) = args.pinned_version {
+    config.pinned_version = Some(pin.trim().to_string()).filter(|p| !p.is_empty());
+  }
+  save_ytdlp_update_config(dir, &config).map_err(AppError::Internal)?;
+  serde_json::to_value(config).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+#[derive(Debug, Clone)]
+struct SherpaOnnxRuntime {
+  exe: PathBuf,
+  provider: String,
+}
+
+#[derive(Debug, Clone)]
+struct WhisperCppRuntime {
+  exe: PathBuf,
+  provider: String,
+}
+
+fn has_nvidia_cuda_driver() -> bool {
+  if !cfg!(windows) {
+    return false;
+  }
+  let windir = std::env::var_os("WINDIR")
+    .map(PathBuf::from)
+    .unwrap_or_else(|| PathBuf::from("C:\\Windows"));
+  windir.join("System32").join("nvcuda.dll").is_file()
+}
+
+// A healthy binary prints its usage instantly; anything slower is treated as broken.
+const TOOL_VERIFY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+async fn verify_sherpa_exec(path: &Path) -> Result<(), String> {
+  let mut cmd = tokio::process::Command::new(path);
+  if let Some(dir) = path.parent() {
+    cmd.current_dir(dir);
+  }
+  cmd
+    .kill_on_drop(true)
+    .arg("--help")
+    .stdout(std::process::Stdio::null())
+    .stderr(std::process::Stdio::null());
+  let status = tokio::time::timeout(TOOL_VERIFY_TIMEOUT, cmd.status())
+    .await
+    .map_err(|_| "sherpa-onnx-offline --help timed out".to_string())?
+    .map_err(|e| format!("failed to start sherpa-onnx-offline: {e}"))?;
+  if status.success() {
+    Ok(())
+  } else {
+    Err("sherpa-onnx-offline --help returned non-zero".to_string())
+  }
+}
+
+async fn verify_whisper_exec(path: &Path) -> Result<(), String> {
+  let mut cmd = tokio::process::Command::new(path);
+  if let Some(dir) = path.parent() {
+    cmd.current_dir(dir);
+  }
+  cmd
+    .kill_on_drop(true)
+    .arg("-h")
+    .stdout(std::process::Stdio::null())
+    .stderr(std::process::Stdio::null());
+  let status = tokio::time::timeout(TOOL_VERIFY_TIMEOUT, cmd.status())
+    .await
+    .map_err(|_| "whisper-cli -h timed out".to_string())?
+    .map_err(|e| format!("failed to start whisper-cli: {e}"))?;
+  if status.success() {
+    Ok(())
+  } else {
+    Err("whisper-cli -h returned non-zero".to_string())
+  }
+}
+
+async fn install_sherpa_bundle(
+  app: &tauri::AppHandle,
+  root: &Path,
+  url: &str,
+  archive_file: &str,
+  job_id: &str,
+  media_id: &str,
+  progress_base: f32,
+  progress_span: f32,
+  label: &str,
+) -> Result<PathBuf, AppError> {
+  tokio::fs::create_dir_all(root)
+    .await
+    .map_err(|e| AppError::Internal(format!("create sherpa dir failed: {e}")))?;
+
+  let archive_path = root.join(archive_file);
+
+  let job_id_s = job_id.to_string();
+  let media_id_s = media_id.to_string();
+  let archive_label = archive_file.to_string();
+
+  http_download_to_file_with_progress(url, &archive_path, move |done, total| {
+    let p = match total {
+      Some(t) if t > 0 => {
+        let frac = (done as f32 / t as f32).clamp(0.0, 1.0);
+        progress_base + frac * progress_span
+      }
+      _ => progress_base,
+    };
+    let msg = match total {
+      Some(t) => format!(
+        "downloading {label} ({archive_label}) {} / {}",
+        human_bytes(done),
+        human_bytes(t)
+      ),
+      None => format!("downloading {label} ({archive_label}, {})", human_bytes(done)),
+    };
+    let _ = emit_job(app, JobProgressEvent {
+      job_id: job_id_s.clone(),
+      media_id: media_id_s.clone(),
+      job_type: JobType::Transcribe,
+      status: JobStatus::Running,
+      progress: p,
+      message: Some(msg),
+      error_code: None,
+    });
+  })
+  .await?;
 
-  // GitHub release downloads (esp. large archives) can pause for a while depending on network.
-  // Keep a stall guard, but allow several automatic resume attempts.
-  const STALL_TIMEOUT_SECS: u64 = 300;
-  const MAX_CONSECUTIVE_RETRIES_WITHOUT_PROGRESS: usize = 6;
-  const RETRY_BACKOFF_SECS: u64 = 2;
+  let _ = emit_job(app, JobProgressEvent {
+    job_id: job_id.to_string(),
+    media_id: media_id.to_string(),
+    job_type: JobType::Transcribe,
+    status: JobStatus::Running,
+    progress: progress_base + progress_span,
+    message: Some(format!("extracting {label}")),
+    error_code: None,
+  });
 
-  let dir = dest
-    .parent()
-    .ok_or_else(|| "invalid destination path".to_string())?;
-  tokio::fs::create_dir_all(dir)
+  let tmp_extract = root.join(".extract");
+  let _ = tokio::fs::remove_dir_all(&tmp_extract).await;
+  tokio::fs::create_dir_all(&tmp_extract)
     .await
-    .map_err(|e| format!("create dir failed: {e}"))?;
+    .map_err(|e| AppError::Internal(format!("create extract dir failed: {e}")))?;
 
-  let tmp = dest.with_extension("part");
+  let archive_path_clone = archive_path.clone();
+  let tmp_extract_clone = tmp_extract.clone();
+  tokio::task::spawn_blocking(move || extract_tar_bz2_to_dir(&archive_path_clone, &tmp_extract_clone))
+    .await
+    .map_err(|e| AppError::Internal(format!("join extract task failed: {e}")))?
+    .map_err(AppError::Internal)?;
 
-  let client = reqwest::Client::new();
-  let mut consecutive_retries_without_progress = 0usize;
-  let mut last_progress_downloaded: u64 = tokio::fs::metadata(&tmp)
+  let exe_name = sidecar_basename("sherpa-onnx-offline");
+  let found = find_file_recursive(&tmp_extract, &exe_name)
+    .ok_or_else(|| AppError::ToolDownloadFailed(format!("{label} missing {exe_name} after extraction")))?;
+  let Some(bin_dir) = found.parent() else {
+    return Err(AppError::ToolDownloadFailed(format!("invalid {label} layout")));
+  };
+
+  // Move the bin dir into a stable location so DLL lookup works.
+  let release_dir = root.join("Release");
+  let _ = tokio::fs::remove_dir_all(&release_dir).await;
+  tokio::fs::create_dir_all(&release_dir)
     .await
-    .map(|m| m.len())
-    .unwrap_or(0);
+    .map_err(|e| AppError::Internal(format!("create Release dir failed: {e}")))?;
 
-  loop {
-    let (resp, mut resume_from): (reqwest::Response, u64) = {
-      let mut restarted = false;
-      loop {
-        let resume_from: u64 = tokio::fs::metadata(&tmp)
-          .await
-          .map(|m| m.len())
-          .unwrap_or(0);
+  // Try fast path: rename the whole bin dir.
+  if tokio::fs::rename(bin_dir, &release_dir).await.is_err() {
+    // Fallback: copy files.
+    let mut rd = tokio::fs::read_dir(bin_dir)
+      .await
+      .map_err(|e| AppError::Internal(format!("read extracted bin dir failed: {e}")))?;
+    while let Some(ent) = rd
+      .next_entry()
+      .await
+      .map_err(|e| AppError::Internal(format!("read dir entry failed: {e}")))?
+    {
+      let p = ent.path();
+      if !p.is_file() {
+        continue;
+      }
+      if let Some(name) = p.file_name() {
+        let _ = tokio::fs::copy(&p, release_dir.join(name)).await;
+      }
+    }
+  }
 
-        let mut req = client.get(url).header("User-Agent", "vecho-studio");
-        if resume_from > 0 {
-          req = req.header("Range", format!("bytes={resume_from}-"));
-        }
+  let _ = tokio::fs::remove_dir_all(&tmp_extract).await;
+  let _ = tokio::fs::remove_file(&archive_path).await;
 
-        let resp = match req.send().await {
-          Ok(r) => r,
-          Err(e) => {
-            consecutive_retries_without_progress = consecutive_retries_without_progress.saturating_add(1);
-            if consecutive_retries_without_progress > MAX_CONSECUTIVE_RETRIES_WITHOUT_PROGRESS {
-              return Err(format!("download failed ({url}): {e}"));
-            }
-            sleep(Duration::from_secs(RETRY_BACKOFF_SECS)).await;
-            continue;
-          }
-        };
+  let exe = release_dir.join(exe_name);
+  if !exe.is_file() {
+    return Err(AppError::ToolDownloadFailed(format!("{label} install failed: executable missing")));
+  }
+  verify_sherpa_exec(&exe).await.map_err(AppError::ToolDownloadFailed)?;
+  Ok(exe)
+}
 
-        let status = resp.status();
-        if status == StatusCode::RANGE_NOT_SATISFIABLE && resume_from > 0 {
-          let _ = tokio::fs::remove_file(&tmp).await;
-          if restarted {
-            return Err("download failed: range not satisfiable".to_string());
-          }
-          restarted = true;
-          continue;
-        }
-        break (resp, resume_from);
+async fn install_whisper_cpp_bundle(
+  app: &tauri::AppHandle,
+  root: &Path,
+  url: &str,
+  archive_file: &str,
+  job_id: &str,
+  media_id: &str,
+  progress_base: f32,
+  progress_span: f32,
+  label: &str,
+) -> Result<PathBuf, AppError> {
+  tokio::fs::create_dir_all(root)
+    .await
+    .map_err(|e| AppError::Internal(format!("create whisper.cpp dir failed: {e}")))?;
+
+  let archive_path = root.join(archive_file);
+  let job_id_s = job_id.to_string();
+  let media_id_s = media_id.to_string();
+  let archive_label = archive_file.to_string();
+
+  http_download_to_file_with_progress(url, &archive_path, move |done, total| {
+    let p = match total {
+      Some(t) if t > 0 => {
+        let frac = (done as f32 / t as f32).clamp(0.0, 1.0);
+        progress_base + frac * progress_span
       }
+      _ => progress_base,
+    };
+    let msg = match total {
+      Some(t) => format!(
+        "downloading {label} ({archive_label}) {} / {}",
+        human_bytes(done),
+        human_bytes(t)
+      ),
+      None => format!("downloading {label} ({archive_label}, {})", human_bytes(done)),
     };
+    let _ = emit_job(app, JobProgressEvent {
+      job_id: job_id_s.clone(),
+      media_id: media_id_s.clone(),
+      job_type: JobType::Transcribe,
+      status: JobStatus::Running,
+      progress: p,
+      message: Some(msg),
+      error_code: None,
+    });
+  })
+  .await?;
 
-    let status = resp.status();
-    if !(status.is_success() || status == StatusCode::PARTIAL_CONTENT) {
-      return Err(format!("download failed ({url}): http {}", status));
-    }
+  let _ = emit_job(app, JobProgressEvent {
+    job_id: job_id.to_string(),
+    media_id: media_id.to_string(),
+    job_type: JobType::Transcribe,
+    status: JobStatus::Running,
+    progress: progress_base + progress_span,
+    message: Some(format!("extracting {label}")),
+    error_code: None,
+  });
 
-    // If server ignored our Range request, restart from scratch.
-    let resuming = resume_from > 0 && status == StatusCode::PARTIAL_CONTENT;
-    if resume_from > 0 && !resuming {
-      let _ = tokio::fs::remove_file(&tmp).await;
-      resume_from = 0;
-    }
+  let tmp_extract = root.join(".extract");
+  let _ = tokio::fs::remove_dir_all(&tmp_extract).await;
+  tokio::fs::create_dir_all(&tmp_extract)
+    .await
+    .map_err(|e| AppError::Internal(format!("create extract dir failed: {e}")))?;
 
-    // Determine total size.
-    let mut total: Option<u64> = None;
-    if status == StatusCode::PARTIAL_CONTENT {
-      if let Some(v) = resp.headers().get("Content-Range").and_then(|h| h.to_str().ok()) {
-        // Example: "bytes 100-999/1000"
-        if let Some(total_str) = v.split('/').nth(1) {
-          if let Ok(t) = total_str.trim().parse::<u64>() {
-            total = Some(t);
-          }
-        }
-      }
-    }
-    if total.is_none() {
-      // For 200 OK or if Content-Range is missing.
-      total = if status == StatusCode::PARTIAL_CONTENT {
-        resp.content_length().map(|t| t.saturating_add(resume_from))
-      } else {
-        resp.content_length()
-      };
-    }
+  let archive_path_clone = archive_path.clone();
+  let tmp_extract_clone = tmp_extract.clone();
+  tokio::task::spawn_blocking(move || extract_zip_to_dir(&archive_path_clone, &tmp_extract_clone))
+    .await
+    .map_err(|e| AppError::Internal(format!("join extract task failed: {e}")))?
+    .map_err(AppError::Internal)?;
 
-    // Open destination temp file.
-    let mut downloaded: u64 = resume_from;
-    let mut file = if resuming {
-      tokio::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&tmp)
-        .await
-        .map_err(|e| format!("open temp file failed: {e}"))?
-    } else {
-      downloaded = 0;
-      tokio::fs::File::create(&tmp)
+  let exe_name = sidecar_basename("whisper-cli");
+  let found = find_file_recursive(&tmp_extract, &exe_name)
+    .ok_or_else(|| AppError::ToolDownloadFailed(format!("{label} missing {exe_name} after extraction")))?;
+  let Some(bin_dir) = found.parent() else {
+    return Err(AppError::ToolDownloadFailed(format!("invalid {label} layout")));
+  };
+
+  // Move the whole directory next to the executable into a stable location so DLL lookup works.
+  let release_dir = root.join("Release");
+  let _ = tokio::fs::remove_dir_all(&release_dir).await;
+  tokio::fs::create_dir_all(&release_dir)
+    .await
+    .map_err(|e| AppError::Internal(format!("create Release dir failed: {e}")))?;
+
+  if tokio::fs::rename(bin_dir, &release_dir).await.is_err() {
+    copy_dir_files_flat(bin_dir, &release_dir).await.map_err(AppError::Internal)?;
+  }
+
+  let _ = tokio::fs::remove_dir_all(&tmp_extract).await;
+  let _ = tokio::fs::remove_file(&archive_path).await;
+
+  let exe = release_dir.join(exe_name);
+  if !exe.is_file() {
+    return Err(AppError::ToolDownloadFailed(format!("{label} install failed: executable missing")));
+  }
+  verify_whisper_exec(&exe).await.map_err(AppError::ToolDownloadFailed)?;
+  Ok(exe)
+}
+
+async fn copy_dir_files_flat(src_dir: &Path, dest_dir: &Path) -> Result<(), String> {
+  let mut rd = tokio::fs::read_dir(src_dir)
+    .await
+    .map_err(|e| format!("read extracted dir failed: {e}"))?;
+  while let Some(ent) = rd
+    .next_entry()
+    .await
+    .map_err(|e| format!("read dir entry failed: {e}"))?
+  {
+    let p = ent.path();
+    if !p.is_file() {
+      continue;
+    }
+    if let Some(name) = p.file_name() {
+      tokio::fs::copy(&p, dest_dir.join(name))
         .await
-        .map_err(|e| format!("create temp file failed: {e}"))?
-    };
+        .map_err(|e| format!("copy file failed: {e}"))?;
+    }
+  }
+  Ok(())
+}
 
-    // Emit initial progress (useful for resume).
-    on_progress(downloaded, total);
-    let mut last_emit_at = Instant::now();
-    let mut last_emit_downloaded: u64 = downloaded;
+async fn ensure_sherpa_onnx_offline(
+  app: &tauri::AppHandle,
+  state: &Arc<AppState>,
+  data_root: &Path,
+  job_id: &str,
+  media_id: &str,
+  allow_cuda: bool,
+  require_cuda: bool,
+) -> Result<SherpaOnnxRuntime, AppError> {
+  let _guard = state.tools_lock.lock().await;
 
-    let mut stream = resp.bytes_stream();
-    let mut restart = false;
+  if cfg!(windows) && std::env::consts::ARCH != "x86_64" {
+    return Err(AppError::ToolMissing("sherpa-onnx local transcription currently supports Windows x64 only".to_string()));
+  }
 
-    let mut stall_secs: u64 = 0;
-    loop {
-      // Use a short timeout so we can emit periodic progress updates.
-      // This avoids the UI looking frozen during long network pauses.
-      let next = timeout(Duration::from_secs(1), stream.next()).await;
-      let next = match next {
-        Ok(v) => {
-          stall_secs = 0;
-          v
-        }
-        Err(_) => {
-          stall_secs = stall_secs.saturating_add(1);
-          if last_emit_at.elapsed() >= Duration::from_secs(1) {
-            on_progress(downloaded, total);
-            last_emit_at = Instant::now();
-            last_emit_downloaded = downloaded;
-          }
-          if stall_secs >= STALL_TIMEOUT_SECS {
-            // Stalled; try to resume by restarting the request.
-            consecutive_retries_without_progress = consecutive_retries_without_progress.saturating_add(1);
-            if consecutive_retries_without_progress > MAX_CONSECUTIVE_RETRIES_WITHOUT_PROGRESS {
-              return Err(format!("download stalled (no data for {STALL_TIMEOUT_SECS}s): {url}"));
-            }
-            restart = true;
-            break;
-          }
-          continue;
-        }
-      };
+  let has_cuda = allow_cuda && has_nvidia_cuda_driver();
+  if require_cuda && !has_cuda {
+    return Err(AppError::ToolMissing("CUDA requested, but NVIDIA driver (nvcuda.dll) not found. Set localAccelerator=cpu/auto or install NVIDIA driver.".to_string()));
+  }
 
-      let Some(chunk) = next else { break; };
+  const VER: &str = "v1.12.23";
+  const WIN_X64_CPU: &str = "https://github.com/k2-fsa/sherpa-onnx/releases/download/v1.12.23/sherpa-onnx-v1.12.23-win-x64-shared.tar.bz2";
+  const WIN_X64_CUDA: &str = "https://github.com/k2-fsa/sherpa-onnx/releases/download/v1.12.23/sherpa-onnx-v1.12.23-cuda-12.x-cudnn-9.x-win-x64-cuda.tar.bz2";
 
-      let chunk = match chunk {
-        Ok(c) => c,
-        Err(e) => {
-          consecutive_retries_without_progress = consecutive_retries_without_progress.saturating_add(1);
-          if consecutive_retries_without_progress > MAX_CONSECUTIVE_RETRIES_WITHOUT_PROGRESS {
-            return Err(format!("download stream error ({url}): {e}"));
-          }
-          restart = true;
-          break;
-        }
-      };
+  let exe_name = sidecar_basename("sherpa-onnx-offline");
 
-      downloaded = downloaded.saturating_add(chunk.len() as u64);
+  // Reuse previously downloaded runtimes from other data roots (e.g. installed -> portable).
+  for bin_dir in candidate_tools_bin_dirs(app, data_root) {
+    let base_root = bin_dir.join("sherpa_onnx");
+    let cuda_root = base_root.join("cuda").join(VER);
+    let cuda_exe = cuda_root.join("Release").join(&exe_name);
+    let cpu_root = base_root.join("cpu").join(VER);
+    let cpu_exe = cpu_root.join("Release").join(&exe_name);
 
-      // Throttle progress emissions (emitting on every chunk can be too chatty).
-      if downloaded > last_emit_downloaded
-        && (downloaded.saturating_sub(last_emit_downloaded) >= 256 * 1024
-          || last_emit_at.elapsed() >= Duration::from_millis(250))
-      {
-        on_progress(downloaded, total);
-        last_emit_at = Instant::now();
-        last_emit_downloaded = downloaded;
+    if has_cuda {
+      if cuda_exe.is_file() && verify_sherpa_exec(&cuda_exe).await.is_ok() {
+        let _ = emit_job(app, JobProgressEvent {
+          job_id: job_id.to_string(),
+          media_id: media_id.to_string(),
+          job_type: JobType::Transcribe,
+          status: JobStatus::Running,
+          progress: 0.18,
+          message: Some("using sherpa-onnx (CUDA)".to_string()),
+          error_code: None,
+        });
+        return Ok(SherpaOnnxRuntime { exe: cuda_exe, provider: "cuda".to_string() });
       }
-      file
-        .write_all(&chunk)
-        .await
-        .map_err(|e| format!("write temp file failed: {e}"))?;
+    }
 
-      if downloaded > last_progress_downloaded {
-        last_progress_downloaded = downloaded;
-        consecutive_retries_without_progress = 0;
-      }
+    if cpu_exe.is_file() && verify_sherpa_exec(&cpu_exe).await.is_ok() {
+      return Ok(SherpaOnnxRuntime { exe: cpu_exe, provider: "cpu".to_string() });
     }
+  }
 
-    file.flush().await.ok();
+  let base_root = tools_bin_dir(data_root).join("sherpa_onnx");
+  tokio::fs::create_dir_all(&base_root)
+    .await
+    .map_err(|e| AppError::Internal(format!("create sherpa root dir failed: {e}")))?;
 
-    if restart {
-      sleep(Duration::from_secs(RETRY_BACKOFF_SECS)).await;
-      continue;
-    }
+  let cuda_root = base_root.join("cuda").join(VER);
+  let cuda_exe = cuda_root.join("Release").join(&exe_name);
+  let cpu_root = base_root.join("cpu").join(VER);
+  let cpu_exe = cpu_root.join("Release").join(&exe_name);
 
-    // Finished download; finalize atomically.
-    if dest.is_file() {
-      let _ = tokio::fs::remove_file(dest).await;
+  // Resolution strategy:
+  // - If NVIDIA driver exists and user did not force CPU: auto-download CUDA runtime and use it.
+  // - Otherwise: use/install CPU.
+  if has_cuda {
+    if cuda_exe.is_file() && verify_sherpa_exec(&cuda_exe).await.is_ok() {
+      let _ = emit_job(app, JobProgressEvent {
+        job_id: job_id.to_string(),
+        media_id: media_id.to_string(),
+        job_type: JobType::Transcribe,
+        status: JobStatus::Running,
+        progress: 0.18,
+        message: Some("using sherpa-onnx (CUDA)".to_string()),
+        error_code: None,
+      });
+      return Ok(SherpaOnnxRuntime { exe: cuda_exe, provider: "cuda".to_string() });
     }
-    tokio::fs::rename(&tmp, dest)
-      .await
-      .map_err(|e| format!("finalize download failed: {e}"))?;
-    return Ok(());
+
+    // Auto mode (or explicit CUDA): install CUDA bundle.
+    let exe = install_sherpa_bundle(
+      app,
+      &cuda_root,
+      WIN_X64_CUDA,
+      "sherpa-onnx-cuda.tar.bz2",
+      job_id,
+      media_id,
+      0.12,
+      0.06,
+      "sherpa-onnx runtime (CUDA)",
+    )
+    .await?;
+    let _ = emit_job(app, JobProgressEvent {
+      job_id: job_id.to_string(),
+      media_id: media_id.to_string(),
+      job_type: JobType::Transcribe,
+      status: JobStatus::Running,
+      progress: 0.18,
+      message: Some("using sherpa-onnx (CUDA)".to_string()),
+      error_code: None,
+    });
+    return Ok(SherpaOnnxRuntime { exe, provider: "cuda".to_string() });
+  }
+
+  if cpu_exe.is_file() && verify_sherpa_exec(&cpu_exe).await.is_ok() {
+    return Ok(SherpaOnnxRuntime { exe: cpu_exe, provider: "cpu".to_string() });
+  }
+
+  let exe = install_sherpa_bundle(
+    app,
+    &cpu_root,
+    WIN_X64_CPU,
+    "sherpa-onnx-cpu.tar.bz2",
+    job_id,
+    media_id,
+    0.12,
+    0.06,
+    "sherpa-onnx runtime",
+  )
+  .await?;
+  Ok(SherpaOnnxRuntime { exe, provider: "cpu".to_string() })
+}
+
+// A whisper-cli chosen by the user (TranscriptionConfig.whisperCppPath).
+async fn whisper_cpp_runtime_from_path(path: &Path, allow_gpu: bool) -> Result<WhisperCppRuntime, String> {
+  if !path.is_file() {
+    return Err(format!("whisper-cli not found at {}", path.display()));
   }
+  verify_whisper_exec(path).await?;
+  Ok(WhisperCppRuntime {
+    exe: path.to_path_buf(),
+    provider: if allow_gpu { "auto" } else { "cpu" }.to_string(),
+  })
 }
 
-fn human_bytes(bytes: u64) -> String {
-  const KB: f64 = 1024.0;
-  const MB: f64 = KB * 1024.0;
-  const GB: f64 = MB * 1024.0;
+// No prebuilt Linux/macOS binaries are published, so look for one instead of downloading:
+// a source build under <tools>/whisper_cpp (e.g. build/bin/whisper-cli), then PATH and the usual
+// Homebrew/manual install dirs (GUI apps on macOS do not inherit the shell PATH).
+async fn resolve_unix_whisper_cpp(
+  app: &tauri::AppHandle,
+  data_root: &Path,
+  allow_gpu: bool,
+) -> Result<WhisperCppRuntime, String> {
+  let names = ["whisper-cli", "whisper-cpp"];
+  let mut candidates: Vec<PathBuf> = Vec::new();
 
-  let b = bytes as f64;
-  if b < KB {
-    return format!("{bytes} B");
+  for bin_dir in candidate_tools_bin_dirs(app, data_root) {
+    let root = bin_dir.join("whisper_cpp");
+    for name in names {
+      if let Some(p) = find_file_recursive(&root, name) {
+        candidates.push(p);
+      }
+    }
   }
-  if b < MB {
-    return format!("{:.1} KB", b / KB);
+
+  let mut search_dirs: Vec<PathBuf> = std::env::var_os("PATH")
+    .map(|p| std::env::split_paths(&p).collect())
+    .unwrap_or_default();
+  for extra in ["/opt/homebrew/bin", "/usr/local/bin", "/usr/bin"] {
+    let extra = PathBuf::from(extra);
+    if !search_dirs.contains(&extra) {
+      search_dirs.push(extra);
+    }
   }
-  if b < GB {
-    return format!("{:.1} MB", b / MB);
+  for dir in search_dirs {
+    for name in names {
+      candidates.push(dir.join(name));
+    }
   }
-  format!("{:.2} GB", b / GB)
+
+  for exe in candidates {
+    if exe.is_file() && verify_whisper_exec(&exe).await.is_ok() {
+      return Ok(WhisperCppRuntime {
+        exe,
+        provider: if allow_gpu { "auto" } else { "cpu" }.to_string(),
+      });
+    }
+  }
+
+  Err(format!(
+    "whisper.cpp not found. Install whisper-cli (e.g. `brew install whisper-cpp` or your distro package), \
+     set whisperCppPath, or build whisper.cpp into {}",
+    tools_bin_dir(data_root).join("whisper_cpp").display()
+  ))
 }
 
-async fn ensure_ytdlp(app: &tauri::AppHandle, state: &Arc<AppState>, data_root: &Path) -> Result<PathBuf, String> {
+async fn ensure_whisper_cpp(
+  app: &tauri::AppHandle,
+  state: &Arc<AppState>,
+  data_root: &Path,
+  job_id: &str,
+  media_id: &str,
+  allow_cuda: bool,
+  require_cuda: bool,
+) -> Result<WhisperCppRuntime, AppError> {
   let _guard = state.tools_lock.lock().await;
 
-  let bin_dir = tools_bin_dir(data_root);
-  tokio::fs::create_dir_all(&bin_dir)
-    .await
-    .map_err(|e| format!("create bin dir failed: {e}"))?;
-
-  let dest = bin_dir.join(sidecar_basename("yt-dlp"));
-  if dest.is_file() && !is_probably_stub_binary(&dest) {
-    return Ok(dest);
+  if !cfg!(windows) {
+    return resolve_unix_whisper_cpp(app, data_root, allow_cuda).await.map_err(AppError::ToolMissing);
+  }
+  if std::env::consts::ARCH != "x86_64" {
+    return Err(AppError::ToolMissing("whisper.cpp local transcription currently supports Windows x64 only".to_string()));
   }
 
-  // Prefer a bundled sidecar (no first-run download).
-  if let Ok(p) = resolve_sidecar(app, "yt-dlp") {
-    return Ok(p);
+  let has_cuda = allow_cuda && has_nvidia_cuda_driver();
+  if require_cuda && !has_cuda {
+    return Err(AppError::ToolMissing("CUDA requested, but NVIDIA driver (nvcuda.dll) not found. Set localAccelerator=cpu/auto or install NVIDIA driver.".to_string()));
   }
 
-  let url = if cfg!(windows) {
-    "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp.exe".to_string()
-  } else if cfg!(target_os = "macos") {
-    "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp_macos".to_string()
-  } else {
-    match std::env::consts::ARCH {
-      "aarch64" => "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp_linux_aarch64".to_string(),
-      _ => "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp_linux".to_string(),
-    }
-  };
+  const VER: &str = "v1.8.3";
+  const WIN_X64_CPU: &str = "https://github.com/ggml-org/whisper.cpp/releases/download/v1.8.3/whisper-blas-bin-x64.zip";
+  const WIN_X64_CUDA_12_4: &str = "https://github.com/ggml-org/whisper.cpp/releases/download/v1.8.3/whisper-cublas-12.4.0-bin-x64.zip";
+  const WIN_X64_CUDA_11_8: &str = "https://github.com/ggml-org/whisper.cpp/releases/download/v1.8.3/whisper-cublas-11.8.0-bin-x64.zip";
 
-  http_download_to_file(&url, &dest).await?;
-  set_executable(&dest)?;
+  let exe_name = sidecar_basename("whisper-cli");
 
-  // Prefer this directory for future sidecar resolution.
-  std::env::set_var(SIDECAR_ENV_DIR, &bin_dir);
+  // Reuse previously downloaded runtimes from other data roots (e.g. installed -> portable).
+  for bin_dir in candidate_tools_bin_dirs(app, data_root) {
+    let base_root = bin_dir.join("whisper_cpp");
+    let cuda_root = base_root.join("cuda").join(VER);
+    let cuda_exe = cuda_root.join("Release").join(&exe_name);
+    let cpu_root = base_root.join("cpu").join(VER);
+    let cpu_exe = cpu_root.join("Release").join(&exe_name);
+
+    if has_cuda {
+      if cuda_exe.is_file() && verify_whisper_exec(&cuda_exe).await.is_ok() {
+        let _ = emit_job(app, JobProgressEvent {
+          job_id: job_id.to_string(),
+          media_id: media_id.to_string(),
+          job_type: JobType::Transcribe,
+          status: JobStatus::Running,
+          progress: 0.18,
+          message: Some("using whisper.cpp (CUDA)".to_string()),
+          error_code: None,
+        });
+        return Ok(WhisperCppRuntime { exe: cuda_exe, provider: "cuda".to_string() });
+      }
+    }
 
-  // Basic sanity: if it's still tiny, treat as failure.
-  if is_probably_stub_binary(&dest) {
-    return Err("downloaded yt-dlp looks invalid".to_string());
+    if cpu_exe.is_file() && verify_whisper_exec(&cpu_exe).await.is_ok() {
+      return Ok(WhisperCppRuntime { exe: cpu_exe, provider: "cpu".to_string() });
+    }
   }
 
-  Ok(dest)
-}
+  let base_root = tools_bin_dir(data_root).join("whisper_cpp");
+  tokio::fs::create_dir_all(&base_root)
+    .await
+    .map_err(|e| AppError::Internal(format!("create whisper root dir failed: {e}")))?;
 
-#[derive(Debug, Clone)]
-struct SherpaOnnxRuntime {
-  exe: PathBuf,
-  provider: String,
-}
+  let cuda_root = base_root.join("cuda").join(VER);
+  let cuda_exe = cuda_root.join("Release").join(&exe_name);
+  let cpu_root = base_root.join("cpu").join(VER);
+  let cpu_exe = cpu_root.join("Release").join(&exe_name);
 
-#[derive(Debug, Clone)]
-struct WhisperCppRuntime {
-  exe: PathBuf,
-  provider: String,
-}
+  if has_cuda {
+    if cuda_exe.is_file() && verify_whisper_exec(&cuda_exe).await.is_ok() {
+      let _ = emit_job(app, JobProgressEvent {
+        job_id: job_id.to_string(),
+        media_id: media_id.to_string(),
+        job_type: JobType::Transcribe,
+        status: JobStatus::Running,
+        progress: 0.18,
+        message: Some("using whisper.cpp (CUDA)".to_string()),
+        error_code: None,
+      });
+      return Ok(WhisperCppRuntime { exe: cuda_exe, provider: "cuda".to_string() });
+    }
 
-fn has_nvidia_cuda_driver() -> bool {
-  if !cfg!(windows) {
-    return false;
-  }
-  let windir = std::env::var_os("WINDIR")
-    .map(PathBuf::from)
-    .unwrap_or_else(|| PathBuf::from("C:\\Windows"));
-  windir.join("System32").join("nvcuda.dll").is_file()
-}
+    // Auto mode (or explicit CUDA): try CUDA 12.4 first, then 11.8.
+    let exe = match install_whisper_cpp_bundle(
+      app,
+      &cuda_root,
+      WIN_X64_CUDA_12_4,
+      "whisper-cublas-12.4.0-bin-x64.zip",
+      job_id,
+      media_id,
+      0.12,
+      0.06,
+      "whisper.cpp runtime (CUDA 12.4)",
+    )
+    .await
+    {
+      Ok(exe) => Ok(exe),
+      Err(e12) => {
+        if require_cuda {
+          Err(e12)
+        } else {
+          install_whisper_cpp_bundle(
+            app,
+            &cuda_root,
+            WIN_X64_CUDA_11_8,
+            "whisper-cublas-11.8.0-bin-x64.zip",
+            job_id,
+            media_id,
+            0.12,
+            0.06,
+            "whisper.cpp runtime (CUDA 11.8)",
+          )
+          .await
+        }
+      }
+    }?;
 
-async fn verify_sherpa_exec(path: &Path) -> Result<(), String> {
-  let mut cmd = tokio::process::Command::new(path);
-  if let Some(dir) = path.parent() {
-    cmd.current_dir(dir);
+    let _ = emit_job(app, JobProgressEvent {
+      job_id: job_id.to_string(),
+      media_id: media_id.to_string(),
+      job_type: JobType::Transcribe,
+      status: JobStatus::Running,
+      progress: 0.18,
+      message: Some("using whisper.cpp (CUDA)".to_string()),
+      error_code: None,
+    });
+    return Ok(WhisperCppRuntime { exe, provider: "cuda".to_string() });
   }
-  let status = cmd
-    .arg("--help")
-    .stdout(std::process::Stdio::null())
-    .stderr(std::process::Stdio::null())
-    .status()
-    .await
-    .map_err(|e| format!("failed to start sherpa-onnx-offline: {e}"))?;
-  if status.success() {
-    Ok(())
-  } else {
-    Err("sherpa-onnx-offline --help returned non-zero".to_string())
+
+  if cpu_exe.is_file() && verify_whisper_exec(&cpu_exe).await.is_ok() {
+    return Ok(WhisperCppRuntime { exe: cpu_exe, provider: "cpu".to_string() });
   }
+
+  let exe = install_whisper_cpp_bundle(
+    app,
+    &cpu_root,
+    WIN_X64_CPU,
+    "whisper-blas-bin-x64.zip",
+    job_id,
+    media_id,
+    0.12,
+    0.06,
+    "whisper.cpp runtime",
+  )
+  .await?;
+  Ok(WhisperCppRuntime { exe, provider: "cpu".to_string() })
 }
 
-async fn verify_whisper_exec(path: &Path) -> Result<(), String> {
-  let mut cmd = tokio::process::Command::new(path);
-  if let Some(dir) = path.parent() {
-    cmd.current_dir(dir);
-  }
-  let status = cmd
-    .arg("-h")
-    .stdout(std::process::Stdio::null())
-    .stderr(std::process::Stdio::null())
-    .status()
-    .await
-    .map_err(|e| format!("failed to start whisper-cli: {e}"))?;
-  if status.success() {
+// whisper.cpp GGML models published at huggingface.co/ggerganov/whisper.cpp, with approximate sizes (MiB)
+// used to reject truncated downloads.
+const WHISPER_CPP_MODELS: &[(&str, u64)] = &[
+  ("tiny", 75),
+  ("tiny.en", 75),
+  ("tiny-q5_1", 31),
+  ("tiny-q8_0", 42),
+  ("base", 142),
+  ("base.en", 142),
+  ("base-q5_1", 57),
+  ("base-q8_0", 78),
+  ("small", 466),
+  ("small.en", 466),
+  ("small-q5_1", 181),
+  ("small-q8_0", 252),
+  ("medium", 1500),
+  ("medium.en", 1500),
+  ("medium-q5_0", 514),
+  ("medium-q8_0", 785),
+  ("large-v1", 2900),
+  ("large-v2", 2900),
+  ("large-v2-q5_0", 1030),
+  ("large-v2-q8_0", 1500),
+  ("large-v3", 2900),
+  ("large-v3-q5_0", 1030),
+  ("large-v3-turbo", 1500),
+  ("large-v3-turbo-q5_0", 547),
+  ("large-v3-turbo-q8_0", 834),
+];
+
+// Prefer the quantized large-v3-turbo model for a good accuracy/size trade-off.
+const DEFAULT_WHISPER_CPP_MODEL: &str = "large-v3-turbo-q5_0";
+
+fn whisper_cpp_model_min_bytes(model: &str) -> Option<u64> {
+  WHISPER_CPP_MODELS
+    .iter()
+    .find(|(id, _)| *id == model)
+    .map(|(_, mib)| mib * 1024 * 1024 * 85 / 100)
+}
+
+// Checks the GGML ("lmgg" on disk) or GGUF magic so a wrong file fails before whisper-cli does.
+fn verify_ggml_file(path: &Path) -> Result<(), String> {
+  use std::io::Read;
+  let mut f = std::fs::File::open(path).map_err(|e| format!("open model {} failed: {e}", path.display()))?;
+  let mut magic = [0u8; 4];
+  f.read_exact(&mut magic)
+    .map_err(|e| format!("read model {} failed: {e}", path.display()))?;
+  if &magic == b"lmgg" || &magic == b"GGUF" {
     Ok(())
   } else {
-    Err("whisper-cli -h returned non-zero".to_string())
+    Err(format!("{} is not a GGML model file", path.display()))
   }
 }
 
-async fn install_sherpa_bundle(
+async fn ensure_whisper_cpp_model(
   app: &tauri::AppHandle,
-  root: &Path,
-  url: &str,
-  archive_file: &str,
+  state: &Arc<AppState>,
+  data_root: &Path,
   job_id: &str,
   media_id: &str,
-  progress_base: f32,
-  progress_span: f32,
-  label: &str,
-) -> Result<PathBuf, String> {
-  tokio::fs::create_dir_all(root)
+  model: &str,
+) -> Result<PathBuf, AppError> {
+  let _guard = state.tools_lock.lock().await;
+
+  let min_bytes = whisper_cpp_model_min_bytes(model)
+    .ok_or_else(|| AppError::InvalidInput(format!("unknown whisper.cpp model: {model}")))?;
+  let model_file = format!("ggml-{model}.bin");
+  let url = format!("https://huggingface.co/ggerganov/whisper.cpp/resolve/main/{model_file}");
+
+  // Reuse an existing model from other model roots (e.g. installed -> portable, or a shared cache).
+  for models_root in candidate_model_roots(app, data_root) {
+    let p = models_root.join("whispercpp").join(&model_file);
+    if tokio::fs::metadata(&p)
+      .await
+      .map(|m| m.len() > min_bytes)
+      .unwrap_or(false)
+    {
+      return Ok(p);
+    }
+  }
+
+  let model_dir = data_root.join("models").join("whispercpp");
+  tokio::fs::create_dir_all(&model_dir)
     .await
-    .map_err(|e| format!("create sherpa dir failed: {e}"))?;
+    .map_err(|e| AppError::Internal(format!("create whispercpp model dir failed: {e}")))?;
 
-  let archive_path = root.join(archive_file);
+  let model_path = model_dir.join(&model_file);
+  // (If a partially downloaded file exists here, we re-download into this path.)
 
   let job_id_s = job_id.to_string();
   let media_id_s = media_id.to_string();
-  let archive_label = archive_file.to_string();
-
-  http_download_to_file_with_progress(url, &archive_path, move |done, total| {
+  let model_s = model.to_string();
+  http_download_to_file_with_progress(&url, &model_path, move |done, total| {
     let p = match total {
       Some(t) if t > 0 => {
         let frac = (done as f32 / t as f32).clamp(0.0, 1.0);
-        progress_base + frac * progress_span
+        0.18 + frac * 0.08
       }
-      _ => progress_base,
+      _ => 0.18,
     };
     let msg = match total {
-      Some(t) => format!(
-        "downloading {label} ({archive_label}) {} / {}",
-        human_bytes(done),
-        human_bytes(t)
-      ),
-      None => format!("downloading {label} ({archive_label}, {})", human_bytes(done)),
+      Some(t) => format!("downloading Whisper model {model_s} {} / {}", human_bytes(done), human_bytes(t)),
+      None => format!("downloading Whisper model {model_s} ({})", human_bytes(done)),
     };
     let _ = emit_job(app, JobProgressEvent {
       job_id: job_id_s.clone(),
@@ -4028,521 +8508,832 @@ async fn install_sherpa_bundle(
       status: JobStatus::Running,
       progress: p,
       message: Some(msg),
+      error_code: None,
     });
   })
   .await?;
 
-  let _ = emit_job(app, JobProgressEvent {
-    job_id: job_id.to_string(),
-    media_id: media_id.to_string(),
-    job_type: JobType::Transcribe,
-    status: JobStatus::Running,
-    progress: progress_base + progress_span,
-    message: Some(format!("extracting {label}")),
-  });
-
-  let tmp_extract = root.join(".extract");
-  let _ = tokio::fs::remove_dir_all(&tmp_extract).await;
-  tokio::fs::create_dir_all(&tmp_extract)
-    .await
-    .map_err(|e| format!("create extract dir failed: {e}"))?;
+  Ok(model_path)
+}
 
-  let archive_path_clone = archive_path.clone();
-  let tmp_extract_clone = tmp_extract.clone();
-  tokio::task::spawn_blocking(move || extract_tar_bz2_to_dir(&archive_path_clone, &tmp_extract_clone))
-    .await
-    .map_err(|e| format!("join extract task failed: {e}"))??;
+// Model folders under each candidate model root, with the kind reported by list_local_models.
+const LOCAL_MODEL_DIRS: &[(&str, &str)] = &[
+  ("whispercpp", "whisper_cpp"),
+  ("sensevoice", "sensevoice"),
+  ("diarization", "diarization"),
+  ("vad", "vad"),
+];
 
-  let exe_name = sidecar_basename("sherpa-onnx-offline");
-  let found = find_file_recursive(&tmp_extract, &exe_name)
-    .ok_or_else(|| format!("{label} missing {exe_name} after extraction"))?;
-  let Some(bin_dir) = found.parent() else {
-    return Err(format!("invalid {label} layout"));
+fn path_size(p: &Path) -> u64 {
+  let Ok(meta) = std::fs::symlink_metadata(p) else {
+    return 0;
   };
+  if !meta.is_dir() {
+    return meta.len();
+  }
+  std::fs::read_dir(p)
+    .map(|rd| rd.flatten().map(|e| path_size(&e.path())).sum())
+    .unwrap_or(0)
+}
 
-  // Move the bin dir into a stable location so DLL lookup works.
-  let release_dir = root.join("Release");
-  let _ = tokio::fs::remove_dir_all(&release_dir).await;
-  tokio::fs::create_dir_all(&release_dir)
-    .await
-    .map_err(|e| format!("create Release dir failed: {e}"))?;
+#[derive(Debug, Clone, serde::Serialize)]
+struct LocalModel {
+  kind: &'static str,
+  id: String,
+  name: String,
+  path: String,
+  size_bytes: u64,
+  is_dir: bool,
+  root: String,
+}
 
-  // Try fast path: rename the whole bin dir.
-  if tokio::fs::rename(bin_dir, &release_dir).await.is_err() {
-    // Fallback: copy files.
-    let mut rd = tokio::fs::read_dir(bin_dir)
-      .await
-      .map_err(|e| format!("read extracted bin dir failed: {e}"))?;
-    while let Some(ent) = rd.next_entry().await.map_err(|e| format!("read dir entry failed: {e}"))? {
-      let p = ent.path();
-      if !p.is_file() {
+fn scan_local_models(roots: &[PathBuf]) -> Vec<LocalModel> {
+  let mut out = Vec::new();
+  for root in roots {
+    for (sub, kind) in LOCAL_MODEL_DIRS {
+      let Ok(rd) = std::fs::read_dir(root.join(sub)) else {
         continue;
-      }
-      if let Some(name) = p.file_name() {
-        let _ = tokio::fs::copy(&p, release_dir.join(name)).await;
+      };
+      for entry in rd.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        // Skip in-progress downloads and extraction scratch dirs.
+        if name.starts_with('.') || name.ends_with(".part") || name.ends_with(".tar.bz2") {
+          continue;
+        }
+        let id = if *kind == "whisper_cpp" {
+          name.trim_start_matches("ggml-").trim_end_matches(".bin").to_string()
+        } else {
+          name.clone()
+        };
+        out.push(LocalModel {
+          kind,
+          id,
+          name,
+          path: path.to_string_lossy().to_string(),
+          size_bytes: path_size(&path),
+          is_dir: path.is_dir(),
+          root: root.to_string_lossy().to_string(),
+        });
       }
     }
   }
+  out
+}
 
-  let _ = tokio::fs::remove_dir_all(&tmp_extract).await;
-  let _ = tokio::fs::remove_file(&archive_path).await;
+#[tauri::command]
+async fn list_local_models(app: tauri::AppHandle, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
+
+  let roots = candidate_model_roots(&app, dir);
+  let roots_clone = roots.clone();
+  let models = tokio::task::spawn_blocking(move || scan_local_models(&roots_clone))
+    .await
+    .map_err(|e| AppError::Internal(format!("join model scan failed: {e}")))?;
+  let total: u64 = models.iter().map(|m| m.size_bytes).sum();
+
+  let catalog: Vec<serde_json::Value> = WHISPER_CPP_MODELS
+    .iter()
+    .map(|(id, mib)| serde_json::json!({ "id": id, "size_bytes": mib * 1024 * 1024 }))
+    .collect();
+
+  Ok(serde_json::json!({
+    "roots": roots.iter().map(|p| p.to_string_lossy().to_string()).collect::<Vec<_>>(),
+    "models": models,
+    "total_bytes": total,
+    "whisper_cpp_catalog": catalog,
+    "whisper_cpp_default": DEFAULT_WHISPER_CPP_MODEL,
+  }))
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeleteLocalModelArgs {
+  path: String,
+}
+
+#[tauri::command]
+async fn delete_local_model(
+  app: tauri::AppHandle,
+  args: DeleteLocalModelArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
+
+  let entries = local_model_entries(&app, dir).await;
+  let entry = find_managed_entry(&entries, &args.path)
+    .ok_or_else(|| AppError::InvalidInput("path is not a local model".to_string()))?;
+  delete_managed_entry(state.inner(), entry).await
+}
+
+// ==================== Tool/model manager ====================
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct ManagedToolEntry {
+  tool: String,
+  variant: Option<String>,
+  version: Option<String>,
+  path: String,
+  size_bytes: u64,
+  verified: bool,
+  /// "downloaded" (under a tools/model root), "bundled" (app sidecar) or "system".
+  source: String,
+  #[serde(skip)]
+  remove_paths: Vec<PathBuf>,
+}
 
-  let exe = release_dir.join(exe_name);
-  if !exe.is_file() {
-    return Err(format!("{label} install failed: executable missing"));
+impl ManagedToolEntry {
+  fn deletable(&self) -> bool {
+    !self.remove_paths.is_empty()
   }
-  verify_sherpa_exec(&exe).await?;
-  Ok(exe)
 }
 
-async fn install_whisper_cpp_bundle(
-  app: &tauri::AppHandle,
-  root: &Path,
-  url: &str,
-  archive_file: &str,
-  job_id: &str,
-  media_id: &str,
-  progress_base: f32,
-  progress_span: f32,
-  label: &str,
-) -> Result<PathBuf, String> {
-  tokio::fs::create_dir_all(root)
+async fn tool_version_line(exe: &Path, arg: &str) -> Option<String> {
+  let mut cmd = tokio::process::Command::new(exe);
+  cmd.kill_on_drop(true).arg(arg);
+  if let Some(dir) = exe.parent() {
+    cmd.current_dir(dir);
+  }
+  let out = tokio::time::timeout(std::time::Duration::from_secs(15), cmd.output())
     .await
-    .map_err(|e| format!("create whisper.cpp dir failed: {e}"))?;
+    .ok()?
+    .ok()?;
+  if !out.status.success() {
+    return None;
+  }
+  String::from_utf8_lossy(&out.stdout)
+    .lines()
+    .map(str::trim)
+    .find(|l| !l.is_empty())
+    .map(|l| l.to_string())
+}
 
-  let archive_path = root.join(archive_file);
-  let job_id_s = job_id.to_string();
-  let media_id_s = media_id.to_string();
-  let archive_label = archive_file.to_string();
+fn is_under_any(path: &Path, roots: &[PathBuf]) -> bool {
+  roots.iter().any(|r| path.starts_with(r))
+}
 
-  http_download_to_file_with_progress(url, &archive_path, move |done, total| {
-    let p = match total {
-      Some(t) if t > 0 => {
-        let frac = (done as f32 / t as f32).clamp(0.0, 1.0);
-        progress_base + frac * progress_span
+// Runtime bundles are laid out as <bin>/<dir>/<variant>/<version>/Release/<exe>.
+async fn inventory_runtime_bundles(bin_dirs: &[PathBuf], tool: &str, dir: &str, exe: &str) -> Vec<ManagedToolEntry> {
+  let exe_name = sidecar_basename(exe);
+  let mut out = Vec::new();
+  for bin_dir in bin_dirs {
+    for variant in ["cpu", "cuda"] {
+      let Ok(rd) = std::fs::read_dir(bin_dir.join(dir).join(variant)) else {
+        continue;
+      };
+      for entry in rd.flatten() {
+        let root = entry.path();
+        let exe_path = root.join("Release").join(&exe_name);
+        if !exe_path.is_file() {
+          continue;
+        }
+        let verified = if tool == "sherpa_onnx" {
+          verify_sherpa_exec(&exe_path).await.is_ok()
+        } else {
+          verify_whisper_exec(&exe_path).await.is_ok()
+        };
+        out.push(ManagedToolEntry {
+          tool: tool.to_string(),
+          variant: Some(variant.to_string()),
+          version: Some(entry.file_name().to_string_lossy().to_string()),
+          path: exe_path.to_string_lossy().to_string(),
+          size_bytes: path_size(&root),
+          verified,
+          source: "downloaded".to_string(),
+          remove_paths: vec![root],
+        });
       }
-      _ => progress_base,
-    };
-    let msg = match total {
-      Some(t) => format!(
-        "downloading {label} ({archive_label}) {} / {}",
-        human_bytes(done),
-        human_bytes(t)
-      ),
-      None => format!("downloading {label} ({archive_label}, {})", human_bytes(done)),
-    };
-    let _ = emit_job(app, JobProgressEvent {
-      job_id: job_id_s.clone(),
-      media_id: media_id_s.clone(),
-      job_type: JobType::Transcribe,
-      status: JobStatus::Running,
-      progress: p,
-      message: Some(msg),
-    });
-  })
-  .await?;
+    }
+  }
+  out
+}
 
-  let _ = emit_job(app, JobProgressEvent {
-    job_id: job_id.to_string(),
-    media_id: media_id.to_string(),
-    job_type: JobType::Transcribe,
-    status: JobStatus::Running,
-    progress: progress_base + progress_span,
-    message: Some(format!("extracting {label}")),
-  });
+async fn inventory_tools(app: &tauri::AppHandle, data_root: &Path) -> Vec<ManagedToolEntry> {
+  let bin_dirs = candidate_tools_bin_dirs(app, data_root);
+  let mut out: Vec<ManagedToolEntry> = Vec::new();
 
-  let tmp_extract = root.join(".extract");
-  let _ = tokio::fs::remove_dir_all(&tmp_extract).await;
-  tokio::fs::create_dir_all(&tmp_extract)
-    .await
-    .map_err(|e| format!("create extract dir failed: {e}"))?;
+  // ffmpeg/ffprobe and yt-dlp: downloaded copies in each bin dir, else the bundled sidecar.
+  for (tool, companions, version_arg) in [("ffmpeg", vec!["ffmpeg", "ffprobe"], "-version"), ("yt-dlp", vec!["yt-dlp"], "--version")] {
+    let mut found = false;
+    for bin_dir in &bin_dirs {
+      let exe = bin_dir.join(sidecar_basename(tool));
+      if !exe.is_file() {
+        continue;
+      }
+      found = true;
+      let files: Vec<PathBuf> = companions.iter().map(|n| bin_dir.join(sidecar_basename(n))).filter(|p| p.is_file()).collect();
+      let version = if is_probably_stub_binary(&exe) { None } else { tool_version_line(&exe, version_arg).await };
+      out.push(ManagedToolEntry {
+        tool: tool.to_string(),
+        variant: None,
+        verified: version.is_some() && files.iter().all(|p| !is_probably_stub_binary(p)),
+        version,
+        path: exe.to_string_lossy().to_string(),
+        size_bytes: files.iter().map(|p| path_size(p)).sum(),
+        source: "downloaded".to_string(),
+        remove_paths: files,
+      });
+    }
+    if !found {
+      if let Ok(exe) = resolve_sidecar(app, tool) {
+        let version = tool_version_line(&exe, version_arg).await;
+        out.push(ManagedToolEntry {
+          tool: tool.to_string(),
+          variant: None,
+          verified: version.is_some(),
+          version,
+          path: exe.to_string_lossy().to_string(),
+          size_bytes: path_size(&exe),
+          source: "bundled".to_string(),
+          remove_paths: Vec::new(),
+        });
+      }
+    }
+  }
 
-  let archive_path_clone = archive_path.clone();
-  let tmp_extract_clone = tmp_extract.clone();
-  tokio::task::spawn_blocking(move || extract_zip_to_dir(&archive_path_clone, &tmp_extract_clone))
-    .await
-    .map_err(|e| format!("join extract task failed: {e}"))??;
+  out.extend(inventory_runtime_bundles(&bin_dirs, "sherpa_onnx", "sherpa_onnx", "sherpa-onnx-offline").await);
+  out.extend(inventory_runtime_bundles(&bin_dirs, "whisper_cpp", "whisper_cpp", "whisper-cli").await);
+  if !cfg!(windows) {
+    if let Ok(rt) = resolve_unix_whisper_cpp(app, data_root, true).await {
+      if !is_under_any(&rt.exe, &bin_dirs) {
+        out.push(ManagedToolEntry {
+          tool: "whisper_cpp".to_string(),
+          variant: None,
+          version: None,
+          path: rt.exe.to_string_lossy().to_string(),
+          size_bytes: path_size(&rt.exe),
+          verified: true,
+          source: "system".to_string(),
+          remove_paths: Vec::new(),
+        });
+      }
+    }
+  }
 
-  let exe_name = sidecar_basename("whisper-cli");
-  let found = find_file_recursive(&tmp_extract, &exe_name)
-    .ok_or_else(|| format!("{label} missing {exe_name} after extraction"))?;
-  let Some(bin_dir) = found.parent() else {
-    return Err(format!("invalid {label} layout"));
-  };
+  out.extend(local_model_entries(app, data_root).await);
+  out
+}
 
-  // Move the whole directory next to the executable into a stable location so DLL lookup works.
-  let release_dir = root.join("Release");
-  let _ = tokio::fs::remove_dir_all(&release_dir).await;
-  tokio::fs::create_dir_all(&release_dir)
-    .await
-    .map_err(|e| format!("create Release dir failed: {e}"))?;
+async fn local_model_entries(app: &tauri::AppHandle, data_root: &Path) -> Vec<ManagedToolEntry> {
+  let roots = candidate_model_roots(app, data_root);
+  let models = tokio::task::spawn_blocking(move || scan_local_models(&roots)).await.unwrap_or_default();
+  models
+    .into_iter()
+    .map(|m| {
+      let path = PathBuf::from(&m.path);
+      let verified = match m.kind {
+        "whisper_cpp" => {
+          whisper_cpp_model_min_bytes(&m.id).map(|min| m.size_bytes > min).unwrap_or(true) && verify_ggml_file(&path).is_ok()
+        }
+        "sensevoice" => path.join("model.onnx").is_file() && path.join("tokens.txt").is_file(),
+        "diarization" if m.is_dir => path.join("model.onnx").is_file(),
+        _ => m.size_bytes > 0,
+      };
+      ManagedToolEntry {
+        tool: if m.kind == "whisper_cpp" { "whisper_model".to_string() } else { m.kind.to_string() },
+        variant: Some(m.id),
+        version: None,
+        path: m.path,
+        size_bytes: m.size_bytes,
+        verified,
+        source: "downloaded".to_string(),
+        remove_paths: vec![path],
+      }
+    })
+    .collect()
+}
 
-  if tokio::fs::rename(bin_dir, &release_dir).await.is_err() {
-    copy_dir_files_flat(bin_dir, &release_dir).await?;
+// Matches the listed path verbatim or, failing that, by canonical path.
+fn find_managed_entry<'a>(entries: &'a [ManagedToolEntry], path: &str) -> Option<&'a ManagedToolEntry> {
+  let path = path.trim();
+  if let Some(e) = entries.iter().find(|e| e.path == path) {
+    return Some(e);
   }
+  let wanted = std::fs::canonicalize(path).ok()?;
+  entries.iter().find(|e| std::fs::canonicalize(&e.path).ok().as_deref() == Some(wanted.as_path()))
+}
 
-  let _ = tokio::fs::remove_dir_all(&tmp_extract).await;
-  let _ = tokio::fs::remove_file(&archive_path).await;
-
-  let exe = release_dir.join(exe_name);
-  if !exe.is_file() {
-    return Err(format!("{label} install failed: executable missing"));
+async fn delete_managed_entry(state: &Arc<AppState>, entry: &ManagedToolEntry) -> Result<serde_json::Value, AppError> {
+  if !entry.deletable() {
+    return Err(AppError::InvalidInput(format!("{} is {} and cannot be deleted here", entry.tool, entry.source)));
   }
-  verify_whisper_exec(&exe).await?;
-  Ok(exe)
+  // Don't pull a tool or model out from under a running download/extract.
+  let _guard = state.tools_lock.lock().await;
+  let freed = remove_tool_entry(entry).await.map_err(AppError::Internal)?;
+  Ok(serde_json::json!({ "tool": entry.tool, "path": entry.path, "freed_bytes": freed }))
 }
 
-async fn copy_dir_files_flat(src_dir: &Path, dest_dir: &Path) -> Result<(), String> {
-  let mut rd = tokio::fs::read_dir(src_dir)
-    .await
-    .map_err(|e| format!("read extracted dir failed: {e}"))?;
-  while let Some(ent) = rd
-    .next_entry()
-    .await
-    .map_err(|e| format!("read dir entry failed: {e}"))?
-  {
-    let p = ent.path();
-    if !p.is_file() {
-      continue;
-    }
-    if let Some(name) = p.file_name() {
-      tokio::fs::copy(&p, dest_dir.join(name))
-        .await
-        .map_err(|e| format!("copy file failed: {e}"))?;
-    }
+async fn remove_tool_entry(entry: &ManagedToolEntry) -> Result<u64, String> {
+  for p in &entry.remove_paths {
+    let res = if p.is_dir() {
+      tokio::fs::remove_dir_all(p).await
+    } else {
+      tokio::fs::remove_file(p).await
+    };
+    res.map_err(|e| format!("delete {} failed: {e}", p.display()))?;
   }
-  Ok(())
+  Ok(entry.size_bytes)
 }
 
-async fn ensure_sherpa_onnx_offline(
-  app: &tauri::AppHandle,
-  state: &Arc<AppState>,
-  data_root: &Path,
-  job_id: &str,
-  media_id: &str,
-  allow_cuda: bool,
-  require_cuda: bool,
-) -> Result<SherpaOnnxRuntime, String> {
-  let _guard = state.tools_lock.lock().await;
+#[tauri::command]
+async fn list_tools(app: tauri::AppHandle, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
 
-  if cfg!(windows) && std::env::consts::ARCH != "x86_64" {
-    return Err("sherpa-onnx local transcription currently supports Windows x64 only".to_string());
-  }
+  let entries = inventory_tools(&app, dir).await;
+  let tools: Vec<serde_json::Value> = entries
+    .iter()
+    .map(|e| {
+      let mut v = serde_json::to_value(e).unwrap_or_default();
+      v["deletable"] = serde_json::Value::Bool(e.deletable());
+      v
+    })
+    .collect();
+  Ok(serde_json::json!({
+    "tools": tools,
+    "total_bytes": entries.iter().filter(|e| e.deletable()).map(|e| e.size_bytes).sum::<u64>(),
+  }))
+}
 
-  let has_cuda = allow_cuda && has_nvidia_cuda_driver();
-  if require_cuda && !has_cuda {
-    return Err("CUDA requested, but NVIDIA driver (nvcuda.dll) not found. Set localAccelerator=cpu/auto or install NVIDIA driver.".to_string());
-  }
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeleteToolArgs {
+  /// `path` of an entry returned by list_tools.
+  path: String,
+}
 
-  const VER: &str = "v1.12.23";
-  const WIN_X64_CPU: &str = "https://github.com/k2-fsa/sherpa-onnx/releases/download/v1.12.23/sherpa-onnx-v1.12.23-win-x64-shared.tar.bz2";
-  const WIN_X64_CUDA: &str = "https://github.com/k2-fsa/sherpa-onnx/releases/download/v1.12.23/sherpa-onnx-v1.12.23-cuda-12.x-cudnn-9.x-win-x64-cuda.tar.bz2";
+#[tauri::command]
+async fn delete_tool(
+  app: tauri::AppHandle,
+  args: DeleteToolArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
 
-  let exe_name = sidecar_basename("sherpa-onnx-offline");
+  let entries = inventory_tools(&app, dir).await;
+  let entry = find_managed_entry(&entries, &args.path)
+    .ok_or_else(|| AppError::InvalidInput("tool not found".to_string()))?;
+  delete_managed_entry(state.inner(), entry).await
+}
 
-  // Reuse previously downloaded runtimes from other data roots (e.g. installed -> portable).
-  for bin_dir in candidate_tools_bin_dirs(app, data_root) {
-    let base_root = bin_dir.join("sherpa_onnx");
-    let cuda_root = base_root.join("cuda").join(VER);
-    let cuda_exe = cuda_root.join("Release").join(&exe_name);
-    let cpu_root = base_root.join("cpu").join(VER);
-    let cpu_exe = cpu_root.join("Release").join(&exe_name);
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstallToolArgs {
+  /// ffmpeg, yt-dlp, sherpa_onnx, whisper_cpp, sensevoice, whisper_model, diarization or vad.
+  tool: String,
+  /// cpu/cuda for runtimes, model id for whisper_model.
+  #[serde(default)]
+  variant: Option<String>,
+  /// Delete this data root's copy first.
+  #[serde(default)]
+  reinstall: bool,
+}
 
-    if has_cuda {
-      if cuda_exe.is_file() && verify_sherpa_exec(&cuda_exe).await.is_ok() {
-        let _ = emit_job(app, JobProgressEvent {
-          job_id: job_id.to_string(),
-          media_id: media_id.to_string(),
-          job_type: JobType::Transcribe,
-          status: JobStatus::Running,
-          progress: 0.18,
-          message: Some("using sherpa-onnx (CUDA)".to_string()),
-        });
-        return Ok(SherpaOnnxRuntime { exe: cuda_exe, provider: "cuda".to_string() });
-      }
-    }
+#[tauri::command]
+async fn install_tool(
+  app: tauri::AppHandle,
+  args: InstallToolArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
 
-    if cpu_exe.is_file() && verify_sherpa_exec(&cpu_exe).await.is_ok() {
-      return Ok(SherpaOnnxRuntime { exe: cpu_exe, provider: "cpu".to_string() });
-    }
-  }
+  let tool = args.tool.trim().to_string();
+  let variant = args.variant.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(|v| v.to_string());
+  let job_type = match tool.as_str() {
+    "ffmpeg" | "yt-dlp" => JobType::Download,
+    "sherpa_onnx" | "whisper_cpp" | "sensevoice" | "whisper_model" | "diarization" | "vad" => JobType::Transcribe,
+    other => return Err(AppError::InvalidInput(format!("unknown tool: {other}"))),
+  };
 
-  let base_root = tools_bin_dir(data_root).join("sherpa_onnx");
-  tokio::fs::create_dir_all(&base_root)
-    .await
-    .map_err(|e| format!("create sherpa root dir failed: {e}"))?;
+  let job_id = new_job_id();
+  let media_id = String::new();
+  let _ = emit_job(&app, JobProgressEvent {
+    job_id: job_id.clone(),
+    media_id: media_id.clone(),
+    job_type: job_type.clone(),
+    status: JobStatus::Running,
+    progress: 0.0,
+    message: Some(format!("installing {tool}")),
+    error_code: None,
+  });
 
-  let cuda_root = base_root.join("cuda").join(VER);
-  let cuda_exe = cuda_root.join("Release").join(&exe_name);
-  let cpu_root = base_root.join("cpu").join(VER);
-  let cpu_exe = cpu_root.join("Release").join(&exe_name);
+  let result: Result<serde_json::Value, AppError> = run_job(&app, &job_id, async {
+    if args.reinstall {
+      let own_roots = [tools_bin_dir(dir), dir.join("models")];
+      let entries = inventory_tools(&app, dir).await;
+      let _guard = state.tools_lock.lock().await;
+      for e in entries.iter().filter(|e| {
+        let tool_matches = e.tool == tool && (variant.is_none() || e.variant == variant);
+        tool_matches && e.deletable() && is_under_any(Path::new(&e.path), &own_roots)
+      }) {
+        remove_tool_entry(e).await.map_err(AppError::Internal)?;
+      }
+    }
+
+    let cuda = variant.as_deref() == Some("cuda");
+    let path: PathBuf = match tool.as_str() {
+      "ffmpeg" => {
+        ensure_ffmpeg_bundle_with_job(&app, state.inner(), dir, Some((&job_id, &media_id, JobType::Download, 0.0, 0.95)))
+          .await?
+          .0
+      }
+      "yt-dlp" => ensure_ytdlp(&app, state.inner(), dir).await?,
+      "sherpa_onnx" => ensure_sherpa_onnx_offline(&app, state.inner(), dir, &job_id, &media_id, cuda, cuda).await?.exe,
+      "whisper_cpp" => ensure_whisper_cpp(&app, state.inner(), dir, &job_id, &media_id, cuda, cuda).await?.exe,
+      "sensevoice" => ensure_sense_voice_model(&app, state.inner(), dir, &job_id, &media_id).await?.0,
+      "whisper_model" => {
+        let model = variant.as_deref().unwrap_or(DEFAULT_WHISPER_CPP_MODEL);
+        ensure_whisper_cpp_model(&app, state.inner(), dir, &job_id, &media_id, model).await?
+      }
+      "diarization" => ensure_diarization_models(&app, state.inner(), dir, &job_id, &media_id).await?.0,
+      _ => ensure_silero_vad_model(&app, state.inner(), dir).await?,
+    };
 
-  // Resolution strategy:
-  // - If NVIDIA driver exists and user did not force CPU: auto-download CUDA runtime and use it.
-  // - Otherwise: use/install CPU.
-  if has_cuda {
-    if cuda_exe.is_file() && verify_sherpa_exec(&cuda_exe).await.is_ok() {
-      let _ = emit_job(app, JobProgressEvent {
-        job_id: job_id.to_string(),
-        media_id: media_id.to_string(),
-        job_type: JobType::Transcribe,
-        status: JobStatus::Running,
-        progress: 0.18,
-        message: Some("using sherpa-onnx (CUDA)".to_string()),
+    Ok(serde_json::json!({
+      "job_id": job_id,
+      "tool": tool,
+      "variant": variant,
+      "path": path.to_string_lossy().to_string(),
+    }))
+  })
+  .await;
+
+  match result {
+    Ok(payload) => {
+      let _ = emit_job(&app, JobProgressEvent {
+        job_id: job_id.clone(),
+        media_id: media_id.clone(),
+        job_type,
+        status: JobStatus::Succeeded,
+        progress: 1.0,
+        message: None,
+        error_code: None,
+      });
+      Ok(payload)
+    }
+    Err(e) => {
+      let _ = emit_job(&app, JobProgressEvent {
+        job_id: job_id.clone(),
+        media_id: media_id.clone(),
+        job_type,
+        status: JobStatus::Failed,
+        progress: 1.0,
+        message: Some(e.to_string()),
+        error_code: Some(e.code()),
       });
-      return Ok(SherpaOnnxRuntime { exe: cuda_exe, provider: "cuda".to_string() });
+      Err(e)
     }
-
-    // Auto mode (or explicit CUDA): install CUDA bundle.
-    let exe = install_sherpa_bundle(
-      app,
-      &cuda_root,
-      WIN_X64_CUDA,
-      "sherpa-onnx-cuda.tar.bz2",
-      job_id,
-      media_id,
-      0.12,
-      0.06,
-      "sherpa-onnx runtime (CUDA)",
-    )
-    .await?;
-    let _ = emit_job(app, JobProgressEvent {
-      job_id: job_id.to_string(),
-      media_id: media_id.to_string(),
-      job_type: JobType::Transcribe,
-      status: JobStatus::Running,
-      progress: 0.18,
-      message: Some("using sherpa-onnx (CUDA)".to_string()),
-    });
-    return Ok(SherpaOnnxRuntime { exe, provider: "cuda".to_string() });
-  }
-
-  if cpu_exe.is_file() && verify_sherpa_exec(&cpu_exe).await.is_ok() {
-    return Ok(SherpaOnnxRuntime { exe: cpu_exe, provider: "cpu".to_string() });
   }
-
-  let exe = install_sherpa_bundle(
-    app,
-    &cpu_root,
-    WIN_X64_CPU,
-    "sherpa-onnx-cpu.tar.bz2",
-    job_id,
-    media_id,
-    0.12,
-    0.06,
-    "sherpa-onnx runtime",
-  )
-  .await?;
-  Ok(SherpaOnnxRuntime { exe, provider: "cpu".to_string() })
 }
 
-async fn ensure_whisper_cpp(
-  app: &tauri::AppHandle,
-  state: &Arc<AppState>,
-  data_root: &Path,
-  job_id: &str,
-  media_id: &str,
-  allow_cuda: bool,
-  require_cuda: bool,
-) -> Result<WhisperCppRuntime, String> {
-  let _guard = state.tools_lock.lock().await;
+// ==================== Offline bundles ====================
 
-  if !cfg!(windows) {
-    return Err("whisper.cpp local transcription currently supports Windows only".to_string());
-  }
-  if std::env::consts::ARCH != "x86_64" {
-    return Err("whisper.cpp local transcription currently supports Windows x64 only".to_string());
+// A bundle mirrors the data root layout: `bin/...` for tools and `models/...` for models,
+// either at the archive root or inside a single top-level folder.
+fn find_offline_bundle_root(dir: &Path) -> Option<PathBuf> {
+  let is_root = |p: &Path| p.join("bin").is_dir() || p.join("models").is_dir();
+  if is_root(dir) {
+    return Some(dir.to_path_buf());
   }
+  std::fs::read_dir(dir)
+    .ok()?
+    .flatten()
+    .map(|e| e.path())
+    .find(|p| p.is_dir() && is_root(p))
+}
 
-  let has_cuda = allow_cuda && has_nvidia_cuda_driver();
-  if require_cuda && !has_cuda {
-    return Err("CUDA requested, but NVIDIA driver (nvcuda.dll) not found. Set localAccelerator=cpu/auto or install NVIDIA driver.".to_string());
+fn copy_tree(src: &Path, dest: &Path, executable: bool, copied: &mut Vec<PathBuf>) -> Result<(), String> {
+  std::fs::create_dir_all(dest).map_err(|e| format!("create dir failed: {e}"))?;
+  let rd = std::fs::read_dir(src).map_err(|e| format!("read bundle dir failed: {e}"))?;
+  for entry in rd.flatten() {
+    let p = entry.path();
+    let out = dest.join(entry.file_name());
+    if p.is_dir() {
+      copy_tree(&p, &out, executable, copied)?;
+    } else if p.is_file() {
+      std::fs::copy(&p, &out).map_err(|e| format!("copy {} failed: {e}", p.display()))?;
+      if executable {
+        set_executable(&out)?;
+      }
+      copied.push(out);
+    }
   }
+  Ok(())
+}
 
-  const VER: &str = "v1.8.3";
-  const WIN_X64_CPU: &str = "https://github.com/ggml-org/whisper.cpp/releases/download/v1.8.3/whisper-blas-bin-x64.zip";
-  const WIN_X64_CUDA_12_4: &str = "https://github.com/ggml-org/whisper.cpp/releases/download/v1.8.3/whisper-cublas-12.4.0-bin-x64.zip";
-  const WIN_X64_CUDA_11_8: &str = "https://github.com/ggml-org/whisper.cpp/releases/download/v1.8.3/whisper-cublas-11.8.0-bin-x64.zip";
-
-  let exe_name = sidecar_basename("whisper-cli");
-
-  // Reuse previously downloaded runtimes from other data roots (e.g. installed -> portable).
-  for bin_dir in candidate_tools_bin_dirs(app, data_root) {
-    let base_root = bin_dir.join("whisper_cpp");
-    let cuda_root = base_root.join("cuda").join(VER);
-    let cuda_exe = cuda_root.join("Release").join(&exe_name);
-    let cpu_root = base_root.join("cpu").join(VER);
-    let cpu_exe = cpu_root.join("Release").join(&exe_name);
-
-    if has_cuda {
-      if cuda_exe.is_file() && verify_whisper_exec(&cuda_exe).await.is_ok() {
-        let _ = emit_job(app, JobProgressEvent {
-          job_id: job_id.to_string(),
-          media_id: media_id.to_string(),
-          job_type: JobType::Transcribe,
-          status: JobStatus::Running,
-          progress: 0.18,
-          message: Some("using whisper.cpp (CUDA)".to_string()),
-        });
-        return Ok(WhisperCppRuntime { exe: cuda_exe, provider: "cuda".to_string() });
+// Moves staged files to the same relative path under `dest_root`. If any move fails, the ones
+// already done are undone and whatever they replaced is put back.
+fn commit_staged_files(stage: &Path, dest_root: &Path, files: &[PathBuf]) -> Result<(), String> {
+  let backup_ext = format!(".bundle-backup-{}", nanoid());
+  let mut done: Vec<(PathBuf, Option<PathBuf>)> = Vec::new();
+  let mut failed = None;
+  for src in files {
+    let step = (|| {
+      let rel = src.strip_prefix(stage).map_err(|_| format!("staged file outside stage: {}", src.display()))?;
+      let dest = dest_root.join(rel);
+      if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("create dir failed: {e}"))?;
+      }
+      let backup = if dest.exists() {
+        let mut name = dest.file_name().unwrap_or_default().to_os_string();
+        name.push(&backup_ext);
+        let b = dest.with_file_name(name);
+        std::fs::rename(&dest, &b).map_err(|e| format!("move {} aside failed: {e}", dest.display()))?;
+        Some(b)
+      } else {
+        None
+      };
+      if let Err(e) = std::fs::rename(src, &dest) {
+        if let Some(b) = &backup {
+          let _ = std::fs::rename(b, &dest);
+        }
+        return Err(format!("install {} failed: {e}", dest.display()));
+      }
+      Ok((dest, backup))
+    })();
+    match step {
+      Ok(d) => done.push(d),
+      Err(e) => {
+        failed = Some(e);
+        break;
       }
     }
+  }
 
-    if cpu_exe.is_file() && verify_whisper_exec(&cpu_exe).await.is_ok() {
-      return Ok(WhisperCppRuntime { exe: cpu_exe, provider: "cpu".to_string() });
+  if let Some(e) = failed {
+    for (dest, backup) in done.into_iter().rev() {
+      let _ = std::fs::remove_file(&dest);
+      if let Some(b) = backup {
+        let _ = std::fs::rename(&b, &dest);
+      }
+    }
+    return Err(e);
+  }
+  for (_, backup) in done {
+    if let Some(b) = backup {
+      let _ = std::fs::remove_file(&b);
     }
   }
+  Ok(())
+}
 
-  let base_root = tools_bin_dir(data_root).join("whisper_cpp");
-  tokio::fs::create_dir_all(&base_root)
-    .await
-    .map_err(|e| format!("create whisper root dir failed: {e}"))?;
+async fn install_offline_bundle(
+  app: &tauri::AppHandle,
+  state: &Arc<AppState>,
+  data_root: &Path,
+  source: &Path,
+) -> Result<serde_json::Value, AppError> {
+  if !source.exists() {
+    return Err(AppError::InvalidInput(format!("bundle not found: {}", source.display())));
+  }
 
-  let cuda_root = base_root.join("cuda").join(VER);
-  let cuda_exe = cuda_root.join("Release").join(&exe_name);
-  let cpu_root = base_root.join("cpu").join(VER);
-  let cpu_exe = cpu_root.join("Release").join(&exe_name);
+  // Everything is copied into a staging data root and verified there first; only a fully
+  // verified bundle is moved into bin/ and models/.
+  let staging = data_root.join(format!(".offline-bundle-{}", nanoid()));
+  let stage = staging.join("stage");
+  let _guard = state.tools_lock.lock().await;
+  let result = async {
+    let extracted = if source.is_dir() {
+      source.to_path_buf()
+    } else {
+      let src = source.to_path_buf();
+      let dest = staging.join("extract");
+      tokio::task::spawn_blocking(move || extract_archive_to_dir(&src, &dest))
+        .await
+        .map_err(|e| AppError::Internal(format!("join extract task failed: {e}")))?
+        .map_err(AppError::Internal)?;
+      staging.join("extract")
+    };
+    let root = find_offline_bundle_root(&extracted)
+      .ok_or_else(|| AppError::InvalidInput("bundle has no bin/ or models/ folder".to_string()))?;
 
-  if has_cuda {
-    if cuda_exe.is_file() && verify_whisper_exec(&cuda_exe).await.is_ok() {
-      let _ = emit_job(app, JobProgressEvent {
-        job_id: job_id.to_string(),
-        media_id: media_id.to_string(),
-        job_type: JobType::Transcribe,
-        status: JobStatus::Running,
-        progress: 0.18,
-        message: Some("using whisper.cpp (CUDA)".to_string()),
-      });
-      return Ok(WhisperCppRuntime { exe: cuda_exe, provider: "cuda".to_string() });
+    let sums = std::fs::read_to_string(root.join(checksums::BUNDLE_SUMS_FILE)).ok();
+    let stage_bin = tools_bin_dir(&stage);
+    let stage_models = stage.join("models");
+    let copied = tokio::task::spawn_blocking(move || {
+      let mut copied = Vec::new();
+      if root.join("bin").is_dir() {
+        copy_tree(&root.join("bin"), &stage_bin, true, &mut copied)?;
+      }
+      if root.join("models").is_dir() {
+        copy_tree(&root.join("models"), &stage_models, false, &mut copied)?;
+      }
+      Ok::<_, String>(copied)
+    })
+    .await
+    .map_err(|e| AppError::Internal(format!("join copy task failed: {e}")))?
+    .map_err(AppError::Internal)?;
+    if copied.is_empty() {
+      return Err(AppError::InvalidInput("bundle contains no files".to_string()));
     }
 
-    // Auto mode (or explicit CUDA): try CUDA 12.4 first, then 11.8.
-    let exe = match install_whisper_cpp_bundle(
-      app,
-      &cuda_root,
-      WIN_X64_CUDA_12_4,
-      "whisper-cublas-12.4.0-bin-x64.zip",
-      job_id,
-      media_id,
-      0.12,
-      0.06,
-      "whisper.cpp runtime (CUDA 12.4)",
-    )
+    // Staged files are only hashed here, never run: the bundle is untrusted until it matches.
+    let files: Vec<(String, PathBuf)> = copied
+      .iter()
+      .filter_map(|p| {
+        let rel = p.strip_prefix(&stage).ok()?;
+        let rel: Vec<String> = rel.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+        Some((rel.join("/"), p.clone()))
+      })
+      .collect();
+    let local_manifest = std::fs::read_to_string(data_root.join(TOOL_HASHES_FILE)).unwrap_or_default();
+    let problems = tokio::task::spawn_blocking(move || {
+      checksums::verify_bundle_files(&files, sums.as_deref(), &[local_manifest.as_str(), PINNED_SHA256_MANIFEST])
+    })
     .await
-    {
-      Ok(exe) => Ok(exe),
-      Err(e12) => {
-        if require_cuda {
-          Err(e12)
-        } else {
-          install_whisper_cpp_bundle(
-            app,
-            &cuda_root,
-            WIN_X64_CUDA_11_8,
-            "whisper-cublas-11.8.0-bin-x64.zip",
-            job_id,
-            media_id,
-            0.12,
-            0.06,
-            "whisper.cpp runtime (CUDA 11.8)",
-          )
-          .await
-        }
-      }
-    }?;
-
-    let _ = emit_job(app, JobProgressEvent {
-      job_id: job_id.to_string(),
-      media_id: media_id.to_string(),
-      job_type: JobType::Transcribe,
-      status: JobStatus::Running,
-      progress: 0.18,
-      message: Some("using whisper.cpp (CUDA)".to_string()),
-    });
-    return Ok(WhisperCppRuntime { exe, provider: "cuda".to_string() });
+    .map_err(|e| AppError::Internal(format!("join verify task failed: {e}")))?;
+    if !problems.is_empty() {
+      return Err(AppError::ChecksumMismatch(format!(
+        "bundle failed verification, nothing installed: {}",
+        problems.join(", ")
+      )));
+    }
+
+    let stage_dir = stage.clone();
+    let dest_root = data_root.to_path_buf();
+    let count = copied.len();
+    tokio::task::spawn_blocking(move || commit_staged_files(&stage_dir, &dest_root, &copied))
+      .await
+      .map_err(|e| AppError::Internal(format!("join install task failed: {e}")))?
+      .map_err(AppError::Internal)?;
+    Ok::<_, AppError>(count)
   }
+  .await;
+  let _ = tokio::fs::remove_dir_all(&staging).await;
+  drop(_guard);
+  let files_copied = result?;
 
-  if cpu_exe.is_file() && verify_whisper_exec(&cpu_exe).await.is_ok() {
-    return Ok(WhisperCppRuntime { exe: cpu_exe, provider: "cpu".to_string() });
-  }
+  let own_roots = [tools_bin_dir(data_root), data_root.join("models")];
+  let entries: Vec<ManagedToolEntry> = inventory_tools(app, data_root)
+    .await
+    .into_iter()
+    .filter(|e| is_under_any(Path::new(&e.path), &own_roots))
+    .collect();
 
-  let exe = install_whisper_cpp_bundle(
-    app,
-    &cpu_root,
-    WIN_X64_CPU,
-    "whisper-blas-bin-x64.zip",
-    job_id,
-    media_id,
-    0.12,
-    0.06,
-    "whisper.cpp runtime",
-  )
-  .await?;
-  Ok(WhisperCppRuntime { exe, provider: "cpu".to_string() })
+  Ok(serde_json::json!({
+    "source": source.to_string_lossy().to_string(),
+    "files_copied": files_copied,
+    "tools": entries,
+  }))
 }
 
-async fn ensure_whisper_cpp_model(
+async fn install_offline_bundle_with_job(
   app: &tauri::AppHandle,
   state: &Arc<AppState>,
   data_root: &Path,
-  job_id: &str,
-  media_id: &str,
-) -> Result<PathBuf, String> {
-  let _guard = state.tools_lock.lock().await;
+  source: &Path,
+) -> Result<serde_json::Value, AppError> {
+  let job_id = new_job_id();
+  let media_id = String::new();
+  let _ = emit_job(app, JobProgressEvent {
+    job_id: job_id.clone(),
+    media_id: media_id.clone(),
+    job_type: JobType::Download,
+    status: JobStatus::Running,
+    progress: 0.0,
+    message: Some(format!("installing tools from {}", source.display())),
+    error_code: None,
+  });
 
-  // Prefer the quantized large-v3-turbo model for a good accuracy/size trade-off.
-  const MODEL_FILE: &str = "ggml-large-v3-turbo-q5_0.bin";
-  const URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3-turbo-q5_0.bin";
+  let result = run_job(app, &job_id, install_offline_bundle(app, state, data_root, source)).await;
 
-  // Reuse an existing model from other model roots (e.g. installed -> portable, or a shared cache).
-  for models_root in candidate_model_roots(app, data_root) {
-    let p = models_root.join("whispercpp").join(MODEL_FILE);
-    if tokio::fs::metadata(&p)
-      .await
-      .map(|m| m.len() > 300 * 1024 * 1024)
-      .unwrap_or(false)
-    {
-      return Ok(p);
+  let (status, message) = match &result {
+    Ok(_) => (JobStatus::Succeeded, None),
+    Err(e) => (JobStatus::Failed, Some(e.to_string())),
+  };
+  let _ = emit_job(app, JobProgressEvent {
+    job_id: job_id.clone(),
+    media_id,
+    job_type: JobType::Download,
+    status,
+    progress: 1.0,
+    message,
+    error_code: result.as_ref().err().map(|e| e.code()),
+  });
+  result.map(|mut v| {
+    v["job_id"] = serde_json::Value::String(job_id);
+    v
+  })
+}
+
+// Installs VECHO_OFFLINE_BUNDLE once per bundle file (keyed by path, size and mtime).
+fn install_offline_bundle_on_startup(app: &tauri::AppHandle, data_root: &Path) {
+  let Some(source) = env_path(OFFLINE_BUNDLE_ENV) else {
+    return;
+  };
+  let stamp = std::fs::metadata(&source).ok().map(|m| {
+    let mtime = m
+      .modified()
+      .ok()
+      .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+      .map(|d| d.as_secs())
+      .unwrap_or(0);
+    format!("{}|{}|{mtime}", source.display(), m.len())
+  });
+  let marker = data_root.join("offline-bundle.stamp");
+  if stamp.is_some() && std::fs::read_to_string(&marker).ok() == stamp {
+    return;
+  }
+
+  let app = app.clone();
+  let data_root = data_root.to_path_buf();
+  tauri::async_runtime::spawn(async move {
+    let state = app.state::<Arc<AppState>>().inner().clone();
+    if install_offline_bundle_with_job(&app, &state, &data_root, &source).await.is_ok() {
+      if let Some(stamp) = stamp {
+        let _ = std::fs::write(&marker, stamp);
+      }
     }
+  });
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstallToolsFromArchiveArgs {
+  /// .zip/.tar/.tar.gz/.tar.bz2/.tar.xz archive, or an already unpacked folder.
+  path: String,
+}
+
+#[tauri::command]
+async fn install_tools_from_archive(
+  app: tauri::AppHandle,
+  args: InstallToolsFromArchiveArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
+
+  let source = PathBuf::from(args.path.trim());
+  if args.path.trim().is_empty() {
+    return Err(AppError::InvalidInput("path is required".to_string()));
   }
+  install_offline_bundle_with_job(&app, state.inner(), dir, &source).await
+}
 
-  let model_dir = data_root.join("models").join("whispercpp");
-  tokio::fs::create_dir_all(&model_dir)
-    .await
-    .map_err(|e| format!("create whispercpp model dir failed: {e}"))?;
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetOfflineModeArgs {
+  enabled: bool,
+}
 
-  let model_path = model_dir.join(MODEL_FILE);
-  // (If a partially downloaded file exists here, we re-download into this path.)
+#[tauri::command]
+async fn get_offline_mode(app: tauri::AppHandle, state: State<'_, Arc<AppState>>) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
 
-  let job_id_s = job_id.to_string();
-  let media_id_s = media_id.to_string();
-  http_download_to_file_with_progress(URL, &model_path, move |done, total| {
-    let p = match total {
-      Some(t) if t > 0 => {
-        let frac = (done as f32 / t as f32).clamp(0.0, 1.0);
-        0.18 + frac * 0.08
-      }
-      _ => 0.18,
-    };
-    let msg = match total {
-      Some(t) => format!("downloading Whisper model {} / {}", human_bytes(done), human_bytes(t)),
-      None => format!("downloading Whisper model ({})", human_bytes(done)),
-    };
-    let _ = emit_job(app, JobProgressEvent {
-      job_id: job_id_s.clone(),
-      media_id: media_id_s.clone(),
-      job_type: JobType::Transcribe,
-      status: JobStatus::Running,
-      progress: p,
-      message: Some(msg),
-    });
-  })
-  .await?;
+  Ok(serde_json::json!({
+    "enabled": offline_mode(),
+    "flag_file": offline_flag_path(dir).is_file(),
+    "bundle": env_path(OFFLINE_BUNDLE_ENV).map(|p| p.to_string_lossy().to_string()),
+  }))
+}
 
-  Ok(model_path)
+#[tauri::command]
+async fn set_offline_mode(
+  app: tauri::AppHandle,
+  args: SetOfflineModeArgs,
+  state: State<'_, Arc<AppState>>,
+) -> Result<serde_json::Value, AppError> {
+  let dir = state
+    .data_root
+    .get_or_try_init(|| async { portable::resolve_data_root(&app).map_err(AppError::Internal) })
+    .await?;
+
+  let flag = offline_flag_path(dir);
+  if args.enabled {
+    tokio::fs::write(&flag, b"")
+      .await
+      .map_err(|e| AppError::Internal(format!("write offline flag failed: {e}")))?;
+  } else if flag.is_file() {
+    tokio::fs::remove_file(&flag)
+      .await
+      .map_err(|e| AppError::Internal(format!("remove offline flag failed: {e}")))?;
+  }
+  // Env-driven offline mode can't be switched off from the UI.
+  let enabled = args.enabled || offline_mode_from_env(dir);
+  set_offline_mode_flag(enabled);
+  Ok(serde_json::json!({ "enabled": enabled }))
 }
 
-fn parse_whisper_cpp_json(v: &serde_json::Value) -> (Option<String>, Vec<(i64, i64, String)>) {
+fn parse_whisper_cpp_json(v: &serde_json::Value) -> (Option<String>, Vec<TimedSegment>) {
   let lang = v
     .get("result")
     .and_then(|r| r.get("language"))
@@ -4550,7 +9341,7 @@ fn parse_whisper_cpp_json(v: &serde_json::Value) -> (Option<String>, Vec<(i64, i
     .map(|s| s.trim().to_string())
     .filter(|s| !s.is_empty());
 
-  let mut segs: Vec<(i64, i64, String)> = Vec::new();
+  let mut segs: Vec<TimedSegment> = Vec::new();
   let items = v.get("transcription").and_then(|t| t.as_array()).cloned().unwrap_or_default();
   for item in items {
     let text = item.get("text").and_then(|x| x.as_str()).unwrap_or("").trim().to_string();
@@ -4567,12 +9358,44 @@ fn parse_whisper_cpp_json(v: &serde_json::Value) -> (Option<String>, Vec<(i64, i
       .and_then(|o| o.get("to"))
       .and_then(|x| x.as_i64())
       .unwrap_or(from_ms);
-    segs.push((from_ms.max(0), to_ms.max(from_ms), text));
+    let words = whisper_cpp_segment_words(&item, &text);
+    segs.push((from_ms.max(0), to_ms.max(from_ms), text, words));
   }
 
   (lang, segs)
 }
 
+// Token timing from `--output-json-full`. Special tokens ([_BEG_], [_TT_n]) are skipped; if the tokens do not
+// spell the segment text (multi-byte characters split across tokens), the words are dropped.
+fn whisper_cpp_segment_words(item: &serde_json::Value, text: &str) -> Vec<WordTiming> {
+  let Some(tokens) = item.get("tokens").and_then(|t| t.as_array()) else {
+    return Vec::new();
+  };
+  let mut toks: Vec<WordTiming> = Vec::new();
+  for tok in tokens {
+    let t = tok.get("text").and_then(|x| x.as_str()).unwrap_or("");
+    if t.trim().is_empty() || t.starts_with("[_") {
+      continue;
+    }
+    let from_ms = tok.get("offsets").and_then(|o| o.get("from")).and_then(|x| x.as_i64()).unwrap_or(0);
+    let to_ms = tok.get("offsets").and_then(|o| o.get("to")).and_then(|x| x.as_i64()).unwrap_or(from_ms);
+    toks.push(WordTiming {
+      start_ms: from_ms.max(0),
+      end_ms: to_ms.max(from_ms),
+      text: t.to_string(),
+      confidence: tok.get("p").and_then(|x| x.as_f64()).map(|p| p as f32),
+    });
+  }
+
+  let words = group_tokens_into_words(&toks);
+  let spelled: String = words.iter().flat_map(|w| w.text.chars()).filter(|c| !c.is_whitespace()).collect();
+  let expected: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+  if spelled != expected {
+    return Vec::new();
+  }
+  words
+}
+
 fn sanitize_json_unicode_surrogates(input: &[u8]) -> Vec<u8> {
   fn hex_nibble(b: u8) -> Option<u8> {
     match b {
@@ -4709,7 +9532,7 @@ async fn run_whisper_cpp(
   num_threads: u32,
   job_id: &str,
   media_id: &str,
-) -> Result<(Option<String>, Vec<(i64, i64, String)>), String> {
+) -> Result<(Option<String>, Vec<TimedSegment>), String> {
   use tokio::io::{AsyncBufReadExt, BufReader};
 
   let out_base = wav_path
@@ -4720,6 +9543,7 @@ async fn run_whisper_cpp(
   let _ = tokio::fs::remove_file(&json_path).await;
 
   let mut cmd = tokio::process::Command::new(&runtime.exe);
+  cmd.kill_on_drop(true);
   if let Some(dir) = runtime.exe.parent() {
     cmd.current_dir(dir);
   }
@@ -4729,7 +9553,7 @@ async fn run_whisper_cpp(
     .arg(model_path)
     .arg("--file")
     .arg(wav_path)
-    .arg("--output-json")
+    .arg("--output-json-full")
     .arg("--output-file")
     .arg(&out_base)
     .arg("--no-prints")
@@ -4807,6 +9631,7 @@ async fn run_whisper_cpp(
               "recognizing (whisper.cpp, provider={}, {}%)",
               runtime.provider, pct
             )),
+            error_code: None,
           });
         }
       }
@@ -4825,6 +9650,7 @@ async fn run_whisper_cpp(
     status: JobStatus::Running,
     progress: 0.74,
     message: Some("parsing whisper.cpp result".to_string()),
+    error_code: None,
   });
 
   if !status.success() {
@@ -4880,7 +9706,7 @@ async fn ensure_sense_voice_model(
   data_root: &Path,
   job_id: &str,
   media_id: &str,
-) -> Result<(PathBuf, PathBuf), String> {
+) -> Result<(PathBuf, PathBuf), AppError> {
   let _guard = state.tools_lock.lock().await;
 
   const DIR_NAME: &str = "sherpa-onnx-sense-voice-zh-en-ja-ko-yue-2024-07-17";
@@ -4910,7 +9736,7 @@ async fn ensure_sense_voice_model(
   let models_root = data_root.join("models").join("sensevoice");
   tokio::fs::create_dir_all(&models_root)
     .await
-    .map_err(|e| format!("create sensevoice model dir failed: {e}"))?;
+    .map_err(|e| AppError::Internal(format!("create sensevoice model dir failed: {e}")))?;
 
   let model_dir = models_root.join(DIR_NAME);
   let model_path = model_dir.join("model.onnx");
@@ -4951,6 +9777,7 @@ async fn ensure_sense_voice_model(
       status: JobStatus::Running,
       progress: p,
       message: Some(msg),
+      error_code: None,
     });
   })
   .await?;
@@ -4962,6 +9789,7 @@ async fn ensure_sense_voice_model(
     status: JobStatus::Running,
     progress: 0.26,
     message: Some("extracting SenseVoice model".to_string()),
+    error_code: None,
   });
 
   // Remove old extracted directory to avoid mixing versions.
@@ -4971,19 +9799,282 @@ async fn ensure_sense_voice_model(
   let models_root_clone = models_root.clone();
   tokio::task::spawn_blocking(move || extract_tar_bz2_to_dir(&archive_path_clone, &models_root_clone))
     .await
-    .map_err(|e| format!("join sensevoice extract task failed: {e}"))??;
+    .map_err(|e| AppError::Internal(format!("join sensevoice extract task failed: {e}")))?
+    .map_err(AppError::Internal)?;
 
   let _ = tokio::fs::remove_file(&archive_path).await;
 
   if !model_path.is_file() {
-    return Err("SenseVoice model.onnx missing after extraction".to_string());
+    return Err(AppError::ToolDownloadFailed("SenseVoice model.onnx missing after extraction".to_string()));
   }
   if !tokens_path.is_file() {
-    return Err("SenseVoice tokens.txt missing after extraction".to_string());
+    return Err(AppError::ToolDownloadFailed("SenseVoice tokens.txt missing after extraction".to_string()));
   }
   Ok((model_path, tokens_path))
 }
 
+const DIARIZATION_SEGMENTATION_DIR: &str = "sherpa-onnx-pyannote-segmentation-3-0";
+const DIARIZATION_SEGMENTATION_URL: &str = "https://github.com/k2-fsa/sherpa-onnx/releases/download/speaker-segmentation-models/sherpa-onnx-pyannote-segmentation-3-0.tar.bz2";
+const DIARIZATION_EMBEDDING_FILE: &str = "3dspeaker_speech_eres2net_base_sv_zh-cn_3dspeaker_16k.onnx";
+const DIARIZATION_EMBEDDING_URL: &str = "https://github.com/k2-fsa/sherpa-onnx/releases/download/speaker-recongition-models/3dspeaker_speech_eres2net_base_sv_zh-cn_3dspeaker_16k.onnx";
+
+// Pyannote segmentation + speaker embedding models for sherpa-onnx-offline-speaker-diarization.
+async fn ensure_diarization_models(
+  app: &tauri::AppHandle,
+  state: &Arc<AppState>,
+  data_root: &Path,
+  job_id: &str,
+  media_id: &str,
+) -> Result<(PathBuf, PathBuf), AppError> {
+  let _guard = state.tools_lock.lock().await;
+
+  let is_ready = |seg: &Path, emb: &Path| {
+    seg.is_file()
+      && emb.is_file()
+      && std::fs::metadata(seg).map(|m| m.len() > 1024 * 1024).unwrap_or(false)
+      && std::fs::metadata(emb).map(|m| m.len() > 1024 * 1024).unwrap_or(false)
+  };
+
+  for models_root in candidate_model_roots(app, data_root) {
+    let root = models_root.join("diarization");
+    let seg = root.join(DIARIZATION_SEGMENTATION_DIR).join("model.onnx");
+    let emb = root.join(DIARIZATION_EMBEDDING_FILE);
+    if is_ready(&seg, &emb) {
+      return Ok((seg, emb));
+    }
+  }
+
+  let root = data_root.join("models").join("diarization");
+  tokio::fs::create_dir_all(&root)
+    .await
+    .map_err(|e| AppError::Internal(format!("create diarization model dir failed: {e}")))?;
+  let seg = root.join(DIARIZATION_SEGMENTATION_DIR).join("model.onnx");
+  let emb = root.join(DIARIZATION_EMBEDDING_FILE);
+
+  let progress = |label: &'static str, base: f32| {
+    let job_id = job_id.to_string();
+    let media_id = media_id.to_string();
+    move |done: u64, total: Option<u64>| {
+      let p = match total {
+        Some(t) if t > 0 => base + (done as f32 / t as f32).clamp(0.0, 1.0) * 0.02,
+        _ => base,
+      };
+      let msg = match total {
+        Some(t) => format!("downloading {label} {} / {}", human_bytes(done), human_bytes(t)),
+        None => format!("downloading {label} ({})", human_bytes(done)),
+      };
+      let _ = emit_job(app, JobProgressEvent {
+        job_id: job_id.clone(),
+        media_id: media_id.clone(),
+        job_type: JobType::Transcribe,
+        status: JobStatus::Running,
+        progress: p,
+        message: Some(msg),
+        error_code: None,
+      });
+    }
+  };
+
+  if !seg.is_file() {
+    let archive_path = root.join("segmentation.tar.bz2");
+    http_download_to_file_with_progress(
+      DIARIZATION_SEGMENTATION_URL,
+      &archive_path,
+      progress("speaker segmentation model", 0.965),
+    )
+    .await?;
+    let _ = tokio::fs::remove_dir_all(root.join(DIARIZATION_SEGMENTATION_DIR)).await;
+    let archive_path_clone = archive_path.clone();
+    let root_clone = root.clone();
+    tokio::task::spawn_blocking(move || extract_tar_bz2_to_dir(&archive_path_clone, &root_clone))
+      .await
+      .map_err(|e| AppError::Internal(format!("join segmentation extract task failed: {e}")))?
+      .map_err(AppError::Internal)?;
+    let _ = tokio::fs::remove_file(&archive_path).await;
+  }
+
+  if !emb.is_file() {
+    http_download_to_file_with_progress(DIARIZATION_EMBEDDING_URL, &emb, progress("speaker embedding model", 0.97)).await?;
+  }
+
+  if !is_ready(&seg, &emb) {
+    return Err(AppError::ToolDownloadFailed("speaker diarization models missing after download".to_string()));
+  }
+  Ok((seg, emb))
+}
+
+// Returns speaker turns as (start, end, cluster label), e.g. (0.318, 6.865, "speaker_00").
+async fn run_sherpa_onnx_diarization(
+  runtime: &SherpaOnnxRuntime,
+  segmentation_model: &Path,
+  embedding_model: &Path,
+  wav: &Path,
+  num_speakers: Option<u32>,
+) -> Result<Vec<(f64, f64, String)>, String> {
+  let exe = runtime
+    .exe
+    .with_file_name(sidecar_basename("sherpa-onnx-offline-speaker-diarization"));
+  if !exe.is_file() {
+    return Err("sherpa-onnx runtime has no speaker diarization tool".to_string());
+  }
+
+  let threads = std::thread::available_parallelism()
+    .map(|n| n.get() as u32)
+    .unwrap_or(4)
+    .clamp(1, 16);
+
+  let mut cmd = tokio::process::Command::new(&exe);
+  cmd.kill_on_drop(true);
+  if let Some(dir) = exe.parent() {
+    cmd.current_dir(dir);
+  }
+  cmd
+    .arg(format!("--segmentation.pyannote-model={}", segmentation_model.to_string_lossy()))
+    .arg(format!("--segmentation.num-threads={threads}"))
+    .arg(format!("--embedding.model={}", embedding_model.to_string_lossy()))
+    .arg(format!("--embedding.num-threads={threads}"));
+  match num_speakers.filter(|n| *n > 0) {
+    Some(n) => cmd.arg(format!("--clustering.num-clusters={n}")),
+    None => cmd.arg("--clustering.cluster-threshold=0.5"),
+  };
+  cmd.arg(wav);
+
+  let out = cmd
+    .output()
+    .await
+    .map_err(|e| format!("spawn speaker diarization failed: {e}"))?;
+  let stdout = String::from_utf8_lossy(&out.stdout);
+  if !out.status.success() {
+    return Err(format!("speaker diarization failed: {}", output_tail(&out.stderr, 12)));
+  }
+
+  let mut turns: Vec<(f64, f64, String)> = Vec::new();
+  for line in stdout.lines() {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() != 4 || parts[1] != "--" {
+      continue;
+    }
+    let (Ok(start), Ok(end)) = (parts[0].parse::<f64>(), parts[2].parse::<f64>()) else {
+      continue;
+    };
+    if end > start {
+      turns.push((start, end, parts[3].to_string()));
+    }
+  }
+  Ok(turns)
+}
+
+// Labels each segment with the speaker whose turns overlap it most and rebuilds the `speakers` list.
+// Speaker ids are stable per run (spk-1, spk-2, ... by first appearance); names start as "Speaker N".
+fn assign_speakers(transcription: &mut serde_json::Value, turns: &[(f64, f64, String)]) -> usize {
+  let mut ids: Vec<String> = Vec::new();
+  let Some(segs) = transcription.get_mut("segments").and_then(|v| v.as_array_mut()) else {
+    return 0;
+  };
+  for seg in segs.iter_mut() {
+    let start = seg.get("start").and_then(|v| v.as_f64()).unwrap_or(0.0);
+    let end = seg.get("end").and_then(|v| v.as_f64()).unwrap_or(start).max(start);
+    let mid = (start + end) / 2.0;
+
+    let mut overlap_by_label: Vec<(&str, f64)> = Vec::new();
+    for (ts, te, label) in turns {
+      let overlap = end.min(*te) - start.max(*ts);
+      if overlap > 0.0 {
+        match overlap_by_label.iter_mut().find(|(l, _)| l == label) {
+          Some((_, o)) => *o += overlap,
+          None => overlap_by_label.push((label.as_str(), overlap)),
+        }
+      }
+    }
+    let best = overlap_by_label
+      .into_iter()
+      .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+      .map(|(l, _)| l)
+      .or_else(|| {
+        // Zero-length or gap segments: take the nearest turn.
+        turns
+          .iter()
+          .min_by(|a, b| {
+            let da = (mid - mid.clamp(a.0, a.1)).abs();
+            let db = (mid - mid.clamp(b.0, b.1)).abs();
+            da.partial_cmp(&db).unwrap_or(std::cmp::Ordering::Equal)
+          })
+          .map(|t| t.2.as_str())
+      });
+
+    let Some(label) = best else {
+      continue;
+    };
+    let idx = match ids.iter().position(|l| l == label) {
+      Some(i) => i,
+      None => {
+        ids.push(label.to_string());
+        ids.len() - 1
+      }
+    };
+    seg["speaker"] = serde_json::Value::String(format!("spk-{}", idx + 1));
+  }
+
+  let speakers: Vec<serde_json::Value> = (1..=ids.len())
+    .map(|i| serde_json::json!({ "id": format!("spk-{i}"), "name": format!("Speaker {i}") }))
+    .collect();
+  transcription["speakers"] = serde_json::Value::Array(speakers);
+  ids.len()
+}
+
+async fn diarize_transcription(
+  app: &tauri::AppHandle,
+  state: &Arc<AppState>,
+  data_root: &Path,
+  job_id: &str,
+  media_id: &str,
+  transcription: &mut serde_json::Value,
+  num_speakers: Option<u32>,
+) -> Result<usize, AppError> {
+  // Reuses the 16 kHz mono WAV extracted for recognition.
+  let wav_path = data_root.join("media").join(media_id).join("audio.16k.wav");
+  if !wav_path.is_file() {
+    return Err(AppError::InvalidInput("audio.16k.wav missing; transcribe the media first".to_string()));
+  }
+  let runtime = ensure_sherpa_onnx_offline(app, state, data_root, job_id, media_id, false, false).await?;
+  let (seg_model, emb_model) = ensure_diarization_models(app, state, data_root, job_id, media_id).await?;
+
+  let _ = emit_job(app, JobProgressEvent {
+    job_id: job_id.to_string(),
+    media_id: media_id.to_string(),
+    job_type: JobType::Transcribe,
+    status: JobStatus::Running,
+    progress: 0.975,
+    message: Some("identifying speakers".to_string()),
+    error_code: None,
+  });
+
+  let turns = run_sherpa_onnx_diarization(&runtime, &seg_model, &emb_model, &wav_path, num_speakers)
+    .await
+    .map_err(AppError::Internal)?;
+  if turns.is_empty() {
+    return Err(AppError::NothingRecognized("speaker diarization found no speech".to_string()));
+  }
+  Ok(assign_speakers(transcription, &turns))
+}
+
+fn transcription_speaker_names(t: &serde_json::Value) -> HashMap<String, String> {
+  let mut out = HashMap::new();
+  for s in t.get("speakers").and_then(|v| v.as_array()).into_iter().flatten() {
+    let id = s.get("id").and_then(|v| v.as_str()).unwrap_or("").trim();
+    let name = s.get("name").and_then(|v| v.as_str()).unwrap_or("").trim();
+    if !id.is_empty() {
+      out.insert(id.to_string(), if name.is_empty() { id.to_string() } else { name.to_string() });
+    }
+  }
+  out
+}
+
+fn segment_speaker_name<'a>(names: &'a HashMap<String, String>, seg: &serde_json::Value) -> Option<&'a str> {
+  let id = seg.get("speaker").and_then(|v| v.as_str())?;
+  names.get(id).map(|s| s.as_str())
+}
+
 #[derive(Debug, Clone)]
 struct SenseVoiceResult {
   language: String,
@@ -4997,6 +10088,8 @@ struct AudioChunk {
   path: PathBuf,
   start_ms: i64,
   duration_ms: i64,
+  /// Fixed-window chunks overlap their neighbours, so tokens near their edges can be dropped.
+  overlapped: bool,
 }
 
 fn parse_sense_voice_results(output: &str) -> Vec<SenseVoiceResult> {
@@ -5132,7 +10225,7 @@ fn tokens_with_estimated_timestamps_ms(res: &SenseVoiceResult) -> Vec<(i64, Stri
   out
 }
 
-fn segments_from_merged_tokens_with_lang(tokens: &[(i64, String)], lang_hint: Option<&str>) -> Vec<(i64, i64, String)> {
+fn segments_from_merged_tokens_with_lang(tokens: &[(i64, String)], lang_hint: Option<&str>) -> Vec<TimedSegment> {
   if tokens.is_empty() {
     return Vec::new();
   }
@@ -5142,9 +10235,10 @@ fn segments_from_merged_tokens_with_lang(tokens: &[(i64, String)], lang_hint: Op
   let is_en = matches!(lang_hint.unwrap_or("").trim(), "en" | "EN");
 
   let punct: [&str; 10] = ["。", "！", "？", ".", "!", "?", "；", ";", "…", "……"];
-  let mut out: Vec<(i64, i64, String)> = Vec::new();
+  let mut out: Vec<TimedSegment> = Vec::new();
   let mut cur = String::new();
   let mut start_ms: Option<i64> = None;
+  let mut cur_tokens: Vec<WordTiming> = Vec::new();
 
   for i in 0..tokens.len() {
     let (ts_ms, tok) = &tokens[i];
@@ -5153,6 +10247,14 @@ fn segments_from_merged_tokens_with_lang(tokens: &[(i64, String)], lang_hint: Op
     }
     cur.push_str(tok);
 
+    // SenseVoice only gives token start times; a token lasts until the next one (capped).
+    let tok_end_ms = if i + 1 < tokens.len() {
+      tokens[i + 1].0.min(ts_ms.saturating_add(1000))
+    } else {
+      ts_ms.saturating_add(300)
+    };
+    cur_tokens.push(WordTiming { start_ms: *ts_ms, end_ms: tok_end_ms, text: tok.clone(), confidence: None });
+
     let trimmed_tok = tok.trim();
     let is_eos = punct.iter().any(|p| *p == trimmed_tok);
     let next_gap_ms = if i + 1 < tokens.len() {
@@ -5168,21 +10270,22 @@ fn segments_from_merged_tokens_with_lang(tokens: &[(i64, String)], lang_hint: Op
     if should_split {
       let txt = cur.trim();
       if !txt.is_empty() {
-        out.push((start_ms.unwrap_or(*ts_ms), *ts_ms, txt.to_string()));
+        out.push((start_ms.unwrap_or(*ts_ms), *ts_ms, txt.to_string(), group_tokens_into_words(&cur_tokens)));
       }
       cur.clear();
+      cur_tokens.clear();
       start_ms = None;
     }
   }
 
   if !cur.trim().is_empty() {
     let last_ms = tokens.last().map(|t| t.0).unwrap_or(0);
-    out.push((start_ms.unwrap_or(last_ms), last_ms, cur.trim().to_string()));
+    out.push((start_ms.unwrap_or(last_ms), last_ms, cur.trim().to_string(), group_tokens_into_words(&cur_tokens)));
   }
   out
 }
 
-fn merge_sense_voice_chunks(res_list: &[SenseVoiceResult], chunks: &[AudioChunk], lang_hint: Option<&str>) -> Vec<(i64, i64, String)> {
+fn merge_sense_voice_chunks(res_list: &[SenseVoiceResult], chunks: &[AudioChunk], lang_hint: Option<&str>) -> Vec<TimedSegment> {
   #[derive(Clone)]
   struct TokenRec {
     ts_ms: i64,
@@ -5209,7 +10312,7 @@ fn merge_sense_voice_chunks(res_list: &[SenseVoiceResult], chunks: &[AudioChunk]
     } else {
       ch.duration_ms.saturating_sub(EDGE_GUARD_MS)
     };
-    let use_guard = ch.duration_ms > 0 && keep_right > keep_left + 500;
+    let use_guard = ch.overlapped && ch.duration_ms > 0 && keep_right > keep_left + 500;
 
     for (rel_ms, tok) in rel {
       let rel_ms = rel_ms.max(0);
@@ -5238,12 +10341,12 @@ fn merge_sense_voice_chunks(res_list: &[SenseVoiceResult], chunks: &[AudioChunk]
 
   // If token stream is unusable, fall back to per-chunk text.
   if all.is_empty() {
-    let mut segs: Vec<(i64, i64, String)> = Vec::new();
+    let mut segs: Vec<TimedSegment> = Vec::new();
     for (idx, res) in res_list.iter().enumerate() {
       let Some(ch) = chunks.get(idx) else { continue; };
       let t = res.text.trim();
       if t.is_empty() { continue; }
-      segs.push((ch.start_ms, ch.start_ms.saturating_add(ch.duration_ms.max(0)), t.to_string()));
+      segs.push((ch.start_ms, ch.start_ms.saturating_add(ch.duration_ms.max(0)), t.to_string(), Vec::new()));
     }
     return segs;
   }
@@ -5378,6 +10481,7 @@ async fn run_sherpa_onnx_sense_voice(
     use_itn: bool,
   ) -> Result<(std::process::ExitStatus, String, String), String> {
     let mut cmd = tokio::process::Command::new(exe);
+    cmd.kill_on_drop(true);
     if let Some(dir) = exe.parent() {
       cmd.current_dir(dir);
     }
@@ -5422,6 +10526,7 @@ async fn run_sherpa_onnx_sense_voice(
       status: JobStatus::Running,
       progress: 0.28,
       message: Some(format!("运行 SenseVoice（provider={provider}, threads={threads}，输入={}段）", wavs.len())),
+      error_code: None,
     });
 
     let (status, stdout, stderr) = run_once(&runtime.exe, provider, threads, tokens, model, wavs, lang, use_itn).await?;
@@ -5463,6 +10568,7 @@ async fn run_sherpa_onnx_sense_voice(
         status: JobStatus::Running,
         progress: 0.28,
         message: Some("CUDA 运行时不可用（缺少 cuDNN / cudnn64_9.dll），已自动切换到 CPU".to_string()),
+        error_code: None,
       });
       continue;
     }
@@ -5488,38 +10594,59 @@ struct GithubAsset {
   url: String,
 }
 
-async fn github_latest_assets(owner: &str, repo: &str) -> Result<Vec<GithubAsset>, String> {
-  let api = format!("https://api.github.com/repos/{owner}/{repo}/releases/latest");
-  let client = reqwest::Client::new();
+async fn github_latest_assets(owner: &str, repo: &str) -> Result<Vec<GithubAsset>, AppError> {
+  github_release_assets(owner, repo, None).await.map(|(_, assets)| assets)
+}
+
+// `tag = None` means the latest release. Returns the release tag and its assets.
+async fn github_release_assets(owner: &str, repo: &str, tag: Option<&str>) -> Result<(String, Vec<GithubAsset>), AppError> {
+  ensure_online(&format!("{owner}/{repo}"))?;
+  let release = match tag {
+    Some(t) => format!("tags/{t}"),
+    None => "latest".to_string(),
+  };
+  let api = format!("https://api.github.com/repos/{owner}/{repo}/releases/{release}");
+  let fetch_api = mirror_url(&api);
+  // Digests are only trusted when they came from GitHub itself, not a mirror.
+  let trust_digests = fetch_api == api;
+  let client = http_client();
   let resp = client
-    .get(api)
+    .get(&fetch_api)
     .header("User-Agent", "vecho-studio")
     .send()
     .await
-    .map_err(|e| format!("github api request failed: {e}"))?;
+    .map_err(|e| AppError::ToolDownloadFailed(format!("github api request failed: {e}")))?;
   if !resp.status().is_success() {
-    return Err(format!("github api request failed: http {}", resp.status()));
+    return Err(AppError::ToolDownloadFailed(format!("github api request failed: http {}", resp.status())));
   }
 
   let v: serde_json::Value = resp
     .json()
     .await
-    .map_err(|e| format!("parse github api json failed: {e}"))?;
+    .map_err(|e| AppError::ToolDownloadFailed(format!("parse github api json failed: {e}")))?;
 
   let assets = v
     .get("assets")
     .and_then(|a| a.as_array())
-    .ok_or_else(|| "github api response missing assets".to_string())?;
+    .ok_or_else(|| AppError::ToolDownloadFailed("github api response missing assets".to_string()))?;
 
   let mut out = Vec::new();
   for a in assets {
     let name = a.get("name").and_then(|s| s.as_str());
     let url = a.get("browser_download_url").and_then(|s| s.as_str());
     if let (Some(name), Some(url)) = (name, url) {
+      let digest = a.get("digest").and_then(|d| d.as_str()).and_then(checksums::normalize_sha256);
+      if let Some(digest) = digest.filter(|_| trust_digests) {
+        github_digest_cache()
+          .lock()
+          .unwrap_or_else(|e| e.into_inner())
+          .insert(url.to_string(), digest);
+      }
       out.push(GithubAsset { name: name.to_string(), url: url.to_string() });
     }
   }
-  Ok(out)
+  let tag = v.get("tag_name").and_then(|t| t.as_str()).unwrap_or("").to_string();
+  Ok((tag, out))
 }
 
 fn pick_ffmpeg_asset(assets: &[GithubAsset]) -> Option<GithubAsset> {
@@ -5809,12 +10936,38 @@ fn extract_zip_to_dir(archive_path: &Path, dest_dir: &Path) -> Result<(), String
 }
 
 fn extract_tar_bz2_to_dir(archive_path: &Path, dest_dir: &Path) -> Result<(), String> {
-  use std::fs::File;
-
-  let f = File::open(archive_path)
+  let f = std::fs::File::open(archive_path)
     .map_err(|e| format!("open tar.bz2 failed: {e}"))?;
-  let dec = bzip2::read::BzDecoder::new(f);
-  let mut ar = tar::Archive::new(dec);
+  unpack_tar_to_dir(bzip2::read::BzDecoder::new(f), dest_dir)
+}
+
+// Picks the decoder from the file name: .zip, .tar, .tar.gz/.tgz, .tar.bz2/.tbz2, .tar.xz/.txz.
+fn extract_archive_to_dir(archive_path: &Path, dest_dir: &Path) -> Result<(), String> {
+  let name = archive_path
+    .file_name()
+    .map(|n| n.to_string_lossy().to_lowercase())
+    .unwrap_or_default();
+  if name.ends_with(".zip") {
+    return extract_zip_to_dir(archive_path, dest_dir);
+  }
+  if name.ends_with(".tar.bz2") || name.ends_with(".tbz2") {
+    return extract_tar_bz2_to_dir(archive_path, dest_dir);
+  }
+  let f = std::fs::File::open(archive_path)
+    .map_err(|e| format!("open archive failed: {e}"))?;
+  if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+    unpack_tar_to_dir(flate2::read::GzDecoder::new(f), dest_dir)
+  } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+    unpack_tar_to_dir(xz2::read::XzDecoder::new(f), dest_dir)
+  } else if name.ends_with(".tar") {
+    unpack_tar_to_dir(f, dest_dir)
+  } else {
+    Err(format!("unsupported archive type: {name}"))
+  }
+}
+
+fn unpack_tar_to_dir<R: std::io::Read>(reader: R, dest_dir: &Path) -> Result<(), String> {
+  let mut ar = tar::Archive::new(reader);
 
   std::fs::create_dir_all(dest_dir)
     .map_err(|e| format!("create dest dir failed: {e}"))?;
@@ -5872,7 +11025,7 @@ fn find_file_recursive(dir: &Path, file_name: &str) -> Option<PathBuf> {
   None
 }
 
-async fn ensure_ffmpeg_bundle(app: &tauri::AppHandle, state: &Arc<AppState>, data_root: &Path) -> Result<(PathBuf, PathBuf), String> {
+async fn ensure_ffmpeg_bundle(app: &tauri::AppHandle, state: &Arc<AppState>, data_root: &Path) -> Result<(PathBuf, PathBuf), AppError> {
   ensure_ffmpeg_bundle_with_job(app, state, data_root, None).await
 }
 
@@ -5881,13 +11034,13 @@ async fn ensure_ffmpeg_bundle_with_job(
   state: &Arc<AppState>,
   data_root: &Path,
   job: Option<(&str, &str, JobType, f32, f32)>,
-) -> Result<(PathBuf, PathBuf), String> {
+) -> Result<(PathBuf, PathBuf), AppError> {
   let _guard = state.tools_lock.lock().await;
 
   let bin_dir = tools_bin_dir(data_root);
   tokio::fs::create_dir_all(&bin_dir)
     .await
-    .map_err(|e| format!("create bin dir failed: {e}"))?;
+    .map_err(|e| AppError::Internal(format!("create bin dir failed: {e}")))?;
 
   let ffmpeg_path = bin_dir.join(sidecar_basename("ffmpeg"));
   let ffprobe_path = bin_dir.join(sidecar_basename("ffprobe"));
@@ -5902,12 +11055,14 @@ async fn ensure_ffmpeg_bundle_with_job(
     }
   }
 
+  ensure_online("ffmpeg")?;
+
   // Fetch latest ffmpeg build archive from yt-dlp maintained builds.
   // Prefer GitHub API (more robust selection), but fall back to direct known filenames
   // in case the API is blocked.
   let asset: GithubAsset = match github_latest_assets("yt-dlp", "FFmpeg-Builds").await {
     Ok(assets) => pick_ffmpeg_asset(&assets)
-      .ok_or_else(|| "no suitable ffmpeg build asset found".to_string())?,
+      .ok_or_else(|| AppError::ToolDownloadFailed("no suitable ffmpeg build asset found".to_string()))?,
     Err(_) => {
       let base = "https://github.com/yt-dlp/FFmpeg-Builds/releases/latest/download/";
       let candidates: Vec<&str> = if cfg!(windows) {
@@ -5947,11 +11102,11 @@ async fn ensure_ffmpeg_bundle_with_job(
       };
 
       let mut picked: Option<GithubAsset> = None;
-      let client = reqwest::Client::new();
+      let client = http_client();
       for name in candidates {
         let url = format!("{base}{name}");
         let ok = client
-          .head(&url)
+          .head(mirror_url(&url))
           .header("User-Agent", "vecho-studio")
           .send()
           .await
@@ -5963,7 +11118,7 @@ async fn ensure_ffmpeg_bundle_with_job(
         }
       }
 
-      picked.ok_or_else(|| "failed to locate ffmpeg build asset".to_string())?
+      picked.ok_or_else(|| AppError::ToolDownloadFailed("failed to locate ffmpeg build asset".to_string()))?
     }
   };
 
@@ -6016,6 +11171,7 @@ async fn ensure_ffmpeg_bundle_with_job(
         status: JobStatus::Running,
         progress: p,
         message: Some(msg),
+        error_code: None,
       });
     })
     .await?;
@@ -6027,6 +11183,7 @@ async fn ensure_ffmpeg_bundle_with_job(
       status: JobStatus::Running,
       progress: (base + span).clamp(0.0, 1.0),
       message: Some("extracting ffmpeg".to_string()),
+      error_code: None,
     });
   } else {
     http_download_to_file(&asset.url, &archive_path).await?;
@@ -6046,12 +11203,13 @@ async fn ensure_ffmpeg_bundle_with_job(
     }
   })
   .await
-  .map_err(|e| format!("join ffmpeg extract task failed: {e}"))??;
+  .map_err(|e| AppError::Internal(format!("join ffmpeg extract task failed: {e}")))?
+  .map_err(AppError::Internal)?;
 
   let _ = tokio::fs::remove_file(&archive_path).await;
 
-  set_executable(&extracted.0)?;
-  set_executable(&extracted.1)?;
+  set_executable(&extracted.0).map_err(AppError::Internal)?;
+  set_executable(&extracted.1).map_err(AppError::Internal)?;
 
   std::env::set_var(SIDECAR_ENV_DIR, &bin_dir);
   Ok(extracted)
@@ -6108,9 +11266,10 @@ fn parse_rate(rate: &str) -> Option<f32> {
 }
 
 async fn ffprobe_analyze(app: &tauri::AppHandle, media_path: &Path) -> Result<(Option<f64>, Option<serde_json::Value>, bool), String> {
-  let ffprobe = resolve_sidecar(app, "ffprobe")?;
+  let ffprobe = resolve_sidecar(app, "ffprobe").map_err(|e| e.to_string())?;
 
   let out = tokio::process::Command::new(ffprobe)
+    .kill_on_drop(true)
     .arg("-v")
     .arg("error")
     .arg("-print_format")
@@ -6187,7 +11346,7 @@ async fn ffmpeg_thumbnail_data_url(
   media_path: &Path,
   seek_sec: Option<f64>,
 ) -> Result<String, String> {
-  let ffmpeg = resolve_sidecar(app, "ffmpeg")?;
+  let ffmpeg = resolve_sidecar(app, "ffmpeg").map_err(|e| e.to_string())?;
   let parent = media_path
     .parent()
     .ok_or_else(|| "invalid media path".to_string())?;
@@ -6199,6 +11358,7 @@ async fn ffmpeg_thumbnail_data_url(
 
   // Try to capture a representative frame (not too early).
   let status = tokio::process::Command::new(ffmpeg)
+    .kill_on_drop(true)
     .arg("-y")
     .arg("-hide_banner")
     .arg("-loglevel")
@@ -6290,6 +11450,7 @@ async fn ffmpeg_extract_audio_wav(ffmpeg: &Path, input: &Path, out_wav: &Path) -
 
   // 16kHz mono PCM S16LE is a safe default for local ASR and most STT endpoints.
   let status = tokio::process::Command::new(ffmpeg)
+    .kill_on_drop(true)
     .arg("-y")
     .arg("-hide_banner")
     .arg("-loglevel")
@@ -6344,6 +11505,7 @@ async fn ffmpeg_split_wav_segments_with_overlap(
     // Fallback: still produce a single chunk.
     let out_path = out_dir.join("chunk-00000.wav");
     let status = tokio::process::Command::new(ffmpeg)
+      .kill_on_drop(true)
       .arg("-y")
       .arg("-hide_banner")
       .arg("-loglevel")
@@ -6359,7 +11521,7 @@ async fn ffmpeg_split_wav_segments_with_overlap(
     if !status.success() {
       return Err("ffmpeg chunking failed".to_string());
     }
-    return Ok(vec![AudioChunk { path: out_path, start_ms: 0, duration_ms: chunk_ms }]);
+    return Ok(vec![AudioChunk { path: out_path, start_ms: 0, duration_ms: chunk_ms, overlapped: false }]);
   }
 
   let mut out: Vec<AudioChunk> = Vec::new();
@@ -6370,40 +11532,234 @@ async fn ffmpeg_split_wav_segments_with_overlap(
     let this_len = remaining.min(chunk_ms).max(1);
 
     let out_path = out_dir.join(format!("chunk-{idx:05}.wav"));
-    let ss = format!("{:.3}", (start_ms as f64) / 1000.0);
-    let tt = format!("{:.3}", (this_len as f64) / 1000.0);
-
+    let ss = format!("{:.3}", (start_ms as f64) / 1000.0);
+    let tt = format!("{:.3}", (this_len as f64) / 1000.0);
+
+    let status = tokio::process::Command::new(ffmpeg)
+      .kill_on_drop(true)
+      .arg("-y")
+      .arg("-hide_banner")
+      .arg("-loglevel")
+      .arg("error")
+      .arg("-i")
+      .arg(input_wav)
+      .arg("-ss")
+      .arg(ss)
+      .arg("-t")
+      .arg(tt)
+      .arg("-c")
+      .arg("copy")
+      .arg(&out_path)
+      .status()
+      .await
+      .map_err(|e| format!("spawn ffmpeg failed: {e}"))?;
+
+    if !status.success() {
+      return Err("ffmpeg chunking failed".to_string());
+    }
+
+    out.push(AudioChunk { path: out_path, start_ms, duration_ms: this_len, overlapped: true });
+    idx = idx.saturating_add(1);
+    start_ms = start_ms.saturating_add(step_ms);
+  }
+
+  if out.is_empty() {
+    return Err("ffmpeg chunking produced no wav files".to_string());
+  }
+  Ok(out)
+}
+
+const SILERO_VAD_URL: &str = "https://github.com/k2-fsa/sherpa-onnx/releases/download/asr-models/silero_vad.onnx";
+
+async fn ensure_silero_vad_model(app: &tauri::AppHandle, state: &Arc<AppState>, data_root: &Path) -> Result<PathBuf, AppError> {
+  let _guard = state.tools_lock.lock().await;
+
+  for models_root in candidate_model_roots(app, data_root) {
+    let p = models_root.join("vad").join("silero_vad.onnx");
+    if p.is_file() && std::fs::metadata(&p).map(|m| m.len() > 256 * 1024).unwrap_or(false) {
+      return Ok(p);
+    }
+  }
+
+  let root = data_root.join("models").join("vad");
+  tokio::fs::create_dir_all(&root)
+    .await
+    .map_err(|e| AppError::Internal(format!("create vad model dir failed: {e}")))?;
+  let p = root.join("silero_vad.onnx");
+  http_download_to_file_with_progress(SILERO_VAD_URL, &p, |_, _| {}).await?;
+  Ok(p)
+}
+
+// Speech spans (ms) from sherpa-onnx-vad, which prints "start -- end" (seconds) per detected segment.
+async fn run_sherpa_onnx_vad(runtime: &SherpaOnnxRuntime, model: &Path, wav: &Path, out_dir: &Path) -> Result<Vec<(i64, i64)>, String> {
+  let exe = runtime.exe.with_file_name(sidecar_basename("sherpa-onnx-vad"));
+  if !exe.is_file() {
+    return Err("sherpa-onnx runtime has no VAD tool".to_string());
+  }
+  let speech_wav = out_dir.join("_speech.wav");
+
+  let mut cmd = tokio::process::Command::new(&exe);
+  cmd.kill_on_drop(true);
+  if let Some(dir) = exe.parent() {
+    cmd.current_dir(dir);
+  }
+  let out = cmd
+    .arg(format!("--silero-vad-model={}", model.to_string_lossy()))
+    .arg("--silero-vad-threshold=0.5")
+    .arg("--silero-vad-min-silence-duration=0.4")
+    .arg("--silero-vad-min-speech-duration=0.25")
+    .arg("--silero-vad-max-speech-duration=25")
+    .arg(wav)
+    .arg(&speech_wav)
+    .output()
+    .await
+    .map_err(|e| format!("spawn sherpa-onnx-vad failed: {e}"))?;
+  let _ = tokio::fs::remove_file(&speech_wav).await;
+  if !out.status.success() {
+    return Err(format!("sherpa-onnx-vad failed: {}", output_tail(&out.stderr, 12)));
+  }
+
+  let text = format!("{}\n{}", String::from_utf8_lossy(&out.stdout), String::from_utf8_lossy(&out.stderr));
+  let mut spans: Vec<(i64, i64)> = Vec::new();
+  for line in text.lines() {
+    let Some((a, b)) = line.split_once("--") else {
+      continue;
+    };
+    let (Ok(start), Ok(end)) = (a.trim().parse::<f64>(), b.trim().trim_end_matches(':').parse::<f64>()) else {
+      continue;
+    };
+    if end > start {
+      spans.push(((start * 1000.0).round() as i64, (end * 1000.0).round() as i64));
+    }
+  }
+  spans.sort();
+  Ok(spans)
+}
+
+async fn ffmpeg_cut_wav_chunks(ffmpeg: &Path, input_wav: &Path, out_dir: &Path, plan: &[(i64, i64)]) -> Result<Vec<AudioChunk>, String> {
+  let mut out: Vec<AudioChunk> = Vec::with_capacity(plan.len());
+  for (idx, &(start_ms, end_ms)) in plan.iter().enumerate() {
+    let out_path = out_dir.join(format!("chunk-{idx:05}.wav"));
+    let len_ms = end_ms.saturating_sub(start_ms).max(1);
     let status = tokio::process::Command::new(ffmpeg)
+      .kill_on_drop(true)
       .arg("-y")
       .arg("-hide_banner")
       .arg("-loglevel")
       .arg("error")
+      .arg("-ss")
+      .arg(format!("{:.3}", (start_ms as f64) / 1000.0))
       .arg("-i")
       .arg(input_wav)
-      .arg("-ss")
-      .arg(ss)
       .arg("-t")
-      .arg(tt)
+      .arg(format!("{:.3}", (len_ms as f64) / 1000.0))
       .arg("-c")
       .arg("copy")
       .arg(&out_path)
       .status()
       .await
       .map_err(|e| format!("spawn ffmpeg failed: {e}"))?;
-
     if !status.success() {
       return Err("ffmpeg chunking failed".to_string());
     }
+    out.push(AudioChunk { path: out_path, start_ms, duration_ms: len_ms, overlapped: false });
+  }
+  Ok(out)
+}
 
-    out.push(AudioChunk { path: out_path, start_ms, duration_ms: this_len });
-    idx = idx.saturating_add(1);
-    start_ms = start_ms.saturating_add(step_ms);
+// Splits the WAV on silero-vad speech boundaries; silence between chunks is never decoded.
+async fn vad_split_wav(
+  app: &tauri::AppHandle,
+  state: &Arc<AppState>,
+  data_root: &Path,
+  runtime: &SherpaOnnxRuntime,
+  ffmpeg: &Path,
+  input_wav: &Path,
+  out_dir: &Path,
+) -> Result<Vec<AudioChunk>, AppError> {
+  const MAX_CHUNK_MS: i64 = 30_000;
+
+  let model = ensure_silero_vad_model(app, state, data_root).await?;
+  let _ = tokio::fs::remove_dir_all(out_dir).await;
+  tokio::fs::create_dir_all(out_dir)
+    .await
+    .map_err(|e| AppError::Internal(format!("create chunks dir failed: {e}")))?;
+
+  let spans = run_sherpa_onnx_vad(runtime, &model, input_wav, out_dir).await.map_err(AppError::Internal)?;
+  let plan = vad::plan_vad_chunks(&spans, MAX_CHUNK_MS);
+  if plan.is_empty() {
+    return Err(AppError::NothingRecognized("no speech detected".to_string()));
   }
+  ffmpeg_cut_wav_chunks(ffmpeg, input_wav, out_dir, &plan).await.map_err(AppError::Internal)
+}
 
-  if out.is_empty() {
-    return Err("ffmpeg chunking produced no wav files".to_string());
+#[derive(Debug, Clone)]
+struct WordTiming {
+  start_ms: i64,
+  end_ms: i64,
+  text: String,
+  confidence: Option<f32>,
+}
+
+// (start_ms, end_ms, text, words). `words` may be empty when the engine gives no token timing.
+type TimedSegment = (i64, i64, String, Vec<WordTiming>);
+
+// Groups sub-word tokens into words: a token starting with whitespace opens a new word, CJK characters are
+// words on their own, and punctuation sticks to the previous word.
+fn group_tokens_into_words(tokens: &[WordTiming]) -> Vec<WordTiming> {
+  let mut out: Vec<WordTiming> = Vec::new();
+  let mut conf_n: Vec<u32> = Vec::new();
+  for tok in tokens {
+    let text = tok.text.trim();
+    if text.is_empty() {
+      continue;
+    }
+    let first = text.chars().next().unwrap_or(' ');
+    let is_punct = text.chars().all(|c| !c.is_alphanumeric());
+    let starts_word = tok.text.starts_with(char::is_whitespace) || search::is_cjk(first);
+    let prev_cjk = out.last().and_then(|w| w.text.chars().last()).map(search::is_cjk).unwrap_or(false);
+
+    match out.last_mut() {
+      Some(w) if is_punct || (!starts_word && !prev_cjk) => {
+        w.text.push_str(text);
+        w.end_ms = w.end_ms.max(tok.end_ms);
+        if !is_punct {
+          if let Some(c) = tok.confidence {
+            let n = conf_n.last_mut().unwrap();
+            w.confidence = Some((w.confidence.unwrap_or(0.0) * (*n as f32) + c) / (*n as f32 + 1.0));
+            *n += 1;
+          }
+        }
+      }
+      _ => {
+        out.push(WordTiming {
+          start_ms: tok.start_ms,
+          end_ms: tok.end_ms.max(tok.start_ms),
+          text: text.to_string(),
+          confidence: tok.confidence,
+        });
+        conf_n.push(u32::from(tok.confidence.is_some()));
+      }
+    }
   }
-  Ok(out)
+  out
+}
+
+fn words_to_json(words: &[WordTiming]) -> Vec<serde_json::Value> {
+  words
+    .iter()
+    .map(|w| {
+      let mut v = serde_json::json!({
+        "start": (w.start_ms as f64) / 1000.0,
+        "end": (w.end_ms as f64) / 1000.0,
+        "text": w.text,
+      });
+      if let Some(c) = w.confidence {
+        v["confidence"] = serde_json::json!(((c.clamp(0.0, 1.0) as f64) * 1000.0).round() / 1000.0);
+      }
+      v
+    })
+    .collect()
 }
 
 fn build_transcription(
@@ -6411,21 +11767,38 @@ fn build_transcription(
   language: Option<&str>,
   model: &str,
   segments_ms: Vec<(i64, i64, String)>,
+) -> serde_json::Value {
+  let segments = segments_ms
+    .into_iter()
+    .map(|(from_ms, to_ms, text)| (from_ms, to_ms, text, Vec::new()))
+    .collect();
+  build_transcription_with_words(media_id, language, model, segments)
+}
+
+fn build_transcription_with_words(
+  media_id: &str,
+  language: Option<&str>,
+  model: &str,
+  segments: Vec<TimedSegment>,
 ) -> serde_json::Value {
   let mut segs = Vec::new();
   let mut word_count = 0usize;
   let mut char_count = 0usize;
 
-  for (idx, (from_ms, to_ms, text)) in segments_ms.into_iter().enumerate() {
+  for (idx, (from_ms, to_ms, text, words)) in segments.into_iter().enumerate() {
     word_count += text.split_whitespace().count();
     char_count += text.chars().filter(|c| !c.is_whitespace()).count();
 
-    segs.push(serde_json::json!({
+    let mut seg = serde_json::json!({
       "id": format!("seg-{}", idx + 1),
       "start": (from_ms as f64) / 1000.0,
       "end": (to_ms as f64) / 1000.0,
       "text": text,
-    }));
+    });
+    if !words.is_empty() {
+      seg["words"] = serde_json::Value::Array(words_to_json(&words));
+    }
+    segs.push(seg);
   }
 
   let wc = if word_count > 0 { word_count } else { char_count };
@@ -6449,21 +11822,21 @@ async fn openai_transcribe(
   wav_path: &Path,
   language: &str,
   cfg: &OpenAiTranscriptionConfig,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, AppError> {
   let base = normalize_base_url(&cfg.base_url);
   if base.is_empty() {
-    return Err("openai baseUrl is empty".to_string());
+    return Err(AppError::InvalidInput("openai baseUrl is empty".to_string()));
   }
   let url = format!("{base}/audio/transcriptions");
 
   let bytes = tokio::fs::read(wav_path)
     .await
-    .map_err(|e| format!("read wav failed: {e}"))?;
+    .map_err(|e| AppError::Internal(format!("read wav failed: {e}")))?;
 
   let part = reqwest::multipart::Part::bytes(bytes)
     .file_name("audio.wav")
     .mime_str("audio/wav")
-    .map_err(|e| format!("invalid wav mime: {e}"))?;
+    .map_err(|e| AppError::Internal(format!("invalid wav mime: {e}")))?;
 
   let mut form = reqwest::multipart::Form::new()
     .part("file", part)
@@ -6478,7 +11851,7 @@ async fn openai_transcribe(
   // Many OpenAI-compatible providers accept this extension.
   form = form.text("timestamp_granularities[]", "segment");
 
-  let client = reqwest::Client::new();
+  let client = http_client();
   let mut req = client.post(url).multipart(form);
   let key = cfg.api_key.trim();
   if !key.is_empty() {
@@ -6488,19 +11861,19 @@ async fn openai_transcribe(
   let resp = req
     .send()
     .await
-    .map_err(|e| format!("openai transcribe request failed: {e}"))?;
+    .map_err(|e| AppError::ProviderError(format!("openai transcribe request failed: {e}")))?;
   let status = resp.status();
   let body = resp
     .text()
     .await
-    .map_err(|e| format!("read openai transcribe response failed: {e}"))?;
+    .map_err(|e| AppError::ProviderError(format!("read openai transcribe response failed: {e}")))?;
 
   if !status.is_success() {
-    return Err(format!("openai transcribe failed: http {status}\n{body}"));
+    return Err(AppError::provider_status(status.as_u16(), format!("openai transcribe failed: http {status}\n{body}")));
   }
 
   let v = serde_json::from_str::<serde_json::Value>(&body)
-    .map_err(|e| format!("parse openai transcribe json failed: {e}"))?;
+    .map_err(|e| AppError::ProviderError(format!("parse openai transcribe json failed: {e}")))?;
 
   let lang = v
     .get("language")
@@ -6526,7 +11899,7 @@ async fn openai_transcribe(
   }
 
   if segs.is_empty() {
-    return Err("openai transcription returned no text".to_string());
+    return Err(AppError::ProviderError("openai transcription returned no text".to_string()));
   }
 
   let model_label = format!("openai:{}", cfg.model);
@@ -6547,15 +11920,9 @@ fn seconds_to_timestamp(sec: f64) -> String {
 }
 
 fn build_transcript_text(transcription: &serde_json::Value, max_chars: usize) -> String {
-  let segs = transcription.get("segments").and_then(|s| s.as_array()).cloned().unwrap_or_default();
   let mut out = String::new();
 
-  for seg in segs {
-    let start = seg.get("start").and_then(|n| n.as_f64()).unwrap_or(0.0);
-    let text = seg.get("text").and_then(|t| t.as_str()).unwrap_or("").trim();
-    if text.is_empty() {
-      continue;
-    }
+  for (start, _end, text) in extract_transcript_segments_with_speakers(transcription) {
     let line = format!("[{}] {}\n", seconds_to_timestamp(start), text);
     if out.len() + line.len() > max_chars {
       break;
@@ -6689,7 +12056,14 @@ fn parse_openai_sse_text(text: &str) -> Result<String, String> {
   Err("openai event-stream returned no content".to_string())
 }
 
-async fn read_openai_event_stream(resp: reqwest::Response) -> Result<String, String> {
+// Receives (content, reasoning) deltas from streaming chat completions.
+type ChatDeltaFn<'a> = dyn FnMut(Option<&str>, Option<&str>) + Send + 'a;
+
+// `on_delta(content, reasoning)` is called for every parsed SSE chunk.
+async fn read_openai_event_stream_with(
+  resp: reqwest::Response,
+  on_delta: &mut ChatDeltaFn<'_>,
+) -> Result<String, String> {
   use tokio::time::{timeout, Duration};
   let mut stream = resp.bytes_stream();
   let mut buf = String::new();
@@ -6736,6 +12110,7 @@ async fn read_openai_event_stream(resp: reqwest::Response) -> Result<String, Str
         continue;
       };
       let (c, r) = extract_openai_chat_delta_parts(&v);
+      on_delta(c.as_deref(), r.as_deref());
       if let Some(s) = c {
         content_out.push_str(&s);
       }
@@ -6758,6 +12133,7 @@ async fn read_openai_event_stream(resp: reqwest::Response) -> Result<String, Str
       }
       if let Ok(v) = serde_json::from_str::<serde_json::Value>(payload) {
         let (c, r) = extract_openai_chat_delta_parts(&v);
+        on_delta(c.as_deref(), r.as_deref());
         if let Some(s) = c {
           content_out.push_str(&s);
         }
@@ -6782,13 +12158,13 @@ async fn openai_chat_completion(
   api_key: &str,
   model: &str,
   messages: Vec<serde_json::Value>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
   let base = normalize_base_url(base_url);
   if base.is_empty() {
-    return Err("openai baseUrl is empty".to_string());
+    return Err(AppError::InvalidInput("openai baseUrl is empty".to_string()));
   }
   if model.trim().is_empty() {
-    return Err("openai model is empty".to_string());
+    return Err(AppError::InvalidInput("openai model is empty".to_string()));
   }
   let body = serde_json::json!({
     "model": model,
@@ -6804,13 +12180,13 @@ async fn openai_chat_completion_json_object(
   api_key: &str,
   model: &str,
   messages: Vec<serde_json::Value>,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
   let base = normalize_base_url(base_url);
   if base.is_empty() {
-    return Err("openai baseUrl is empty".to_string());
+    return Err(AppError::InvalidInput("openai baseUrl is empty".to_string()));
   }
   if model.trim().is_empty() {
-    return Err("openai model is empty".to_string());
+    return Err(AppError::InvalidInput("openai model is empty".to_string()));
   }
   let body = serde_json::json!({
     "model": model,
@@ -6822,14 +12198,47 @@ async fn openai_chat_completion_json_object(
   openai_chat_completion_with_body(&base, api_key, body).await
 }
 
+// Streams deltas to `on_delta` when the provider answers with SSE; otherwise returns the whole reply.
+async fn openai_chat_completion_stream(
+  base_url: &str,
+  api_key: &str,
+  model: &str,
+  messages: Vec<serde_json::Value>,
+  on_delta: &mut ChatDeltaFn<'_>,
+) -> Result<String, AppError> {
+  let base = normalize_base_url(base_url);
+  if base.is_empty() {
+    return Err(AppError::InvalidInput("openai baseUrl is empty".to_string()));
+  }
+  if model.trim().is_empty() {
+    return Err(AppError::InvalidInput("openai model is empty".to_string()));
+  }
+  let body = serde_json::json!({
+    "model": model,
+    "messages": messages,
+    "temperature": 0.2,
+    "stream": true,
+  });
+  openai_chat_completion_with_body_and_deltas(&base, api_key, body, on_delta).await
+}
+
 async fn openai_chat_completion_with_body(
   base: &str,
   api_key: &str,
   body: serde_json::Value,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
+  openai_chat_completion_with_body_and_deltas(base, api_key, body, &mut |_, _| {}).await
+}
+
+async fn openai_chat_completion_with_body_and_deltas(
+  base: &str,
+  api_key: &str,
+  body: serde_json::Value,
+  on_delta: &mut ChatDeltaFn<'_>,
+) -> Result<String, AppError> {
   let url = format!("{base}/chat/completions");
 
-  let client = reqwest::Client::new();
+  let client = http_client();
   let mut req = client
     .post(url)
     .header("Accept", "application/json, text/event-stream")
@@ -6841,7 +12250,7 @@ async fn openai_chat_completion_with_body(
   let resp = req
     .send()
     .await
-    .map_err(|e| format!("openai request failed: {e}"))?;
+    .map_err(|e| AppError::ProviderError(format!("openai request failed: {e}")))?;
   let status = resp.status();
   let headers = resp.headers().clone();
   let ct = headers
@@ -6854,18 +12263,18 @@ async fn openai_chat_completion_with_body(
     let text = resp
       .text()
       .await
-      .map_err(|e| format!("read openai response failed: {e}"))?;
-    return Err(format!("openai request failed: http {status}\n{text}"));
+      .map_err(|e| AppError::ProviderError(format!("read openai response failed: {e}")))?;
+    return Err(AppError::provider_status(status.as_u16(), format!("openai request failed: http {status}\n{text}")));
   }
 
   if ct.to_lowercase().contains("text/event-stream") {
-    return read_openai_event_stream(resp).await;
+    return read_openai_event_stream_with(resp, on_delta).await.map_err(AppError::ProviderError);
   }
 
   let text = resp
     .text()
     .await
-    .map_err(|e| format!("read openai response failed: {e}"))?;
+    .map_err(|e| AppError::ProviderError(format!("read openai response failed: {e}")))?;
 
   let trimmed = text.trim_start();
   if trimmed.starts_with('<') {
@@ -6874,14 +12283,14 @@ async fn openai_chat_completion_with_body(
       .and_then(|v| v.to_str().ok())
       .unwrap_or("");
     let preview = trimmed.chars().take(200).collect::<String>();
-    return Err(format!(
+    return Err(AppError::ProviderError(format!(
       "openai response is not JSON (looks like HTML). Check baseUrl (should end with /v1).\ncontent-type: {ct}\nbody (first 200 chars):\n{preview}"
-    ));
+    )));
   }
 
   // Some OpenAI-compatible providers always respond with SSE regardless of the stream flag.
   if trimmed.starts_with("data:") {
-    return parse_openai_sse_text(&text);
+    return parse_openai_sse_text(&text).map_err(AppError::ProviderError);
   }
 
   let v = match serde_json::from_str::<serde_json::Value>(&text) {
@@ -6895,9 +12304,9 @@ async fn openai_chat_completion_with_body(
           .and_then(|v| v.to_str().ok())
           .unwrap_or("");
         let preview = text.chars().take(400).collect::<String>();
-        return Err(format!(
+        return Err(AppError::ProviderError(format!(
           "parse openai json failed: {e}\ncontent-type: {ct}\nbody (first 400 chars):\n{preview}"
-        ));
+        )));
       }
     }
   };
@@ -6912,161 +12321,598 @@ async fn openai_chat_completion_with_body(
     .to_string();
 
   if content.trim().is_empty() {
-    return Err("openai response missing content".to_string());
+    return Err(AppError::ProviderError("openai response missing content".to_string()));
+  }
+  Ok(content)
+}
+
+async fn gemini_generate_content(base_url: &str, api_key: &str, model: &str, prompt: &str) -> Result<String, AppError> {
+  gemini_generate_content_with_config(base_url, api_key, model, prompt, None).await
+}
+
+async fn gemini_generate_content_with_config(
+  base_url: &str,
+  api_key: &str,
+  model: &str,
+  prompt: &str,
+  max_output_tokens: Option<u32>,
+) -> Result<String, AppError> {
+  let mut gen = serde_json::json!({ "temperature": 0.2 });
+  if let Some(m) = max_output_tokens {
+    if m > 0 {
+      gen["maxOutputTokens"] = serde_json::Value::Number(serde_json::Number::from(m));
+    }
+  }
+
+  let body = serde_json::json!({
+    "contents": [
+      { "role": "user", "parts": [ { "text": prompt } ] }
+    ],
+    "generationConfig": gen
+  });
+  gemini_generate_content_with_body(base_url, api_key, model, &body).await
+}
+
+async fn gemini_generate_content_with_body(
+  base_url: &str,
+  api_key: &str,
+  model: &str,
+  body: &serde_json::Value,
+) -> Result<String, AppError> {
+  if api_key.trim().is_empty() {
+    return Err(AppError::InvalidInput("gemini apiKey is empty".to_string()));
+  }
+  if model.trim().is_empty() {
+    return Err(AppError::InvalidInput("gemini model is empty".to_string()));
+  }
+
+  // Allow both:
+  // - https://generativelanguage.googleapis.com
+  // - https://generativelanguage.googleapis.com/v1beta
+  let base = gemini_api_base(base_url);
+  let url = format!("{base}/models/{model}:generateContent?key={}", api_key.trim());
+
+  let client = http_client();
+  let resp = client
+    .post(url)
+    .json(body)
+    .send()
+    .await
+    .map_err(|e| AppError::ProviderError(format!("gemini request failed: {e}")))?;
+  let status = resp.status();
+  let text = resp
+    .text()
+    .await
+    .map_err(|e| AppError::ProviderError(format!("read gemini response failed: {e}")))?;
+  if !status.is_success() {
+    return Err(AppError::provider_status(status.as_u16(), format!("gemini request failed: http {status}\n{text}")));
+  }
+  let v = serde_json::from_str::<serde_json::Value>(&text)
+    .map_err(|e| AppError::ProviderError(format!("parse gemini json failed: {e}")))?;
+
+  let (out, _) = gemini_chunk_parts(&v);
+  if out.trim().is_empty() {
+    return Err(AppError::ProviderError(gemini_empty_reason(&v)));
+  }
+  Ok(out)
+}
+
+// Explains an empty reply: a blocked prompt or a candidate stopped for safety/recitation.
+fn gemini_empty_reason(v: &serde_json::Value) -> String {
+  if let Some(reason) = v
+    .get("promptFeedback")
+    .and_then(|f| f.get("blockReason"))
+    .and_then(|r| r.as_str())
+  {
+    return format!("gemini blocked the prompt: {reason}");
+  }
+  let finish = v
+    .get("candidates")
+    .and_then(|c| c.as_array())
+    .and_then(|arr| arr.first())
+    .and_then(|c| c.get("finishReason"))
+    .and_then(|r| r.as_str())
+    .unwrap_or("");
+  if matches!(finish, "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT") {
+    return format!("gemini blocked the response: {finish}");
+  }
+  "gemini response missing text".to_string()
+}
+
+const GEMINI_HARM_CATEGORIES: [&str; 4] = [
+  "HARM_CATEGORY_HARASSMENT",
+  "HARM_CATEGORY_HATE_SPEECH",
+  "HARM_CATEGORY_SEXUALLY_EXPLICIT",
+  "HARM_CATEGORY_DANGEROUS_CONTENT",
+];
+
+// Multi-turn chat request: system messages go to `systemInstruction`, the rest become alternating
+// user/model `contents` (consecutive turns of one role are merged, leading model turns dropped).
+fn gemini_chat_body(system: &str, messages: &[ChatMessageIn], cfg: &GeminiAiConfig) -> serde_json::Value {
+  let mut system_parts: Vec<serde_json::Value> = Vec::new();
+  if !system.trim().is_empty() {
+    system_parts.push(serde_json::json!({ "text": system }));
+  }
+  let mut contents: Vec<serde_json::Value> = Vec::new();
+  for m in messages {
+    let role = match m.role {
+      ChatRole::System => {
+        system_parts.push(serde_json::json!({ "text": m.content }));
+        continue;
+      }
+      ChatRole::User => "user",
+      ChatRole::Assistant => "model",
+    };
+    if m.content.trim().is_empty() || (contents.is_empty() && role == "model") {
+      continue;
+    }
+    let part = serde_json::json!({ "text": m.content });
+    match contents.last_mut() {
+      Some(last) if last.get("role").and_then(|r| r.as_str()) == Some(role) => {
+        if let Some(parts) = last.get_mut("parts").and_then(|p| p.as_array_mut()) {
+          parts.push(part);
+        }
+      }
+      _ => contents.push(serde_json::json!({ "role": role, "parts": [part] })),
+    }
+  }
+
+  let mut gen = serde_json::json!({ "temperature": cfg.temperature.unwrap_or(0.2).clamp(0.0, 2.0) });
+  if let Some(m) = cfg.max_output_tokens.filter(|m| *m > 0) {
+    gen["maxOutputTokens"] = serde_json::json!(m);
+  }
+  let mut body = serde_json::json!({ "contents": contents, "generationConfig": gen });
+  if !system_parts.is_empty() {
+    body["systemInstruction"] = serde_json::json!({ "parts": system_parts });
+  }
+  if let Some(threshold) = cfg.safety_threshold.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+    body["safetySettings"] = GEMINI_HARM_CATEGORIES
+      .iter()
+      .map(|c| serde_json::json!({ "category": c, "threshold": threshold }))
+      .collect();
+  }
+  body
+}
+
+fn gemini_api_base(base_url: &str) -> String {
+  let mut base = normalize_base_url(base_url);
+  if base.is_empty() {
+    base = "https://generativelanguage.googleapis.com".to_string();
+  }
+  if base.ends_with("/v1beta") || base.ends_with("/v1") {
+    base
+  } else {
+    format!("{base}/v1beta")
+  }
+}
+
+// Returns (text, thought) from one GenerateContentResponse chunk.
+fn gemini_chunk_parts(v: &serde_json::Value) -> (String, String) {
+  let mut text = String::new();
+  let mut thought = String::new();
+  let parts = v
+    .get("candidates")
+    .and_then(|c| c.as_array())
+    .and_then(|arr| arr.first())
+    .and_then(|c| c.get("content"))
+    .and_then(|c| c.get("parts"))
+    .and_then(|p| p.as_array());
+  for p in parts.into_iter().flatten() {
+    let Some(t) = p.get("text").and_then(|t| t.as_str()) else {
+      continue;
+    };
+    if p.get("thought").and_then(|b| b.as_bool()).unwrap_or(false) {
+      thought.push_str(t);
+    } else {
+      text.push_str(t);
+    }
+  }
+  (text, thought)
+}
+
+// streamGenerateContent with `alt=sse`: one JSON response per `data:` line.
+async fn gemini_stream_generate_content(
+  base_url: &str,
+  api_key: &str,
+  model: &str,
+  body: &serde_json::Value,
+  on_delta: &mut ChatDeltaFn<'_>,
+) -> Result<String, AppError> {
+  if api_key.trim().is_empty() {
+    return Err(AppError::InvalidInput("gemini apiKey is empty".to_string()));
+  }
+  if model.trim().is_empty() {
+    return Err(AppError::InvalidInput("gemini model is empty".to_string()));
+  }
+  let base = gemini_api_base(base_url);
+  let url = format!("{base}/models/{model}:streamGenerateContent?alt=sse&key={}", api_key.trim());
+
+  let resp = http_client()
+    .post(url)
+    .json(body)
+    .send()
+    .await
+    .map_err(|e| AppError::ProviderError(format!("gemini request failed: {e}")))?;
+  let status = resp.status();
+  if !status.is_success() {
+    let text = resp.text().await.unwrap_or_default();
+    return Err(AppError::provider_status(status.as_u16(), format!("gemini request failed: http {status}\n{text}")));
+  }
+
+  let mut out = String::new();
+  let mut last = serde_json::Value::Null;
+  let mut on_line = |line: &str| {
+    let Some(payload) = line.strip_prefix("data:") else {
+      return Ok(());
+    };
+    let Ok(v) = serde_json::from_str::<serde_json::Value>(payload.trim()) else {
+      return Ok(());
+    };
+    let (text, thought) = gemini_chunk_parts(&v);
+    if !thought.is_empty() {
+      on_delta(None, Some(&thought));
+    }
+    if !text.is_empty() {
+      on_delta(Some(&text), None);
+      out.push_str(&text);
+    }
+    last = v;
+    Ok(())
+  };
+  read_stream_lines(resp, "gemini", &mut on_line).await.map_err(AppError::ProviderError)?;
+
+  if out.trim().is_empty() {
+    return Err(AppError::ProviderError(gemini_empty_reason(&last)));
+  }
+  Ok(out)
+}
+
+// ==================== Anthropic / Ollama ====================
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const ANTHROPIC_DEFAULT_MAX_TOKENS: u32 = 4096;
+
+type StreamLineFn<'a> = dyn FnMut(&str) -> Result<(), String> + Send + 'a;
+
+// Feeds each complete line of a streamed body to `on_line` (split on bytes, so multi-byte
+// characters across chunk boundaries stay intact).
+async fn read_stream_lines(resp: reqwest::Response, what: &str, on_line: &mut StreamLineFn<'_>) -> Result<(), String> {
+  use tokio::time::{timeout, Duration};
+  let mut stream = resp.bytes_stream();
+  let mut buf: Vec<u8> = Vec::new();
+  loop {
+    let next = timeout(Duration::from_secs(60), stream.next())
+      .await
+      .map_err(|_| format!("{what} stream stalled"))?;
+    let Some(chunk) = next else {
+      break;
+    };
+    buf.extend_from_slice(&chunk.map_err(|e| format!("{what} stream error: {e}"))?);
+    while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+      let line: Vec<u8> = buf.drain(..=pos).collect();
+      let line = String::from_utf8_lossy(&line);
+      if !line.trim().is_empty() {
+        on_line(line.trim())?;
+      }
+    }
+  }
+  let rest = String::from_utf8_lossy(&buf);
+  if !rest.trim().is_empty() {
+    on_line(rest.trim())?;
+  }
+  Ok(())
+}
+
+// Anthropic wants alternating user/assistant turns starting with a user turn; system text is separate.
+fn anthropic_messages_from_chat(system: &mut String, messages: &[ChatMessageIn]) -> Vec<serde_json::Value> {
+  let mut out: Vec<serde_json::Value> = Vec::new();
+  for m in messages {
+    let role = match m.role {
+      ChatRole::System => {
+        if !system.is_empty() {
+          system.push_str("\n\n");
+        }
+        system.push_str(&m.content);
+        continue;
+      }
+      ChatRole::User => "user",
+      ChatRole::Assistant => "assistant",
+    };
+    if m.content.trim().is_empty() || (out.is_empty() && role == "assistant") {
+      continue;
+    }
+    match out.last_mut() {
+      Some(last) if last.get("role").and_then(|r| r.as_str()) == Some(role) => {
+        let merged = format!("{}\n\n{}", last["content"].as_str().unwrap_or(""), m.content);
+        last["content"] = serde_json::json!(merged);
+      }
+      _ => out.push(serde_json::json!({ "role": role, "content": m.content })),
+    }
   }
-  Ok(content)
+  out
 }
 
-async fn gemini_generate_content(base_url: &str, api_key: &str, model: &str, prompt: &str) -> Result<String, String> {
-  let mut base = normalize_base_url(base_url);
-  if base.is_empty() {
-    base = "https://generativelanguage.googleapis.com".to_string();
+// Messages API. Streams `text_delta`/`thinking_delta` through `on_delta` when given.
+async fn anthropic_messages(
+  cfg: &AnthropicAiConfig,
+  system: &str,
+  messages: Vec<serde_json::Value>,
+  max_tokens: Option<u32>,
+  on_delta: Option<&mut ChatDeltaFn<'_>>,
+) -> Result<String, AppError> {
+  if cfg.api_key.trim().is_empty() {
+    return Err(AppError::InvalidInput("anthropic apiKey is empty".to_string()));
   }
-  if api_key.trim().is_empty() {
-    return Err("gemini apiKey is empty".to_string());
+  if cfg.model.trim().is_empty() {
+    return Err(AppError::InvalidInput("anthropic model is empty".to_string()));
   }
-  if model.trim().is_empty() {
-    return Err("gemini model is empty".to_string());
+  let mut base = normalize_base_url(&cfg.base_url);
+  if base.is_empty() {
+    base = "https://api.anthropic.com".to_string();
   }
+  let url = format!("{}/v1/messages", base.trim_end_matches("/v1"));
 
-  // Allow both:
-  // - https://generativelanguage.googleapis.com
-  // - https://generativelanguage.googleapis.com/v1beta
-  let base = if base.ends_with("/v1beta") || base.ends_with("/v1") {
-    base
-  } else {
-    format!("{base}/v1beta")
-  };
-
-  let url = format!("{base}/models/{model}:generateContent?key={}", api_key.trim());
-  let body = serde_json::json!({
-    "contents": [
-      { "role": "user", "parts": [ { "text": prompt } ] }
-    ],
-    "generationConfig": {
-      "temperature": 0.2
-    }
+  let max_tokens = max_tokens.or(cfg.max_tokens).filter(|m| *m > 0).unwrap_or(ANTHROPIC_DEFAULT_MAX_TOKENS);
+  let mut body = serde_json::json!({
+    "model": cfg.model.trim(),
+    "messages": messages,
+    "max_tokens": max_tokens,
+    "temperature": 0.2,
+    "stream": on_delta.is_some(),
   });
+  if !system.trim().is_empty() {
+    body["system"] = serde_json::json!(system);
+  }
 
-  let client = reqwest::Client::new();
-  let resp = client
+  let resp = http_client()
     .post(url)
+    .header("x-api-key", cfg.api_key.trim())
+    .header("anthropic-version", ANTHROPIC_VERSION)
     .json(&body)
     .send()
     .await
-    .map_err(|e| format!("gemini request failed: {e}"))?;
+    .map_err(|e| AppError::ProviderError(format!("anthropic request failed: {e}")))?;
   let status = resp.status();
-  let text = resp
-    .text()
-    .await
-    .map_err(|e| format!("read gemini response failed: {e}"))?;
   if !status.is_success() {
-    return Err(format!("gemini request failed: http {status}\n{text}"));
+    let text = resp.text().await.unwrap_or_default();
+    return Err(AppError::provider_status(
+      status.as_u16(),
+      format!("anthropic request failed: http {status}\n{text}"),
+    ));
   }
-  let v = serde_json::from_str::<serde_json::Value>(&text)
-    .map_err(|e| format!("parse gemini json failed: {e}"))?;
 
   let mut out = String::new();
-  if let Some(parts) = v
-    .get("candidates")
-    .and_then(|c| c.as_array())
-    .and_then(|arr| arr.first())
-    .and_then(|c| c.get("content"))
-    .and_then(|c| c.get("parts"))
-    .and_then(|p| p.as_array())
-  {
-    for p in parts {
-      if let Some(t) = p.get("text").and_then(|t| t.as_str()) {
-        out.push_str(t);
+  match on_delta {
+    Some(on_delta) => {
+      let mut on_line = |line: &str| {
+        let Some(payload) = line.strip_prefix("data:") else {
+          return Ok(());
+        };
+        let Ok(v) = serde_json::from_str::<serde_json::Value>(payload.trim()) else {
+          return Ok(());
+        };
+        match v.get("type").and_then(|t| t.as_str()) {
+          Some("content_block_delta") => {
+            let delta = v.get("delta");
+            if let Some(t) = delta.and_then(|d| d.get("text")).and_then(|t| t.as_str()) {
+              on_delta(Some(t), None);
+              out.push_str(t);
+            } else if let Some(t) = delta.and_then(|d| d.get("thinking")).and_then(|t| t.as_str()) {
+              on_delta(None, Some(t));
+            }
+          }
+          Some("error") => {
+            let msg = v
+              .get("error")
+              .and_then(|e| e.get("message"))
+              .and_then(|m| m.as_str())
+              .unwrap_or("unknown error");
+            return Err(format!("anthropic stream error: {msg}"));
+          }
+          _ => {}
+        }
+        Ok(())
+      };
+      read_stream_lines(resp, "anthropic", &mut on_line).await.map_err(AppError::ProviderError)?;
+    }
+    None => {
+      let v = resp
+        .json::<serde_json::Value>()
+        .await
+        .map_err(|e| AppError::ProviderError(format!("parse anthropic json failed: {e}")))?;
+      for block in v.get("content").and_then(|c| c.as_array()).into_iter().flatten() {
+        if block.get("type").and_then(|t| t.as_str()) == Some("text") {
+          out.push_str(block.get("text").and_then(|t| t.as_str()).unwrap_or(""));
+        }
       }
     }
   }
 
   if out.trim().is_empty() {
-    return Err("gemini response missing text".to_string());
+    return Err(AppError::ProviderError("anthropic response missing text".to_string()));
   }
   Ok(out)
 }
 
-async fn gemini_generate_content_with_config(
-  base_url: &str,
-  api_key: &str,
-  model: &str,
-  prompt: &str,
-  max_output_tokens: Option<u32>,
-) -> Result<String, String> {
-  let mut base = normalize_base_url(base_url);
+fn ollama_api_base(base_url: &str) -> String {
+  let base = normalize_base_url(base_url);
+  let base = base.trim_end_matches("/api").trim_end_matches("/v1");
   if base.is_empty() {
-    base = "https://generativelanguage.googleapis.com".to_string();
-  }
-  if api_key.trim().is_empty() {
-    return Err("gemini apiKey is empty".to_string());
-  }
-  if model.trim().is_empty() {
-    return Err("gemini model is empty".to_string());
-  }
-
-  let base = if base.ends_with("/v1beta") || base.ends_with("/v1") {
-    base
+    "http://localhost:11434".to_string()
   } else {
-    format!("{base}/v1beta")
-  };
-
-  let url = format!("{base}/models/{model}:generateContent?key={}", api_key.trim());
-  let mut gen = serde_json::json!({ "temperature": 0.2 });
-  if let Some(m) = max_output_tokens {
-    if m > 0 {
-      gen["maxOutputTokens"] = serde_json::Value::Number(serde_json::Number::from(m));
-    }
+    base.to_string()
   }
+}
 
-  let body = serde_json::json!({
-    "contents": [
-      { "role": "user", "parts": [ { "text": prompt } ] }
-    ],
-    "generationConfig": gen
+// Native `/api/chat`; OpenAI-style `{ role, content }` messages. Streams NDJSON when `on_delta` is given.
+async fn ollama_chat(
+  cfg: &OllamaAiConfig,
+  messages: Vec<serde_json::Value>,
+  json_output: bool,
+  on_delta: Option<&mut ChatDeltaFn<'_>>,
+) -> Result<String, AppError> {
+  if cfg.model.trim().is_empty() {
+    return Err(AppError::InvalidInput("ollama model is empty".to_string()));
+  }
+  let url = format!("{}/api/chat", ollama_api_base(&cfg.base_url));
+  let mut body = serde_json::json!({
+    "model": cfg.model.trim(),
+    "messages": messages,
+    "stream": on_delta.is_some(),
+    "options": { "temperature": 0.2 },
   });
+  if json_output {
+    body["format"] = serde_json::json!("json");
+  }
 
-  let client = reqwest::Client::new();
-  let resp = client
+  let resp = http_client()
     .post(url)
     .json(&body)
     .send()
     .await
-    .map_err(|e| format!("gemini request failed: {e}"))?;
+    .map_err(|e| AppError::ProviderError(format!("ollama request failed: {e}")))?;
   let status = resp.status();
-  let text = resp
-    .text()
-    .await
-    .map_err(|e| format!("read gemini response failed: {e}"))?;
   if !status.is_success() {
-    return Err(format!("gemini request failed: http {status}\n{text}"));
+    let text = resp.text().await.unwrap_or_default();
+    return Err(AppError::provider_status(
+      status.as_u16(),
+      format!("ollama request failed: http {status}\n{text}"),
+    ));
   }
-  let v = serde_json::from_str::<serde_json::Value>(&text)
-    .map_err(|e| format!("parse gemini json failed: {e}"))?;
 
   let mut out = String::new();
-  if let Some(parts) = v
-    .get("candidates")
-    .and_then(|c| c.as_array())
-    .and_then(|arr| arr.first())
-    .and_then(|c| c.get("content"))
-    .and_then(|c| c.get("parts"))
-    .and_then(|p| p.as_array())
-  {
-    for p in parts {
-      if let Some(t) = p.get("text").and_then(|t| t.as_str()) {
-        out.push_str(t);
-      }
+  match on_delta {
+    Some(on_delta) => {
+      let mut on_line = |line: &str| {
+        let Ok(v) = serde_json::from_str::<serde_json::Value>(line) else {
+          return Ok(());
+        };
+        if let Some(e) = v.get("error").and_then(|e| e.as_str()) {
+          return Err(format!("ollama stream error: {e}"));
+        }
+        let msg = v.get("message");
+        if let Some(t) = msg.and_then(|m| m.get("thinking")).and_then(|t| t.as_str()).filter(|t| !t.is_empty()) {
+          on_delta(None, Some(t));
+        }
+        if let Some(t) = msg.and_then(|m| m.get("content")).and_then(|t| t.as_str()).filter(|t| !t.is_empty()) {
+          on_delta(Some(t), None);
+          out.push_str(t);
+        }
+        Ok(())
+      };
+      read_stream_lines(resp, "ollama", &mut on_line).await.map_err(AppError::ProviderError)?;
+    }
+    None => {
+      let v = resp
+        .json::<serde_json::Value>()
+        .await
+        .map_err(|e| AppError::ProviderError(format!("parse ollama json failed: {e}")))?;
+      out = v
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_str())
+        .unwrap_or("")
+        .to_string();
     }
   }
 
   if out.trim().is_empty() {
-    return Err("gemini response missing text".to_string());
+    return Err(AppError::ProviderError("ollama response missing content".to_string()));
   }
   Ok(out)
 }
 
+async fn ollama_list_models(base_url: &str) -> Result<Vec<serde_json::Value>, AppError> {
+  let url = format!("{}/api/tags", ollama_api_base(base_url));
+  let resp = http_client()
+    .get(url)
+    .send()
+    .await
+    .map_err(|e| AppError::ProviderError(format!("ollama request failed: {e}")))?;
+  let status = resp.status();
+  if !status.is_success() {
+    let text = resp.text().await.unwrap_or_default();
+    return Err(AppError::provider_status(
+      status.as_u16(),
+      format!("ollama request failed: http {status}\n{text}"),
+    ));
+  }
+  let v = resp
+    .json::<serde_json::Value>()
+    .await
+    .map_err(|e| AppError::ProviderError(format!("parse ollama json failed: {e}")))?;
+  Ok(v
+    .get("models")
+    .and_then(|m| m.as_array())
+    .into_iter()
+    .flatten()
+    .map(|m| {
+      serde_json::json!({
+        "name": m.get("name").or_else(|| m.get("model")),
+        "size": m.get("size"),
+        "modified_at": m.get("modified_at"),
+        "family": m.get("details").and_then(|d| d.get("family")),
+        "parameter_size": m.get("details").and_then(|d| d.get("parameter_size")),
+      })
+    })
+    .collect())
+}
+
+// One-shot system + user prompt for any provider (used where the OpenAI/Gemini paths have no
+// special handling, and for the providers added later).
+async fn ai_complete(ai: &AiSettings, system: &str, prompt: &str, json_output: bool) -> Result<String, AppError> {
+  match ai.provider {
+    AiProvider::OpenaiCompatible => {
+      let mut messages = Vec::new();
+      if !system.is_empty() {
+        messages.push(serde_json::json!({ "role": "system", "content": system }));
+      }
+      messages.push(serde_json::json!({ "role": "user", "content": prompt }));
+      openai_chat_completion(&ai.openai.base_url, &ai.openai.api_key, &ai.openai.chat_model, messages).await
+    }
+    AiProvider::Gemini => {
+      let body = gemini_chat_body(
+        system,
+        &[ChatMessageIn { role: ChatRole::User, content: prompt.to_string() }],
+        &ai.gemini,
+      );
+      let g = &ai.gemini;
+      gemini_generate_content_with_body(&g.base_url, &g.api_key, &g.model, &body).await
+    }
+    AiProvider::Anthropic => {
+      let mut messages = vec![serde_json::json!({ "role": "user", "content": prompt })];
+      if json_output {
+        // No JSON mode in the Messages API; prefilling "{" makes the reply continue a JSON object.
+        messages.push(serde_json::json!({ "role": "assistant", "content": "{" }));
+        let rest = anthropic_messages(&ai.anthropic, system, messages, None, None).await?;
+        return Ok(format!("{{{rest}"));
+      }
+      anthropic_messages(&ai.anthropic, system, messages, None, None).await
+    }
+    AiProvider::Ollama => {
+      let mut messages = Vec::new();
+      if !system.is_empty() {
+        messages.push(serde_json::json!({ "role": "system", "content": system }));
+      }
+      messages.push(serde_json::json!({ "role": "user", "content": prompt }));
+      ollama_chat(&ai.ollama, messages, json_output, None).await
+    }
+  }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListOllamaModelsArgs {
+  #[serde(default)]
+  base_url: Option<String>,
+}
+
+#[tauri::command]
+async fn list_ollama_models(args: ListOllamaModelsArgs) -> Result<serde_json::Value, AppError> {
+  let models = ollama_list_models(args.base_url.as_deref().unwrap_or("")).await?;
+  Ok(serde_json::json!({ "models": models }))
+}
+
 fn try_parse_json_object(text: &str) -> Option<serde_json::Value> {
   let mut t = text.trim().to_string();
 
@@ -7141,19 +12987,19 @@ async fn optimize_transcription_with_ai(
   glossary: Option<&str>,
   job_id: &str,
   app: &tauri::AppHandle,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, AppError> {
   let segs = transcription
     .get("segments")
     .and_then(|v| v.as_array())
     .cloned()
     .unwrap_or_default();
   if segs.is_empty() {
-    return Err("transcription has no segments".to_string());
+    return Err(AppError::InvalidInput("transcription has no segments".to_string()));
   }
 
   let transcript_text = build_transcript_text(transcription, 140_000);
   if transcript_text.trim().is_empty() {
-    return Err("transcription is empty".to_string());
+    return Err(AppError::InvalidInput("transcription is empty".to_string()));
   }
 
   let _ = emit_job(app, JobProgressEvent {
@@ -7163,6 +13009,7 @@ async fn optimize_transcription_with_ai(
     status: JobStatus::Running,
     progress: 0.18,
     message: Some("analyzing transcript".to_string()),
+    error_code: None,
   });
 
   let gloss = glossary.unwrap_or("").trim();
@@ -7205,10 +13052,11 @@ Transcript (may be truncated):\n\n{transcript}\n",
     AiProvider::Gemini => {
       gemini_generate_content(&ai.gemini.base_url, &ai.gemini.api_key, &ai.gemini.model, &prompt).await?
     }
+    AiProvider::Anthropic | AiProvider::Ollama => ai_complete(ai, "You output strict JSON.", &prompt, true).await?,
   };
 
   let parsed = try_parse_json_value(&raw)
-    .ok_or_else(|| "optimize output missing JSON".to_string())?;
+    .ok_or_else(|| AppError::ProviderError("optimize output missing JSON".to_string()))?;
 
   let reps = parsed
     .get("replacements")
@@ -7240,6 +13088,7 @@ Transcript (may be truncated):\n\n{transcript}\n",
     status: JobStatus::Running,
     progress: 0.72,
     message: Some(format!("applying fixes (rules={})", rules.len())),
+    error_code: None,
   });
 
   let mut out = transcription.clone();
@@ -7257,6 +13106,10 @@ Transcript (may be truncated):\n\n{transcript}\n",
       }
       if next != old {
         seg["text"] = serde_json::Value::String(next);
+        // Word timing no longer matches the rewritten text.
+        if let Some(obj) = seg.as_object_mut() {
+          obj.remove("words");
+        }
       }
     }
   }
@@ -7268,6 +13121,8 @@ Transcript (may be truncated):\n\n{transcript}\n",
   let model_label = match ai.provider {
     AiProvider::OpenaiCompatible => format!("ai_opt:openai_compatible:{}", ai.openai.chat_model),
     AiProvider::Gemini => format!("ai_opt:gemini:{}", ai.gemini.model),
+    AiProvider::Anthropic => format!("ai_opt:anthropic:{}", ai.anthropic.model),
+    AiProvider::Ollama => format!("ai_opt:ollama:{}", ai.ollama.model),
   };
   out["model"] = serde_json::Value::String(model_label);
 
@@ -7283,7 +13138,7 @@ async fn summarize_from_transcription(
   user_lang: Option<&str>,
   prompt_id: Option<&str>,
   prompt_template: Option<&str>,
-) -> Result<serde_json::Value, String> {
+) -> Result<serde_json::Value, AppError> {
   use futures_util::stream::{FuturesUnordered, StreamExt};
   use tokio::sync::Semaphore;
 
@@ -7300,7 +13155,7 @@ async fn summarize_from_transcription(
 
   let transcript_text = build_transcript_text(transcription, 240_000);
   if transcript_text.trim().is_empty() {
-    return Err("transcription is empty".to_string());
+    return Err(AppError::InvalidInput("transcription is empty".to_string()));
   }
 
   let lang_hint = user_lang.unwrap_or("").trim().to_lowercase();
@@ -7326,6 +13181,7 @@ async fn summarize_from_transcription(
       status: JobStatus::Running,
       progress: 0.20,
       message: Some("summarizing (single pass)".to_string()),
+      error_code: None,
     });
 
     let base_prompt = "You are creating the FINAL summary for a media transcript.\n\n\
@@ -7371,6 +13227,9 @@ Input (transcript):\n\n{{input}}\n";
       AiProvider::Gemini => {
         gemini_generate_content(&ai.gemini.base_url, &ai.gemini.api_key, &ai.gemini.model, &final_prompt).await?
       }
+      AiProvider::Anthropic | AiProvider::Ollama => {
+        ai_complete(ai, "You output strict JSON.", &final_prompt, true).await?
+      }
     };
 
     let used = if let Some(pid) = prompt_id {
@@ -7391,6 +13250,7 @@ Input (transcript):\n\n{{input}}\n";
       status: JobStatus::Running,
       progress: 0.08,
       message: Some(format!("summarizing chunks (n={})", total)),
+      error_code: None,
     });
 
     let sem = std::sync::Arc::new(Semaphore::new(CHUNK_CONCURRENCY));
@@ -7403,7 +13263,7 @@ Input (transcript):\n\n{{input}}\n";
       let chunk2 = chunk.clone();
       let lp = lang_prefix_chunk.clone();
       futs.push(async move {
-        let _permit = sem.acquire_owned().await.map_err(|e| e.to_string())?;
+        let _permit = sem.acquire_owned().await.map_err(|e| AppError::Internal(e.to_string()))?;
         let prompt_body = format!(
           "You are summarizing a portion of a media transcript.\n\n\
  Return ONLY markdown. Keep it concise but information-dense.\n\
@@ -7432,9 +13292,12 @@ Input (transcript):\n\n{{input}}\n";
           AiProvider::Gemini => {
             gemini_generate_content(&ai2.gemini.base_url, &ai2.gemini.api_key, &ai2.gemini.model, &prompt).await?
           }
+          AiProvider::Anthropic | AiProvider::Ollama => {
+            ai_complete(&ai2, "You produce high-quality summaries.", &prompt, false).await?
+          }
         };
 
-        Ok::<(usize, String), String>((i, out))
+        Ok::<(usize, String), AppError>((i, out))
       });
     }
 
@@ -7454,6 +13317,7 @@ Input (transcript):\n\n{{input}}\n";
         status: JobStatus::Running,
         progress: p,
         message: Some(format!("summarizing chunks {}/{}", done, total)),
+        error_code: None,
       });
     }
 
@@ -7464,6 +13328,7 @@ Input (transcript):\n\n{{input}}\n";
       status: JobStatus::Running,
       progress: 0.72,
       message: Some("composing final summary".to_string()),
+      error_code: None,
     });
 
     let combined = partials.join("\n\n---\n\n");
@@ -7509,6 +13374,9 @@ Input (notes):\n\n{{input}}\n";
       AiProvider::Gemini => {
         gemini_generate_content(&ai.gemini.base_url, &ai.gemini.api_key, &ai.gemini.model, &final_prompt).await?
       }
+      AiProvider::Anthropic | AiProvider::Ollama => {
+        ai_complete(ai, "You output strict JSON.", &final_prompt, true).await?
+      }
     };
 
     let used = if let Some(pid) = prompt_id {
@@ -7578,6 +13446,8 @@ Input (notes):\n\n{{input}}\n";
       format!("openai_compatible:{m}")
     }
     AiProvider::Gemini => format!("gemini:{}", ai.gemini.model),
+    AiProvider::Anthropic => format!("anthropic:{}", ai.anthropic.model),
+    AiProvider::Ollama => format!("ollama:{}", ai.ollama.model),
   };
 
   let mut out = serde_json::json!({
@@ -7941,6 +13811,11 @@ fn build_chat_context(transcription: Option<&serde_json::Value>, query: &str) ->
   }
 
   let q = query.trim().to_lowercase();
+  let names = transcription_speaker_names(t);
+  let labeled = |seg: &serde_json::Value, text: &str| match segment_speaker_name(&names, seg) {
+    Some(name) => format!("{name}: {}", text.trim()),
+    None => text.trim().to_string(),
+  };
 
   // Extract tokens for keyword matching
   let tokens = if !q.is_empty() { extract_query_tokens(&q) } else { Vec::new() };
@@ -7965,7 +13840,7 @@ fn build_chat_context(transcription: Option<&serde_json::Value>, query: &str) ->
     if score <= 0 {
       continue;
     }
-    scored.push((score, start, text.trim().to_string()));
+    scored.push((score, start, labeled(seg, text)));
   }
 
   // If no keyword matches, provide uniformly sampled context from the entire transcription
@@ -7976,7 +13851,7 @@ fn build_chat_context(transcription: Option<&serde_json::Value>, query: &str) ->
         let start = seg.get("start").and_then(|n| n.as_f64()).unwrap_or(0.0);
         let text = seg.get("text").and_then(|t| t.as_str()).unwrap_or("");
         if !text.trim().is_empty() {
-          scored.push((0, start, text.trim().to_string()));
+          scored.push((0, start, labeled(seg, text)));
         }
       }
     }
@@ -8001,95 +13876,319 @@ fn build_chat_context(transcription: Option<&serde_json::Value>, query: &str) ->
   out
 }
 
+// ==================== Chat retrieval (BM25 + embeddings) ====================
+
+const EMBEDDINGS_FILE: &str = "embeddings.json";
+const CHAT_WINDOW_CHARS: usize = 400;
+const CHAT_CONTEXT_CHARS: usize = 20_000;
+const EMBEDDING_BATCH: usize = 64;
+// After an embeddings request fails, retrieval is BM25-only for this long before retrying.
+const EMBEDDING_RETRY_AFTER: std::time::Duration = std::time::Duration::from_secs(300);
+
+struct ChatWindow {
+  start: f64,
+  text: String,
+}
+
+// Consecutive transcript segments joined into ~CHAT_WINDOW_CHARS windows, speaker-labeled.
+fn transcript_windows(t: &serde_json::Value) -> Vec<ChatWindow> {
+  let segs = t.get("segments").and_then(|s| s.as_array()).cloned().unwrap_or_default();
+  let names = transcription_speaker_names(t);
+  let mut out: Vec<ChatWindow> = Vec::new();
+  let mut cur: Option<ChatWindow> = None;
+  for seg in &segs {
+    let text = seg.get("text").and_then(|t| t.as_str()).unwrap_or("").trim();
+    if text.is_empty() {
+      continue;
+    }
+    let line = match segment_speaker_name(&names, seg) {
+      Some(name) => format!("{name}: {text}"),
+      None => text.to_string(),
+    };
+    let start = seg.get("start").and_then(|n| n.as_f64()).unwrap_or(0.0);
+    let w = cur.get_or_insert_with(|| ChatWindow { start, text: String::new() });
+    if !w.text.is_empty() {
+      w.text.push(' ');
+    }
+    w.text.push_str(&line);
+    if w.text.chars().count() >= CHAT_WINDOW_CHARS {
+      out.extend(cur.take());
+    }
+  }
+  out.extend(cur);
+  out
+}
+
+// Embeddings use their own OpenAI-compatible endpoint, falling back to the chat endpoint's.
+fn embedding_endpoint(ai: &AiSettings) -> Option<(String, String, String)> {
+  let e = &ai.embedding;
+  let model = e.model.trim();
+  if model.is_empty() {
+    return None;
+  }
+  let ollama_v1;
+  let (base, key) = if e.base_url.trim().is_empty() && matches!(ai.provider, AiProvider::Ollama) {
+    // Ollama serves OpenAI-compatible embeddings under /v1.
+    ollama_v1 = format!("{}/v1", ollama_api_base(&ai.ollama.base_url));
+    (ollama_v1.as_str(), "")
+  } else if e.base_url.trim().is_empty() {
+    (ai.openai.base_url.as_str(), ai.openai.api_key.as_str())
+  } else {
+    (e.base_url.as_str(), e.api_key.as_str())
+  };
+  let base = normalize_base_url(base);
+  if base.is_empty() {
+    return None;
+  }
+  Some((base, key.trim().to_string(), model.to_string()))
+}
+
+async fn openai_embeddings(base: &str, api_key: &str, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+  let mut req = http_client()
+    .post(format!("{base}/embeddings"))
+    .json(&serde_json::json!({ "model": model, "input": inputs }));
+  if !api_key.is_empty() {
+    req = req.bearer_auth(api_key);
+  }
+  let resp = req.send().await.map_err(|e| format!("openai embeddings request failed: {e}"))?;
+  let status = resp.status();
+  let text = resp
+    .text()
+    .await
+    .map_err(|e| format!("read openai embeddings response failed: {e}"))?;
+  if !status.is_success() {
+    return Err(format!("openai embeddings failed: http {status}\n{text}"));
+  }
+  let v: serde_json::Value = serde_json::from_str(&text).map_err(|e| format!("parse openai embeddings json failed: {e}"))?;
+  let mut rows: Vec<(u64, Vec<f32>)> = v
+    .get("data")
+    .and_then(|d| d.as_array())
+    .ok_or_else(|| "openai embeddings response missing data".to_string())?
+    .iter()
+    .enumerate()
+    .map(|(i, item)| {
+      let idx = item.get("index").and_then(|x| x.as_u64()).unwrap_or(i as u64);
+      let vec = item
+        .get("embedding")
+        .and_then(|e| e.as_array())
+        .map(|a| a.iter().filter_map(|x| x.as_f64()).map(|x| x as f32).collect())
+        .unwrap_or_default();
+      (idx, vec)
+    })
+    .collect();
+  rows.sort_by_key(|(i, _)| *i);
+  if rows.len() != inputs.len() || rows.iter().any(|(_, v)| v.is_empty()) {
+    return Err("openai embeddings response has missing vectors".to_string());
+  }
+  Ok(rows.into_iter().map(|(_, v)| v).collect())
+}
+
+// Last failure per embedding endpoint ("<base> <model>").
+fn embedding_failures() -> &'static std::sync::Mutex<HashMap<String, std::time::Instant>> {
+  static FAILURES: std::sync::OnceLock<std::sync::Mutex<HashMap<String, std::time::Instant>>> = std::sync::OnceLock::new();
+  FAILURES.get_or_init(|| std::sync::Mutex::new(HashMap::new()))
+}
+
+// The endpoint to embed with, or None while a recent failure is backing it off.
+fn usable_embedding_endpoint(ai: &AiSettings) -> Option<(String, String, String)> {
+  let endpoint = embedding_endpoint(ai)?;
+  let key = format!("{} {}", endpoint.0, endpoint.2);
+  let failures = embedding_failures().lock().unwrap_or_else(|e| e.into_inner());
+  match failures.get(&key) {
+    Some(at) if at.elapsed() < EMBEDDING_RETRY_AFTER => None,
+    _ => Some(endpoint),
+  }
+}
+
+fn record_embedding_result<T>(endpoint: &(String, String, String), result: &Result<T, String>) {
+  let key = format!("{} {}", endpoint.0, endpoint.2);
+  let mut failures = embedding_failures().lock().unwrap_or_else(|e| e.into_inner());
+  match result {
+    Ok(_) => {
+      failures.remove(&key);
+    }
+    Err(_) => {
+      failures.insert(key, std::time::Instant::now());
+    }
+  }
+}
+
+fn windows_fingerprint(model: &str, windows: &[ChatWindow]) -> String {
+  use sha2::{Digest, Sha256};
+  let mut h = Sha256::new();
+  h.update(model.as_bytes());
+  for w in windows {
+    h.update(b"\n");
+    h.update(w.text.as_bytes());
+  }
+  h.finalize().iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// <media>/embeddings.json is reused while the model and transcript windows are unchanged.
+async fn load_or_build_embedding_index(
+  media_dir: &Path,
+  windows: &[ChatWindow],
+  endpoint: &(String, String, String),
+) -> Result<Vec<Vec<f32>>, String> {
+  let (base, key, model) = endpoint;
+  let path = media_dir.join(EMBEDDINGS_FILE);
+  let fingerprint = windows_fingerprint(model, windows);
+  if let Some(saved) = try_load_json(&path).await {
+    if saved.get("fingerprint").and_then(|f| f.as_str()) == Some(fingerprint.as_str()) {
+      if let Ok(vectors) = serde_json::from_value::<Vec<Vec<f32>>>(saved.get("vectors").cloned().unwrap_or_default()) {
+        if vectors.len() == windows.len() {
+          return Ok(vectors);
+        }
+      }
+    }
+  }
+
+  let mut vectors: Vec<Vec<f32>> = Vec::with_capacity(windows.len());
+  for batch in windows.chunks(EMBEDDING_BATCH) {
+    let inputs: Vec<String> = batch.iter().map(|w| w.text.clone()).collect();
+    vectors.extend(openai_embeddings(base, key, model, &inputs).await?);
+  }
+  write_json_atomic(&path, &serde_json::json!({
+    "model": model,
+    "fingerprint": fingerprint,
+    "created_at": now_iso(),
+    "vectors": vectors,
+  }))?;
+  Ok(vectors)
+}
+
+// Hybrid retrieval: BM25 and embedding similarity fused by rank, excerpts in time order.
+// Without an embedding model (or if the endpoint fails) BM25 alone ranks; with no hits at all
+// the keyword/sampling context from `build_chat_context` is used.
+async fn retrieve_chat_context(
+  media_dir: &Path,
+  transcription: Option<&serde_json::Value>,
+  query: &str,
+  ai: &AiSettings,
+) -> String {
+  let Some(t) = transcription else {
+    return String::new();
+  };
+  let windows = transcript_windows(t);
+  if windows.is_empty() || query.trim().is_empty() {
+    return build_chat_context(transcription, query);
+  }
+
+  let texts: Vec<&str> = windows.iter().map(|w| w.text.as_str()).collect();
+  let mut lists = vec![search::Bm25::new(&texts).scores(query)];
+  if let Some(endpoint) = usable_embedding_endpoint(ai) {
+    let embedded = async {
+      let index = load_or_build_embedding_index(media_dir, &windows, &endpoint).await?;
+      let q = openai_embeddings(&endpoint.0, &endpoint.1, &endpoint.2, &[query.to_string()]).await?;
+      Ok((index, q))
+    }
+    .await;
+    record_embedding_result(&endpoint, &embedded);
+    if let Ok((index, q)) = embedded {
+      lists.push(index.iter().map(|v| search::cosine(v, &q[0]).max(0.0)).collect());
+    }
+  }
+  let fused = search::reciprocal_rank_fusion(&lists, 60.0);
+
+  let mut order: Vec<usize> = (0..windows.len()).filter(|&i| fused[i] > 0.0).collect();
+  if order.is_empty() {
+    return build_chat_context(transcription, query);
+  }
+  order.sort_by(|&a, &b| fused[b].partial_cmp(&fused[a]).unwrap_or(std::cmp::Ordering::Equal));
+
+  let mut picked: Vec<usize> = Vec::new();
+  let mut used = 0usize;
+  for i in order {
+    let len = windows[i].text.chars().count() + 12;
+    if used + len > CHAT_CONTEXT_CHARS {
+      break;
+    }
+    used += len;
+    picked.push(i);
+  }
+  picked.sort_unstable();
+
+  let mut out = String::new();
+  for i in picked {
+    out.push_str(&format!("[{}] {}\n", seconds_to_timestamp(windows[i].start), windows[i].text));
+  }
+  out
+}
+
 async fn chat_with_media_context(
-  _media_id: &str,
+  media_dir: &Path,
   transcription: Option<&serde_json::Value>,
   summary_md: Option<&str>,
   user_lang: Option<&str>,
   ai: &AiSettings,
   messages: &[ChatMessageIn],
-) -> Result<String, String> {
+  on_delta: Option<&mut ChatDeltaFn<'_>>,
+) -> Result<String, AppError> {
   let last_user = messages.iter().rev().find(|m| matches!(m.role, ChatRole::User));
   let query = last_user.map(|m| m.content.as_str()).unwrap_or("");
-  let ctx = build_chat_context(transcription, query);
+  let ctx = retrieve_chat_context(media_dir, transcription, query, ai).await;
 
   let lang_hint = user_lang.unwrap_or("").trim().to_lowercase();
-  let prefer_zh = lang_hint.starts_with("zh");
-  let prefer_en = lang_hint.starts_with("en");
+  let mut sys = String::from("You are a helpful assistant for a media player. Answer using the user's language. ");
+  if lang_hint.starts_with("zh") {
+    sys.push_str("Prefer Chinese. ");
+  } else if lang_hint.starts_with("en") {
+    sys.push_str("Prefer English. ");
+  }
+  sys.push_str("If you reference transcript facts, include timestamps like [MM:SS]. ");
+  if !ctx.trim().is_empty() {
+    sys.push_str("Use the provided transcript excerpts as the primary source of truth.");
+  }
+
+  // Reference material, sent ahead of the conversation.
+  let mut context_msgs: Vec<String> = Vec::new();
+  if let Some(s) = summary_md.map(str::trim).filter(|s| !s.is_empty()) {
+    context_msgs.push(format!("AI summary (may be partial):\n\n{s}"));
+  }
+  if !ctx.trim().is_empty() {
+    context_msgs.push(format!("Transcript excerpts:\n\n{ctx}"));
+  }
 
   match ai.provider {
-    AiProvider::OpenaiCompatible => {
-      let mut out_msgs: Vec<serde_json::Value> = Vec::new();
-      let mut sys = String::from("You are a helpful assistant for a media player. Answer using the user's language. ");
-      if prefer_zh {
-        sys.push_str("Prefer Chinese. ");
-      } else if prefer_en {
-        sys.push_str("Prefer English. ");
-      }
-      sys.push_str("If you reference transcript facts, include timestamps like [MM:SS]. ");
-      if !ctx.trim().is_empty() {
-        sys.push_str("Use the provided transcript excerpts as the primary source of truth.");
+    AiProvider::OpenaiCompatible | AiProvider::Ollama => {
+      let mut out_msgs: Vec<serde_json::Value> = vec![serde_json::json!({ "role": "system", "content": sys })];
+      for c in context_msgs {
+        out_msgs.push(serde_json::json!({ "role": "user", "content": c }));
       }
-      out_msgs.push(serde_json::json!({ "role": "system", "content": sys }));
-
-      if let Some(s) = summary_md {
-        let s = s.trim();
-        if !s.is_empty() {
-          out_msgs.push(serde_json::json!({
-            "role": "user",
-            "content": format!("AI summary (may be partial):\n\n{s}"),
-          }));
-        }
+      // Callers pass history already compacted by `chat_messages_with_summary`.
+      for m in messages {
+        out_msgs.push(serde_json::json!({ "role": chat_role_label(&m.role), "content": m.content }));
       }
 
-      if !ctx.trim().is_empty() {
-        out_msgs.push(serde_json::json!({
-          "role": "user",
-          "content": format!("Transcript excerpts:\n\n{ctx}"),
-        }));
+      if matches!(ai.provider, AiProvider::Ollama) {
+        return ollama_chat(&ai.ollama, out_msgs, false, on_delta).await;
       }
-
-      // Keep only the last few turns.
-      let tail = if messages.len() > 10 { &messages[messages.len() - 10..] } else { messages };
-      for m in tail {
-        let role = match m.role {
-          ChatRole::User => "user",
-          ChatRole::Assistant => "assistant",
-          ChatRole::System => "system",
-        };
-        out_msgs.push(serde_json::json!({ "role": role, "content": m.content }));
+      let o = &ai.openai;
+      match on_delta {
+        Some(f) => openai_chat_completion_stream(&o.base_url, &o.api_key, &o.chat_model, out_msgs, f).await,
+        None => openai_chat_completion(&o.base_url, &o.api_key, &o.chat_model, out_msgs).await,
       }
-
-      openai_chat_completion(&ai.openai.base_url, &ai.openai.api_key, &ai.openai.chat_model, out_msgs).await
+    }
+    AiProvider::Anthropic => {
+      for c in context_msgs {
+        sys.push_str("\n\n");
+        sys.push_str(&c);
+      }
+      let msgs = anthropic_messages_from_chat(&mut sys, messages);
+      anthropic_messages(&ai.anthropic, &sys, msgs, None, on_delta).await
     }
     AiProvider::Gemini => {
-      // Gemini: send a single prompt (keep MVP simple).
-      let mut prompt = String::new();
-      prompt.push_str("You are a helpful assistant for a media transcript. Answer using the user's language. ");
-      if prefer_zh {
-        prompt.push_str("Prefer Chinese. ");
-      } else if prefer_en {
-        prompt.push_str("Prefer English. ");
-      }
-      prompt.push_str("If you reference transcript facts, include timestamps like [MM:SS].\n\n");
-
-      if let Some(s) = summary_md {
-        let s = s.trim();
-        if !s.is_empty() {
-          prompt.push_str("AI summary (may be partial):\n\n");
-          prompt.push_str(s);
-          prompt.push_str("\n\n");
-        }
+      // Gemini has no mid-conversation system turns; reference material joins the system instruction.
+      for c in context_msgs {
+        sys.push_str("\n\n");
+        sys.push_str(&c);
       }
-      if !ctx.trim().is_empty() {
-        prompt.push_str("Transcript excerpts:\n\n");
-        prompt.push_str(&ctx);
-        prompt.push_str("\n\n");
+      let g = &ai.gemini;
+      let body = gemini_chat_body(&sys, messages, g);
+      match on_delta {
+        Some(f) => gemini_stream_generate_content(&g.base_url, &g.api_key, &g.model, &body, f).await,
+        None => gemini_generate_content_with_body(&g.base_url, &g.api_key, &g.model, &body).await,
       }
-      prompt.push_str("User question:\n");
-      prompt.push_str(query);
-
-      gemini_generate_content(&ai.gemini.base_url, &ai.gemini.api_key, &ai.gemini.model, &prompt).await
     }
   }
 }
@@ -8115,6 +14214,17 @@ fn main() {
       std::fs::create_dir_all(&media_dir)?;
       let _ = handle.asset_protocol_scope().allow_directory(&media_dir, true);
 
+      // Air-gapped installs: never download tools/models, optionally seed them from a bundle.
+      let _ = DATA_ROOT_FOR_DOWNLOADS.set(dir.clone());
+      load_network_config(&dir);
+      set_offline_mode_flag(offline_mode_from_env(&dir));
+      install_offline_bundle_on_startup(&handle, &dir);
+
+      // Pick up jobs that were still queued/running when the app last exited. Runs after the
+      // network/offline config is loaded so resumed downloads honor mirrors and offline mode.
+      restore_jobs(&handle, &dir);
+      ytdlp_auto_check_on_startup(&handle, &dir);
+
       // Window chrome: macOS keeps native traffic lights, others use frameless.
       // The base config uses decorations=false. On macOS we override to true + Overlay.
       #[cfg(target_os = "macos")]
@@ -8141,10 +14251,41 @@ fn main() {
       upload_chunk,
       upload_finish,
       transcribe_media,
+      list_local_models,
+      delete_local_model,
+      list_tools,
+      delete_tool,
+      install_tool,
+      install_tools_from_archive,
+      get_offline_mode,
+      set_offline_mode,
+      get_network_config,
+      set_network_config,
+      update_ytdlp,
+      check_ytdlp_update,
+      set_ytdlp_update_config,
+      diarize_media,
+      rename_speaker,
+      merge_speakers,
       optimize_transcription,
       summarize_media,
       export_media,
-      chat_media
+      render_video,
+      chat_media,
+      list_jobs,
+      get_job,
+      cancel_job,
+      cancel_chat,
+      search_library,
+      chat_collection,
+      create_conversation,
+      list_conversations,
+      get_conversation,
+      rename_conversation,
+      delete_conversation,
+      list_ollama_models,
+      set_job_concurrency,
+      import_subtitle_file
     ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
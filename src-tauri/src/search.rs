@@ -0,0 +1,422 @@
+//! Text retrieval helpers: tokenizer, BM25 scoring, rank fusion and the library index.
+//!
+//! Tokens are lowercase ASCII words (2+ chars) plus overlapping bigrams for runs of CJK
+//! characters, so Chinese/Japanese/Korean text matches without a word segmenter. A one-character
+//! CJK query also matches every indexed bigram containing that character.
+
+use std::collections::HashMap;
+
+pub fn is_cjk(ch: char) -> bool {
+    // Han, Hiragana/Katakana, Hangul
+    ('\u{4E00}'..='\u{9FFF}').contains(&ch)
+        || ('\u{3040}'..='\u{30FF}').contains(&ch)
+        || ('\u{AC00}'..='\u{D7AF}').contains(&ch)
+}
+
+/// Tokens in text order (not deduplicated), as `(token, char_start, char_end)`.
+pub fn tokenize_with_offsets(text: &str) -> Vec<(String, usize, usize)> {
+    let mut out = Vec::new();
+    let mut word = String::new();
+    let mut word_start = 0;
+    let mut cjk_run: Vec<(usize, char)> = Vec::new();
+
+    let flush_word = |word: &mut String, start: usize, end: usize, out: &mut Vec<(String, usize, usize)>| {
+        if word.chars().count() >= 2 {
+            out.push((std::mem::take(word), start, end));
+        } else {
+            word.clear();
+        }
+    };
+    let flush_cjk = |run: &mut Vec<(usize, char)>, out: &mut Vec<(String, usize, usize)>| {
+        if run.len() == 1 {
+            let (i, c) = run[0];
+            out.push((c.to_string(), i, i + 1));
+        }
+        for pair in run.windows(2) {
+            out.push((format!("{}{}", pair[0].1, pair[1].1), pair[0].0, pair[1].0 + 1));
+        }
+        run.clear();
+    };
+
+    let chars: Vec<char> = text.chars().collect();
+    for (i, &ch) in chars.iter().enumerate() {
+        if ch.is_alphanumeric() && !is_cjk(ch) {
+            if word.is_empty() {
+                word_start = i;
+            }
+            word.extend(ch.to_lowercase());
+            flush_cjk(&mut cjk_run, &mut out);
+            continue;
+        }
+        flush_word(&mut word, word_start, i, &mut out);
+        if is_cjk(ch) {
+            cjk_run.push((i, ch));
+        } else {
+            flush_cjk(&mut cjk_run, &mut out);
+        }
+    }
+    flush_word(&mut word, word_start, chars.len(), &mut out);
+    flush_cjk(&mut cjk_run, &mut out);
+    out
+}
+
+pub fn tokenize(text: &str) -> Vec<String> {
+    tokenize_with_offsets(text).into_iter().map(|(t, _, _)| t).collect()
+}
+
+fn single_cjk(token: &str) -> Option<char> {
+    let mut chars = token.chars();
+    let c = chars.next()?;
+    (chars.next().is_none() && is_cjk(c)).then_some(c)
+}
+
+/// Deduplicated query tokens. A lone CJK character is only a token where it stood alone in the
+/// text, so it is expanded to the bigrams in `vocab` that contain it.
+fn query_terms<'a>(query: &str, vocab: impl Iterator<Item = &'a String>) -> Vec<String> {
+    let mut q = tokenize(query);
+    let singles: Vec<char> = q.iter().filter_map(|t| single_cjk(t)).collect();
+    if !singles.is_empty() {
+        q.extend(
+            vocab
+                .filter(|t| t.chars().count() == 2 && t.chars().any(|c| singles.contains(&c)))
+                .cloned(),
+        );
+    }
+    q.sort();
+    q.dedup();
+    q
+}
+
+/// Okapi BM25 over a fixed set of documents.
+pub struct Bm25 {
+    doc_terms: Vec<HashMap<String, u32>>,
+    doc_len: Vec<f32>,
+    avg_len: f32,
+    df: HashMap<String, u32>,
+}
+
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+impl Bm25 {
+    pub fn new<S: AsRef<str>>(docs: &[S]) -> Self {
+        let mut doc_terms = Vec::with_capacity(docs.len());
+        let mut doc_len = Vec::with_capacity(docs.len());
+        let mut df: HashMap<String, u32> = HashMap::new();
+        for d in docs {
+            let mut tf: HashMap<String, u32> = HashMap::new();
+            let tokens = tokenize(d.as_ref());
+            doc_len.push(tokens.len() as f32);
+            for t in tokens {
+                *tf.entry(t).or_default() += 1;
+            }
+            for t in tf.keys() {
+                *df.entry(t.clone()).or_default() += 1;
+            }
+            doc_terms.push(tf);
+        }
+        let avg_len = if doc_len.is_empty() {
+            0.0
+        } else {
+            doc_len.iter().sum::<f32>() / doc_len.len() as f32
+        };
+        Self { doc_terms, doc_len, avg_len, df }
+    }
+
+    /// One score per document; 0 when no query token occurs.
+    pub fn scores(&self, query: &str) -> Vec<f32> {
+        let q = query_terms(query, self.df.keys());
+        let n = self.doc_terms.len() as f32;
+        let avg = self.avg_len.max(1.0);
+        self.doc_terms
+            .iter()
+            .zip(&self.doc_len)
+            .map(|(tf, len)| {
+                q.iter()
+                    .filter_map(|t| {
+                        let f = *tf.get(t)? as f32;
+                        let df = *self.df.get(t)? as f32;
+                        let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+                        Some(idf * f * (BM25_K1 + 1.0) / (f + BM25_K1 * (1.0 - BM25_B + BM25_B * len / avg)))
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let (mut dot, mut na, mut nb) = (0.0f32, 0.0f32, 0.0f32);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        na += x * x;
+        nb += y * y;
+    }
+    if na == 0.0 || nb == 0.0 {
+        0.0
+    } else {
+        dot / (na.sqrt() * nb.sqrt())
+    }
+}
+
+/// Reciprocal rank fusion: each list holds per-document scores (higher is better);
+/// documents with a score <= 0 in a list are not ranked by it.
+pub fn reciprocal_rank_fusion(lists: &[Vec<f32>], k: f32) -> Vec<f32> {
+    let n = lists.iter().map(|l| l.len()).max().unwrap_or(0);
+    let mut fused = vec![0.0f32; n];
+    for scores in lists {
+        let mut order: Vec<usize> = (0..scores.len()).filter(|&i| scores[i] > 0.0).collect();
+        order.sort_by(|&a, &b| scores[b].partial_cmp(&scores[a]).unwrap_or(std::cmp::Ordering::Equal));
+        for (rank, i) in order.into_iter().enumerate() {
+            fused[i] += 1.0 / (k + rank as f32 + 1.0);
+        }
+    }
+    fused
+}
+
+/// A searchable unit: one transcript/subtitle segment, note or summary paragraph.
+#[derive(Debug, Clone)]
+pub struct IndexDoc {
+    pub media_id: String,
+    pub kind: &'static str,
+    pub segment_id: Option<String>,
+    pub track: Option<String>,
+    pub start: Option<f64>,
+    pub text: String,
+}
+
+struct Slot {
+    doc: IndexDoc,
+    terms: Vec<String>,
+    len: u32,
+}
+
+/// Inverted index over many media items; [`InvertedIndex::replace`] swaps all documents of one key.
+#[derive(Default)]
+pub struct InvertedIndex {
+    slots: Vec<Option<Slot>>,
+    free: Vec<usize>,
+    postings: HashMap<String, HashMap<usize, u32>>,
+    keys: HashMap<String, Vec<usize>>,
+    total_len: u64,
+    live: usize,
+}
+
+impl InvertedIndex {
+    pub fn remove(&mut self, key: &str) {
+        let Some(ids) = self.keys.remove(key) else {
+            return;
+        };
+        for id in ids {
+            let Some(slot) = self.slots[id].take() else {
+                continue;
+            };
+            for t in &slot.terms {
+                if let Some(p) = self.postings.get_mut(t) {
+                    p.remove(&id);
+                    if p.is_empty() {
+                        self.postings.remove(t);
+                    }
+                }
+            }
+            self.total_len -= slot.len as u64;
+            self.live -= 1;
+            self.free.push(id);
+        }
+    }
+
+    pub fn replace(&mut self, key: &str, docs: Vec<IndexDoc>) {
+        self.remove(key);
+        let mut ids = Vec::with_capacity(docs.len());
+        for doc in docs {
+            let mut tf: HashMap<String, u32> = HashMap::new();
+            let tokens = tokenize(&doc.text);
+            let len = tokens.len() as u32;
+            for t in tokens {
+                *tf.entry(t).or_default() += 1;
+            }
+            if tf.is_empty() {
+                continue;
+            }
+            let id = match self.free.pop() {
+                Some(id) => id,
+                None => {
+                    self.slots.push(None);
+                    self.slots.len() - 1
+                }
+            };
+            for (t, n) in &tf {
+                self.postings.entry(t.clone()).or_default().insert(id, *n);
+            }
+            self.slots[id] = Some(Slot { doc, terms: tf.into_keys().collect(), len });
+            self.total_len += len as u64;
+            self.live += 1;
+            ids.push(id);
+        }
+        self.keys.insert(key.to_string(), ids);
+    }
+
+    /// BM25-ranked documents matching any query token, best first.
+    pub fn search(&self, query: &str, filter: impl Fn(&IndexDoc) -> bool) -> Vec<(&IndexDoc, f32)> {
+        let q = query_terms(query, self.postings.keys());
+        let n = self.live as f32;
+        let avg = if self.live == 0 { 1.0 } else { (self.total_len as f32 / n).max(1.0) };
+        let mut acc: HashMap<usize, f32> = HashMap::new();
+        for t in &q {
+            let Some(p) = self.postings.get(t) else {
+                continue;
+            };
+            let df = p.len() as f32;
+            let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+            for (&id, &f) in p {
+                let Some(slot) = self.slots[id].as_ref() else {
+                    continue;
+                };
+                let f = f as f32;
+                let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * slot.len as f32 / avg);
+                *acc.entry(id).or_default() += idf * f * (BM25_K1 + 1.0) / (f + norm);
+            }
+        }
+        let mut hits: Vec<(&IndexDoc, f32)> = acc
+            .into_iter()
+            .filter_map(|(id, score)| self.slots[id].as_ref().map(|s| (&s.doc, score)))
+            .filter(|(doc, _)| filter(doc))
+            .collect();
+        hits.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        hits
+    }
+}
+
+/// Cuts a snippet of at most `max_chars` around the first query match.
+/// Returns the snippet and the matched ranges within it as `(char_start, char_end)`.
+pub fn highlight_snippet(text: &str, query: &str, max_chars: usize) -> (String, Vec<(usize, usize)>) {
+    let q: std::collections::HashSet<String> = tokenize(query).into_iter().collect();
+    let singles: Vec<char> = q.iter().filter_map(|t| single_cjk(t)).collect();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (t, s, e) in tokenize_with_offsets(text) {
+        let (s, e) = if q.contains(&t) {
+            (s, e)
+        } else if let Some(k) = t.chars().position(|c| singles.contains(&c)) {
+            (s + k, s + k + 1)
+        } else {
+            continue;
+        };
+        match ranges.last_mut() {
+            Some(last) if s <= last.1 => last.1 = last.1.max(e),
+            _ => ranges.push((s, e)),
+        }
+    }
+
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= max_chars {
+        return (text.to_string(), ranges);
+    }
+    let first = ranges.first().map(|r| r.0).unwrap_or(0);
+    let start = first.saturating_sub(max_chars / 4).min(chars.len() - max_chars);
+    let end = start + max_chars;
+
+    let mut snippet = String::new();
+    let mut shift = start;
+    if start > 0 {
+        snippet.push('…');
+        shift -= 1;
+    }
+    snippet.extend(&chars[start..end]);
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    let ranges = ranges
+        .into_iter()
+        .filter(|&(s, e)| s >= start && e <= end)
+        .map(|(s, e)| (s - shift, e - shift))
+        .collect();
+    (snippet, ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(media_id: &str, kind: &'static str, text: &str) -> IndexDoc {
+        IndexDoc {
+            media_id: media_id.to_string(),
+            kind,
+            segment_id: None,
+            track: None,
+            start: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn tokenizes_words_and_cjk_bigrams() {
+        assert_eq!(tokenize("Hello, a World 42"), vec!["hello", "world", "42"]);
+        assert_eq!(tokenize("会议记录"), vec!["会议", "议记", "记录"]);
+        assert_eq!(tokenize("会 x"), vec!["会"]);
+        assert_eq!(
+            tokenize_with_offsets("ok 会议"),
+            vec![("ok".to_string(), 0, 2), ("会议".to_string(), 3, 5)]
+        );
+    }
+
+    #[test]
+    fn bm25_ranks_matching_docs() {
+        let docs = ["the cat sat", "a dog barked at the cat cat", "nothing here"];
+        let scores = Bm25::new(&docs).scores("cat");
+        assert!(scores[1] > scores[0] && scores[0] > 0.0);
+        assert_eq!(scores[2], 0.0);
+
+        let scores = Bm25::new(&["今天开会", "明天休息"]).scores("会");
+        assert!(scores[0] > 0.0);
+        assert_eq!(scores[1], 0.0);
+    }
+
+    #[test]
+    fn rrf_fuses_ranks_and_skips_zero_scores() {
+        let fused = reciprocal_rank_fusion(&[vec![3.0, 1.0, 0.0], vec![0.0, 0.9, 0.5]], 60.0);
+        assert!(fused[1] > fused[0]);
+        assert!(fused[0] > fused[2]);
+        assert!(fused[2] > 0.0);
+        assert_eq!(reciprocal_rank_fusion(&[vec![0.0, 0.0]], 60.0), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn inverted_index_replace_remove_and_filter() {
+        let mut index = InvertedIndex::default();
+        index.replace("a", vec![doc("a", "transcript", "quarterly budget review"), doc("a", "note", "budget")]);
+        index.replace("b", vec![doc("b", "summary", "会议纪要：预算")]);
+
+        let hits = index.search("budget", |_| true);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].0.kind, "note");
+        assert_eq!(index.search("budget", |d| d.kind == "transcript").len(), 1);
+        assert_eq!(index.search("预算", |_| true)[0].0.media_id, "b");
+        assert_eq!(index.search("会", |_| true)[0].0.media_id, "b");
+
+        index.replace("a", vec![doc("a", "transcript", "travel plans")]);
+        assert!(index.search("budget", |_| true).is_empty());
+        index.remove("b");
+        assert!(index.search("预算", |_| true).is_empty());
+        assert_eq!(index.search("travel", |_| true).len(), 1);
+    }
+
+    #[test]
+    fn highlights_matches_within_snippet() {
+        let (snippet, ranges) = highlight_snippet("The Budget and the budget", "budget", 100);
+        assert_eq!(snippet, "The Budget and the budget");
+        assert_eq!(ranges, vec![(4, 10), (19, 25)]);
+
+        let (_, ranges) = highlight_snippet("今天开会讨论", "会", 100);
+        assert_eq!(ranges, vec![(3, 4)]);
+
+        let text = format!("{} needle {}", "x ".repeat(50), "y ".repeat(50));
+        let (snippet, ranges) = highlight_snippet(&text, "needle", 20);
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        let chars: Vec<char> = snippet.chars().collect();
+        let (s, e) = ranges[0];
+        assert_eq!(chars[s..e].iter().collect::<String>(), "needle");
+    }
+}
@@ -0,0 +1,933 @@
+//! Subtitle file parsing (SRT, WebVTT, ASS/SSA, YouTube json3) and writers (SRT, WebVTT, ASS, TTML).
+
+/// A single timed cue, times in seconds.
+#[derive(Debug, Clone)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    /// ASS style name, kept only when styling is preserved.
+    pub style: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+    Ass,
+    Json3,
+}
+
+impl SubtitleFormat {
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.trim().trim_start_matches('.').to_lowercase().as_str() {
+            "srt" => Some(Self::Srt),
+            "vtt" | "webvtt" => Some(Self::Vtt),
+            "ass" | "ssa" => Some(Self::Ass),
+            "json3" => Some(Self::Json3),
+            _ => None,
+        }
+    }
+
+    /// Uses the file extension when known, otherwise sniffs the content.
+    pub fn detect(file_name: Option<&str>, raw: &str) -> Self {
+        if let Some(fmt) = file_name
+            .and_then(|n| n.rsplit_once('.'))
+            .and_then(|(_, ext)| Self::from_extension(ext))
+        {
+            return fmt;
+        }
+        let head = raw.trim_start_matches('\u{feff}').trim_start();
+        if head.starts_with("WEBVTT") {
+            Self::Vtt
+        } else if head.starts_with("[Script Info]") || raw.contains("\n[Events]") {
+            Self::Ass
+        } else if head.starts_with('{') {
+            Self::Json3
+        } else {
+            Self::Srt
+        }
+    }
+}
+
+/// Parses a subtitle file. With `keep_styling`, inline tags (`<i>`, `{\b1}`) and ASS style names are kept.
+pub fn parse_subtitles(format: SubtitleFormat, raw: &str, keep_styling: bool) -> Result<Vec<Cue>, String> {
+    match format {
+        SubtitleFormat::Srt | SubtitleFormat::Vtt => Ok(parse_srt_or_vtt(raw, keep_styling)),
+        SubtitleFormat::Ass => parse_ass(raw, keep_styling),
+        SubtitleFormat::Json3 => parse_json3(raw),
+    }
+}
+
+/// Decodes subtitle bytes, honouring UTF-8/UTF-16 byte order marks (ASS files are often UTF-16).
+pub fn decode_text(bytes: &[u8]) -> String {
+    let utf16 = |data: &[u8], le: bool| {
+        let units: Vec<u16> = data
+            .chunks_exact(2)
+            .map(|c| if le { u16::from_le_bytes([c[0], c[1]]) } else { u16::from_be_bytes([c[0], c[1]]) })
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, true),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, false),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Parses `HH:MM:SS,mmm`, `HH:MM:SS.mmm` and `MM:SS.mmm`.
+pub fn parse_timestamp(s: &str) -> Option<f64> {
+    let s = s.trim().replace(',', ".");
+    // Only plain digits and dots: f64 parsing would also accept signs, "inf" and "NaN".
+    let num = |p: &str| -> Option<f64> {
+        if !p.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+            return None;
+        }
+        p.parse().ok()
+    };
+    let parts: Vec<&str> = s.split(':').collect();
+    let (h, m, sec) = match parts.as_slice() {
+        [h, m, sec] => (num(h)?, num(m)?, num(sec)?),
+        [m, sec] => (0.0, num(m)?, num(sec)?),
+        _ => return None,
+    };
+    Some(h * 3600.0 + m * 60.0 + sec)
+}
+
+/// SRT and WebVTT share the same block layout: optional id line, a `start --> end` line, then text.
+fn parse_srt_or_vtt(raw: &str, keep_styling: bool) -> Vec<Cue> {
+    let text = raw.trim_start_matches('\u{feff}').replace("\r\n", "\n").replace('\r', "\n");
+    let mut cues = Vec::new();
+
+    for block in text.split("\n\n") {
+        let mut lines = block.lines().skip_while(|l| !l.contains("-->"));
+        let Some(timing) = lines.next() else {
+            continue;
+        };
+        let Some((start_raw, rest)) = timing.split_once("-->") else {
+            continue;
+        };
+        // WebVTT cue settings (`align:start position:10%`) follow the end time.
+        let end_raw = rest.split_whitespace().next().unwrap_or("");
+        let (Some(start), Some(end)) = (parse_timestamp(start_raw), parse_timestamp(end_raw)) else {
+            continue;
+        };
+
+        let body: Vec<String> = lines
+            .map(|l| if keep_styling { l.to_string() } else { strip_markup(l) })
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect();
+        if body.is_empty() {
+            continue;
+        }
+        cues.push(Cue {
+            start,
+            end: end.max(start),
+            text: body.join("\n"),
+            style: None,
+        });
+    }
+    cues
+}
+
+/// Reads `Dialogue:` lines from the `[Events]` section, using its `Format:` line for field order.
+fn parse_ass(raw: &str, keep_styling: bool) -> Result<Vec<Cue>, String> {
+    let text = raw.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let mut in_events = false;
+    let mut fields: Vec<String> = Vec::new();
+    let mut cues = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("Format:") {
+            fields = rest.split(',').map(|f| f.trim().to_lowercase()).collect();
+            continue;
+        }
+        let Some(rest) = line.strip_prefix("Dialogue:") else {
+            continue;
+        };
+        if fields.is_empty() {
+            // SSA/ASS default order when the Format line is missing.
+            fields = ["layer", "start", "end", "style", "name", "marginl", "marginr", "marginv", "effect", "text"]
+                .iter()
+                .map(|f| f.to_string())
+                .collect();
+        }
+
+        // Text is always last and may itself contain commas.
+        let values: Vec<&str> = rest.trim_start().splitn(fields.len(), ',').collect();
+        if values.len() < fields.len() {
+            continue;
+        }
+        let get = |name: &str| fields.iter().position(|f| f == name).map(|i| values[i].trim());
+        let (Some(start), Some(end)) = (get("start").and_then(parse_timestamp), get("end").and_then(parse_timestamp)) else {
+            continue;
+        };
+        let raw_text = values[fields.len() - 1];
+        let body = raw_text.replace("\\N", "\n").replace("\\n", "\n").replace("\\h", " ");
+        let body = if keep_styling { body } else { strip_markup(&body) };
+        let body = body
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if body.is_empty() {
+            continue;
+        }
+        cues.push(Cue {
+            start,
+            end: end.max(start),
+            text: body,
+            style: if keep_styling { get("style").map(|s| s.to_string()) } else { None },
+        });
+    }
+
+    if fields.is_empty() && cues.is_empty() {
+        return Err("no [Events] section found in ASS/SSA file".to_string());
+    }
+    // Dialogue lines are not required to be in time order.
+    cues.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Ok(cues)
+}
+
+fn parse_json3(raw: &str) -> Result<Vec<Cue>, String> {
+    let v: serde_json::Value = serde_json::from_str(raw.trim_start_matches('\u{feff}'))
+        .map_err(|e| format!("parse json3 subtitles failed: {e}"))?;
+    let events = v
+        .get("events")
+        .and_then(|e| e.as_array())
+        .ok_or_else(|| "json3 subtitles have no events".to_string())?;
+
+    let mut cues = Vec::new();
+    for ev in events {
+        let Some(segs) = ev.get("segs").and_then(|s| s.as_array()) else {
+            continue;
+        };
+        let text: String = segs
+            .iter()
+            .filter_map(|s| s.get("utf8").and_then(|t| t.as_str()))
+            .collect();
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let start_ms = ev.get("tStartMs").and_then(|t| t.as_f64()).unwrap_or(0.0);
+        let dur_ms = ev.get("dDurationMs").and_then(|t| t.as_f64()).unwrap_or(0.0);
+        cues.push(Cue {
+            start: start_ms / 1000.0,
+            end: (start_ms + dur_ms) / 1000.0,
+            text: text.to_string(),
+            style: None,
+        });
+    }
+    Ok(cues)
+}
+
+/// Removes `<...>` tags (VTT voice/class/timestamp tags, SRT `<i>`) and `{\...}` override blocks.
+pub fn strip_markup(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut in_angle = false;
+    let mut in_brace = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '<' if !in_brace => in_angle = true,
+            '>' if in_angle => in_angle = false,
+            '{' if !in_angle && chars.peek() == Some(&'\\') => in_brace = true,
+            '}' if in_brace => in_brace = false,
+            _ if in_angle || in_brace => {}
+            _ => out.push(c),
+        }
+    }
+    out.replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+}
+
+/// Auto-generated captions repeat the previous line at the top of each cue ("roll-up" style).
+/// Drop the repeated lines and any cue that ends up empty or is a tiny transition cue.
+pub fn dedupe_rolling_cues(cues: Vec<Cue>) -> Vec<Cue> {
+    let mut out: Vec<Cue> = Vec::with_capacity(cues.len());
+    for cue in cues {
+        let prev_last = out
+            .last()
+            .and_then(|p| p.text.lines().last())
+            .map(|s| s.to_string());
+        let lines: Vec<&str> = cue
+            .text
+            .lines()
+            .skip_while(|l| prev_last.as_deref() == Some(*l))
+            .collect();
+        if lines.is_empty() {
+            if let Some(p) = out.last_mut() {
+                p.end = p.end.max(cue.end);
+            }
+            continue;
+        }
+        if cue.end - cue.start < 0.02 {
+            continue;
+        }
+        out.push(Cue {
+            text: lines.join("\n"),
+            ..cue
+        });
+    }
+    out
+}
+
+// ==================== Writers ====================
+
+/// One output format. `export_media` and friends pick writers by name via [`writer_for`].
+pub trait SubtitleWriter {
+    fn extension(&self) -> &'static str;
+    fn write(&self, cues: &[Cue]) -> String;
+}
+
+pub const DEFAULT_EXPORT_FORMATS: &[&str] = &["srt", "vtt"];
+
+/// Builds the writer for a format name (`srt`, `vtt`, `ass`, `ttml`, `dfxp`).
+pub fn writer_for(format: &str, language: &str, ass_style: &AssStyle) -> Option<Box<dyn SubtitleWriter>> {
+    match format.trim().to_lowercase().as_str() {
+        "srt" => Some(Box::new(SrtWriter)),
+        "vtt" | "webvtt" => Some(Box::new(VttWriter)),
+        "ass" | "ssa" => Some(Box::new(AssWriter {
+            style: ass_style.clone(),
+        })),
+        "ttml" => Some(Box::new(TtmlWriter {
+            language: language.to_string(),
+            dfxp: false,
+        })),
+        "dfxp" => Some(Box::new(TtmlWriter {
+            language: language.to_string(),
+            dfxp: true,
+        })),
+        _ => None,
+    }
+}
+
+fn split_time(seconds: f64) -> (u64, u64, u64, u64) {
+    let s = if seconds.is_finite() { seconds.max(0.0) } else { 0.0 };
+    let ms_total = (s * 1000.0).round() as u64;
+    let total_sec = ms_total / 1000;
+    (total_sec / 3600, (total_sec / 60) % 60, total_sec % 60, ms_total % 1000)
+}
+
+pub fn format_srt_time(seconds: f64) -> String {
+    let (h, m, s, ms) = split_time(seconds);
+    format!("{:02}:{:02}:{:02},{:03}", h, m, s, ms)
+}
+
+pub fn format_vtt_time(seconds: f64) -> String {
+    let (h, m, s, ms) = split_time(seconds);
+    format!("{:02}:{:02}:{:02}.{:03}", h, m, s, ms)
+}
+
+/// ASS uses centiseconds and a single-digit hour.
+pub fn format_ass_time(seconds: f64) -> String {
+    let s = if seconds.is_finite() { seconds.max(0.0) } else { 0.0 };
+    let cs_total = (s * 100.0).round() as u64;
+    let total_sec = cs_total / 100;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        total_sec / 3600,
+        (total_sec / 60) % 60,
+        total_sec % 60,
+        cs_total % 100
+    )
+}
+
+pub struct SrtWriter;
+
+impl SubtitleWriter for SrtWriter {
+    fn extension(&self) -> &'static str {
+        "srt"
+    }
+
+    fn write(&self, cues: &[Cue]) -> String {
+        let mut out = String::new();
+        for (i, c) in cues.iter().enumerate() {
+            out.push_str(&format!(
+                "{}\n{} --> {}\n{}\n\n",
+                i + 1,
+                format_srt_time(c.start),
+                format_srt_time(c.end),
+                c.text
+            ));
+        }
+        out
+    }
+}
+
+pub struct VttWriter;
+
+impl SubtitleWriter for VttWriter {
+    fn extension(&self) -> &'static str {
+        "vtt"
+    }
+
+    fn write(&self, cues: &[Cue]) -> String {
+        let mut out = String::from("WEBVTT\n\n");
+        for c in cues {
+            out.push_str(&format!(
+                "{} --> {}\n{}\n\n",
+                format_vtt_time(c.start),
+                format_vtt_time(c.end),
+                c.text
+            ));
+        }
+        out
+    }
+}
+
+/// Style of the generated `Default` ASS style. Colours are `#RRGGBB` (or `#AARRGGBB`).
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AssStyle {
+    pub font_name: String,
+    pub font_size: u32,
+    pub primary_color: String,
+    pub outline_color: String,
+    pub back_color: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub outline: f32,
+    pub shadow: f32,
+    /// `bottom`, `middle` or `top`.
+    pub position: String,
+    pub margin_v: u32,
+    pub play_res_x: u32,
+    pub play_res_y: u32,
+}
+
+impl Default for AssStyle {
+    fn default() -> Self {
+        Self {
+            font_name: "Arial".to_string(),
+            font_size: 48,
+            primary_color: "#FFFFFF".to_string(),
+            outline_color: "#000000".to_string(),
+            back_color: "#80000000".to_string(),
+            bold: false,
+            italic: false,
+            outline: 2.0,
+            shadow: 0.0,
+            position: "bottom".to_string(),
+            margin_v: 40,
+            play_res_x: 1920,
+            play_res_y: 1080,
+        }
+    }
+}
+
+impl AssStyle {
+    /// Numpad-style alignment (2 = bottom centre, 5 = middle, 8 = top).
+    pub fn alignment(&self) -> u8 {
+        match self.position.trim().to_lowercase().as_str() {
+            "top" => 8,
+            "middle" | "center" | "centre" => 5,
+            _ => 2,
+        }
+    }
+}
+
+/// `#RRGGBB` / `#AARRGGBB` (CSS-ish, alpha 00 = transparent) -> ASS `&HAABBGGRR` (alpha 00 = opaque).
+pub fn ass_color(css: &str) -> String {
+    let hex = css.trim().trim_start_matches('#');
+    // Only ASCII hex digits below, so byte slicing is on char boundaries.
+    let valid = hex.chars().all(|c| c.is_ascii_hexdigit());
+    let (a, rgb) = match hex.len() {
+        8 if valid => (u8::from_str_radix(&hex[..2], 16).unwrap_or(255), &hex[2..]),
+        6 if valid => (255, hex),
+        _ => (255, "FFFFFF"),
+    };
+    let r = u8::from_str_radix(&rgb[0..2], 16).unwrap_or(255);
+    let g = u8::from_str_radix(&rgb[2..4], 16).unwrap_or(255);
+    let b = u8::from_str_radix(&rgb[4..6], 16).unwrap_or(255);
+    format!("&H{:02X}{:02X}{:02X}{:02X}", 255 - a, b, g, r)
+}
+
+pub struct AssWriter {
+    pub style: AssStyle,
+}
+
+impl SubtitleWriter for AssWriter {
+    fn extension(&self) -> &'static str {
+        "ass"
+    }
+
+    fn write(&self, cues: &[Cue]) -> String {
+        let st = &self.style;
+        let flag = |b: bool| if b { -1 } else { 0 };
+        let mut out = String::new();
+        out.push_str("[Script Info]\nScriptType: v4.00+\nWrapStyle: 0\nScaledBorderAndShadow: yes\n");
+        out.push_str(&format!("PlayResX: {}\nPlayResY: {}\n\n", st.play_res_x, st.play_res_y));
+        out.push_str("[V4+ Styles]\n");
+        out.push_str("Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n");
+        out.push_str(&format!(
+            "Style: Default,{},{},{},{},{},{},{},{},0,0,100,100,0,0,1,{},{},{},20,20,{},1\n\n",
+            st.font_name.replace(',', " "),
+            st.font_size,
+            ass_color(&st.primary_color),
+            ass_color(&st.primary_color),
+            ass_color(&st.outline_color),
+            ass_color(&st.back_color),
+            flag(st.bold),
+            flag(st.italic),
+            st.outline,
+            st.shadow,
+            st.alignment(),
+            st.margin_v
+        ));
+        out.push_str("[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n");
+        for c in cues {
+            // Only styles we declared are valid; imported style names fall back to Default.
+            out.push_str(&format!(
+                "Dialogue: 0,{},{},Default,,0,0,0,,{}\n",
+                format_ass_time(c.start),
+                format_ass_time(c.end),
+                c.text.replace("\r\n", "\n").replace('\n', "\\N")
+            ));
+        }
+        out
+    }
+}
+
+pub struct TtmlWriter {
+    pub language: String,
+    /// DFXP is the older name of the same format; only the extension differs.
+    pub dfxp: bool,
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl SubtitleWriter for TtmlWriter {
+    fn extension(&self) -> &'static str {
+        if self.dfxp {
+            "dfxp"
+        } else {
+            "ttml"
+        }
+    }
+
+    fn write(&self, cues: &[Cue]) -> String {
+        let lang = if self.language.trim().is_empty() { "und" } else { self.language.trim() };
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str(&format!(
+            "<tt xmlns=\"http://www.w3.org/ns/ttml\" xmlns:tts=\"http://www.w3.org/ns/ttml#styling\" xml:lang=\"{}\">\n",
+            xml_escape(lang)
+        ));
+        out.push_str("  <body>\n    <div>\n");
+        for c in cues {
+            let text = c
+                .text
+                .lines()
+                .map(xml_escape)
+                .collect::<Vec<_>>()
+                .join("<br/>");
+            out.push_str(&format!(
+                "      <p begin=\"{}\" end=\"{}\">{}</p>\n",
+                format_vtt_time(c.start),
+                format_vtt_time(c.end),
+                text
+            ));
+        }
+        out.push_str("    </div>\n  </body>\n</tt>\n");
+        out
+    }
+}
+
+/// Stacks `secondary` under `primary`: each secondary cue is attached to the primary cue it overlaps most.
+pub fn stack_bilingual(primary: &[Cue], secondary: &[Cue]) -> Vec<Cue> {
+    let mut attached: Vec<Vec<&str>> = vec![Vec::new(); primary.len()];
+    for s in secondary {
+        let best = primary
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (i, p.end.min(s.end) - p.start.max(s.start)))
+            .filter(|(_, overlap)| *overlap > 0.0)
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        if let Some((i, _)) = best {
+            attached[i].push(s.text.as_str());
+        }
+    }
+    primary
+        .iter()
+        .zip(attached)
+        .map(|(p, parts)| {
+            let text = if parts.is_empty() {
+                p.text.clone()
+            } else {
+                format!("{}\n{}", p.text, parts.join(" "))
+            };
+            Cue {
+                text,
+                ..p.clone()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(cues: &[Cue]) -> Vec<(f64, f64, &str)> {
+        cues.iter().map(|c| (c.start, c.end, c.text.as_str())).collect()
+    }
+
+    #[test]
+    fn detect_by_extension_then_content() {
+        let cases = [
+            (Some("a.SRT"), "", SubtitleFormat::Srt),
+            (Some("a.webvtt"), "", SubtitleFormat::Vtt),
+            (Some("a.ssa"), "", SubtitleFormat::Ass),
+            (Some("a.en.json3"), "", SubtitleFormat::Json3),
+            (None, "\u{feff}WEBVTT\n\n", SubtitleFormat::Vtt),
+            (Some("a.txt"), "[Script Info]\nTitle: x", SubtitleFormat::Ass),
+            (None, "; comment\n[Events]\nFormat: Start", SubtitleFormat::Ass),
+            (None, "  {\"events\": []}", SubtitleFormat::Json3),
+            (None, "1\n00:00:01,000 --> 00:00:02,000\nhi", SubtitleFormat::Srt),
+        ];
+        for (name, raw, want) in cases {
+            assert_eq!(SubtitleFormat::detect(name, raw), want, "{name:?} {raw:?}");
+        }
+    }
+
+    #[test]
+    fn decode_text_handles_boms() {
+        let utf16 = |s: &str, le: bool| {
+            let mut out = if le { vec![0xFF, 0xFE] } else { vec![0xFE, 0xFF] };
+            for u in s.encode_utf16() {
+                out.extend(if le { u.to_le_bytes() } else { u.to_be_bytes() });
+            }
+            out
+        };
+        let cases: [(Vec<u8>, &str); 4] = [
+            (b"\xEF\xBB\xBFhello".to_vec(), "hello"),
+            ("plain 字幕".as_bytes().to_vec(), "plain 字幕"),
+            (utf16("[Script Info]\r\n字幕", true), "[Script Info]\r\n字幕"),
+            (utf16("WEBVTT", false), "WEBVTT"),
+        ];
+        for (bytes, want) in cases {
+            assert_eq!(decode_text(&bytes), want);
+        }
+    }
+
+    #[test]
+    fn parse_timestamps() {
+        let cases = [
+            ("00:00:01,500", Some(1.5)),
+            ("01:02:03.250", Some(3723.25)),
+            ("02:03.5", Some(123.5)),
+            (" 00:00:00.000 ", Some(0.0)),
+            ("12", None),
+            ("aa:bb:cc", None),
+            ("-00:01.000", None),
+            ("00:00:inf", None),
+        ];
+        for (raw, want) in cases {
+            assert_eq!(parse_timestamp(raw), want, "{raw}");
+        }
+    }
+
+    #[test]
+    fn srt_with_crlf_bom_and_markup() {
+        let raw = "\u{feff}1\r\n00:00:01,000 --> 00:00:02,500\r\n<i>Hello</i>\r\nworld\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,000\r\n{\\b1}Bold{\\b0} &amp; more\r\n";
+        let cues = parse_subtitles(SubtitleFormat::Srt, raw, false).unwrap();
+        assert_eq!(texts(&cues), vec![(1.0, 2.5, "Hello\nworld"), (3.0, 4.0, "Bold & more")]);
+
+        let styled = parse_subtitles(SubtitleFormat::Srt, raw, true).unwrap();
+        assert_eq!(styled[0].text, "<i>Hello</i>\nworld");
+    }
+
+    #[test]
+    fn vtt_cue_settings_ids_and_notes() {
+        let raw = "WEBVTT - title\n\nNOTE a comment\n\nintro\n00:01.000 --> 00:02.000 align:start position:10%\n<v Roger>Hi there\n\n00:00:03.000 --> 00:00:04.000 line:0\n<c.yellow>Bye</c>\n\n00:00:05.000 --> 00:00:06.000\n\n";
+        let cues = parse_subtitles(SubtitleFormat::Vtt, raw, false).unwrap();
+        assert_eq!(texts(&cues), vec![(1.0, 2.0, "Hi there"), (3.0, 4.0, "Bye")]);
+    }
+
+    #[test]
+    fn srt_end_before_start_is_clamped() {
+        let cues = parse_subtitles(SubtitleFormat::Srt, "1\n00:00:05,000 --> 00:00:04,000\nx\n", false).unwrap();
+        assert_eq!(texts(&cues), vec![(5.0, 5.0, "x")]);
+    }
+
+    #[test]
+    fn ass_format_variants() {
+        let events = "Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello, world\\Nsecond line\n";
+        let cases = [
+            // Standard Format line, CRLF and a BOM.
+            format!("\u{feff}[Script Info]\r\nTitle: t\r\n\r\n[Events]\r\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\r\n{}", events.replace('\n', "\r\n")),
+            // No Format line: default ASS field order.
+            format!("[Events]\n{events}"),
+            // Reordered fields.
+            "[Events]\nFormat: Start, End, Style, Layer, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0:00:01.00,0:00:02.00,Default,0,,0,0,0,,Hello, world\\Nsecond line\n".to_string(),
+        ];
+        for raw in cases {
+            let cues = parse_subtitles(SubtitleFormat::Ass, &raw, false).unwrap();
+            assert_eq!(texts(&cues), vec![(1.0, 2.0, "Hello, world\nsecond line")], "{raw}");
+        }
+    }
+
+    #[test]
+    fn ass_sorts_dialogue_and_keeps_styles() {
+        let raw = "[V4+ Styles]\nFormat: Name, Fontname\nStyle: Sign,Arial\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:05.00,0:00:06.00,Sign,,0,0,0,,{\\an8}Later\nComment: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,ignored\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Earlier\\hone\n";
+        let cues = parse_subtitles(SubtitleFormat::Ass, raw, false).unwrap();
+        assert_eq!(texts(&cues), vec![(1.0, 2.0, "Earlier one"), (5.0, 6.0, "Later")]);
+
+        let styled = parse_subtitles(SubtitleFormat::Ass, raw, true).unwrap();
+        assert_eq!(styled[1].text, "{\\an8}Later");
+        assert_eq!(styled[1].style.as_deref(), Some("Sign"));
+    }
+
+    #[test]
+    fn ass_without_events_is_an_error() {
+        assert!(parse_subtitles(SubtitleFormat::Ass, "[Script Info]\nTitle: x\n", false).is_err());
+    }
+
+    #[test]
+    fn json3_events() {
+        let raw = r#"{"events":[
+            {"tStartMs":0,"dDurationMs":5000,"id":1,"wpWinPosId":1},
+            {"tStartMs":200,"dDurationMs":2160,"wWinId":1,"segs":[{"utf8":"hello"},{"utf8":" everyone","tOffsetMs":480}]},
+            {"tStartMs":2360,"dDurationMs":10,"wWinId":1,"aAppend":1,"segs":[{"utf8":"\n"}]},
+            {"tStartMs":2370,"dDurationMs":1630,"wWinId":1,"segs":[{"utf8":"to the channel"}]}
+        ]}"#;
+        let cues = parse_subtitles(SubtitleFormat::Json3, raw, false).unwrap();
+        assert_eq!(texts(&cues), vec![(0.2, 2.36, "hello everyone"), (2.37, 4.0, "to the channel")]);
+        assert!(parse_subtitles(SubtitleFormat::Json3, "{}", false).is_err());
+        assert!(parse_subtitles(SubtitleFormat::Json3, "not json", false).is_err());
+    }
+
+    #[test]
+    fn dedupe_youtube_rolling_vtt() {
+        // Trimmed from a yt-dlp auto-caption download: each cue repeats the previous line,
+        // with 10 ms "hold" cues between them.
+        let raw = "WEBVTT
+Kind: captions
+Language: en
+
+00:00:00.000 --> 00:00:02.350 align:start position:0%
+ 
+hello<00:00:00.480><c> everyone</c><00:00:00.960><c> welcome</c>
+
+00:00:02.350 --> 00:00:02.360 align:start position:0%
+hello everyone welcome
+ 
+
+00:00:02.360 --> 00:00:04.790 align:start position:0%
+hello everyone welcome
+to<00:00:02.800><c> the</c><00:00:03.120><c> channel</c>
+
+00:00:04.790 --> 00:00:04.800 align:start position:0%
+to the channel
+ 
+
+00:00:04.800 --> 00:00:07.000 align:start position:0%
+to the channel
+today<00:00:05.200><c> we</c><00:00:05.600><c> look</c><00:00:06.000><c> at</c>
+
+00:00:07.000 --> 00:00:07.010 align:start position:0%
+today we look at
+ 
+
+00:00:07.010 --> 00:00:09.500 align:start position:0%
+today we look at
+today we look at
+";
+        let cues = parse_subtitles(SubtitleFormat::Vtt, raw, false).unwrap();
+        assert_eq!(cues.len(), 7);
+        let deduped = dedupe_rolling_cues(cues);
+        assert_eq!(
+            texts(&deduped),
+            vec![
+                (0.0, 2.36, "hello everyone welcome"),
+                (2.36, 4.8, "to the channel"),
+                (4.8, 9.5, "today we look at"),
+            ]
+        );
+    }
+
+    #[test]
+    fn dedupe_keeps_non_rolling_cues_and_drops_blips() {
+        let raw = "1\n00:00:01,000 --> 00:00:02,000\nSame line\n\n2\n00:00:03,000 --> 00:00:04,000\nOther\nSame line\n\n3\n00:00:04,000 --> 00:00:04,010\nBlink\n";
+        let cues = parse_subtitles(SubtitleFormat::Srt, raw, false).unwrap();
+        assert_eq!(
+            texts(&dedupe_rolling_cues(cues)),
+            vec![(1.0, 2.0, "Same line"), (3.0, 4.0, "Other\nSame line")]
+        );
+    }
+
+    #[test]
+    fn ass_color_rgb() {
+        assert_eq!(ass_color("#FF8000"), "&H000080FF");
+        assert_eq!(ass_color("  ff8000 "), "&H000080FF");
+    }
+
+    #[test]
+    fn ass_color_argb() {
+        // CSS alpha 80 -> ASS alpha 7F (inverted).
+        assert_eq!(ass_color("#80102030"), "&H7F302010");
+        assert_eq!(ass_color("#00FFFFFF"), "&HFFFFFFFF");
+    }
+
+    #[test]
+    fn ass_color_invalid_falls_back_to_white() {
+        assert_eq!(ass_color(""), "&H00FFFFFF");
+        assert_eq!(ass_color("#12345"), "&H00FFFFFF");
+        assert_eq!(ass_color("#GG0000"), "&H00FFFFFF");
+    }
+
+    #[test]
+    fn ass_color_non_ascii_does_not_panic() {
+        // 6 bytes, but 'é' spans two of them.
+        assert_eq!(ass_color("1é234"), "&H00FFFFFF");
+        assert_eq!(ass_color("#ééé"), "&H00FFFFFF");
+        assert_eq!(ass_color("红绿蓝"), "&H00FFFFFF");
+    }
+
+    fn cue(start: f64, end: f64, text: &str) -> Cue {
+        Cue {
+            start,
+            end,
+            text: text.to_string(),
+            style: None,
+        }
+    }
+
+    #[test]
+    fn time_formatting_rounds_and_carries() {
+        let cases = [
+            (0.0, "00:00:00,000", "00:00:00.000", "0:00:00.00"),
+            (0.9995, "00:00:01,000", "00:00:01.000", "0:00:01.00"),
+            (1.9994, "00:00:01,999", "00:00:01.999", "0:00:02.00"),
+            (59.9995, "00:01:00,000", "00:01:00.000", "0:01:00.00"),
+            (3599.9995, "01:00:00,000", "01:00:00.000", "1:00:00.00"),
+            (36000.125, "10:00:00,125", "10:00:00.125", "10:00:00.13"),
+            (-1.0, "00:00:00,000", "00:00:00.000", "0:00:00.00"),
+            (f64::NAN, "00:00:00,000", "00:00:00.000", "0:00:00.00"),
+        ];
+        for (secs, srt, vtt, ass) in cases {
+            assert_eq!(format_srt_time(secs), srt, "{secs}");
+            assert_eq!(format_vtt_time(secs), vtt, "{secs}");
+            assert_eq!(format_ass_time(secs), ass, "{secs}");
+        }
+    }
+
+    #[test]
+    fn srt_and_vtt_golden_output() {
+        let cues = [cue(1.0, 2.5, "Hello\nworld"), cue(61.25, 3723.0, "Bye")];
+        assert_eq!(
+            SrtWriter.write(&cues),
+            "1\n00:00:01,000 --> 00:00:02,500\nHello\nworld\n\n2\n00:01:01,250 --> 01:02:03,000\nBye\n\n"
+        );
+        assert_eq!(
+            VttWriter.write(&cues),
+            "WEBVTT\n\n00:00:01.000 --> 00:00:02.500\nHello\nworld\n\n00:01:01.250 --> 01:02:03.000\nBye\n\n"
+        );
+        assert_eq!(VttWriter.write(&[]), "WEBVTT\n\n");
+    }
+
+    #[test]
+    fn written_srt_and_vtt_parse_back() {
+        let cues = [cue(0.5, 1.75, "a, b"), cue(2.0, 3.0, "two\nlines")];
+        for (format, out) in [
+            (SubtitleFormat::Srt, SrtWriter.write(&cues)),
+            (SubtitleFormat::Vtt, VttWriter.write(&cues)),
+        ] {
+            let back = parse_subtitles(format, &out, false).unwrap();
+            assert_eq!(texts(&back), texts(&cues));
+        }
+    }
+
+    #[test]
+    fn ass_writer_line_breaks_and_style() {
+        let style = AssStyle {
+            font_name: "Noto, Sans".to_string(),
+            position: "top".to_string(),
+            ..AssStyle::default()
+        };
+        let out = AssWriter { style }.write(&[cue(1.0, 2.0, "line1\r\nline2\nline3"), cue(3.0, 4.0, "a, b")]);
+        assert!(out.contains("Style: Default,Noto  Sans,48,&H00FFFFFF,&H00FFFFFF,&H00000000,&H7F000000,0,0,0,0,100,100,0,0,1,2,0,8,20,20,40,1\n"));
+        assert!(out.contains("Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,line1\\Nline2\\Nline3\n"));
+        assert!(out.contains("Dialogue: 0,0:00:03.00,0:00:04.00,Default,,0,0,0,,a, b\n"));
+
+        let back = parse_subtitles(SubtitleFormat::Ass, &out, false).unwrap();
+        assert_eq!(texts(&back), vec![(1.0, 2.0, "line1\nline2\nline3"), (3.0, 4.0, "a, b")]);
+    }
+
+    #[test]
+    fn ttml_escapes_and_breaks_lines() {
+        let writer = TtmlWriter {
+            language: " ".to_string(),
+            dfxp: false,
+        };
+        let out = writer.write(&[cue(1.0, 2.0, "<b>Tom & \"Jerry\"</b>\nnext")]);
+        assert!(out.contains("xml:lang=\"und\""));
+        assert!(out.contains(
+            "      <p begin=\"00:00:01.000\" end=\"00:00:02.000\">&lt;b&gt;Tom &amp; &quot;Jerry&quot;&lt;/b&gt;<br/>next</p>\n"
+        ));
+        assert_eq!(writer.extension(), "ttml");
+
+        let dfxp = TtmlWriter {
+            language: "a\"b".to_string(),
+            dfxp: true,
+        };
+        assert!(dfxp.write(&[]).contains("xml:lang=\"a&quot;b\""));
+        assert_eq!(dfxp.extension(), "dfxp");
+    }
+
+    #[test]
+    fn writer_for_names() {
+        let style = AssStyle::default();
+        let cases = [
+            ("srt", Some("srt")),
+            (" WebVTT ", Some("vtt")),
+            ("ssa", Some("ass")),
+            ("ttml", Some("ttml")),
+            ("dfxp", Some("dfxp")),
+            ("txt", None),
+        ];
+        for (name, ext) in cases {
+            assert_eq!(writer_for(name, "en", &style).map(|w| w.extension()), ext, "{name}");
+        }
+    }
+
+    #[test]
+    fn stack_bilingual_attaches_by_largest_overlap() {
+        let primary = [cue(0.0, 2.0, "One"), cue(2.0, 4.0, "Two"), cue(4.0, 6.0, "Three")];
+        let secondary = [
+            // Two short cues inside the first primary.
+            cue(0.0, 1.0, "Un"),
+            cue(1.0, 2.0, "Deux"),
+            // Straddles primaries 2 and 3, mostly inside 3.
+            cue(3.5, 5.5, "Trois"),
+            // Touches primary 3 only at its end: no overlap, dropped.
+            cue(6.0, 7.0, "Quatre"),
+        ];
+        assert_eq!(
+            texts(&stack_bilingual(&primary, &secondary)),
+            vec![(0.0, 2.0, "One\nUn Deux"), (2.0, 4.0, "Two"), (4.0, 6.0, "Three\nTrois")]
+        );
+        assert_eq!(texts(&stack_bilingual(&primary, &[])), texts(&primary));
+        assert!(stack_bilingual(&[], &secondary).is_empty());
+    }
+}
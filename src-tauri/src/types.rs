@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::errors::ErrorCode;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum JobType {
@@ -30,6 +32,42 @@ pub struct JobProgressEvent {
     pub status: JobStatus,
     pub progress: f32,
     pub message: Option<String>,
+    /// Set on failed events so the UI can offer a targeted fix.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+}
+
+/// Last known state of a job, as tracked by the backend job registry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    #[serde(flatten)]
+    pub event: JobProgressEvent,
+    pub created_at: String,
+    pub updated_at: String,
 }
 
 pub const EVENT_JOB_PROGRESS: &str = "job_progress";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatStreamKind {
+    Content,
+    Reasoning,
+    Done,
+    Error,
+    Cancelled,
+}
+
+/// One streamed piece of an assistant reply. `text` is the delta for content/reasoning
+/// and the error message for `error`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatStreamEvent {
+    pub message_id: String,
+    pub media_id: String,
+    pub kind: ChatStreamKind,
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+}
+
+pub const EVENT_CHAT_STREAM: &str = "chat_stream";
@@ -0,0 +1,80 @@
+//! Turns voice-activity spans into transcription chunks.
+//!
+//! Spans come from `sherpa-onnx-vad` in milliseconds. Each is padded so word edges are not clipped,
+//! and neighbours separated by short pauses are packed together up to a maximum chunk length.
+
+const PAD_MS: i64 = 250;
+const JOIN_GAP_MS: i64 = 800;
+
+/// Pads speech spans and packs neighbours separated by short pauses into chunks of at most `max_ms`.
+/// `spans` must be sorted by start.
+pub fn plan_vad_chunks(spans: &[(i64, i64)], max_ms: i64) -> Vec<(i64, i64)> {
+    let mut out: Vec<(i64, i64)> = Vec::new();
+    for &(s, e) in spans {
+        let s = s.saturating_sub(PAD_MS).max(0);
+        let e = e.saturating_add(PAD_MS);
+        match out.last_mut() {
+            Some(last) if s <= last.1.saturating_add(JOIN_GAP_MS) && e.saturating_sub(last.0) <= max_ms => {
+                last.1 = last.1.max(e);
+            }
+            Some(last) if s < last.1 => {
+                // Padding overlaps the previous chunk but joining would make it too long: split at the midpoint.
+                let mid = (s.saturating_add(PAD_MS) + last.1.saturating_sub(PAD_MS)) / 2;
+                last.1 = mid;
+                out.push((mid, e));
+            }
+            _ => out.push((s, e)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_vad_chunks_table() {
+        let cases = [
+            ("empty", vec![], 30_000, vec![]),
+            ("pads both sides", vec![(1000, 2000)], 30_000, vec![(750, 2250)]),
+            ("padding clamps at zero", vec![(100, 900)], 30_000, vec![(0, 1150)]),
+            ("joins short pause", vec![(1000, 2000), (2500, 3000)], 30_000, vec![(750, 3250)]),
+            ("joins up to the gap limit", vec![(1000, 2000), (3300, 4000)], 30_000, vec![(750, 4250)]),
+            ("keeps long pause apart", vec![(1000, 2000), (4000, 5000)], 30_000, vec![(750, 2250), (3750, 5250)]),
+            (
+                "splits overlapping padding at the midpoint when too long",
+                vec![(1000, 3000), (3200, 5000)],
+                3000,
+                vec![(750, 3100), (3100, 5250)],
+            ),
+        ];
+        for (name, spans, max_ms, want) in cases {
+            assert_eq!(plan_vad_chunks(&spans, max_ms), want, "{name}");
+        }
+    }
+
+    #[test]
+    fn too_long_without_overlap_starts_a_new_chunk() {
+        // Within the join gap, but joining would exceed max_ms and the padded spans do not touch.
+        assert_eq!(
+            plan_vad_chunks(&[(1000, 3000), (3700, 5000)], 3000),
+            vec![(750, 3250), (3450, 5250)]
+        );
+    }
+
+    #[test]
+    fn chunks_stay_ordered_and_disjoint() {
+        let spans: Vec<(i64, i64)> = (0..50).map(|i| (i * 1100, i * 1100 + 900)).collect();
+        let plan = plan_vad_chunks(&spans, 5000);
+        assert!(plan.len() > 1);
+        for w in plan.windows(2) {
+            assert!(w[0].0 < w[0].1 && w[0].1 <= w[1].0, "{w:?}");
+        }
+        for &(s, e) in &plan {
+            assert!(e - s <= 5000, "{s}..{e}");
+        }
+        assert_eq!(plan.first().unwrap().0, 0);
+        assert_eq!(plan.last().unwrap().1, 49 * 1100 + 900 + PAD_MS);
+    }
+}
@@ -5,7 +5,7 @@ import { Router } from '@angular/router';
 import { IconComponent } from '../components/icons';
 import { ConfigService } from '../services/config.service';
 import { StateService } from '../services/state.service';
-import { BackendService } from '../services/backend.service';
+import { BackendService, backendErrorCode, isPlaylistImportResult, type ImportUrlResult } from '../services/backend.service';
 import { ToastService } from '../services/toast.service';
 import { ProcessingJob, MediaItem } from '../types';
 
@@ -185,7 +185,12 @@ import { ProcessingJob, MediaItem } from '../types';
                          <div class="flex-1 min-w-0">
                             <div class="flex items-center justify-between mb-1">
                                 <span class="text-xs font-medium text-zinc-900 dark:text-zinc-100">{{ jobLabel(job.type) }}</span>
-                                <span class="text-[9px] font-mono text-zinc-400 uppercase">{{ jobStatusLabel(job.status) }}</span>
+                                <div class="flex items-center gap-2">
+                                  <span class="text-[9px] font-mono text-zinc-400 uppercase">{{ jobStatusLabel(job.status) }}</span>
+                                  @if (job.cancellable && (job.status === 'pending' || job.status === 'processing')) {
+                                    <button class="text-[9px] font-semibold text-zinc-400 hover:text-red-500 transition-colors" (click)="cancelJob(job)">{{ config.t().home.inbox.cancelJob }}</button>
+                                  }
+                                </div>
                             </div>
                             <!-- Progress Bar -->
                              <div class="h-1 w-full bg-zinc-100 dark:bg-zinc-800 rounded-sm overflow-hidden">
@@ -335,54 +340,49 @@ export class HomeComponent {
     // Desktop (Tauri): let the backend drive job progress.
     if (await this.backend.isAvailable()) {
       try {
-        const res = await this.backend.importUrl(url, item.id, this.importQuality());
-        if (res.warning) {
-          console.warn('import_url warning', res.warning);
-        }
+        const res = await this.backend.importUrl(url, item.id, this.importQuality(), {
+          playlist: this.isPlaylistUrl(url),
+        });
 
-        const cachedPath = typeof res.stored_rel === 'string' && res.stored_rel.trim()
-          ? res.stored_rel.trim()
-          : (typeof res.stored_path === 'string' ? res.stored_path : undefined);
-        const fileSize = typeof res.file_size === 'number' ? res.file_size : undefined;
-        const title = typeof res.title === 'string' && res.title.trim() ? res.title.trim() : undefined;
-        const uploader = typeof res.uploader === 'string' && res.uploader.trim() ? res.uploader.trim() : undefined;
-        const uploadDate = typeof res.upload_date === 'string' && res.upload_date.trim() ? res.upload_date.trim() : undefined;
-        const detectedType = res.meta?.kind === 'audio' ? 'audio' : 'video';
+        if (isPlaylistImportResult(res)) {
+          for (const entry of res.items) {
+            if (entry.media_id !== item.id) {
+              this.state.addMediaItem({ ...item, name: entry.title || entry.media_id }, entry.media_id);
+            }
+            this.applyImportResult(entry.media_id, entry);
+          }
+          // The placeholder only gets used when at least one entry was imported.
+          if (!res.items.some(entry => entry.media_id === item.id)) {
+            this.state.discardMediaItem(item.id);
+          }
+          const title = res.playlist.title || url;
+          const parts = [`已导入 ${res.items.length} 个`];
+          if (res.skipped.length) parts.push(`跳过 ${res.skipped.length} 个`);
+          if (res.failed.length) parts.push(`失败 ${res.failed.length} 个`);
+          if (res.items.length) {
+            this.toast.success(`${title}：${parts.join('，')}`);
+          } else {
+            this.toast.warning(`${title}：${parts.join('，')}`);
+          }
+          return;
+        }
 
-        this.state.updateMediaItem(item.id, {
-          name: title ?? item.name,
-          type: detectedType,
-          status: 'ready',
-          duration: typeof res.duration === 'number' ? res.duration : 0,
-          thumbnail: typeof res.thumbnail === 'string' ? res.thumbnail : undefined,
-          meta: res.meta ? res.meta : item.meta,
-          source: {
-            ...item.source,
-            originalTitle: title,
-            uploader,
-            uploadDate,
-            cachedPath,
-            fileSize
-          } as any
-        });
+        this.applyImportResult(item.id, res);
       } catch (e) {
         console.error('import_url failed', e);
-        const msg = String((e as any)?.message ?? e ?? '');
+        const code = backendErrorCode(e);
+        // Tauri rejects mismatched command arguments itself, before any backend code runs.
+        const argsMismatch = code === null && String((e as any)?.message ?? e ?? '').includes('invalid args');
+        const toolsUnavailable =
+          code === 'ToolMissing' || code === 'ToolDownloadFailed' || code === 'ChecksumMismatch' || code === 'Offline';
         const shouldDiscard =
-          msg.includes('invalid args') ||
-          msg.includes('only http') ||
-          msg.includes('sidecar') ||
-          msg.includes('downloaded yt-dlp looks invalid') ||
-          msg.includes('no suitable ffmpeg') ||
-          msg.includes('failed to locate ffmpeg') ||
-          msg.includes('yt-dlp') ||
-          msg.includes('ffmpeg');
-
-        if (msg.includes('invalid args')) {
+          argsMismatch || toolsUnavailable || code === 'InvalidInput' || code === 'MediaDownloadFailed';
+
+        if (argsMismatch) {
           this.toast.error('导入失败：客户端与后端版本不匹配，请重启应用');
-        } else if (msg.includes('github api request failed') || msg.includes('download failed')) {
+        } else if (code === 'ToolDownloadFailed' || code === 'ChecksumMismatch') {
           this.toast.error('导入失败：无法自动准备下载器，请检查网络连接');
-        } else if (msg.includes('sidecar') || msg.includes('ffmpeg') || msg.includes('yt-dlp')) {
+        } else if (code === 'ToolMissing' || code === 'Offline') {
           this.toast.error('导入失败：下载器不可用（yt-dlp/ffmpeg）');
         } else {
           this.toast.error('导入失败：请检查链接或 cookies 配置');
@@ -410,6 +410,62 @@ export class HomeComponent {
     this.state.addProcessingJob(item.id, 'download');
   }
 
+  private applyImportResult(mediaId: string, res: ImportUrlResult): void {
+    if (res.warning) {
+      console.warn('import_url warning', res.warning);
+    }
+    const item = this.state.mediaItems().find(m => m.id === mediaId);
+    if (!item) return;
+
+    const cachedPath = typeof res.stored_rel === 'string' && res.stored_rel.trim()
+      ? res.stored_rel.trim()
+      : (typeof res.stored_path === 'string' ? res.stored_path : undefined);
+    const fileSize = typeof res.file_size === 'number' ? res.file_size : undefined;
+    const title = typeof res.title === 'string' && res.title.trim() ? res.title.trim() : undefined;
+    const uploader = typeof res.uploader === 'string' && res.uploader.trim() ? res.uploader.trim() : undefined;
+    const uploadDate = typeof res.upload_date === 'string' && res.upload_date.trim() ? res.upload_date.trim() : undefined;
+    const detectedType = res.meta?.kind === 'audio' ? 'audio' : 'video';
+
+    this.state.updateMediaItem(mediaId, {
+      name: title ?? item.name,
+      type: detectedType,
+      status: 'ready',
+      duration: typeof res.duration === 'number' ? res.duration : 0,
+      thumbnail: typeof res.thumbnail === 'string' ? res.thumbnail : undefined,
+      meta: res.meta ? res.meta : item.meta,
+      source: {
+        ...item.source,
+        originalTitle: title,
+        uploader,
+        uploadDate,
+        cachedPath,
+        fileSize
+      } as any
+    });
+  }
+
+  /** Playlist / channel / favourites links import every entry as its own media item. */
+  private isPlaylistUrl(url: string): boolean {
+    let u: URL;
+    try {
+      u = new URL(url);
+    } catch {
+      return false;
+    }
+    const host = u.hostname.toLowerCase();
+    const path = u.pathname.toLowerCase();
+    if (host.includes('youtube.com')) {
+      if (path.startsWith('/playlist')) return true;
+      if (u.searchParams.has('list') && !u.searchParams.has('v')) return true;
+      return /^\/(@[^/]+|channel\/[^/]+|c\/[^/]+|user\/[^/]+)(\/(videos|streams|shorts))?\/?$/.test(path);
+    }
+    if (host === 'space.bilibili.com') return true;
+    if (host.includes('bilibili.com')) {
+      return path.startsWith('/list/') || path.startsWith('/medialist/') || path.includes('/favlist');
+    }
+    return false;
+  }
+
   private parseShareText(raw: string): { url: string | null; title: string | null } {
     const text = (raw || '').trim();
     if (!text) return { url: null, title: null };
@@ -599,6 +655,15 @@ export class HomeComponent {
     }
   }
 
+  async cancelJob(job: ProcessingJob): Promise<void> {
+    try {
+      await this.state.cancelProcessingJob(job.id);
+    } catch (err: any) {
+      console.error('cancelJob failed', err);
+      this.toast.error(err?.message || String(err));
+    }
+  }
+
   jobLabel(type: ProcessingJob['type']): string {
     const t = this.config.t();
     return ((t.home.inbox.jobTypes as any)[type] || type) as string;
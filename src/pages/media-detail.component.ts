@@ -1301,11 +1301,15 @@ type TabId = 'transcript' | 'notes' | 'bookmarks' | 'summary' | 'chat';
                            @if (chatSending()) {
                              <div class="flex">
                                <div class="max-w-[90%] rounded-2xl px-4 py-2 bg-white dark:bg-zinc-900 border border-zinc-200 dark:border-zinc-800 text-zinc-800 dark:text-zinc-100">
-                                 <div class="flex items-center gap-1.5">
-                                   <span class="vecho-typing-dot w-1.5 h-1.5 rounded-full bg-zinc-400"></span>
-                                   <span class="vecho-typing-dot w-1.5 h-1.5 rounded-full bg-zinc-400"></span>
-                                   <span class="vecho-typing-dot w-1.5 h-1.5 rounded-full bg-zinc-400"></span>
-                                 </div>
+                                 @if (chatMsgVisible(chatStreamText())) {
+                                   <app-markdown variant="compact" [content]="chatMsgVisible(chatStreamText())" [title]="m.name"></app-markdown>
+                                 } @else {
+                                   <div class="flex items-center gap-1.5">
+                                     <span class="vecho-typing-dot w-1.5 h-1.5 rounded-full bg-zinc-400"></span>
+                                     <span class="vecho-typing-dot w-1.5 h-1.5 rounded-full bg-zinc-400"></span>
+                                     <span class="vecho-typing-dot w-1.5 h-1.5 rounded-full bg-zinc-400"></span>
+                                   </div>
+                                 }
                                </div>
                              </div>
                            }
@@ -1321,13 +1325,23 @@ type TabId = 'transcript' | 'notes' | 'bookmarks' | 'summary' | 'chat';
                             (ngModelChange)="chatDraft.set($event)"
                             (keydown.enter)="onChatEnter($event)"
                           ></textarea>
-                          <button
-                            class="h-11 px-4 rounded-xl text-xs font-bold bg-zinc-900 text-white dark:bg-white dark:text-black hover:opacity-90 transition-opacity disabled:opacity-50 disabled:cursor-not-allowed"
-                            (click)="sendChat()"
-                            [disabled]="chatSending() || !chatDraft().trim() || !activeConversation()"
-                          >
-                            @if (chatSending()) { 发送中… } @else { 发送 }
-                          </button>
+                          @if (chatSending()) {
+                            <button
+                              class="h-11 px-4 rounded-xl text-xs font-bold border border-zinc-200 dark:border-zinc-700 text-zinc-700 dark:text-zinc-200 hover:bg-zinc-50 dark:hover:bg-zinc-800 transition-colors disabled:opacity-50 disabled:cursor-not-allowed"
+                              (click)="cancelChat()"
+                              [disabled]="!chatStreamMessageId()"
+                            >
+                              停止
+                            </button>
+                          } @else {
+                            <button
+                              class="h-11 px-4 rounded-xl text-xs font-bold bg-zinc-900 text-white dark:bg-white dark:text-black hover:opacity-90 transition-opacity disabled:opacity-50 disabled:cursor-not-allowed"
+                              (click)="sendChat()"
+                              [disabled]="!chatDraft().trim() || !activeConversation()"
+                            >
+                              发送
+                            </button>
+                          }
                         </div>
                       </div>
                     }
@@ -1732,6 +1746,8 @@ export class MediaDetailComponent implements OnInit, OnDestroy {
   summaryRegenerating = signal<'timeline' | 'mindmap' | null>(null);
   summaryPromptId = signal<string>('');
   chatSending = signal(false);
+  chatStreamMessageId = signal<string | null>(null);
+  chatStreamText = signal('');
   chatDraft = signal('');
   activeChatId = signal<string | null>(null);
   chatEditingId = signal<string | null>(null);
@@ -1933,6 +1949,9 @@ export class MediaDetailComponent implements OnInit, OnDestroy {
       danger: true,
     });
     if (!ok) return;
+    if (chat?.conversationId) {
+      void this.backend.deleteConversation(m.id, chat.conversationId).catch(err => console.error('deleteConversation failed', err));
+    }
     this.state.deleteAIConversation(m.id, chatId);
     // if deleting active, fall back
     if (this.activeChatId() === chatId) {
@@ -3622,25 +3641,58 @@ Input (transcript):\n\n{{input}}\n`;
     this.state.addMessageToConversation(m.id, chat.id, { role: 'user', content });
     this.chatDraft.set('');
 
+    const messageId = `msg-${Date.now()}-${Math.random().toString(36).slice(2, 8)}`;
     this.chatSending.set(true);
+    this.chatStreamText.set('');
+    this.chatStreamMessageId.set(messageId);
+    const unlisten = await this.backend.listenChatStream(e => {
+      if (e.message_id !== messageId || e.kind !== 'content' || !e.text) return;
+      this.chatStreamText.update(t => t + e.text);
+    });
     try {
       const updated = this.state.mediaItems().find(x => x.id === m.id)?.aiChats.find(c => c.id === chat!.id) || chat;
-      const payload = (updated?.messages || []).slice(-12).map(msg => ({ role: msg.role, content: msg.content }));
+      // The backend conversation keeps the history (and its running summary); send only the new
+      // message once it exists. A new one is seeded with the recent local messages.
+      let conversationId = updated.conversationId;
+      let payload: Array<Pick<AIMessage, 'role' | 'content'>> = [{ role: 'user', content }];
+      if (!conversationId) {
+        conversationId = (await this.backend.createConversation(m.id, updated.title)).id;
+        this.state.setAIConversationBackendId(m.id, chat.id, conversationId);
+        payload = (updated.messages || []).slice(-12).map(msg => ({ role: msg.role, content: msg.content }));
+      }
       const res = await this.backend.chatMedia(m.id, this.state.settings().ai, payload, {
         includeTranscription: this.chatIncludeTranscription(),
         includeSummary: this.chatIncludeSummary(),
         userLang: this.config.lang(),
+        stream: true,
+        messageId,
+        conversationId,
       });
-      this.state.addMessageToConversation(m.id, chat.id, {
-        role: res.message.role,
-        content: res.message.content,
-        referencedSegments: res.message.referencedSegments,
-      });
+      if (res.message.content) {
+        this.state.addMessageToConversation(m.id, chat.id, {
+          role: res.message.role,
+          content: res.message.content,
+          referencedSegments: res.message.referencedSegments,
+        });
+      }
     } catch (err: any) {
       console.error('chatMedia failed', err);
       this.toast.error(this.formatError(err) || '发送失败');
     } finally {
+      unlisten?.();
+      this.chatStreamMessageId.set(null);
+      this.chatStreamText.set('');
       this.chatSending.set(false);
     }
   }
+
+  async cancelChat(): Promise<void> {
+    const id = this.chatStreamMessageId();
+    if (!id) return;
+    try {
+      await this.backend.cancelChat(id);
+    } catch (err: any) {
+      console.error('cancelChat failed', err);
+    }
+  }
 }
@@ -4,6 +4,7 @@ import { IconComponent } from '../components/icons';
 import { NgClass } from '@angular/common';
 import { ConfigService } from '../services/config.service';
 import { StateService } from '../services/state.service';
+import { BackendService } from '../services/backend.service';
 import type { AppSettings } from '../types';
 
 @Component({
@@ -275,6 +276,8 @@ import type { AppSettings } from '../types';
                           >
                             <option value="openai_compatible">OpenAI 兼容</option>
                             <option value="gemini">Gemini</option>
+                            <option value="anthropic">Anthropic</option>
+                            <option value="ollama">Ollama（本地）</option>
                           </select>
                         </div>
                       </div>
@@ -355,10 +358,162 @@ import type { AppSettings } from '../types';
                                 (ngModelChange)="patchAIGemini({ apiKey: $event })"
                               />
                             </div>
+                            <div class="space-y-2">
+                              <div class="text-[11px] font-semibold text-zinc-500">Temperature</div>
+                              <input
+                                class="w-full h-10 px-3 rounded-lg border border-zinc-200 dark:border-zinc-700 bg-white dark:bg-zinc-900 text-sm"
+                                type="number"
+                                min="0"
+                                max="2"
+                                step="0.1"
+                                [ngModel]="settings().ai.gemini.temperature"
+                                (ngModelChange)="patchAIGemini({ temperature: optionalNumber($event) })"
+                                placeholder="0.2"
+                              />
+                            </div>
+                            <div class="space-y-2">
+                              <div class="text-[11px] font-semibold text-zinc-500">Max Output Tokens</div>
+                              <input
+                                class="w-full h-10 px-3 rounded-lg border border-zinc-200 dark:border-zinc-700 bg-white dark:bg-zinc-900 text-sm"
+                                type="number"
+                                min="1"
+                                [ngModel]="settings().ai.gemini.maxOutputTokens"
+                                (ngModelChange)="patchAIGemini({ maxOutputTokens: optionalNumber($event) })"
+                                placeholder="不限制"
+                              />
+                            </div>
+                            <div class="sm:col-span-2 space-y-2">
+                              <div class="text-[11px] font-semibold text-zinc-500">安全过滤</div>
+                              <select
+                                class="w-full h-10 px-3 rounded-lg border border-zinc-200 dark:border-zinc-700 bg-white dark:bg-zinc-900 text-sm"
+                                [ngModel]="settings().ai.gemini.safetyThreshold || ''"
+                                (ngModelChange)="patchAIGemini({ safetyThreshold: $event || undefined })"
+                              >
+                                <option value="">默认</option>
+                                <option value="BLOCK_NONE">不拦截（BLOCK_NONE）</option>
+                                <option value="BLOCK_ONLY_HIGH">仅拦截高风险（BLOCK_ONLY_HIGH）</option>
+                                <option value="BLOCK_MEDIUM_AND_ABOVE">拦截中高风险（BLOCK_MEDIUM_AND_ABOVE）</option>
+                                <option value="BLOCK_LOW_AND_ABOVE">拦截低及以上（BLOCK_LOW_AND_ABOVE）</option>
+                              </select>
+                            </div>
+                          </div>
+                        </div>
+                      }
+
+                      @if (settings().ai.provider === 'anthropic') {
+                        <div class="pt-2 border-t border-zinc-100 dark:border-zinc-800 space-y-3">
+                          <div class="text-xs font-semibold text-zinc-900 dark:text-zinc-100">Anthropic</div>
+                          <div class="grid grid-cols-1 sm:grid-cols-2 gap-3">
+                            <div class="space-y-2">
+                              <div class="text-[11px] font-semibold text-zinc-500">Base URL</div>
+                              <input
+                                class="w-full h-10 px-3 rounded-lg border border-zinc-200 dark:border-zinc-700 bg-white dark:bg-zinc-900 text-sm"
+                                [ngModel]="settings().ai.anthropic.baseUrl"
+                                (ngModelChange)="patchAIAnthropic({ baseUrl: $event })"
+                                placeholder="https://api.anthropic.com"
+                              />
+                            </div>
+                            <div class="space-y-2">
+                              <div class="text-[11px] font-semibold text-zinc-500">Model</div>
+                              <input
+                                class="w-full h-10 px-3 rounded-lg border border-zinc-200 dark:border-zinc-700 bg-white dark:bg-zinc-900 text-sm"
+                                [ngModel]="settings().ai.anthropic.model"
+                                (ngModelChange)="patchAIAnthropic({ model: $event })"
+                                placeholder="claude-sonnet-4-5"
+                              />
+                            </div>
+                            <div class="sm:col-span-2 space-y-2">
+                              <div class="text-[11px] font-semibold text-zinc-500">API Key</div>
+                              <input
+                                class="w-full h-10 px-3 rounded-lg border border-zinc-200 dark:border-zinc-700 bg-white dark:bg-zinc-900 text-sm"
+                                type="password"
+                                [ngModel]="settings().ai.anthropic.apiKey"
+                                (ngModelChange)="patchAIAnthropic({ apiKey: $event })"
+                                placeholder="sk-ant-..."
+                              />
+                            </div>
+                          </div>
+                        </div>
+                      }
+
+                      @if (settings().ai.provider === 'ollama') {
+                        <div class="pt-2 border-t border-zinc-100 dark:border-zinc-800 space-y-3">
+                          <div class="text-xs font-semibold text-zinc-900 dark:text-zinc-100">Ollama</div>
+                          <div class="grid grid-cols-1 sm:grid-cols-2 gap-3">
+                            <div class="space-y-2">
+                              <div class="text-[11px] font-semibold text-zinc-500">Base URL</div>
+                              <input
+                                class="w-full h-10 px-3 rounded-lg border border-zinc-200 dark:border-zinc-700 bg-white dark:bg-zinc-900 text-sm"
+                                [ngModel]="settings().ai.ollama.baseUrl"
+                                (ngModelChange)="patchAIOllama({ baseUrl: $event })"
+                                placeholder="http://localhost:11434"
+                              />
+                            </div>
+                            <div class="space-y-2">
+                              <div class="text-[11px] font-semibold text-zinc-500">Model</div>
+                              <div class="flex gap-2">
+                                <input
+                                  class="w-full h-10 px-3 rounded-lg border border-zinc-200 dark:border-zinc-700 bg-white dark:bg-zinc-900 text-sm"
+                                  list="ollama-models"
+                                  [ngModel]="settings().ai.ollama.model"
+                                  (ngModelChange)="patchAIOllama({ model: $event })"
+                                  placeholder="llama3.1"
+                                />
+                                <button
+                                  class="h-10 px-3 shrink-0 rounded-lg text-xs font-semibold border border-zinc-200 dark:border-zinc-800 bg-white dark:bg-zinc-900 hover:bg-zinc-50 dark:hover:bg-zinc-800 transition-colors btn-press"
+                                  (click)="refreshOllamaModels()"
+                                >
+                                  获取模型
+                                </button>
+                              </div>
+                              <datalist id="ollama-models">
+                                @for (m of ollamaModels(); track m) {
+                                  <option [value]="m"></option>
+                                }
+                              </datalist>
+                              @if (ollamaModelsError()) {
+                                <div class="text-[11px] text-red-500">{{ ollamaModelsError() }}</div>
+                              }
+                            </div>
                           </div>
                         </div>
                       }
 
+                      <div class="pt-2 border-t border-zinc-100 dark:border-zinc-800 space-y-3">
+                        <div class="text-xs font-semibold text-zinc-900 dark:text-zinc-100">Embedding（对话检索，可选）</div>
+                        <div class="text-[11px] text-zinc-500">OpenAI 兼容的 /embeddings 接口；留空模型则只用关键词检索，Base URL 留空则沿用 OpenAI 兼容设置。</div>
+                        <div class="grid grid-cols-1 sm:grid-cols-2 gap-3">
+                          <div class="space-y-2">
+                            <div class="text-[11px] font-semibold text-zinc-500">Base URL</div>
+                            <input
+                              class="w-full h-10 px-3 rounded-lg border border-zinc-200 dark:border-zinc-700 bg-white dark:bg-zinc-900 text-sm"
+                              [ngModel]="settings().ai.embedding?.baseUrl || ''"
+                              (ngModelChange)="patchAIEmbedding({ baseUrl: $event })"
+                              placeholder="https://api.openai.com/v1"
+                            />
+                          </div>
+                          <div class="space-y-2">
+                            <div class="text-[11px] font-semibold text-zinc-500">Model</div>
+                            <input
+                              class="w-full h-10 px-3 rounded-lg border border-zinc-200 dark:border-zinc-700 bg-white dark:bg-zinc-900 text-sm"
+                              [ngModel]="settings().ai.embedding?.model || ''"
+                              (ngModelChange)="patchAIEmbedding({ model: $event })"
+                              placeholder="text-embedding-3-small"
+                            />
+                          </div>
+                          <div class="sm:col-span-2 space-y-2">
+                            <div class="text-[11px] font-semibold text-zinc-500">API Key</div>
+                            <input
+                              class="w-full h-10 px-3 rounded-lg border border-zinc-200 dark:border-zinc-700 bg-white dark:bg-zinc-900 text-sm"
+                              type="password"
+                              [ngModel]="settings().ai.embedding?.apiKey || ''"
+                              (ngModelChange)="patchAIEmbedding({ apiKey: $event })"
+                              placeholder="sk-..."
+                            />
+                          </div>
+                        </div>
+                      </div>
+
                       <div class="pt-4 border-t border-zinc-100 dark:border-zinc-800 space-y-3">
                         <div class="flex items-center justify-between gap-3">
                           <div class="text-xs font-semibold text-zinc-900 dark:text-zinc-100">总结 Prompt 模板</div>
@@ -446,9 +601,12 @@ import type { AppSettings } from '../types';
 export class SettingsComponent {
   config = inject(ConfigService);
   private state = inject(StateService);
+  private backend = inject(BackendService);
 
   settings = computed(() => this.state.settings());
   activeSection = signal('general');
+  ollamaModels = signal<string[]>([]);
+  ollamaModelsError = signal('');
 
   
   menuItems = [
@@ -521,6 +679,52 @@ export class SettingsComponent {
     }));
   }
 
+  optionalNumber(v: unknown): number | undefined {
+    if (v === null || v === undefined || v === '') return undefined;
+    const n = Number(v);
+    return Number.isFinite(n) ? n : undefined;
+  }
+
+  patchAIAnthropic(patch: Partial<AppSettings['ai']['anthropic']>): void {
+    this.state.updateSettings((s) => ({
+      ...s,
+      ai: {
+        ...s.ai,
+        anthropic: { ...s.ai.anthropic, ...patch },
+      }
+    }));
+  }
+
+  patchAIOllama(patch: Partial<AppSettings['ai']['ollama']>): void {
+    this.state.updateSettings((s) => ({
+      ...s,
+      ai: {
+        ...s.ai,
+        ollama: { ...s.ai.ollama, ...patch },
+      }
+    }));
+  }
+
+  patchAIEmbedding(patch: Partial<NonNullable<AppSettings['ai']['embedding']>>): void {
+    this.state.updateSettings((s) => ({
+      ...s,
+      ai: {
+        ...s.ai,
+        embedding: { baseUrl: '', apiKey: '', model: '', ...s.ai.embedding, ...patch },
+      }
+    }));
+  }
+
+  async refreshOllamaModels(): Promise<void> {
+    this.ollamaModelsError.set('');
+    try {
+      const models = await this.backend.listOllamaModels(this.settings().ai.ollama.baseUrl);
+      this.ollamaModels.set(models.map(m => m.name));
+    } catch (e: any) {
+      this.ollamaModelsError.set(e?.message || String(e));
+    }
+  }
+
   setDefaultSummaryPrompt(id: string): void {
     const wanted = (id || '').trim();
     if (!wanted) return;
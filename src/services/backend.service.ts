@@ -3,6 +3,24 @@ import { TauriService } from './tauri.service';
 import type { UnlistenFn } from './tauri.service';
 import type { PersistedAppState, Transcription, AISummary, AppSettings, AIMessage } from '../types';
 
+export interface ImportPlaylistResult {
+  job_id: string;
+  playlist: {
+    id?: string | null;
+    title?: string | null;
+    uploader?: string | null;
+    entry_count: number;
+  };
+  /** One single-media result per imported entry; the first uses the caller's media id. */
+  items: ImportUrlResult[];
+  skipped: Array<{ url: string; title?: string | null; media_id?: string | null; reason: string }>;
+  failed: Array<{ url: string; title?: string | null; media_id: string; error: string }>;
+}
+
+export function isPlaylistImportResult(res: ImportUrlResult | ImportPlaylistResult): res is ImportPlaylistResult {
+  return Array.isArray((res as ImportPlaylistResult).items) && !!(res as ImportPlaylistResult).playlist;
+}
+
 export interface ImportUrlResult {
   media_id: string;
   job_id: string;
@@ -86,6 +104,33 @@ export interface UploadFinishResult {
 export type BackendJobType = 'import' | 'download' | 'transcribe' | 'optimize' | 'summary' | 'export' | 'subtitle';
 export type BackendJobStatus = 'queued' | 'running' | 'succeeded' | 'failed' | 'cancelled';
 
+export type BackendErrorCode =
+  | 'MediaNotFound'
+  | 'InvalidInput'
+  | 'ToolMissing'
+  | 'ToolDownloadFailed'
+  | 'ChecksumMismatch'
+  | 'Offline'
+  | 'MediaDownloadFailed'
+  | 'ProviderAuth'
+  | 'ProviderRateLimited'
+  | 'ProviderError'
+  | 'NothingRecognized'
+  | 'Cancelled'
+  | 'Internal';
+
+/** Shape of a rejected `invoke` from the backend. */
+export interface BackendError {
+  code: BackendErrorCode;
+  message: string;
+}
+
+/** `code` of a rejected `invoke`, or null for errors that did not come from a command (e.g. Tauri's own). */
+export function backendErrorCode(e: unknown): BackendErrorCode | null {
+  const code = (e as Partial<BackendError> | null)?.code;
+  return typeof code === 'string' ? code : null;
+}
+
 export interface BackendJobProgressEvent {
   job_id: string;
   media_id: string;
@@ -93,6 +138,17 @@ export interface BackendJobProgressEvent {
   status: BackendJobStatus;
   progress: number;
   message?: string | null;
+  error_code?: BackendErrorCode | null;
+}
+
+export interface BackendJobRecord extends BackendJobProgressEvent {
+  created_at: string;
+  updated_at: string;
+}
+
+export interface ListJobsResult {
+  jobs: BackendJobRecord[];
+  max_concurrent_jobs: number;
 }
 
 export interface TranscribeMediaResult {
@@ -121,6 +177,53 @@ export interface SummarizeMediaOptions {
 
 export interface ChatMediaResult {
   message: AIMessage;
+  cancelled?: boolean;
+  conversation_id?: string | null;
+}
+
+export interface ConversationInfo {
+  id: string;
+  title: string;
+  created_at: string;
+  updated_at: string;
+  message_count: number;
+}
+
+export interface Conversation {
+  version: 1;
+  id: string;
+  mediaId: string;
+  title: string;
+  createdAt: string;
+  updatedAt: string;
+  messages: AIMessage[];
+  /** Running summary of the earliest `covered` messages. */
+  historySummary?: { content: string; covered: number; updatedAt: string };
+}
+
+export interface ChatCollectionSource {
+  media_id: string;
+  media_name: string;
+  timestamp: number;
+  /** The `[media name @ MM:SS]` tag as it appears in the answer. */
+  citation: string;
+}
+
+export interface ChatCollectionResult {
+  collection_id: string;
+  message: AIMessage;
+  sources: ChatCollectionSource[];
+  media_count: number;
+}
+
+export type ChatStreamKind = 'content' | 'reasoning' | 'done' | 'error' | 'cancelled';
+
+export interface ChatStreamEvent {
+  message_id: string;
+  media_id: string;
+  kind: ChatStreamKind;
+  text?: string | null;
+  error_code?: BackendErrorCode | null;
 }
 
 export interface ExportMediaResult {
@@ -130,6 +233,33 @@ export interface ExportMediaResult {
   files: string[];
 }
 
+export interface LibrarySearchHit {
+  media_id: string;
+  media_name?: string | null;
+  kind: 'transcript' | 'subtitle' | 'summary' | 'note';
+  segment_id?: string | null;
+  track?: string | null;
+  timestamp?: number | null;
+  snippet: string;
+  /** `[start, end)` character ranges within `snippet`. */
+  highlights: Array<[number, number]>;
+  score: number;
+}
+
+export interface LibrarySearchResult {
+  query: string;
+  total: number;
+  results: LibrarySearchHit[];
+}
+
+export interface OllamaModel {
+  name: string;
+  size?: number | null;
+  modified_at?: string | null;
+  family?: string | null;
+  parameter_size?: string | null;
+}
+
 @Injectable({ providedIn: 'root' })
 export class BackendService {
   private readonly tauri = inject(TauriService);
@@ -149,11 +279,18 @@ export class BackendService {
     await this.tauri.invoke<void>('save_state', { args: { state } });
   }
 
-  async importUrl(url: string, mediaId?: string, quality?: string): Promise<ImportUrlResult> {
+  async importUrl(
+    url: string,
+    mediaId?: string,
+    quality?: string,
+    options?: { playlist?: boolean; playlistLimit?: number }
+  ): Promise<ImportUrlResult | ImportPlaylistResult> {
     if (!(await this.isAvailable())) {
       throw new Error('backend not available');
     }
-    return this.tauri.invoke<ImportUrlResult>('import_url', { args: { url, mediaId, quality } });
+    return this.tauri.invoke<ImportUrlResult | ImportPlaylistResult>('import_url', {
+      args: { url, mediaId, quality, playlist: options?.playlist ?? false, playlistLimit: options?.playlistLimit }
+    });
   }
 
   async getMediaStorageInfo(mediaId: string): Promise<MediaStorageInfoResult> {
@@ -217,6 +354,30 @@ export class BackendService {
     return this.tauri.listen<BackendJobProgressEvent>('job_progress', handler);
   }
 
+  async listJobs(): Promise<ListJobsResult> {
+    if (!(await this.isAvailable())) return { jobs: [], max_concurrent_jobs: 0 };
+    return this.tauri.invoke<ListJobsResult>('list_jobs');
+  }
+
+  async getJob(jobId: string): Promise<BackendJobRecord> {
+    if (!(await this.isAvailable())) {
+      throw new Error('backend not available');
+    }
+    return this.tauri.invoke<BackendJobRecord>('get_job', { args: { jobId } });
+  }
+
+  async cancelJob(jobId: string): Promise<boolean> {
+    if (!(await this.isAvailable())) return false;
+    const res = await this.tauri.invoke<{ job_id: string; cancelled: boolean }>('cancel_job', { args: { jobId } });
+    return res.cancelled;
+  }
+
+  async setJobConcurrency(limit: number): Promise<number> {
+    if (!(await this.isAvailable())) return limit;
+    const res = await this.tauri.invoke<{ max_concurrent_jobs: number }>('set_job_concurrency', { args: { limit } });
+    return res.max_concurrent_jobs;
+  }
+
   async transcribeMedia(mediaId: string, config: AppSettings['transcription']): Promise<TranscribeMediaResult> {
     if (!(await this.isAvailable())) {
       throw new Error('backend not available');
@@ -256,7 +417,15 @@ export class BackendService {
     mediaId: string,
     ai: AppSettings['ai'],
     messages: Array<Pick<AIMessage, 'role' | 'content'>>,
-    options?: { includeTranscription?: boolean; includeSummary?: boolean; userLang?: 'en' | 'zh' }
+    options?: {
+      includeTranscription?: boolean;
+      includeSummary?: boolean;
+      userLang?: 'en' | 'zh';
+      stream?: boolean;
+      messageId?: string;
+      /** Continue a saved conversation; pass only the new message(s). */
+      conversationId?: string;
+    }
   ): Promise<ChatMediaResult> {
     if (!(await this.isAvailable())) {
       throw new Error('backend not available');
@@ -269,10 +438,98 @@ export class BackendService {
         includeTranscription: options?.includeTranscription ?? true,
         includeSummary: options?.includeSummary ?? false,
         userLang: options?.userLang,
+        stream: options?.stream ?? false,
+        messageId: options?.messageId,
+        conversationId: options?.conversationId,
       }
     });
   }
 
+  async createConversation(mediaId: string, title?: string): Promise<Conversation> {
+    if (!(await this.isAvailable())) {
+      throw new Error('backend not available');
+    }
+    return this.tauri.invoke<Conversation>('create_conversation', { args: { mediaId, title } });
+  }
+
+  async listConversations(mediaId: string): Promise<ConversationInfo[]> {
+    if (!(await this.isAvailable())) return [];
+    const res = await this.tauri.invoke<{ conversations: ConversationInfo[] }>('list_conversations', { args: { mediaId } });
+    return res.conversations;
+  }
+
+  async getConversation(mediaId: string, conversationId: string): Promise<Conversation> {
+    if (!(await this.isAvailable())) {
+      throw new Error('backend not available');
+    }
+    return this.tauri.invoke<Conversation>('get_conversation', { args: { mediaId, conversationId } });
+  }
+
+  async renameConversation(mediaId: string, conversationId: string, title: string): Promise<ConversationInfo> {
+    if (!(await this.isAvailable())) {
+      throw new Error('backend not available');
+    }
+    return this.tauri.invoke<ConversationInfo>('rename_conversation', { args: { mediaId, conversationId, title } });
+  }
+
+  async deleteConversation(mediaId: string, conversationId: string): Promise<void> {
+    if (!(await this.isAvailable())) return;
+    await this.tauri.invoke('delete_conversation', { args: { mediaId, conversationId } });
+  }
+
+  async chatCollection(
+    collectionId: string,
+    ai: AppSettings['ai'],
+    messages: Array<Pick<AIMessage, 'role' | 'content'>>,
+    options?: {
+      mediaIds?: string[];
+      includeSummary?: boolean;
+      userLang?: 'en' | 'zh';
+      contextChars?: number;
+    }
+  ): Promise<ChatCollectionResult> {
+    if (!(await this.isAvailable())) {
+      throw new Error('backend not available');
+    }
+    return this.tauri.invoke<ChatCollectionResult>('chat_collection', {
+      args: {
+        collectionId,
+        ai,
+        messages,
+        mediaIds: options?.mediaIds,
+        includeSummary: options?.includeSummary ?? false,
+        userLang: options?.userLang,
+        contextChars: options?.contextChars,
+      }
+    });
+  }
+
+  async cancelChat(messageId: string): Promise<void> {
+    if (!(await this.isAvailable())) return;
+    await this.tauri.invoke('cancel_chat', { args: { messageId } });
+  }
+
+  async listenChatStream(handler: (event: ChatStreamEvent) => void): Promise<UnlistenFn | null> {
+    if (!(await this.isAvailable())) return null;
+    return this.tauri.listen<ChatStreamEvent>('chat_stream', handler);
+  }
+
+  async listOllamaModels(baseUrl?: string): Promise<OllamaModel[]> {
+    if (!(await this.isAvailable())) return [];
+    const res = await this.tauri.invoke<{ models: OllamaModel[] }>('list_ollama_models', { args: { baseUrl } });
+    return res.models;
+  }
+
+  async searchLibrary(
+    query: string,
+    opts?: { limit?: number; mediaIds?: string[]; kinds?: LibrarySearchHit['kind'][] },
+  ): Promise<LibrarySearchResult> {
+    if (!(await this.isAvailable())) {
+      throw new Error('backend not available');
+    }
+    return this.tauri.invoke<LibrarySearchResult>('search_library', { args: { query, ...opts } });
+  }
+
   async exportMedia(mediaId: string, exportDir?: string): Promise<ExportMediaResult> {
     if (!(await this.isAvailable())) {
       throw new Error('backend not available');
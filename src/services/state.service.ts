@@ -134,6 +134,15 @@ function createDefaultSettings(): AppSettings {
                 baseUrl: 'https://generativelanguage.googleapis.com',
                 apiKey: '',
                 model: 'gemini-1.5-flash'
+            },
+            anthropic: {
+                baseUrl: 'https://api.anthropic.com',
+                apiKey: '',
+                model: 'claude-sonnet-4-5'
+            },
+            ollama: {
+                baseUrl: 'http://localhost:11434',
+                model: 'llama3.1'
             },
              summaryPrompts: [
                   {
@@ -336,6 +345,13 @@ export class StateService {
 
             // Listen for backend job events.
             await this.backend.listenJobProgress((evt) => this.onBackendJobProgress(evt));
+            // Jobs restored by the backend (or started before this window loaded), oldest first.
+            try {
+                const { jobs } = await this.backend.listJobs();
+                [...jobs].reverse().forEach(j => this.onBackendJobProgress(j));
+            } catch (err) {
+                console.error('listJobs failed', err);
+            }
         } else {
             this.loadFromStorage();
             this.initializeMockData();
@@ -415,6 +431,7 @@ export class StateService {
                     startedAt: now,
                     completedAt: (status === 'completed' || status === 'failed' || status === 'cancelled') ? now : undefined,
                     error: status === 'failed' ? (event.message || 'Job failed') : undefined,
+                    cancellable: true,
                 };
                 return [job, ...jobs].slice(0, 100);
             }
@@ -431,6 +448,7 @@ export class StateService {
                     ? (existing.completedAt || now)
                     : undefined,
                 error: status === 'failed' ? (event.message || existing.error) : undefined,
+                cancellable: true,
             };
 
             const next = [...jobs];
@@ -441,10 +459,11 @@ export class StateService {
 
     // ==================== CRUD: Media Items ====================
 
-    addMediaItem(item: Omit<MediaItem, 'id' | 'createdAt' | 'updatedAt' | 'notes' | 'bookmarks' | 'aiChats' | 'playCount'>): MediaItem {
+    /** `id` is only passed for items the backend already created (e.g. playlist entries). */
+    addMediaItem(item: Omit<MediaItem, 'id' | 'createdAt' | 'updatedAt' | 'notes' | 'bookmarks' | 'aiChats' | 'playCount'>, id?: string): MediaItem {
         const newItem: MediaItem = {
             ...item,
-            id: this.generateId('media'),
+            id: id ?? this.generateId('media'),
             notes: [],
             bookmarks: [],
             aiChats: [],
@@ -703,6 +722,13 @@ export class StateService {
         this.updateMediaItem(mediaId, { aiChats: nextChats });
     }
 
+    setAIConversationBackendId(mediaId: string, chatId: string, conversationId: string): void {
+        const media = this.mediaItems().find(m => m.id === mediaId);
+        if (!media) return;
+        const nextChats = media.aiChats.map(c => c.id === chatId ? { ...c, conversationId } : c);
+        this.updateMediaItem(mediaId, { aiChats: nextChats });
+    }
+
     deleteAIConversation(mediaId: string, chatId: string): void {
         const media = this.mediaItems().find(m => m.id === mediaId);
         if (!media) return;
@@ -887,6 +913,10 @@ export class StateService {
 
     // ==================== Processing Jobs ====================
 
+    async cancelProcessingJob(jobId: string): Promise<boolean> {
+        return this.backend.cancelJob(jobId);
+    }
+
     addProcessingJob(mediaId: string, type: ProcessingJob['type']): ProcessingJob {
         const job: ProcessingJob = {
             id: this.generateId('job'),
@@ -1136,7 +1166,7 @@ export class StateService {
 
         let provider: AppSettings['ai']['provider'] = d.ai.provider;
         const providerRaw = aiRaw.provider;
-        if (providerRaw === 'openai_compatible' || providerRaw === 'gemini') {
+        if (providerRaw === 'openai_compatible' || providerRaw === 'gemini' || providerRaw === 'anthropic' || providerRaw === 'ollama') {
             provider = providerRaw;
         } else if (providerRaw === 'openai' || providerRaw === 'local' || providerRaw === 'custom') {
             provider = 'openai_compatible';
@@ -1169,8 +1199,44 @@ export class StateService {
             model: (typeof geminiRaw.model === 'string' && geminiRaw.model.trim())
                 ? geminiRaw.model
                 : d.ai.gemini.model,
+            temperature: typeof geminiRaw.temperature === 'number' ? geminiRaw.temperature : undefined,
+            maxOutputTokens: typeof geminiRaw.maxOutputTokens === 'number' ? geminiRaw.maxOutputTokens : undefined,
+            safetyThreshold: typeof geminiRaw.safetyThreshold === 'string' ? geminiRaw.safetyThreshold : undefined,
+        };
+
+        const anthropicRaw = aiRaw.anthropic || {};
+        const anthropic: AppSettings['ai']['anthropic'] = {
+            baseUrl: (typeof anthropicRaw.baseUrl === 'string' && anthropicRaw.baseUrl.trim())
+                ? anthropicRaw.baseUrl
+                : d.ai.anthropic.baseUrl,
+            apiKey: typeof anthropicRaw.apiKey === 'string'
+                ? anthropicRaw.apiKey
+                : d.ai.anthropic.apiKey,
+            model: (typeof anthropicRaw.model === 'string' && anthropicRaw.model.trim())
+                ? anthropicRaw.model
+                : d.ai.anthropic.model,
+            maxTokens: typeof anthropicRaw.maxTokens === 'number' ? anthropicRaw.maxTokens : undefined,
         };
 
+        const ollamaRaw = aiRaw.ollama || {};
+        const ollama: AppSettings['ai']['ollama'] = {
+            baseUrl: (typeof ollamaRaw.baseUrl === 'string' && ollamaRaw.baseUrl.trim())
+                ? ollamaRaw.baseUrl
+                : d.ai.ollama.baseUrl,
+            model: (typeof ollamaRaw.model === 'string' && ollamaRaw.model.trim())
+                ? ollamaRaw.model
+                : d.ai.ollama.model,
+        };
+
+        const embeddingRaw = aiRaw.embedding;
+        const embedding: AppSettings['ai']['embedding'] = embeddingRaw && typeof embeddingRaw === 'object'
+            ? {
+                baseUrl: typeof embeddingRaw.baseUrl === 'string' ? embeddingRaw.baseUrl : '',
+                apiKey: typeof embeddingRaw.apiKey === 'string' ? embeddingRaw.apiKey : '',
+                model: typeof embeddingRaw.model === 'string' ? embeddingRaw.model : '',
+            }
+            : undefined;
+
         // Summary prompt templates
         const promptsRaw = (aiRaw as any).summaryPrompts;
         let summaryPrompts: AppSettings['ai']['summaryPrompts'] = Array.isArray(promptsRaw)
@@ -1223,6 +1289,9 @@ export class StateService {
                 provider,
                 openai,
                 gemini,
+                anthropic,
+                ollama,
+                embedding,
                 summaryPrompts,
                 defaultSummaryPromptId,
             },
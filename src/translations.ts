@@ -92,7 +92,8 @@ export const translations = {
           completed: 'Completed',
           failed: 'Failed',
           cancelled: 'Cancelled'
-        }
+        },
+        cancelJob: 'Cancel'
       }
     },
     workflow: {
@@ -302,7 +303,8 @@ export const translations = {
           completed: '完成',
           failed: '失败',
           cancelled: '已取消'
-        }
+        },
+        cancelJob: '取消'
       }
     },
     workflow: {
@@ -106,6 +106,7 @@ export interface Transcription {
     mediaId: string;
     language: string;
     segments: TranscriptionSegment[];
+    speakers?: TranscriptionSpeaker[];  // 说话人分离后生成，segment.speaker 引用其 id
     wordCount: number;
     generatedAt: string;
     model?: string;          // 使用的 Whisper 模型
@@ -119,6 +120,19 @@ export interface TranscriptionSegment {
     text: string;
     speaker?: string;        // 说话人（Speaker Diarization）
     confidence?: number;
+    words?: TranscriptionWord[];  // 词级时间戳（本地 SenseVoice / whisper.cpp）
+}
+
+export interface TranscriptionSpeaker {
+    id: string;              // spk-1, spk-2 ...
+    name: string;
+}
+
+export interface TranscriptionWord {
+    start: number;           // 秒
+    end: number;
+    text: string;
+    confidence?: number;     // 0-1，仅 whisper.cpp 提供
 }
 
 // ==================== 笔记（挂载在媒体下） ====================
@@ -177,6 +191,8 @@ export interface AIConversation {
     mediaId: string;
     title: string;                // 对话标题（可自动生成）
     messages: AIMessage[];
+    /** Backend conversation (`<media>/conversations/<id>.json`) that keeps the server-side history. */
+    conversationId?: string;
     createdAt: string;
     updatedAt: string;
 }
@@ -314,7 +330,7 @@ export interface AppSettings {
     };
     ai: {
         /** Cloud LLM provider (BYOK). localhost OpenAI-compatible also works. */
-        provider: 'openai_compatible' | 'gemini';
+        provider: 'openai_compatible' | 'gemini' | 'anthropic' | 'ollama';
 
         openai: {
             baseUrl: string;
@@ -327,6 +343,32 @@ export interface AppSettings {
             baseUrl: string;
             apiKey: string;
             model: string;
+            /** Chat generation config (defaults: temperature 0.2, no token cap). */
+            temperature?: number;
+            maxOutputTokens?: number;
+            /** Applied to every harm category, e.g. 'BLOCK_ONLY_HIGH' | 'BLOCK_NONE'. */
+            safetyThreshold?: string;
+        };
+
+        anthropic: {
+            /** Empty means https://api.anthropic.com */
+            baseUrl: string;
+            apiKey: string;
+            model: string;
+            maxTokens?: number;
+        };
+
+        ollama: {
+            /** Empty means http://localhost:11434 */
+            baseUrl: string;
+            model: string;
+        };
+
+        /** Optional OpenAI-compatible embedding model for chat retrieval; empty baseUrl reuses `openai`. */
+        embedding?: {
+            baseUrl: string;
+            apiKey: string;
+            model: string;
         };
 
         /** Summary prompt templates (used by AI summary). */
@@ -357,6 +399,8 @@ export interface ProcessingJob {
     completedAt?: string;
     error?: string;
     result?: any;
+    /** Backend job that `cancel_job` can stop. */
+    cancellable?: boolean;
 }
 
 // ==================== 活动日志 ====================